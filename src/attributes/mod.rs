@@ -0,0 +1,169 @@
+use crate::error::Result;
+use std::fs;
+use std::path::Path;
+
+/// File holding per-path attributes, like Git's `.gitattributes`
+pub const ATTRIBUTES_FILE: &str = ".kitcatattributes";
+
+/// Value of an attribute for a path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttrValue {
+    /// `attr`
+    Set,
+    /// `-attr`
+    Unset,
+    /// `attr=value`
+    Value(String),
+}
+
+/// One line of the attributes file
+#[derive(Debug, Clone)]
+struct AttrRule {
+    pattern: String,
+    attrs: Vec<(String, AttrValue)>,
+}
+
+/// Rules read from `.kitcatattributes`
+///
+/// Each line is a glob pattern followed by attributes:
+///
+/// ```text
+/// *.lock      diff=lockfile
+/// *.sqlite    diff=sqlite
+/// assets/**   binary
+/// ```
+///
+/// Later lines override earlier ones. `binary` is shorthand for `-diff -merge -text`.
+#[derive(Debug, Clone, Default)]
+pub struct Attributes {
+    rules: Vec<AttrRule>,
+}
+
+impl Attributes {
+    /// Read the attributes file from the repository root (empty if missing)
+    pub fn read() -> Result<Self> {
+        if !Path::new(ATTRIBUTES_FILE).exists() {
+            return Ok(Self::default());
+        }
+        Ok(Self::parse(&fs::read_to_string(ATTRIBUTES_FILE)?))
+    }
+
+    /// Parse attribute rules from text
+    pub fn parse(text: &str) -> Self {
+        let mut rules = Vec::new();
+
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut fields = line.split_whitespace();
+            let Some(pattern) = fields.next() else {
+                continue;
+            };
+
+            let mut attrs = Vec::new();
+            for field in fields {
+                if field == "binary" {
+                    for name in ["diff", "merge", "text"] {
+                        attrs.push((name.to_string(), AttrValue::Unset));
+                    }
+                } else if let Some(name) = field.strip_prefix('-') {
+                    attrs.push((name.to_string(), AttrValue::Unset));
+                } else if let Some((name, value)) = field.split_once('=') {
+                    attrs.push((name.to_string(), AttrValue::Value(value.to_string())));
+                } else {
+                    attrs.push((field.to_string(), AttrValue::Set));
+                }
+            }
+
+            rules.push(AttrRule {
+                pattern: pattern.trim_start_matches('/').to_string(),
+                attrs,
+            });
+        }
+
+        Self { rules }
+    }
+
+    /// Look up an attribute for a path (relative to the repository root)
+    pub fn get(&self, path: &str, name: &str) -> Option<AttrValue> {
+        self.rules
+            .iter()
+            .rev()
+            .filter(|rule| path_matches(&rule.pattern, path))
+            .find_map(|rule| {
+                rule.attrs
+                    .iter()
+                    .rev()
+                    .find(|(attr, _)| attr == name)
+                    .map(|(_, value)| value.clone())
+            })
+    }
+}
+
+/// Match a pattern against a path; patterns without `/` match the file name
+fn path_matches(pattern: &str, path: &str) -> bool {
+    if pattern.contains('/') {
+        glob_match(pattern.as_bytes(), path.as_bytes())
+    } else {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        glob_match(pattern.as_bytes(), name.as_bytes())
+    }
+}
+
+/// Glob matching with `*` (within a component), `**` (across components) and `?`
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some(b'*') if pattern.get(1) == Some(&b'*') => {
+            let rest = pattern[2..].strip_prefix(b"/").unwrap_or(&pattern[2..]);
+            (0..=text.len()).any(|i| glob_match(rest, &text[i..]))
+        }
+        Some(b'*') => {
+            let rest = &pattern[1..];
+            for i in 0..=text.len() {
+                if glob_match(rest, &text[i..]) {
+                    return true;
+                }
+                if text.get(i) == Some(&b'/') {
+                    break;
+                }
+            }
+            false
+        }
+        Some(b'?') => {
+            text.first().is_some_and(|&c| c != b'/') && glob_match(&pattern[1..], &text[1..])
+        }
+        Some(&c) => text.first() == Some(&c) && glob_match(&pattern[1..], &text[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attribute_lookup() {
+        let attrs = Attributes::parse(
+            "# comment\n*.lock diff=lockfile\nassets/** binary\nvendor/*.lock -diff\n",
+        );
+
+        assert_eq!(
+            attrs.get("Cargo.lock", "diff"),
+            Some(AttrValue::Value("lockfile".to_string()))
+        );
+        assert_eq!(attrs.get("vendor/yarn.lock", "diff"), Some(AttrValue::Unset));
+        assert_eq!(attrs.get("assets/img/logo.png", "merge"), Some(AttrValue::Unset));
+        assert_eq!(attrs.get("src/main.rs", "diff"), None);
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match(b"*.rs", b"main.rs"));
+        assert!(!glob_match(b"src/*.rs", b"src/a/main.rs"));
+        assert!(glob_match(b"src/**/*.rs", b"src/a/main.rs"));
+        assert!(glob_match(b"file?.txt", b"file1.txt"));
+    }
+}
@@ -0,0 +1,515 @@
+/// Am command implementation
+///
+/// Applies a series of patches from mbox files (as written by
+/// `kitcat format-patch`) as commits, keeping the original author and
+/// author date. When a patch does not apply the session stops and can be
+/// resumed with `--continue`, `--skip` or `--abort`. Skipping and aborting
+/// only reset the paths the patches touched, so other local changes survive.
+///
+/// Session state lives in `.kitcat/rebase-apply/`:
+/// - `0001`, `0002`, ... - one raw message per patch
+/// - `next`, `last` - the patch being applied and the number of patches
+/// - `orig-head` - HEAD before the session started
+
+use crate::commands::apply::{apply_patch_text, ApplyOptions};
+use crate::commands::checkout::reset_paths_to_commit;
+use crate::diff::patch::parse_patch;
+use crate::error::{KitcatError, Result};
+use crate::index::read_index_binary;
+use crate::merge::get_commit_files;
+use crate::merge::worktree::changed_paths;
+use crate::object::{create_commit_with_author, get_commit_tree, write_tree_from_index, Signature};
+use crate::repo::{resolve_head, update_head};
+use chrono::DateTime;
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Directory holding the state of an am session
+const AM_DIR: &str = ".kitcat/rebase-apply";
+
+/// Options for the am command
+#[derive(Debug, Clone, Default)]
+pub struct AmOptions {
+    /// Mbox files to apply (standard input if empty)
+    pub mbox_files: Vec<String>,
+    /// Commit the resolved patch and carry on
+    pub r#continue: bool,
+    /// Skip the current patch
+    pub skip: bool,
+    /// Abort the session and restore the original HEAD
+    pub abort: bool,
+    /// Fall back to a three-way merge when a patch does not apply
+    pub three_way: bool,
+}
+
+/// A patch message split into its parts
+#[derive(Debug, Clone, PartialEq)]
+pub struct MailPatch {
+    /// Author as "Name <email>"
+    pub author: String,
+    /// Author timestamp (seconds since epoch)
+    pub author_time: i64,
+    /// Author timezone offset, e.g. "+0200"
+    pub author_timezone: String,
+    /// Commit message (subject, blank line, body)
+    pub message: String,
+    /// Everything after the `---` separator
+    pub diff: String,
+}
+
+/// Main am command
+pub fn am(options: AmOptions) -> Result<()> {
+    let in_progress = Path::new(AM_DIR).exists();
+
+    if options.abort {
+        return abort_am();
+    }
+
+    if options.r#continue || options.skip {
+        if !in_progress {
+            return Err(KitcatError::NotFound("No am session in progress".to_string()));
+        }
+
+        if options.skip {
+            if let Some(head) = resolve_head()? {
+                reset_paths_to_commit(&head, current_patch_paths()?.iter().map(String::as_str))?;
+            }
+        } else {
+            commit_resolved()?;
+        }
+
+        advance()?;
+        return run_session(options.three_way);
+    }
+
+    if in_progress {
+        return Err(KitcatError::InvalidInput("An am session is already in progress. Use --continue, --skip or --abort".to_string()));
+    }
+
+    start_session(&options)?;
+    run_session(options.three_way)
+}
+
+/// Split the input into messages and record the session state
+fn start_session(options: &AmOptions) -> Result<()> {
+    let mut messages = Vec::new();
+    if options.mbox_files.is_empty() {
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text)?;
+        messages.extend(split_mbox(&text));
+    } else {
+        for file in &options.mbox_files {
+            messages.extend(split_mbox(&fs::read_to_string(file)?));
+        }
+    }
+
+    if messages.is_empty() {
+        return Err(KitcatError::InvalidInput("No patches found in input".to_string()));
+    }
+
+    let head = resolve_head()?.ok_or_else(|| {
+        KitcatError::NotFound("Cannot apply patches on top of an empty branch".to_string())
+    })?;
+
+    fs::create_dir_all(AM_DIR)?;
+    for (i, message) in messages.iter().enumerate() {
+        fs::write(message_path(i + 1), message)?;
+    }
+    fs::write(format!("{}/next", AM_DIR), "1")?;
+    fs::write(format!("{}/last", AM_DIR), messages.len().to_string())?;
+    fs::write(format!("{}/orig-head", AM_DIR), &head)?;
+
+    Ok(())
+}
+
+/// Apply and commit the remaining patches of the session
+fn run_session(three_way: bool) -> Result<()> {
+    loop {
+        let (next, last) = read_progress()?;
+        if next > last {
+            fs::remove_dir_all(AM_DIR)?;
+            return Ok(());
+        }
+
+        let patch = parse_mail(&fs::read_to_string(message_path(next))?)?;
+        println!("Applying: {}", subject(&patch));
+
+        let apply_options = ApplyOptions {
+            index: true,
+            three_way,
+            ..ApplyOptions::default()
+        };
+
+        if let Err(e) = apply_patch_text(&patch.diff, &apply_options) {
+            eprintln!("error: {}", e);
+            eprintln!("Patch failed at {:04} {}", next, subject(&patch));
+            eprintln!("When you have resolved this problem, run \"kitcat am --continue\".");
+            eprintln!("If you prefer to skip this patch, run \"kitcat am --skip\" instead.");
+            eprintln!(
+                "To restore the original branch and stop patching, run \"kitcat am --abort\"."
+            );
+            return Err(KitcatError::Conflict("Patch failed to apply".to_string()));
+        }
+
+        commit_patch(&patch)?;
+        advance()?;
+    }
+}
+
+/// Commit the current patch after the user resolved it by hand
+fn commit_resolved() -> Result<()> {
+    let (next, _) = read_progress()?;
+    let patch = parse_mail(&fs::read_to_string(message_path(next))?)?;
+    commit_patch(&patch)
+}
+
+/// Commit the index with the patch's authorship on top of HEAD
+fn commit_patch(patch: &MailPatch) -> Result<()> {
+    let head = resolve_head()?.ok_or_else(|| {
+        KitcatError::NotFound("HEAD does not point to a commit".to_string())
+    })?;
+
+    let entries = read_index_binary()?;
+    let tree_hash = write_tree_from_index(&entries)?;
+    if tree_hash == get_commit_tree(&head)? {
+        return Err(KitcatError::InvalidInput("No changes - did you forget to use 'kitcat add'? Use 'kitcat am --skip' to skip this patch".to_string()));
+    }
+
+    let commit_hash = create_commit_with_author(
+        &tree_hash,
+        &[head],
+        &patch.message,
+        &Signature {
+            identity: patch.author.clone(),
+            time: patch.author_time,
+            timezone: patch.author_timezone.clone(),
+        },
+    )?;
+    update_head(&commit_hash)?;
+
+    Ok(())
+}
+
+/// Abort the session and restore the original HEAD
+fn abort_am() -> Result<()> {
+    let orig_head_path = format!("{}/orig-head", AM_DIR);
+    if !Path::new(&orig_head_path).exists() {
+        return Err(KitcatError::NotFound("No am session in progress".to_string()));
+    }
+
+    let orig_head = fs::read_to_string(&orig_head_path)?.trim().to_string();
+
+    // Paths of the committed patches and of the one that stopped; other local changes are kept
+    let mut paths = current_patch_paths()?;
+    if let Some(head) = resolve_head()? {
+        paths.extend(changed_paths(&get_commit_files(&orig_head)?, &get_commit_files(&head)?));
+    }
+    reset_paths_to_commit(&orig_head, paths.iter().map(String::as_str))?;
+    update_head(&orig_head)?;
+
+    fs::remove_dir_all(AM_DIR)?;
+    println!("Am session aborted");
+    Ok(())
+}
+
+/// Read the `next` and `last` patch numbers
+fn read_progress() -> Result<(usize, usize)> {
+    let read_number = |name: &str| -> Result<usize> {
+        fs::read_to_string(format!("{}/{}", AM_DIR, name))?
+            .trim()
+            .parse()
+            .map_err(|_| KitcatError::CorruptObject("Corrupt am state".to_string()))
+    };
+    Ok((read_number("next")?, read_number("last")?))
+}
+
+/// Paths the patch being applied touches (none once all patches are done)
+fn current_patch_paths() -> Result<BTreeSet<String>> {
+    let (next, last) = read_progress()?;
+    if next > last {
+        return Ok(BTreeSet::new());
+    }
+
+    // A patch that does not parse was not applied at all
+    let patch = parse_mail(&fs::read_to_string(message_path(next))?)?;
+    Ok(parse_patch(&patch.diff)
+        .unwrap_or_default()
+        .into_iter()
+        .flat_map(|file| file.old_path.into_iter().chain(file.new_path))
+        .collect())
+}
+
+/// Move on to the next patch
+fn advance() -> Result<()> {
+    let (next, _) = read_progress()?;
+    fs::write(format!("{}/next", AM_DIR), (next + 1).to_string())?;
+    Ok(())
+}
+
+/// Path of the saved message for a patch number
+fn message_path(number: usize) -> String {
+    format!("{}/{:04}", AM_DIR, number)
+}
+
+/// First line of the patch's commit message
+fn subject(patch: &MailPatch) -> &str {
+    patch.message.lines().next().unwrap_or("")
+}
+
+/// Split an mbox into individual messages on `From <hash>` lines
+pub fn split_mbox(text: &str) -> Vec<String> {
+    let mut messages = Vec::new();
+    let mut current = String::new();
+
+    for line in text.lines() {
+        if is_mbox_separator(line) && !current.trim().is_empty() {
+            messages.push(std::mem::take(&mut current));
+        }
+        current.push_str(line);
+        current.push('\n');
+    }
+
+    if !current.trim().is_empty() {
+        messages.push(current);
+    }
+
+    messages
+}
+
+/// Whether a line starts a new mbox message
+fn is_mbox_separator(line: &str) -> bool {
+    line.strip_prefix("From ")
+        .and_then(|rest| rest.split_whitespace().next())
+        .is_some_and(|hash| hash.len() == 40 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Parse one mbox message into authorship, message and diff
+pub fn parse_mail(text: &str) -> Result<MailPatch> {
+    let mut lines = text.lines().peekable();
+    if lines.peek().is_some_and(|l| is_mbox_separator(l)) {
+        lines.next();
+    }
+
+    // Headers, unfolding continuation lines
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for line in lines.by_ref() {
+        if line.is_empty() {
+            break;
+        }
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    };
+
+    let author = header("from")
+        .ok_or_else(|| KitcatError::InvalidInput("Patch has no From: header".to_string()))?
+        .to_string();
+    let subject = strip_patch_prefix(header("subject").unwrap_or(""));
+
+    let date = header("date")
+        .and_then(|d| DateTime::parse_from_rfc2822(d).ok())
+        .ok_or_else(|| {
+            KitcatError::InvalidInput("Patch has no valid Date: header".to_string())
+        })?;
+    let offset = date.offset().local_minus_utc();
+    let author_timezone = format!(
+        "{}{:02}{:02}",
+        if offset < 0 { '-' } else { '+' },
+        offset.abs() / 3600,
+        (offset.abs() % 3600) / 60
+    );
+
+    // Body up to the `---` separator, then the diff
+    let mut body = Vec::new();
+    let mut diff = String::new();
+    let mut in_diff = false;
+    for line in lines {
+        if in_diff {
+            diff.push_str(line);
+            diff.push('\n');
+        } else if line == "---" || line.starts_with("diff --git ") {
+            in_diff = true;
+            if line != "---" {
+                diff.push_str(line);
+                diff.push('\n');
+            }
+        } else {
+            body.push(line);
+        }
+    }
+
+    let body = body.join("\n");
+    let body = body.trim();
+    let message = if body.is_empty() {
+        subject
+    } else {
+        format!("{}\n\n{}", subject, body)
+    };
+
+    Ok(MailPatch {
+        author,
+        author_time: date.timestamp(),
+        author_timezone,
+        message,
+        diff,
+    })
+}
+
+/// Strip a leading `[PATCH ...]` tag from a subject
+fn strip_patch_prefix(subject: &str) -> String {
+    let mut subject = subject.trim();
+    while subject.starts_with('[') {
+        match subject.find(']') {
+            Some(end) => subject = subject[end + 1..].trim_start(),
+            None => break,
+        }
+    }
+    subject.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAIL: &str = "From 1234567890123456789012345678901234567890 Mon Sep 17 00:00:00 2001
+From: Jane Doe <jane@example.com>
+Date: Tue, 1 Jul 2003 10:52:37 +0200
+Subject: [PATCH 1/2] Fix the
+ parser
+
+Handle empty input.
+---
+ a.txt | 2 +-
+ 1 file changed, 1 insertion(+), 1 deletion(-)
+
+diff --git a/a.txt b/a.txt
+--- a/a.txt
++++ b/a.txt
+@@ -1 +1 @@
+-old
++new
+--
+kitcat 0.2.0
+";
+
+    #[test]
+    fn test_parse_mail() {
+        let patch = parse_mail(MAIL).unwrap();
+        assert_eq!(patch.author, "Jane Doe <jane@example.com>");
+        assert_eq!(patch.author_time, 1057049557);
+        assert_eq!(patch.author_timezone, "+0200");
+        assert_eq!(patch.message, "Fix the parser\n\nHandle empty input.");
+        assert!(patch.diff.contains("diff --git a/a.txt b/a.txt"));
+    }
+
+    #[test]
+    fn test_split_mbox() {
+        let text = format!("{}{}", MAIL, MAIL.replace("[PATCH 1/2]", "[PATCH 2/2]"));
+        let messages = split_mbox(&text);
+        assert_eq!(messages.len(), 2);
+        assert!(messages[1].contains("[PATCH 2/2]"));
+    }
+
+    /// Run `am` on a single message written to an mbox file
+    fn am_message(diff: &str) -> Result<()> {
+        let message = MAIL.replace(&MAIL[MAIL.find("diff --git").unwrap()..], diff);
+        fs::write("patch.mbox", message).unwrap();
+        am(AmOptions {
+            mbox_files: vec!["patch.mbox".to_string()],
+            ..AmOptions::default()
+        })
+    }
+
+    #[test]
+    fn test_am_rejects_kitcat_paths() {
+        crate::utils::in_temp_repo("am-kitcat-path", || {
+            let head = crate::utils::commit_files(&[("a.txt", "old\n")], "base");
+            let diff = "diff --git a/.kitcat/hooks/pre-commit b/.kitcat/hooks/pre-commit
+new file mode 100755
+--- /dev/null
++++ b/.kitcat/hooks/pre-commit
+@@ -0,0 +1 @@
++#!/bin/sh
+";
+            assert!(am_message(diff).is_err());
+            assert!(!Path::new(".kitcat/hooks/pre-commit").exists());
+            assert_eq!(resolve_head().unwrap(), Some(head));
+        });
+    }
+
+    #[test]
+    fn test_am_keeps_executable_mode() {
+        use crate::models::MODE_EXECUTABLE;
+        use std::os::unix::fs::PermissionsExt;
+
+        crate::utils::in_temp_repo("am-mode", || {
+            crate::utils::commit_files(&[("a.txt", "old\n")], "base");
+            let diff = "diff --git a/a.txt b/a.txt
+old mode 100644
+new mode 100755
+diff --git a/run.sh b/run.sh
+new file mode 100755
+--- /dev/null
++++ b/run.sh
+@@ -0,0 +1 @@
++echo hi
+";
+            am_message(diff).unwrap();
+            for path in ["a.txt", "run.sh"] {
+                let mode = fs::metadata(path).unwrap().permissions().mode();
+                assert_ne!(mode & 0o111, 0, "{} is not executable", path);
+                let entry = read_index_binary()
+                    .unwrap()
+                    .into_iter()
+                    .find(|e| e.path == path)
+                    .unwrap();
+                assert_eq!(entry.mode, MODE_EXECUTABLE);
+            }
+        });
+    }
+
+    #[test]
+    fn test_skip_and_abort_keep_unrelated_local_changes() {
+        crate::utils::in_temp_repo("am-skip-abort", || {
+            let base = crate::utils::commit_files(&[("a.txt", "old\n"), ("x.txt", "x\n")], "base");
+            let second = MAIL.replace("[PATCH 1/2]", "[PATCH 2/2]").replace("-old\n+new", "-zzz\n+yyy");
+            fs::write("series.mbox", format!("{}{}", MAIL, second)).unwrap();
+            fs::write("x.txt", "x local\n").unwrap();
+            let run = |options: AmOptions| am(AmOptions { mbox_files: vec!["series.mbox".to_string()], ..options });
+
+            // The second patch does not apply
+            assert!(run(AmOptions::default()).is_err());
+            run(AmOptions { abort: true, ..AmOptions::default() }).unwrap();
+            assert_eq!(resolve_head().unwrap(), Some(base.clone()));
+            assert_eq!(fs::read_to_string("a.txt").unwrap(), "old\n");
+            assert_eq!(fs::read_to_string("x.txt").unwrap(), "x local\n");
+
+            assert!(run(AmOptions::default()).is_err());
+            run(AmOptions { skip: true, ..AmOptions::default() }).unwrap();
+            assert!(!Path::new(AM_DIR).exists());
+            assert_ne!(resolve_head().unwrap(), Some(base));
+            assert_eq!(fs::read_to_string("a.txt").unwrap(), "new\n");
+            assert_eq!(fs::read_to_string("x.txt").unwrap(), "x local\n");
+        });
+    }
+
+    #[test]
+    fn test_strip_patch_prefix() {
+        assert_eq!(strip_patch_prefix("[PATCH v2 3/7] Add x"), "Add x");
+        assert_eq!(strip_patch_prefix("Add x"), "Add x");
+    }
+}
@@ -0,0 +1,293 @@
+/// Apply command implementation
+///
+/// Applies unified diffs (including Git binary patches) to the working
+/// tree and optionally to the index:
+/// - Hunks are located with offset and fuzz matching
+/// - `--3way` falls back to a three-way merge against the recorded preimage
+/// - `-R` applies the patch in reverse
+/// - `--check` only verifies that the patch applies
+/// - `new file mode`, `old mode`/`new mode` lines set the file mode
+
+use crate::diff::blob_hash;
+use crate::diff::patch::{apply_hunks, parse_patch, FilePatch};
+use crate::error::{KitcatError, Result};
+use crate::index::{add_file_to_index, read_index_binary, remove_file_from_index};
+use crate::merge::three_way::{merge_text_hunks, resolved_text};
+use crate::merge::{FileConflict, MarkerOptions, StrategyOptions};
+use crate::object::{read_blob_content, resolve_object_hash, write_blob};
+use crate::utils::write_worktree_file;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Options for the apply command
+#[derive(Debug, Clone, Default)]
+pub struct ApplyOptions {
+    /// Patch file to read (None or "-" reads standard input)
+    pub patch: Option<String>,
+    /// Only check whether the patch applies
+    pub check: bool,
+    /// Apply to the index as well as the working tree
+    pub index: bool,
+    /// Fall back to a three-way merge when context does not match
+    pub three_way: bool,
+    /// Apply the patch in reverse
+    pub reverse: bool,
+}
+
+/// What applying a patch does to a file
+#[derive(Debug)]
+enum FileOutcome {
+    /// Write new content
+    Write(Vec<u8>),
+    /// Remove the file
+    Delete,
+}
+
+/// Result of applying a patch to one file
+#[derive(Debug)]
+struct FileResult {
+    path: String,
+    outcome: FileOutcome,
+    conflicted: bool,
+    /// Mode the patch sets (None keeps the file's mode)
+    mode: Option<u32>,
+}
+
+/// Main apply command
+pub fn apply(options: ApplyOptions) -> Result<()> {
+    let text = read_patch_input(options.patch.as_deref())?;
+    apply_patch_text(&text, &options)
+}
+
+/// Apply the patches in `text` (the `patch` option is ignored)
+pub fn apply_patch_text(text: &str, options: &ApplyOptions) -> Result<()> {
+    let mut patches = parse_patch(text)?;
+
+    if patches.is_empty() {
+        return Err(KitcatError::InvalidInput("No valid patches in input".to_string()));
+    }
+
+    if options.reverse {
+        patches = patches
+            .iter()
+            .map(|p| p.reversed())
+            .collect::<Result<Vec<_>>>()?;
+    }
+
+    // Work out every file's new content before touching anything,
+    // so a failing patch leaves the tree unchanged
+    let mut results = Vec::new();
+    let mut failed = false;
+
+    for patch in &patches {
+        match apply_file_patch(patch, options) {
+            Ok(result) => results.push(result),
+            Err(e) => {
+                eprintln!("error: {}: {}", patch.path(), e);
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        return Err(KitcatError::Conflict("patch does not apply".to_string()));
+    }
+
+    if options.check {
+        return Ok(());
+    }
+
+    let mut conflicts = Vec::new();
+    for result in &results {
+        write_result(result, options.index)?;
+        if result.conflicted {
+            conflicts.push(result.path.clone());
+        }
+    }
+
+    if !conflicts.is_empty() {
+        for path in &conflicts {
+            println!("U {}", path);
+        }
+        return Err(KitcatError::Conflict(format!(
+            "Applied patch with conflicts in {} file(s)",
+            conflicts.len()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Read the patch text from a file or standard input
+fn read_patch_input(path: Option<&str>) -> Result<String> {
+    match path {
+        Some(path) if path != "-" => Ok(fs::read_to_string(path)?),
+        _ => {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text)?;
+            Ok(text)
+        }
+    }
+}
+
+/// Compute the result of applying one file patch
+fn apply_file_patch(patch: &FilePatch, options: &ApplyOptions) -> Result<FileResult> {
+    let path = patch.path().to_string();
+    let current = if Path::new(&path).exists() {
+        Some(fs::read(&path)?)
+    } else {
+        None
+    };
+
+    if patch.is_creation() && current.is_some() {
+        return Err(KitcatError::AlreadyExists("already exists in working directory".to_string()));
+    }
+
+    if !patch.is_creation() && current.is_none() {
+        return Err(KitcatError::Conflict("does not exist in working directory".to_string()));
+    }
+
+    if options.index {
+        check_matches_index(&path, current.as_deref())?;
+    }
+
+    let current = current.unwrap_or_default();
+
+    if let Some(binary) = &patch.binary {
+        if let Some(old_hash) = &patch.old_hash
+            && !is_null_hash(old_hash)
+            && !blob_hash(&current).starts_with(old_hash.as_str())
+        {
+            return Err(KitcatError::Conflict("binary patch does not apply (preimage mismatch)".to_string()));
+        }
+
+        let content = binary.forward.apply(&current)?;
+        return Ok(finish(patch, path, content, false));
+    }
+
+    let current_text = String::from_utf8_lossy(&current);
+    match apply_hunks(&current_text, patch) {
+        Ok((content, reports)) => {
+            for report in &reports {
+                if let Some(message) = report.describe() {
+                    println!("{}: {}", path, message);
+                }
+            }
+            Ok(finish(patch, path, content.into_bytes(), false))
+        }
+        Err(failure) if options.three_way => {
+            println!(
+                "{}: Hunk #{} does not apply at line {}, falling back to three-way merge",
+                path, failure.number, failure.line
+            );
+            three_way_apply(patch, path, &current_text)
+        }
+        Err(failure) => Err(KitcatError::Conflict(format!("Hunk #{} FAILED at {}", failure.number, failure.line))),
+    }
+}
+
+/// Build the file result, turning an emptied deleted file into a removal
+fn finish(patch: &FilePatch, path: String, content: Vec<u8>, conflicted: bool) -> FileResult {
+    let outcome = if patch.is_deletion() && content.is_empty() {
+        FileOutcome::Delete
+    } else {
+        FileOutcome::Write(content)
+    };
+
+    FileResult {
+        path,
+        outcome,
+        conflicted,
+        mode: patch.new_mode,
+    }
+}
+
+/// Apply the patch to its recorded preimage and merge the result into the current file
+fn three_way_apply(patch: &FilePatch, path: String, current: &str) -> Result<FileResult> {
+    let old_hash = patch
+        .old_hash
+        .as_deref()
+        .filter(|h| !is_null_hash(h))
+        .ok_or_else(|| {
+            KitcatError::NotFound("patch does not record a preimage, cannot fall back to three-way merge".to_string())
+        })?;
+
+    let base_hash = resolve_object_hash(old_hash)?;
+    let base = String::from_utf8_lossy(&read_blob_content(&base_hash)?).to_string();
+
+    let (theirs, _) = apply_hunks(&base, patch).map_err(|_| {
+        KitcatError::Conflict("patch does not apply to its recorded preimage".to_string())
+    })?;
+
+    let hunks = merge_text_hunks(&base, current, &theirs, &StrategyOptions::default());
+    match resolved_text(&hunks) {
+        Some(merged) => {
+            println!("Applied patch to '{}' cleanly.", path);
+            Ok(finish(patch, path, merged.into_bytes(), false))
+        }
+        None => {
+            println!("Applied patch to '{}' with conflicts.", path);
+            let mut conflict = FileConflict::new(path.clone());
+            conflict.base_content = Some(base.into_bytes());
+            conflict.our_content = Some(current.as_bytes().to_vec());
+            conflict.their_content = Some(theirs.into_bytes());
+            conflict.hunks = hunks;
+            let content =
+                conflict.generate_conflict_markers("ours", "theirs", &MarkerOptions::load(None, "base")?);
+            Ok(finish(patch, path, content, true))
+        }
+    }
+}
+
+/// With `--index`, the working tree file must match the index entry
+fn check_matches_index(path: &str, current: Option<&[u8]>) -> Result<()> {
+    let entries = read_index_binary()?;
+    let entry = entries.iter().find(|e| e.path == path);
+
+    let matches = match (entry, current) {
+        (Some(entry), Some(content)) => entry.hash == blob_hash(content),
+        (None, None) => true,
+        _ => false,
+    };
+
+    if matches {
+        Ok(())
+    } else {
+        Err(KitcatError::Conflict("does not match index".to_string()))
+    }
+}
+
+/// Write one file result to the working tree (and index)
+fn write_result(result: &FileResult, update_index: bool) -> Result<()> {
+    match &result.outcome {
+        FileOutcome::Write(content) => {
+            if let Some(mode) = result.mode {
+                write_worktree_file(&result.path, content, mode)?;
+            } else {
+                if let Some(parent) = Path::new(&result.path).parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(&result.path, content)?;
+            }
+
+            if update_index && !result.conflicted {
+                let hash = write_blob(content)?;
+                add_file_to_index(&result.path, &hash)?;
+            }
+        }
+        FileOutcome::Delete => {
+            fs::remove_file(&result.path)?;
+            if update_index {
+                remove_file_from_index(&result.path)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether a hash is the all-zero hash used for missing files
+fn is_null_hash(hash: &str) -> bool {
+    hash.chars().all(|c| c == '0')
+}
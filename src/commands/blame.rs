@@ -0,0 +1,672 @@
+/// Blame command implementation
+///
+/// Attributes each line of a file to the commit that introduced it. Lines
+/// start out blamed on the given revision and are passed down to a parent
+/// whenever the parent's version of the file has them unchanged (through
+/// the diff's edit script); whatever is left is blamed on the commit itself.
+/// Commits are visited newest first, so every commit is looked at once.
+///
+/// - A file added by a rename is followed to its old name
+/// - `-w`: lines differing only in whitespace are unchanged
+/// - `-M`: blocks of lines moved within the file are passed to the parent
+/// - `-C`: also blocks copied from other files changed by the commit
+///   (`-C -C`: from any file of the parent)
+/// - Ignored revisions (`--ignore-rev`, `--ignore-revs-file` and
+///   `blame.ignoreRevsFile`) pass their changed lines to the parent's lines
+///   at the same place in the hunk
+///
+/// Moved and copied blocks need at least `MOVE_SCORE` alphanumeric characters.
+
+use crate::commands::pretty::{format_date_as, person_email, person_name, subject, DateMode};
+use crate::config::Config;
+use crate::diff::algorithm::{compute_edit_script, Edit};
+use crate::diff::rename::find_rename_source;
+use crate::error::{KitcatError, Result};
+use crate::merge::get_commit_files;
+use crate::models::{Commit, FileEntry};
+use crate::object::{read_blob_content, read_commit};
+use crate::repo::resolve_revision;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::fs;
+
+/// Alphanumeric characters a moved or copied block needs to be detected
+const MOVE_SCORE: usize = 20;
+
+/// Options for the blame command
+#[derive(Debug, Clone, Default)]
+pub struct BlameOptions {
+    /// Revision to blame (defaults to HEAD)
+    pub revision: Option<String>,
+    /// File to blame
+    pub path: String,
+    /// Line ranges to blame (`<start>,<end>`, `<start>,+<count>` or `<start>`)
+    pub ranges: Vec<String>,
+    /// Machine-readable output, commit headers shown once
+    pub porcelain: bool,
+    /// Machine-readable output, commit headers on every line
+    pub line_porcelain: bool,
+    /// Ignore whitespace when comparing lines
+    pub ignore_whitespace: bool,
+    /// Detect lines moved within the file
+    pub detect_moves: bool,
+    /// Detect lines copied from other files (1: changed files, 2: any file)
+    pub detect_copies: u8,
+    /// Revisions whose changes are passed on to their parent
+    pub ignore_revs: Vec<String>,
+    /// Files listing revisions to ignore, one per line
+    pub ignore_revs_files: Vec<String>,
+}
+
+/// A line of the blamed file and where it came from
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlameLine {
+    /// Commit the line is blamed on
+    pub commit: String,
+    /// Path of the file in that commit
+    pub path: String,
+    /// Line number in that commit's version of the file
+    pub orig_line: usize,
+    /// Line number in the blamed file
+    pub final_line: usize,
+    /// Content of the line
+    pub content: String,
+}
+
+/// How lines are compared and passed on
+#[derive(Debug, Clone, Default)]
+pub struct BlameSettings {
+    /// Ignore whitespace when comparing lines
+    pub ignore_whitespace: bool,
+    /// Detect lines moved within the file
+    pub detect_moves: bool,
+    /// Detect lines copied from other files (1: changed files, 2: any file)
+    pub detect_copies: u8,
+    /// Commits whose changes are passed on to their parent
+    pub ignore_revs: HashSet<String>,
+}
+
+/// Main blame command
+pub fn blame(options: BlameOptions) -> Result<()> {
+    let revision = options.revision.as_deref().unwrap_or("HEAD");
+    let hash = resolve_revision(revision)?;
+
+    let files = get_commit_files(&hash)?;
+    let entry = files.get(&options.path).ok_or_else(|| {
+        KitcatError::NotFound(format!("no such path '{}' in {}", options.path, revision))
+    })?;
+    let line_count = split_lines(&read_blob_content(&entry.hash)?).len();
+
+    let ranges = if options.ranges.is_empty() {
+        vec![(1, line_count)]
+    } else {
+        options
+            .ranges
+            .iter()
+            .map(|spec| parse_range(spec, line_count))
+            .collect::<Result<_>>()?
+    };
+
+    let settings = BlameSettings {
+        ignore_whitespace: options.ignore_whitespace,
+        detect_moves: options.detect_moves || options.detect_copies > 0,
+        detect_copies: options.detect_copies,
+        ignore_revs: load_ignore_revs(&options)?,
+    };
+
+    let lines = blame_file(&hash, &options.path, &ranges, &settings)?;
+    if options.porcelain || options.line_porcelain {
+        print!("{}", format_porcelain(&lines, options.line_porcelain)?);
+    } else {
+        print!("{}", format_blame(&lines, &options.path)?);
+    }
+
+    Ok(())
+}
+
+/// Revisions to ignore from the options and `blame.ignoreRevsFile`
+fn load_ignore_revs(options: &BlameOptions) -> Result<HashSet<String>> {
+    let mut files = options.ignore_revs_files.clone();
+    if let Some(file) = Config::read()?.blame.ignore_revs_file {
+        files.push(file);
+    }
+
+    let mut revisions = options.ignore_revs.clone();
+    for file in files {
+        let content = fs::read_to_string(&file).map_err(|e| {
+            KitcatError::from(e).context(format!("could not read ignore-revs file '{}'", file))
+        })?;
+        revisions.extend(
+            content
+                .lines()
+                .map(|line| line.split('#').next().unwrap_or("").trim())
+                .filter(|line| !line.is_empty())
+                .map(str::to_string),
+        );
+    }
+
+    revisions.iter().map(|rev| resolve_revision(rev)).collect()
+}
+
+/// Parse `-L <start>,<end>`, `<start>,+<count>` or `<start>` into a 1-indexed inclusive range
+fn parse_range(spec: &str, line_count: usize) -> Result<(usize, usize)> {
+    let invalid = || {
+        KitcatError::InvalidInput(format!("Invalid line range '{}'", spec))
+    };
+    let number = |text: &str| text.trim().parse::<usize>().map_err(|_| invalid());
+
+    let (start, end) = match spec.split_once(',') {
+        Some((start, end)) => {
+            let start = number(start)?;
+            let end = match end.trim().strip_prefix('+') {
+                Some(count) => start + number(count)?.saturating_sub(1),
+                None => number(end)?,
+            };
+            (start, end)
+        }
+        None => (number(spec)?, line_count),
+    };
+
+    if start == 0 || end < start {
+        return Err(invalid());
+    }
+    if start > line_count {
+        return Err(KitcatError::InvalidInput(format!("file has only {} lines", line_count)));
+    }
+    Ok((start, end.min(line_count)))
+}
+
+/// Lines of some content, without their line endings
+fn split_lines(content: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(content)
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+/// Lines waiting to be blamed in one commit's version of a file
+type Suspects = Vec<(usize, usize)>;
+
+/// Blame lines of `path` as of `hash`; `ranges` are 1-indexed and inclusive
+pub fn blame_file(
+    hash: &str,
+    path: &str,
+    ranges: &[(usize, usize)],
+    settings: &BlameSettings,
+) -> Result<Vec<BlameLine>> {
+    let mut state = BlameState::new(settings);
+
+    // (final line, line in the suspect's version), both 0-indexed
+    let mut lines: Suspects = ranges
+        .iter()
+        .flat_map(|&(start, end)| (start - 1)..end)
+        .map(|line| (line, line))
+        .collect();
+    lines.sort();
+    lines.dedup();
+    let final_lines = state.file_lines(hash, path)?.unwrap_or_default();
+
+    state.queue(hash, path, lines)?;
+    while let Some((hash, path)) = state.next() {
+        state.process(&hash, &path)?;
+    }
+
+    let mut blamed: Vec<BlameLine> = state
+        .blamed
+        .into_iter()
+        .map(|(final_line, (commit, path, orig_line))| BlameLine {
+            commit,
+            path,
+            orig_line: orig_line + 1,
+            final_line: final_line + 1,
+            content: final_lines.get(final_line).cloned().unwrap_or_default(),
+        })
+        .collect();
+    blamed.sort_by_key(|line| line.final_line);
+    Ok(blamed)
+}
+
+/// Suspects waiting to be looked at, and the lines already blamed
+struct BlameState<'a> {
+    settings: &'a BlameSettings,
+    /// Suspects by commit and path
+    pending: HashMap<(String, String), Suspects>,
+    /// Commits to look at, newest committer time first
+    queue: BinaryHeap<(i64, String, String)>,
+    /// Final line -> commit, path and line in that commit (0-indexed)
+    blamed: HashMap<usize, (String, String, usize)>,
+    commits: HashMap<String, Commit>,
+    trees: HashMap<String, HashMap<String, FileEntry>>,
+}
+
+impl<'a> BlameState<'a> {
+    fn new(settings: &'a BlameSettings) -> Self {
+        BlameState {
+            settings,
+            pending: HashMap::new(),
+            queue: BinaryHeap::new(),
+            blamed: HashMap::new(),
+            commits: HashMap::new(),
+            trees: HashMap::new(),
+        }
+    }
+
+    fn commit(&mut self, hash: &str) -> Result<&Commit> {
+        if !self.commits.contains_key(hash) {
+            self.commits.insert(hash.to_string(), read_commit(hash)?);
+        }
+        Ok(&self.commits[hash])
+    }
+
+    fn files(&mut self, hash: &str) -> Result<&HashMap<String, FileEntry>> {
+        if !self.trees.contains_key(hash) {
+            self.trees.insert(hash.to_string(), get_commit_files(hash)?);
+        }
+        Ok(&self.trees[hash])
+    }
+
+    fn file_lines(&mut self, hash: &str, path: &str) -> Result<Option<Vec<String>>> {
+        match self.files(hash)?.get(path) {
+            Some(entry) => Ok(Some(split_lines(&read_blob_content(&entry.hash)?))),
+            None => Ok(None),
+        }
+    }
+
+    /// Add suspects for a commit's version of a file
+    fn queue(&mut self, hash: &str, path: &str, lines: Suspects) -> Result<()> {
+        if lines.is_empty() {
+            return Ok(());
+        }
+        let key = (hash.to_string(), path.to_string());
+        if !self.pending.contains_key(&key) {
+            let time = self.commit(hash)?.committer_time;
+            self.queue.push((time, key.0.clone(), key.1.clone()));
+        }
+        self.pending.entry(key).or_default().extend(lines);
+        Ok(())
+    }
+
+    /// Next commit and path to look at
+    fn next(&mut self) -> Option<(String, String)> {
+        self.queue.pop().map(|(_, hash, path)| (hash, path))
+    }
+
+    /// Pass a commit's suspects to its parents and blame the rest on it
+    fn process(&mut self, hash: &str, path: &str) -> Result<()> {
+        let Some(mut remaining) = self.pending.remove(&(hash.to_string(), path.to_string())) else {
+            return Ok(());
+        };
+        let lines = self.file_lines(hash, path)?.unwrap_or_default();
+        let parents = self.commit(hash)?.parents.clone();
+        let ignore_whitespace = self.settings.ignore_whitespace;
+
+        // Parent versions of the file, following renames
+        let mut sources = Vec::new();
+        for parent in &parents {
+            let parent_files = self.files(parent)?;
+            let parent_path = if parent_files.contains_key(path) {
+                Some(path.to_string())
+            } else {
+                let files = self.trees[hash].clone();
+                find_rename_source(path, &self.trees[parent], &files)?.map(|(source, _)| source)
+            };
+            if let Some(parent_path) = parent_path {
+                let parent_lines = self.file_lines(parent, &parent_path)?.unwrap_or_default();
+                sources.push((parent.clone(), parent_path, parent_lines));
+            }
+        }
+
+        // Unchanged lines
+        let mut guesses = Vec::new();
+        for (parent, parent_path, parent_lines) in &sources {
+            let (kept, guessed) = match_lines(parent_lines, &lines, ignore_whitespace);
+            let (passed, rest): (Suspects, Suspects) =
+                remaining.into_iter().partition(|(_, line)| kept[*line].is_some());
+            remaining = rest;
+            let passed = passed
+                .into_iter()
+                .map(|(final_line, line)| (final_line, kept[line].unwrap_or(line)))
+                .collect();
+            self.queue(parent, parent_path, passed)?;
+            guesses.push(guessed);
+        }
+
+        // Moved and copied blocks
+        if self.settings.detect_moves && !remaining.is_empty() {
+            for (parent, parent_path, parent_lines) in &sources {
+                remaining = self.pass_blocks(remaining, &lines, parent, parent_path, parent_lines)?;
+            }
+        }
+        if self.settings.detect_copies > 0 && !remaining.is_empty() {
+            for parent in &parents {
+                for (other_path, other_lines) in self.copy_sources(hash, parent, path)? {
+                    remaining = self.pass_blocks(remaining, &lines, parent, &other_path, &other_lines)?;
+                }
+            }
+        }
+
+        // Changes of ignored commits go to the first parent's nearby lines
+        if self.settings.ignore_revs.contains(hash)
+            && let (Some((parent, parent_path, _)), Some(guessed)) = (sources.first(), guesses.first())
+        {
+            let (passed, rest): (Suspects, Suspects) =
+                remaining.into_iter().partition(|(_, line)| guessed[*line].is_some());
+            remaining = rest;
+            let passed = passed
+                .into_iter()
+                .map(|(final_line, line)| (final_line, guessed[line].unwrap_or(line)))
+                .collect();
+            self.queue(parent, parent_path, passed)?;
+        }
+
+        for (final_line, line) in remaining {
+            self.blamed.insert(final_line, (hash.to_string(), path.to_string(), line));
+        }
+        Ok(())
+    }
+
+    /// Files of `parent` that lines may have been copied from
+    fn copy_sources(&mut self, hash: &str, parent: &str, path: &str) -> Result<Vec<(String, Vec<String>)>> {
+        let files = self.files(hash)?.clone();
+        let parent_files = self.files(parent)?.clone();
+
+        let mut paths: Vec<&String> = parent_files
+            .iter()
+            .filter(|(other, entry)| {
+                other.as_str() != path
+                    && (self.settings.detect_copies > 1 || files.get(*other) != Some(*entry))
+            })
+            .map(|(other, _)| other)
+            .collect();
+        paths.sort();
+
+        paths
+            .into_iter()
+            .map(|other| Ok((other.clone(), split_lines(&read_blob_content(&parent_files[other].hash)?))))
+            .collect()
+    }
+
+    /// Pass blocks of suspects found in `source_lines` to that file in `parent`
+    fn pass_blocks(
+        &mut self,
+        remaining: Suspects,
+        lines: &[String],
+        parent: &str,
+        source_path: &str,
+        source_lines: &[String],
+    ) -> Result<Suspects> {
+        let ignore_whitespace = self.settings.ignore_whitespace;
+        let mut rest = Vec::new();
+        let mut passed = Vec::new();
+
+        let mut remaining = remaining;
+        remaining.sort_by_key(|(_, line)| *line);
+        let mut i = 0;
+        while i < remaining.len() {
+            // A run of consecutive lines still to blame
+            let mut run_end = i + 1;
+            while run_end < remaining.len() && remaining[run_end].1 == remaining[run_end - 1].1 + 1 {
+                run_end += 1;
+            }
+            let run: Vec<usize> = remaining[i..run_end].iter().map(|(_, line)| *line).collect();
+
+            let mut offset = 0;
+            while offset < run.len() {
+                let block: Vec<&str> = run[offset..].iter().map(|&line| lines[line].as_str()).collect();
+                match find_block(&block, source_lines, ignore_whitespace) {
+                    Some((start, length)) => {
+                        for k in 0..length {
+                            passed.push((remaining[i + offset + k].0, start + k));
+                        }
+                        offset += length;
+                    }
+                    None => {
+                        rest.push(remaining[i + offset]);
+                        offset += 1;
+                    }
+                }
+            }
+            i = run_end;
+        }
+
+        self.queue(parent, source_path, passed)?;
+        Ok(rest)
+    }
+}
+
+/// Form of a line used for comparisons
+fn normalize(line: &str, ignore_whitespace: bool) -> String {
+    if ignore_whitespace {
+        line.chars().filter(|c| !c.is_whitespace()).collect()
+    } else {
+        line.to_string()
+    }
+}
+
+/// Map lines of `new` to lines of `old`
+///
+/// Returns the old line each kept line comes from, and for the ignored
+/// revision heuristic, the old line at the same offset of the hunk for
+/// changed lines (none when the hunk only adds lines).
+fn match_lines(old: &[String], new: &[String], ignore_whitespace: bool) -> (Vec<Option<usize>>, Vec<Option<usize>>) {
+    let old_keys: Vec<String> = old.iter().map(|line| normalize(line, ignore_whitespace)).collect();
+    let new_keys: Vec<String> = new.iter().map(|line| normalize(line, ignore_whitespace)).collect();
+    let old_refs: Vec<&str> = old_keys.iter().map(String::as_str).collect();
+    let new_refs: Vec<&str> = new_keys.iter().map(String::as_str).collect();
+
+    let mut kept = vec![None; new.len()];
+    let (mut old_line, mut new_line) = (0, 0);
+    for edit in compute_edit_script(&old_refs, &new_refs) {
+        match edit {
+            Edit::Keep => {
+                kept[new_line] = Some(old_line);
+                old_line += 1;
+                new_line += 1;
+            }
+            Edit::Delete => old_line += 1,
+            Edit::Insert => new_line += 1,
+        }
+    }
+
+    // Each run of changed lines replaces the old lines between its kept neighbours
+    let mut guessed = vec![None; new.len()];
+    let mut hunk_start = None;
+    for line in 0..=new.len() {
+        if line < new.len() && kept[line].is_none() {
+            hunk_start.get_or_insert(line);
+            continue;
+        }
+        if let Some(start) = hunk_start.take() {
+            let old_start = start.checked_sub(1).and_then(|prev| kept[prev]).map_or(0, |l| l + 1);
+            let old_end = kept.get(line).copied().flatten().unwrap_or(old.len());
+            for (offset, changed) in (start..line).enumerate() {
+                guessed[changed] = Some(old_start + offset).filter(|&old_line| old_line < old_end);
+            }
+        }
+    }
+
+    (kept, guessed)
+}
+
+/// Find the longest prefix of `block` occurring in `source`, if it scores enough
+///
+/// Returns the start in `source` and the number of lines matched.
+fn find_block(block: &[&str], source: &[String], ignore_whitespace: bool) -> Option<(usize, usize)> {
+    let block: Vec<String> = block.iter().map(|line| normalize(line, ignore_whitespace)).collect();
+    let source: Vec<String> = source.iter().map(|line| normalize(line, ignore_whitespace)).collect();
+
+    let mut best: Option<(usize, usize)> = None;
+    for start in 0..source.len() {
+        let length = block
+            .iter()
+            .zip(&source[start..])
+            .take_while(|(a, b)| a == b)
+            .count();
+        if length > 0 && best.is_none_or(|(_, best)| length > best) {
+            best = Some((start, length));
+        }
+    }
+
+    let (start, length) = best?;
+    let score: usize = block[..length]
+        .iter()
+        .map(|line| line.chars().filter(|c| c.is_alphanumeric()).count())
+        .sum();
+    (score >= MOVE_SCORE).then_some((start, length))
+}
+
+/// Default output: `<hash> [<path>] (<author> <date> <line>) <content>`
+///
+/// Root commits get a `^` in front of their hash; the path column is shown
+/// when some lines come from another file.
+fn format_blame(lines: &[BlameLine], path: &str) -> Result<String> {
+    let mut commits: HashMap<&str, Commit> = HashMap::new();
+    for line in lines {
+        if !commits.contains_key(line.commit.as_str()) {
+            commits.insert(&line.commit, read_commit(&line.commit)?);
+        }
+    }
+
+    let show_path = lines.iter().any(|line| line.path != path);
+    let author_width = commits
+        .values()
+        .map(|commit| person_name(&commit.author).chars().count())
+        .max()
+        .unwrap_or(0);
+    let path_width = lines.iter().map(|line| line.path.len()).max().unwrap_or(0);
+    let line_width = lines.last().map_or(1, |line| line.final_line.to_string().len());
+
+    let mut output = String::new();
+    for line in lines {
+        let commit = &commits[line.commit.as_str()];
+        let hash = if commit.parents.is_empty() {
+            format!("^{}", &line.commit[..7])
+        } else {
+            line.commit[..8].to_string()
+        };
+        output.push_str(&hash);
+        if show_path {
+            output.push_str(&format!(" {:<path_width$}", line.path));
+        }
+        output.push_str(&format!(
+            " ({:<author_width$} {} {:>line_width$}) {}\n",
+            person_name(&commit.author),
+            format_date_as(commit.author_time, &commit.author_timezone, DateMode::Iso),
+            line.final_line,
+            line.content
+        ));
+    }
+    Ok(output)
+}
+
+/// `--porcelain` / `--line-porcelain` output
+///
+/// Each group of consecutive lines from one commit starts with
+/// `<hash> <orig line> <final line> <lines in group>`, other lines with
+/// `<hash> <orig line> <final line>`. The commit headers follow the first
+/// line of a commit (or every line with `--line-porcelain`), then the
+/// line itself after a tab.
+fn format_porcelain(lines: &[BlameLine], every_line: bool) -> Result<String> {
+    let mut output = String::new();
+    let mut seen = HashSet::new();
+
+    let mut i = 0;
+    while i < lines.len() {
+        let group = lines[i..]
+            .iter()
+            .enumerate()
+            .take_while(|(k, line)| {
+                line.commit == lines[i].commit
+                    && line.path == lines[i].path
+                    && line.orig_line == lines[i].orig_line + k
+                    && line.final_line == lines[i].final_line + k
+            })
+            .count();
+
+        for (k, line) in lines[i..i + group].iter().enumerate() {
+            output.push_str(&format!("{} {} {}", line.commit, line.orig_line, line.final_line));
+            if k == 0 {
+                output.push_str(&format!(" {}", group));
+            }
+            output.push('\n');
+
+            if every_line || seen.insert((line.commit.clone(), line.path.clone())) {
+                let commit = read_commit(&line.commit)?;
+                output.push_str(&porcelain_headers(&commit, &line.path));
+            }
+            output.push_str(&format!("\t{}\n", line.content));
+        }
+        i += group;
+    }
+
+    Ok(output)
+}
+
+/// Commit headers of porcelain output
+fn porcelain_headers(commit: &Commit, path: &str) -> String {
+    let mut headers = format!(
+        "author {}\nauthor-mail <{}>\nauthor-time {}\nauthor-tz {}\n",
+        person_name(&commit.author),
+        person_email(&commit.author),
+        commit.author_time,
+        commit.author_timezone
+    );
+    headers.push_str(&format!(
+        "committer {}\ncommitter-mail <{}>\ncommitter-time {}\ncommitter-tz {}\n",
+        person_name(&commit.committer),
+        person_email(&commit.committer),
+        commit.committer_time,
+        commit.committer_timezone
+    ));
+    headers.push_str(&format!("summary {}\n", subject(commit)));
+    if commit.parents.is_empty() {
+        headers.push_str("boundary\n");
+    }
+    headers.push_str(&format!("filename {}\n", path));
+    headers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("2,4", 10).unwrap(), (2, 4));
+        assert_eq!(parse_range("3,+2", 10).unwrap(), (3, 4));
+        assert_eq!(parse_range("8", 10).unwrap(), (8, 10));
+        assert_eq!(parse_range("8,20", 10).unwrap(), (8, 10));
+        assert!(parse_range("4,2", 10).is_err());
+        assert!(parse_range("11,12", 10).is_err());
+    }
+
+    #[test]
+    fn test_match_lines() {
+        let old = strings(&["a", "b", "c", "d"]);
+        let new = strings(&["a", "B", "c", "d", "e"]);
+        let (kept, guessed) = match_lines(&old, &new, false);
+        assert_eq!(kept, vec![Some(0), None, Some(2), Some(3), None]);
+        // The changed line pairs with the line it replaced; the added one has none
+        assert_eq!(guessed[1], Some(1));
+        assert_eq!(guessed[4], None);
+
+        // Lines beyond the ones a hunk replaced have nothing to pair with
+        let (_, guessed) = match_lines(&strings(&["a", "b"]), &strings(&["A", "B", "C"]), false);
+        assert_eq!(guessed, vec![Some(0), Some(1), None]);
+
+        let (kept, _) = match_lines(&strings(&["if x {"]), &strings(&["if  x{"]), true);
+        assert_eq!(kept, vec![Some(0)]);
+    }
+
+    #[test]
+    fn test_find_block() {
+        let source = strings(&["fn helper() {", "    compute_something_else();", "}", "other"]);
+        let block = ["    compute_something_else();", "}", "unrelated"];
+        assert_eq!(find_block(&block, &source, false), Some((1, 2)));
+        // Too short to be detected as a move
+        assert_eq!(find_block(&["}"], &source, false), None);
+    }
+}
@@ -1,9 +1,9 @@
+use crate::error::{KitcatError, Result};
 use std::fs;
-use std::io;
 use std::path::Path;
 
 /// List all branches, highlighting the current one
-pub fn list_branches() -> io::Result<()> {
+pub fn list_branches() -> Result<()> {
     let refs_dir = Path::new(".kitcat/refs/heads");
 
     if !refs_dir.exists() {
@@ -37,8 +37,8 @@ pub fn list_branches() -> io::Result<()> {
 }
 
 /// Get the current branch name
-pub fn get_current_branch() -> io::Result<Option<String>> {
-    let head_content = crate::repo::read_head();
+pub fn get_current_branch() -> Result<Option<String>> {
+    let head_content = crate::repo::read_head()?;
 
     if head_content.starts_with("ref: refs/heads/") {
         let branch = head_content
@@ -52,44 +52,32 @@ pub fn get_current_branch() -> io::Result<Option<String>> {
 }
 
 /// Create a new branch at the current HEAD
-pub fn create_branch(name: &str) -> io::Result<()> {
+pub fn create_branch(name: &str) -> Result<()> {
     // Validate branch name
     if name.is_empty() {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            "Branch name cannot be empty",
-        ));
+        return Err(KitcatError::InvalidInput("Branch name cannot be empty".to_string()));
     }
 
     if name.contains("..") || name.contains(" ") || name.starts_with('-') {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            "Invalid branch name",
-        ));
+        return Err(KitcatError::InvalidInput("Invalid branch name".to_string()));
     }
 
     let branch_path = Path::new(".kitcat/refs/heads").join(name);
 
     // Check if branch already exists
     if branch_path.exists() {
-        return Err(io::Error::new(
-            io::ErrorKind::AlreadyExists,
-            format!("Branch '{}' already exists", name),
-        ));
+        return Err(KitcatError::AlreadyExists(format!("Branch '{}' already exists", name)));
     }
 
     // Get current HEAD commit
-    let head_content = crate::repo::read_head();
+    let head_content = crate::repo::read_head()?;
     let commit_hash = if head_content.starts_with("ref:") {
         // HEAD points to a branch - get that branch's commit
         let branch_ref = head_content.trim_start_matches("ref: ").trim();
         let branch_file = format!(".kitcat/{}", branch_ref);
 
         if !Path::new(&branch_file).exists() {
-            return Err(io::Error::new(
-                io::ErrorKind::NotFound,
-                "Cannot create branch: no commits yet",
-            ));
+            return Err(KitcatError::NotFound("Cannot create branch: no commits yet".to_string()));
         }
 
         fs::read_to_string(&branch_file)?.trim().to_string()
@@ -97,18 +85,12 @@ pub fn create_branch(name: &str) -> io::Result<()> {
         // Detached HEAD - use the commit hash directly
         head_content
     } else {
-        return Err(io::Error::new(
-            io::ErrorKind::NotFound,
-            "Cannot create branch: no commits yet",
-        ));
+        return Err(KitcatError::NotFound("Cannot create branch: no commits yet".to_string()));
     };
 
     // Validate commit hash
     if commit_hash.len() != 40 {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidData,
-            "Invalid commit hash",
-        ));
+        return Err(KitcatError::CorruptObject("Invalid commit hash".to_string()));
     }
 
     // Create the branch file
@@ -120,24 +102,18 @@ pub fn create_branch(name: &str) -> io::Result<()> {
 }
 
 /// Delete a branch
-pub fn delete_branch(name: &str, force: bool) -> io::Result<()> {
+pub fn delete_branch(name: &str, force: bool) -> Result<()> {
     let branch_path = Path::new(".kitcat/refs/heads").join(name);
 
     // Check if branch exists
     if !branch_path.exists() {
-        return Err(io::Error::new(
-            io::ErrorKind::NotFound,
-            format!("Branch '{}' not found", name),
-        ));
+        return Err(KitcatError::NotFound(format!("Branch '{}' not found", name)));
     }
 
     // Check if trying to delete current branch
     if let Ok(Some(current)) = get_current_branch() {
         if current == name {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                format!("Cannot delete the current branch '{}'", name),
-            ));
+            return Err(KitcatError::InvalidInput(format!("Cannot delete the current branch '{}'", name)));
         }
     }
 
@@ -153,15 +129,12 @@ pub fn delete_branch(name: &str, force: bool) -> io::Result<()> {
 }
 
 /// Switch to a different branch
-pub fn switch_branch(name: &str) -> io::Result<()> {
+pub fn switch_branch(name: &str) -> Result<()> {
     let branch_path = Path::new(".kitcat/refs/heads").join(name);
 
     // Check if branch exists
     if !branch_path.exists() {
-        return Err(io::Error::new(
-            io::ErrorKind::NotFound,
-            format!("Branch '{}' not found", name),
-        ));
+        return Err(KitcatError::NotFound(format!("Branch '{}' not found", name)));
     }
 
     // Check if already on this branch
@@ -183,18 +156,18 @@ pub fn switch_branch(name: &str) -> io::Result<()> {
 
     // Update HEAD to point to the new branch
     let new_head = format!("ref: refs/heads/{}", name);
-    crate::repo::write_head(&new_head);
+    crate::repo::write_head(&new_head)?;
 
     println!("Switched to branch '{}'", name);
     Ok(())
 }
 
 /// Show the current branch
-pub fn show_current_branch() -> io::Result<()> {
+pub fn show_current_branch() -> Result<()> {
     match get_current_branch()? {
         Some(branch) => println!("{}", branch),
         None => {
-            let head = crate::repo::read_head();
+            let head = crate::repo::read_head()?;
             println!("HEAD detached at {}", &head[0..7]);
         }
     }
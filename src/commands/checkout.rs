@@ -1,13 +1,18 @@
+use crate::diff::is_binary;
+use crate::error::{KitcatError, Result};
+use crate::hooks::run_hook;
 use crate::index::{read_index_binary, write_index};
-use crate::models::IndexEntry;
-use crate::object::{get_commit_tree, read_tree};
-use crate::repo::{read_head, write_head};
+use crate::merge::three_way::merge_text_hunks;
+use crate::merge::{FileConflict, MarkerOptions, StrategyOptions};
+use crate::models::{FileEntry, IndexEntry};
+use crate::object::{get_commit_tree, read_blob_content, read_tree};
+use crate::repo::{read_head, resolve_head, write_head};
+use crate::utils::{file_mode, read_worktree_file, worktree_path_exists, write_worktree_file};
 use std::fs;
-use std::io;
 use std::path::Path;
 
 /// Checkout a branch, commit, or restore files
-pub fn checkout(target: &str, force: bool) -> io::Result<()> {
+pub fn checkout(target: &str, force: bool) -> Result<()> {
     // Check if target is a branch
     let branch_path = format!(".kitcat/refs/heads/{}", target);
 
@@ -18,33 +23,26 @@ pub fn checkout(target: &str, force: bool) -> io::Result<()> {
         // Looks like a commit hash
         checkout_commit(target, force)
     } else {
-        Err(io::Error::new(
-            io::ErrorKind::NotFound,
-            format!("Branch or commit '{}' not found", target),
-        ))
+        Err(KitcatError::UnknownRevision(target.to_string()))
     }
 }
 
 /// Checkout a branch by name
-fn checkout_branch(branch_name: &str, force: bool) -> io::Result<()> {
+fn checkout_branch(branch_name: &str, force: bool) -> Result<()> {
     let branch_path = format!(".kitcat/refs/heads/{}", branch_name);
 
     // Check if branch exists
     if !Path::new(&branch_path).exists() {
-        return Err(io::Error::new(
-            io::ErrorKind::NotFound,
-            format!("Branch '{}' does not exist", branch_name),
-        ));
+        return Err(KitcatError::NotFound(format!("Branch '{}' does not exist", branch_name)));
     }
 
     // Check for uncommitted changes unless force
     if !force && has_uncommitted_changes()? {
-        return Err(io::Error::new(
-            io::ErrorKind::Other,
-            "You have uncommitted changes. Commit or stash them, or use --force",
-        ));
+        return Err(KitcatError::Conflict("You have uncommitted changes. Commit or stash them, or use --force".to_string()));
     }
 
+    let old_head = head_or_null()?;
+
     // Get commit hash from branch
     let commit_hash = fs::read_to_string(&branch_path)?.trim().to_string();
 
@@ -58,24 +56,23 @@ fn checkout_branch(branch_name: &str, force: bool) -> io::Result<()> {
     update_index_from_tree(&tree_hash)?;
 
     // Update HEAD to point to branch
-    write_head(&format!("ref: refs/heads/{}", branch_name));
+    write_head(&format!("ref: refs/heads/{}", branch_name))?;
 
     println!("Switched to branch '{}'", branch_name);
+    run_hook("post-checkout", &[&old_head, &commit_hash, "1"])?;
     Ok(())
 }
 
 /// Checkout a commit by hash (detached HEAD)
-fn checkout_commit(commit_hash: &str, force: bool) -> io::Result<()> {
+fn checkout_commit(commit_hash: &str, force: bool) -> Result<()> {
     // Check for uncommitted changes unless force
     if !force && has_uncommitted_changes()? {
-        return Err(io::Error::new(
-            io::ErrorKind::Other,
-            "You have uncommitted changes. Commit or stash them, or use --force",
-        ));
+        return Err(KitcatError::Conflict("You have uncommitted changes. Commit or stash them, or use --force".to_string()));
     }
 
     // Find full commit hash (support short hashes)
     let full_hash = find_commit_hash(commit_hash)?;
+    let old_head = head_or_null()?;
 
     // Get tree from commit
     let tree_hash = get_commit_tree(&full_hash)?;
@@ -87,14 +84,53 @@ fn checkout_commit(commit_hash: &str, force: bool) -> io::Result<()> {
     update_index_from_tree(&tree_hash)?;
 
     // Update HEAD to point directly to commit (detached)
-    write_head(&full_hash);
+    write_head(&full_hash)?;
 
     println!("HEAD is now at {} (detached)", &full_hash[0..7]);
+    run_hook("post-checkout", &[&old_head, &full_hash, "1"])?;
+    Ok(())
+}
+
+/// Reset the working tree and index to a commit without moving HEAD
+///
+/// Tracked files are overwritten and files not in the commit are removed,
+/// like `git reset --hard` would.
+pub fn reset_to_commit(commit_hash: &str) -> Result<()> {
+    let tree_hash = get_commit_tree(commit_hash)?;
+    checkout_tree(&tree_hash)?;
+    update_index_from_tree(&tree_hash)?;
     Ok(())
 }
 
+/// Reset only `paths` in the working tree and index to a commit
+///
+/// Paths not in the commit are removed. Local changes to every other path,
+/// staged or not, are kept.
+pub fn reset_paths_to_commit<'a>(
+    commit_hash: &str,
+    paths: impl IntoIterator<Item = &'a str>,
+) -> Result<()> {
+    let files = crate::merge::get_commit_files(commit_hash)?;
+    let mut index = read_index_binary()?;
+
+    for path in paths {
+        index.retain(|e| e.path != path);
+        match files.get(path) {
+            Some(entry) => {
+                write_worktree_file(path, &read_blob_content(&entry.hash)?, entry.mode)?;
+                let metadata = fs::symlink_metadata(path)?;
+                index.push(IndexEntry::from_file(path.to_string(), entry.hash.clone(), &metadata));
+            }
+            None if worktree_path_exists(path) => fs::remove_file(path)?,
+            None => {}
+        }
+    }
+
+    write_index(&index)
+}
+
 /// Restore a specific file from the index
-pub fn checkout_file(file_path: &str) -> io::Result<()> {
+pub fn checkout_file(file_path: &str) -> Result<()> {
     let entries = read_index_binary()?;
 
     // Find file in index
@@ -113,30 +149,88 @@ pub fn checkout_file(file_path: &str) -> io::Result<()> {
 
             // Find the null byte separating header from content
             let null_pos = content.iter().position(|&b| b == 0).ok_or_else(|| {
-                io::Error::new(io::ErrorKind::InvalidData, "Invalid blob format")
+                KitcatError::CorruptObject("Invalid blob format".to_string())
             })?;
 
             let file_content = &content[null_pos + 1..];
 
-            // Write to working directory
-            if let Some(parent) = Path::new(file_path).parent() {
-                fs::create_dir_all(parent)?;
-            }
-            fs::write(file_path, file_content)?;
+            // Write to working directory with the index mode
+            write_worktree_file(file_path, file_content, entry.mode)?;
 
             println!("Restored '{}' from index", file_path);
+            let head = head_or_null()?;
+            run_hook("post-checkout", &[&head, &head, "0"])?;
             Ok(())
         }
-        None => Err(io::Error::new(
-            io::ErrorKind::NotFound,
-            format!("File '{}' not in index", file_path),
-        )),
+        None => Err(KitcatError::NotFound(format!("File '{}' not in index", file_path))),
+    }
+}
+
+/// Recreate the conflicted file of an unmerged path from its index stages
+///
+/// Used by `checkout --conflict=<style>` to write the conflict markers
+/// again, possibly in another style.
+pub fn checkout_conflict(file_path: &str, style: &str) -> Result<()> {
+    let options = MarkerOptions::load(Some(style), "base")?;
+
+    let mut conflict = FileConflict::new(file_path.to_string());
+    let mut unmerged = false;
+    for entry in read_index_binary()? {
+        if entry.path != file_path || !entry.is_unmerged() {
+            continue;
+        }
+        let stage = entry.stage() as usize;
+        let content = Some(read_blob_content(&entry.hash)?);
+        conflict.modes[stage - 1] = Some(entry.mode);
+        match stage {
+            1 => conflict.base_content = content,
+            2 => conflict.our_content = content,
+            _ => conflict.their_content = content,
+        }
+        unmerged = true;
+    }
+
+    if !unmerged {
+        return Err(KitcatError::NotFound(format!("Path '{}' has no conflicts to recreate", file_path)));
     }
+
+    conflict.is_binary = [&conflict.base_content, &conflict.our_content, &conflict.their_content]
+        .into_iter()
+        .flatten()
+        .any(|content| is_binary(content));
+
+    // Markers only around the conflicting regions when all three versions exist
+    if let (false, Some(base), Some(ours), Some(theirs)) = (
+        conflict.is_binary,
+        &conflict.base_content,
+        &conflict.our_content,
+        &conflict.their_content,
+    ) {
+        conflict.hunks = merge_text_hunks(
+            &String::from_utf8_lossy(base),
+            &String::from_utf8_lossy(ours),
+            &String::from_utf8_lossy(theirs),
+            &StrategyOptions::default(),
+        );
+    }
+
+    let content = conflict.generate_conflict_markers("ours", "theirs", &options);
+    write_worktree_file(file_path, &content, conflict.worktree_mode())?;
+
+    println!("Recreated conflict in '{}'", file_path);
+    let head = head_or_null()?;
+    run_hook("post-checkout", &[&head, &head, "0"])?;
+    Ok(())
+}
+
+/// HEAD's commit for the `post-checkout` hook, or the null hash on an unborn branch
+fn head_or_null() -> Result<String> {
+    Ok(resolve_head()?.unwrap_or_else(|| "0".repeat(40)))
 }
 
 /// Check if there are uncommitted changes
-fn has_uncommitted_changes() -> io::Result<bool> {
-    let head_content = read_head();
+fn has_uncommitted_changes() -> Result<bool> {
+    let head_content = read_head()?;
 
     // Get HEAD commit (resolve if it's a branch reference)
     let commit_hash = if head_content.starts_with("ref:") {
@@ -172,24 +266,22 @@ fn has_uncommitted_changes() -> io::Result<bool> {
     }
 
     for entry in &index_entries {
-        let entry_hash_hex = &entry.hash;
         match head_entries.get(&entry.path) {
-            Some(head_hash) if head_hash == entry_hash_hex => continue,
+            Some(head) if head.hash == entry.hash && head.mode == entry.mode => continue,
             _ => return Ok(true),
         }
     }
 
     // Compare working directory with index
     for entry in &index_entries {
-        if !Path::new(&entry.path).exists() {
+        let Ok(metadata) = fs::symlink_metadata(&entry.path) else {
             return Ok(true);
-        }
+        };
 
-        let file_content = fs::read(&entry.path)?;
+        let file_content = read_worktree_file(&entry.path)?;
         let work_hash = hash_blob_content(&file_content);
-        let index_hash = &entry.hash;
 
-        if work_hash != *index_hash {
+        if work_hash != entry.hash || file_mode(&metadata) != entry.mode {
             return Ok(true);
         }
     }
@@ -198,11 +290,11 @@ fn has_uncommitted_changes() -> io::Result<bool> {
 }
 
 /// Update working directory to match tree
-fn checkout_tree(tree_hash: &str) -> io::Result<()> {
+fn checkout_tree(tree_hash: &str) -> Result<()> {
     // Remove all tracked files from working directory
     let current_entries = read_index_binary().unwrap_or_default();
     for entry in &current_entries {
-        if Path::new(&entry.path).exists() {
+        if worktree_path_exists(&entry.path) {
             fs::remove_file(&entry.path)?;
         }
     }
@@ -214,7 +306,7 @@ fn checkout_tree(tree_hash: &str) -> io::Result<()> {
 }
 
 /// Recursively restore files from a tree
-fn restore_tree_recursive(tree_hash: &str, prefix: &str) -> io::Result<()> {
+fn restore_tree_recursive(tree_hash: &str, prefix: &str) -> Result<()> {
     let tree_entries = read_tree(tree_hash)?;
 
     for entry in tree_entries {
@@ -230,17 +322,18 @@ fn restore_tree_recursive(tree_hash: &str, prefix: &str) -> io::Result<()> {
             let hash_hex = bytes_to_hex(&entry.hash);
             restore_tree_recursive(&hash_hex, &path)?;
         } else {
-            // Restore blob
+            // Restore blob as a file, executable or symlink
             let hash_hex = bytes_to_hex(&entry.hash);
-            restore_blob(&hash_hex, &path)?;
+            let file = FileEntry::from_tree_mode(&entry.mode, hash_hex);
+            restore_blob(&file.hash, &path, file.mode)?;
         }
     }
 
     Ok(())
 }
 
-/// Restore a blob to a file path
-fn restore_blob(hash: &str, path: &str) -> io::Result<()> {
+/// Restore a blob to a file path with a Git file mode
+fn restore_blob(hash: &str, path: &str, mode: u32) -> Result<()> {
     let obj_dir = &hash[0..2];
     let obj_file = &hash[2..];
     let obj_path = format!(".kitcat/objects/{}/{}", obj_dir, obj_file);
@@ -250,22 +343,16 @@ fn restore_blob(hash: &str, path: &str) -> io::Result<()> {
 
     // Find the null byte separating header from content
     let null_pos = content.iter().position(|&b| b == 0).ok_or_else(|| {
-        io::Error::new(io::ErrorKind::InvalidData, "Invalid blob format")
+        KitcatError::CorruptObject("Invalid blob format".to_string())
     })?;
 
     let file_content = &content[null_pos + 1..];
 
-    // Create parent directories if needed
-    if let Some(parent) = Path::new(path).parent() {
-        fs::create_dir_all(parent)?;
-    }
-
-    fs::write(path, file_content)?;
-    Ok(())
+    write_worktree_file(path, file_content, mode)
 }
 
 /// Update index to match tree
-fn update_index_from_tree(tree_hash: &str) -> io::Result<()> {
+fn update_index_from_tree(tree_hash: &str) -> Result<()> {
     let mut entries = Vec::new();
     collect_index_entries(tree_hash, "", &mut entries)?;
     write_index(&entries)?;
@@ -277,7 +364,7 @@ fn collect_index_entries(
     tree_hash: &str,
     prefix: &str,
     entries: &mut Vec<IndexEntry>,
-) -> io::Result<()> {
+) -> Result<()> {
     let tree_entries = read_tree(tree_hash)?;
 
     for entry in tree_entries {
@@ -291,8 +378,8 @@ fn collect_index_entries(
             let hash_hex = bytes_to_hex(&entry.hash);
             collect_index_entries(&hash_hex, &path, entries)?;
         } else {
-            // Get file metadata
-            let metadata = fs::metadata(&path)?;
+            // Get file metadata (of the link itself for symlinks)
+            let metadata = fs::symlink_metadata(&path)?;
             let index_entry = IndexEntry::from_file(path, bytes_to_hex(&entry.hash), &metadata);
             entries.push(index_entry);
         }
@@ -302,7 +389,7 @@ fn collect_index_entries(
 }
 
 /// Collect all entries from a tree recursively
-fn collect_tree_entries(tree_hash: &str) -> io::Result<std::collections::HashMap<String, String>> {
+fn collect_tree_entries(tree_hash: &str) -> Result<std::collections::HashMap<String, FileEntry>> {
     let mut entries = std::collections::HashMap::new();
     collect_tree_entries_recursive(tree_hash, "", &mut entries)?;
     Ok(entries)
@@ -311,8 +398,8 @@ fn collect_tree_entries(tree_hash: &str) -> io::Result<std::collections::HashMap
 fn collect_tree_entries_recursive(
     tree_hash: &str,
     prefix: &str,
-    entries: &mut std::collections::HashMap<String, String>,
-) -> io::Result<()> {
+    entries: &mut std::collections::HashMap<String, FileEntry>,
+) -> Result<()> {
     let tree_entries = read_tree(tree_hash)?;
 
     for entry in tree_entries {
@@ -327,7 +414,7 @@ fn collect_tree_entries_recursive(
         if entry.is_tree {
             collect_tree_entries_recursive(&hash_hex, &path, entries)?;
         } else {
-            entries.insert(path, hash_hex);
+            entries.insert(path, FileEntry::from_tree_mode(&entry.mode, hash_hex));
         }
     }
 
@@ -335,7 +422,7 @@ fn collect_tree_entries_recursive(
 }
 
 /// Find full commit hash from partial hash
-fn find_commit_hash(partial: &str) -> io::Result<String> {
+fn find_commit_hash(partial: &str) -> Result<String> {
     if partial.len() == 40 {
         return Ok(partial.to_string());
     }
@@ -354,10 +441,7 @@ fn find_commit_hash(partial: &str) -> io::Result<String> {
         }
     }
 
-    Err(io::Error::new(
-        io::ErrorKind::NotFound,
-        format!("Commit '{}' not found", partial),
-    ))
+    Err(KitcatError::UnknownRevision(partial.to_string()))
 }
 
 /// Convert bytes to hex string
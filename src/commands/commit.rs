@@ -0,0 +1,425 @@
+/// Commit command implementation
+///
+/// Records the index as a new commit on top of HEAD:
+/// - `-a`: stage modified and deleted tracked files first
+/// - `--amend`: replace HEAD, keeping its parents and author (and its
+///   message with `--no-edit`)
+/// - `--fixup`/`--squash <commit>`: messages marking the commit to be
+///   folded into another one (`fixup! <subject>` / `squash! <subject>`)
+/// - `-m`, `-F <file>` or `$EDITOR` with a commented status template
+/// - `--cleanup`: how the message is tidied up (`strip`, `whitespace`,
+///   `verbatim`, `scissors` or `default`)
+///
+/// A commit recording the same tree as its parent is refused unless
+/// `--allow-empty` is given. The `pre-commit`, `commit-msg` and
+/// `post-commit` hooks run along the way (see `hooks`).
+
+use crate::commands::pretty::subject;
+use crate::commands::status::{collect_status, current_branch, WorktreeStatus};
+use crate::config::Config;
+use crate::diff::driver::shell_command;
+use crate::error::{KitcatError, Result};
+use crate::hooks::{run_commit_msg_hook, run_hook, verify_hook, COMMIT_EDITMSG};
+use crate::index::{add_file_to_index, read_index, remove_file_from_index};
+use crate::object::{
+    create_signed_commit, get_commit_tree, read_commit, write_blob, write_tree_from_index,
+    Signature,
+};
+use crate::repo::{resolve_head, resolve_revision, update_head};
+use crate::signing::SignRequest;
+use crate::utils::read_worktree_file;
+use std::env;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Line below which `scissors` cleanup drops everything
+const SCISSORS: &str = "# ------------------------ >8 ------------------------";
+
+/// How the commit message is cleaned up (`--cleanup`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CleanupMode {
+    /// Drop `#` comment lines, trailing whitespace and extra blank lines
+    Strip,
+    /// Like `Strip`, but keep comment lines
+    Whitespace,
+    /// Keep the message exactly as given
+    Verbatim,
+    /// Like `Whitespace`, but drop everything from the scissors line on
+    Scissors,
+    /// `Strip` when the message was edited, `Whitespace` otherwise
+    #[default]
+    Default,
+}
+
+impl CleanupMode {
+    /// Parse a `--cleanup` / `commit.cleanup` value
+    pub fn parse(spec: &str) -> Result<Self> {
+        match spec {
+            "strip" => Ok(CleanupMode::Strip),
+            "whitespace" => Ok(CleanupMode::Whitespace),
+            "verbatim" => Ok(CleanupMode::Verbatim),
+            "scissors" => Ok(CleanupMode::Scissors),
+            "default" => Ok(CleanupMode::Default),
+            _ => Err(KitcatError::InvalidInput(format!("Invalid cleanup mode '{}'", spec))),
+        }
+    }
+}
+
+/// Options for the commit command
+#[derive(Debug, Clone, Default)]
+pub struct CommitOptions {
+    /// Message paragraphs (`-m`, may be repeated)
+    pub messages: Vec<String>,
+    /// Read the message from a file (`-F`, `-` for stdin)
+    pub file: Option<String>,
+    /// Open the editor even when a message was given
+    pub edit: bool,
+    /// Keep the amended commit's message without opening the editor
+    pub no_edit: bool,
+    /// Stage modified and deleted tracked files first
+    pub all: bool,
+    /// Replace HEAD instead of adding a commit on top of it
+    pub amend: bool,
+    /// Allow a commit with the same tree as its parent
+    pub allow_empty: bool,
+    /// Make a `fixup!` commit for this revision
+    pub fixup: Option<String>,
+    /// Make a `squash!` commit for this revision
+    pub squash: Option<String>,
+    /// Cleanup mode (defaults to `commit.cleanup`, then `default`)
+    pub cleanup: Option<String>,
+    /// Override the author ("Name <email>")
+    pub author: Option<String>,
+    /// Override the author date
+    pub date: Option<String>,
+    /// Skip the `pre-commit` and `commit-msg` hooks
+    pub no_verify: bool,
+    /// Whether to sign (`-S`, `--no-gpg-sign` or `commit.gpgSign`)
+    pub sign: SignRequest,
+}
+
+/// Main commit command
+pub fn commit(options: CommitOptions) -> Result<()> {
+    if Path::new(".kitcat/MERGE_HEAD").exists() {
+        return Err(KitcatError::Conflict("You are in the middle of a merge. Use 'kitcat merge --continue' to conclude it".to_string()));
+    }
+
+    let config = Config::read()?;
+    let cleanup = match options.cleanup.as_ref().or(config.commit.cleanup.as_ref()) {
+        Some(spec) => CleanupMode::parse(spec)?,
+        None => CleanupMode::Default,
+    };
+
+    let status = collect_status()?;
+    if !status.unmerged.is_empty() {
+        return Err(KitcatError::Conflict("Committing is not possible because you have unmerged files".to_string()));
+    }
+    if options.all {
+        stage_tracked_changes(&status)?;
+    }
+    if !options.no_verify {
+        verify_hook("pre-commit", &[])?;
+    }
+
+    // Staging and the pre-commit hook may have changed the index
+    let status = collect_status()?;
+
+    // The commit being replaced, and the parents of the new one
+    let head = resolve_head()?;
+    let amended = match (&head, options.amend) {
+        (Some(head), true) => Some(read_commit(head)?),
+        (None, true) => {
+            return Err(KitcatError::NotFound("You have nothing to amend".to_string()));
+        }
+        (_, false) => None,
+    };
+    let parents = match &amended {
+        Some(commit) => commit.parents.clone(),
+        None => head.into_iter().collect(),
+    };
+
+    let entries = read_index()?;
+    let tree_hash = write_tree_from_index(&entries)?;
+    let is_empty = match parents.as_slice() {
+        [] => entries.is_empty(),
+        [parent] => get_commit_tree(parent)? == tree_hash,
+        _ => false,
+    };
+    if is_empty && !options.allow_empty {
+        let message = if options.amend {
+            "You asked to amend the most recent commit, but doing so would make it empty. Use --allow-empty to amend anyway"
+        } else if status.has_unstaged() {
+            "no changes added to commit (use \"kitcat add\" and/or \"kitcat commit -a\")"
+        } else {
+            "nothing to commit, working tree clean"
+        };
+        return Err(KitcatError::InvalidInput(message.to_string()));
+    }
+
+    let (message, edit) = initial_message(&options, amended.as_ref().map(|c| c.message.as_str()))?;
+    let (message, cleanup) = if edit {
+        let edited = edit_message(&message, cleanup, &status, &config)?;
+        let cleanup = match cleanup {
+            CleanupMode::Default => CleanupMode::Strip,
+            mode => mode,
+        };
+        (edited, cleanup)
+    } else {
+        (message, cleanup)
+    };
+    let message = if options.no_verify {
+        message
+    } else {
+        run_commit_msg_hook(&message)?
+    };
+    let message = cleanup_message(&message, cleanup);
+    if message.trim().is_empty() {
+        return Err(KitcatError::InvalidInput("Aborting commit due to empty commit message".to_string()));
+    }
+
+    // An amended commit keeps its author unless told otherwise
+    let mut author = match &amended {
+        Some(commit) => Signature {
+            identity: commit.author.clone(),
+            time: commit.author_time,
+            timezone: commit.author_timezone.clone(),
+        },
+        None => Signature::author()?,
+    };
+    if let Some(identity) = &options.author {
+        author = author.with_identity(identity)?;
+    }
+    if let Some(date) = &options.date {
+        author = author.with_date(date)?;
+    }
+
+    let commit_hash = create_signed_commit(&tree_hash, &parents, &message, &author, &options.sign)?;
+    update_head(&commit_hash)?;
+
+    println!("[{}] {}", &commit_hash[0..7], message.lines().next().unwrap_or(""));
+    run_hook("post-commit", &[])?;
+    Ok(())
+}
+
+/// Stage the working copies of modified tracked files and drop deleted ones
+fn stage_tracked_changes(status: &WorktreeStatus) -> Result<()> {
+    for path in &status.unstaged_modified {
+        let hash = write_blob(&read_worktree_file(path)?)?;
+        add_file_to_index(path, &hash)?;
+    }
+    for path in &status.unstaged_deleted {
+        remove_file_from_index(path)?;
+    }
+    Ok(())
+}
+
+/// The message before editing, and whether the editor should be opened
+fn initial_message(options: &CommitOptions, amended: Option<&str>) -> Result<(String, bool)> {
+    let given = if !options.messages.is_empty() {
+        Some(options.messages.join("\n\n"))
+    } else if let Some(file) = &options.file {
+        let mut content = String::new();
+        if file == "-" {
+            io::stdin().read_to_string(&mut content)?;
+        } else {
+            content = fs::read_to_string(file)?;
+        }
+        Some(content)
+    } else {
+        None
+    };
+
+    let target_subject = |rev: &str| -> Result<String> {
+        Ok(subject(&read_commit(&resolve_revision(rev)?)?).to_string())
+    };
+
+    let (message, edit) = if let Some(rev) = &options.fixup {
+        let mut message = format!("fixup! {}", target_subject(rev)?);
+        if let Some(given) = &given {
+            message.push_str("\n\n");
+            message.push_str(given);
+        }
+        (message, false)
+    } else if let Some(rev) = &options.squash {
+        let message = format!("squash! {}\n\n{}", target_subject(rev)?, given.as_deref().unwrap_or(""));
+        (message, given.is_none())
+    } else if let Some(given) = given {
+        (given, false)
+    } else {
+        (amended.unwrap_or("").to_string(), !options.no_edit)
+    };
+
+    Ok((message, edit || options.edit))
+}
+
+/// Let the user edit the message in `.kitcat/COMMIT_EDITMSG`
+fn edit_message(
+    message: &str,
+    cleanup: CleanupMode,
+    status: &WorktreeStatus,
+    config: &Config,
+) -> Result<String> {
+    let mut content = message.trim_end().to_string();
+    if !content.is_empty() {
+        content.push('\n');
+    }
+    content.push('\n');
+    content.push_str(&message_template(cleanup, status, current_branch().as_deref()));
+    fs::write(COMMIT_EDITMSG, content)?;
+
+    let editor = editor_command(config);
+    let result = shell_command(&editor, &[COMMIT_EDITMSG.to_string()]).status()?;
+    if !result.success() {
+        return Err(KitcatError::CommandFailed(format!(
+            "There was a problem with the editor '{}'",
+            editor
+        )));
+    }
+
+    Ok(fs::read_to_string(COMMIT_EDITMSG)?)
+}
+
+/// The editor to run: `KITCAT_EDITOR`, `core.editor`, `VISUAL`, `EDITOR`, then `vi`
+fn editor_command(config: &Config) -> String {
+    env::var("KITCAT_EDITOR")
+        .ok()
+        .or_else(|| config.core.editor.clone())
+        .or_else(|| env::var("VISUAL").ok())
+        .or_else(|| env::var("EDITOR").ok())
+        .filter(|editor| !editor.is_empty())
+        .unwrap_or_else(|| "vi".to_string())
+}
+
+/// Commented help and status appended to the message being edited
+fn message_template(cleanup: CleanupMode, status: &WorktreeStatus, branch: Option<&str>) -> String {
+    let mut lines = Vec::new();
+
+    match cleanup {
+        CleanupMode::Scissors => {
+            lines.push(SCISSORS.to_string());
+            lines.push("# Do not modify or remove the line above.".to_string());
+            lines.push("# Everything below it will be ignored.".to_string());
+        }
+        CleanupMode::Whitespace | CleanupMode::Verbatim => {
+            lines.push("# Please enter the commit message for your changes. Lines starting".to_string());
+            lines.push("# with '#' will be kept; you may remove them yourself if you want to.".to_string());
+            lines.push("# An empty message aborts the commit.".to_string());
+        }
+        CleanupMode::Strip | CleanupMode::Default => {
+            lines.push("# Please enter the commit message for your changes. Lines starting".to_string());
+            lines.push("# with '#' will be ignored, and an empty message aborts the commit.".to_string());
+        }
+    }
+
+    lines.push("#".to_string());
+    match branch {
+        Some(branch) => lines.push(format!("# On branch {}", branch)),
+        None => lines.push("# HEAD detached".to_string()),
+    }
+
+    let mut section = |title: &str, files: Vec<String>| {
+        if !files.is_empty() {
+            lines.push(format!("# {}:", title));
+            lines.extend(files.into_iter().map(|file| format!("#\t{}", file)));
+            lines.push("#".to_string());
+        }
+    };
+    let labelled = |label: &str, files: &[String]| -> Vec<String> {
+        files.iter().map(|file| format!("{:<12}{}", label, file)).collect()
+    };
+
+    section(
+        "Changes to be committed",
+        [
+            labelled("new file:", &status.staged_new),
+            labelled("modified:", &status.staged_modified),
+            labelled("deleted:", &status.staged_deleted),
+        ]
+        .concat(),
+    );
+    section(
+        "Changes not staged for commit",
+        [
+            labelled("modified:", &status.unstaged_modified),
+            labelled("deleted:", &status.unstaged_deleted),
+        ]
+        .concat(),
+    );
+    section("Untracked files", status.untracked.clone());
+
+    let mut template = lines.join("\n");
+    template.push('\n');
+    template
+}
+
+/// Tidy up a commit message according to a cleanup mode
+///
+/// Except for `Verbatim`, trailing whitespace is removed from every line,
+/// runs of blank lines are collapsed and leading and trailing blank lines
+/// are dropped. `Default` is treated as `Whitespace` here.
+pub fn cleanup_message(message: &str, mode: CleanupMode) -> String {
+    if mode == CleanupMode::Verbatim {
+        return message.to_string();
+    }
+
+    let mut lines: Vec<&str> = Vec::new();
+    for line in message.lines() {
+        if mode == CleanupMode::Scissors && line == SCISSORS {
+            break;
+        }
+        if mode == CleanupMode::Strip && line.starts_with('#') {
+            continue;
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() && lines.last().is_none_or(|last| last.is_empty()) {
+            continue;
+        }
+        lines.push(line);
+    }
+    while lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cleanup_message() {
+        let message = "\n\nSubject  \n\n\n# a comment\nBody\n\n";
+        assert_eq!(cleanup_message(message, CleanupMode::Strip), "Subject\n\nBody");
+        assert_eq!(
+            cleanup_message(message, CleanupMode::Whitespace),
+            "Subject\n\n# a comment\nBody"
+        );
+        assert_eq!(cleanup_message(message, CleanupMode::Verbatim), message);
+
+        let scissored = format!("Subject\n# kept\n{}\ndropped\n", SCISSORS);
+        assert_eq!(cleanup_message(&scissored, CleanupMode::Scissors), "Subject\n# kept");
+        assert!(CleanupMode::parse("tidy").is_err());
+    }
+
+    #[test]
+    fn test_message_template() {
+        let status = WorktreeStatus {
+            staged_new: vec!["new.txt".to_string()],
+            unstaged_modified: vec!["old.txt".to_string()],
+            ..WorktreeStatus::default()
+        };
+
+        let template = message_template(CleanupMode::Strip, &status, Some("main"));
+        assert!(template.contains("#\n# On branch main\n"));
+        assert!(template.contains("# Changes to be committed:\n#\tnew file:   new.txt\n#\n"));
+        assert!(template.contains("# Changes not staged for commit:\n#\tmodified:   old.txt\n"));
+        assert!(!template.contains("Untracked"));
+        assert!(template.lines().all(|line| line.starts_with('#')));
+
+        let edited = format!("Subject\n\n{}", message_template(CleanupMode::Scissors, &status, None));
+        assert_eq!(cleanup_message(&edited, CleanupMode::Scissors), "Subject");
+    }
+}
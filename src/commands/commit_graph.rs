@@ -0,0 +1,25 @@
+/// Commit-graph command implementation
+///
+/// Maintains `.kitcat/objects/info/commit-graph` (see `object::commit_graph`):
+/// - `write`: record every commit reachable from the refs
+/// - `verify`: check the file against its checksum and the commit objects
+use crate::commands::gc::write_reachable_commit_graph;
+use crate::error::{KitcatError, Result};
+use crate::object::verify_commit_graph;
+
+/// Main commit-graph command
+pub fn commit_graph(action: &str) -> Result<()> {
+    match action {
+        "write" => {
+            let commits = write_reachable_commit_graph()?;
+            println!("Wrote commit-graph with {} commits", commits);
+            Ok(())
+        }
+        "verify" => {
+            let commits = verify_commit_graph()?;
+            println!("Verified commit-graph with {} commits", commits);
+            Ok(())
+        }
+        other => Err(KitcatError::InvalidInput(format!("Unknown commit-graph subcommand '{}'", other))),
+    }
+}
@@ -5,15 +5,19 @@
 /// - Index vs HEAD (staged changes)
 /// - Commit vs commit
 /// - Working directory vs commit
+/// - Working directory vs both merge parents (combined diff while merging)
 
-use crate::diff::{diff_texts, is_binary};
+use crate::diff::driver::{run_external_diff, run_textconv, DiffDrivers};
+use crate::diff::{blob_hash, combined_diff, diff_blobs, format_combined_diff};
 use crate::diff::format::{format_diff_stats, format_unified_diff, UnifiedDiffOptions};
+use crate::error::{KitcatError, Result};
 use crate::index::read_index_binary;
-use crate::object::{get_commit_tree, read_tree};
+use crate::merge::get_commit_files;
+use crate::models::FileEntry;
 use crate::repo::read_head;
+use crate::utils::{file_mode, read_worktree_file};
 use std::collections::HashMap;
 use std::fs;
-use std::io;
 use std::path::Path;
 
 /// Diff mode
@@ -44,6 +48,10 @@ pub struct DiffOptions {
     pub use_color: bool,
     /// Show statistics
     pub show_stats: bool,
+    /// Emit binary files as applicable `GIT binary patch` hunks
+    pub binary: bool,
+    /// Ignore `diff.external` and driver commands
+    pub no_ext_diff: bool,
 }
 
 impl Default for DiffOptions {
@@ -55,27 +63,32 @@ impl Default for DiffOptions {
             paths: vec![],
             use_color: true,
             show_stats: false,
+            binary: false,
+            no_ext_diff: false,
         }
     }
 }
 
 /// Main diff command
-pub fn diff(options: DiffOptions) -> io::Result<()> {
+pub fn diff(options: DiffOptions) -> Result<()> {
     match options.mode {
+        DiffMode::WorkingVsIndex if Path::new(".kitcat/MERGE_HEAD").exists() => {
+            diff_working_vs_merge_parents(&options)
+        }
         DiffMode::WorkingVsIndex => diff_working_vs_index(&options),
         DiffMode::IndexVsHead => diff_index_vs_head(&options),
         DiffMode::WorkingVsCommit => {
             let commit = options.commit1.as_ref().ok_or_else(|| {
-                io::Error::new(io::ErrorKind::InvalidInput, "Commit hash required")
+                KitcatError::InvalidInput("Commit hash required".to_string())
             })?;
             diff_working_vs_commit(commit, &options)
         }
         DiffMode::CommitVsCommit => {
             let commit1 = options.commit1.as_ref().ok_or_else(|| {
-                io::Error::new(io::ErrorKind::InvalidInput, "First commit hash required")
+                KitcatError::InvalidInput("First commit hash required".to_string())
             })?;
             let commit2 = options.commit2.as_ref().ok_or_else(|| {
-                io::Error::new(io::ErrorKind::InvalidInput, "Second commit hash required")
+                KitcatError::InvalidInput("Second commit hash required".to_string())
             })?;
             diff_commit_vs_commit(commit1, commit2, &options)
         }
@@ -83,12 +96,9 @@ pub fn diff(options: DiffOptions) -> io::Result<()> {
 }
 
 /// Compare working directory vs index
-fn diff_working_vs_index(options: &DiffOptions) -> io::Result<()> {
+fn diff_working_vs_index(options: &DiffOptions) -> Result<()> {
     let index_entries = read_index_binary()?;
-    let unified_opts = UnifiedDiffOptions {
-        use_color: options.use_color,
-        ..Default::default()
-    };
+    let printer = FileDiffPrinter::new(options)?;
 
     let mut any_changes = false;
 
@@ -99,56 +109,93 @@ fn diff_working_vs_index(options: &DiffOptions) -> io::Result<()> {
         }
 
         // Check if file exists in working directory
-        if !Path::new(&entry.path).exists() {
-            println!("deleted file: {}", entry.path);
+        let Some((working_content, working_mode)) = read_working_file(&entry.path)? else {
             any_changes = true;
+            if options.binary {
+                let index_content = read_object_content(&entry.hash)?;
+                printer.print(&entry.path, Some((&index_content, entry.mode)), None)?;
+            } else {
+                println!("deleted file: {}", entry.path);
+            }
             continue;
-        }
+        };
 
-        // Read both versions
-        let working_content = fs::read(&entry.path)?;
-        let index_hash = &entry.hash;
+        // Read the index version
+        let index_content = read_object_content(&entry.hash)?;
 
-        // Read from object store
-        let obj_dir = &index_hash[0..2];
-        let obj_file = &index_hash[2..];
-        let obj_path = format!(".kitcat/objects/{}/{}", obj_dir, obj_file);
+        // Compare contents and modes
+        if working_content != index_content || working_mode != entry.mode {
+            any_changes = true;
+            printer.print(
+                &entry.path,
+                Some((&index_content, entry.mode)),
+                Some((&working_content, working_mode)),
+            )?;
+        }
+    }
 
-        let compressed = fs::read(&obj_path)?;
-        let object_content = crate::utils::decompress(&compressed)?;
+    if !any_changes {
+        if options.use_color {
+            println!("\x1b[32mNo changes\x1b[0m");
+        } else {
+            println!("No changes");
+        }
+    }
 
-        // Find null byte to separate header from content
-        let null_pos = object_content
-            .iter()
-            .position(|&b| b == 0)
-            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid object"))?;
+    Ok(())
+}
 
-        let index_content = &object_content[null_pos + 1..];
+/// Compare working directory against both sides of an in-progress merge
+///
+/// Shows a combined diff for every file that differs from both HEAD and
+/// MERGE_HEAD, i.e. files that still hold conflicts or manual resolutions.
+/// Unmerged paths are compared against their stage 2 (ours) and stage 3
+/// (theirs) index entries.
+fn diff_working_vs_merge_parents(options: &DiffOptions) -> Result<()> {
+    let merge_heads = fs::read_to_string(".kitcat/MERGE_HEAD")?;
+    let their_commit = merge_heads.lines().next().unwrap_or("").trim().to_string();
+    let mut parent_files = [get_head_files()?, get_commit_files(&their_commit)?];
+
+    let unmerged: Vec<_> = read_index_binary()?
+        .into_iter()
+        .filter(|e| e.is_unmerged())
+        .collect();
+    for entry in &unmerged {
+        for files in parent_files.iter_mut() {
+            files.remove(&entry.path);
+        }
+    }
+    for entry in unmerged {
+        let stage = entry.stage();
+        let file = FileEntry::new(entry.mode, entry.hash);
+        match stage {
+            2 => parent_files[0].insert(entry.path, file),
+            3 => parent_files[1].insert(entry.path, file),
+            _ => None,
+        };
+    }
 
-        // Compare contents
-        if working_content != index_content {
-            any_changes = true;
+    let mut paths: Vec<&String> = parent_files.iter().flat_map(|files| files.keys()).collect();
+    paths.sort();
+    paths.dedup();
 
-            // Check if binary
-            if is_binary(&working_content) || is_binary(index_content) {
-                println!("Binary files a/{} and b/{} differ", entry.path, entry.path);
-                continue;
-            }
-
-            // Compute and display diff
-            let old_text = String::from_utf8_lossy(index_content);
-            let new_text = String::from_utf8_lossy(&working_content);
+    let mut any_changes = false;
 
-            let mut diff = diff_texts(&old_text, &new_text);
-            diff.old_path = format!("a/{}", entry.path);
-            diff.new_path = format!("b/{}", entry.path);
+    for path in paths {
+        if !options.paths.is_empty() && !options.paths.contains(path) {
+            continue;
+        }
 
-            let output = format_unified_diff(&diff, &unified_opts);
-            print!("{}", output);
+        let working = read_working_file(path)?.map(|(content, _)| content);
+        let parents = parent_files
+            .iter()
+            .map(|files| files.get(path).map(|e| read_object_content(&e.hash)).transpose())
+            .collect::<Result<Vec<_>>>()?;
+        let parent_refs: Vec<Option<&[u8]>> = parents.iter().map(|p| p.as_deref()).collect();
 
-            if options.show_stats {
-                println!("{}", format_diff_stats(&diff, options.use_color));
-            }
+        if let Some(diff) = combined_diff(path, &parent_refs, working.as_deref()) {
+            any_changes = true;
+            print!("{}", format_combined_diff(&diff, options.use_color));
         }
     }
 
@@ -164,13 +211,10 @@ fn diff_working_vs_index(options: &DiffOptions) -> io::Result<()> {
 }
 
 /// Compare index vs HEAD
-fn diff_index_vs_head(options: &DiffOptions) -> io::Result<()> {
+fn diff_index_vs_head(options: &DiffOptions) -> Result<()> {
     let index_entries = read_index_binary()?;
     let head_files = get_head_files()?;
-    let unified_opts = UnifiedDiffOptions {
-        use_color: options.use_color,
-        ..Default::default()
-    };
+    let printer = FileDiffPrinter::new(options)?;
 
     let mut any_changes = false;
 
@@ -185,37 +229,27 @@ fn diff_index_vs_head(options: &DiffOptions) -> io::Result<()> {
         match head_files.get(&entry.path) {
             None => {
                 // New file in index
-                println!("new file: {}", entry.path);
                 any_changes = true;
+                if options.binary {
+                    let index_content = read_object_content(index_hash)?;
+                    printer.print(&entry.path, None, Some((&index_content, entry.mode)))?;
+                } else {
+                    println!("new file: {}", entry.path);
+                }
             }
-            Some(head_hash) if head_hash != index_hash => {
+            Some(head) if head.hash != *index_hash || head.mode != entry.mode => {
                 // Modified file
                 any_changes = true;
 
                 // Read both versions
                 let index_content = read_object_content(index_hash)?;
-                let head_content = read_object_content(head_hash)?;
-
-                // Check if binary
-                if is_binary(&index_content) || is_binary(&head_content) {
-                    println!("Binary files a/{} and b/{} differ", entry.path, entry.path);
-                    continue;
-                }
-
-                // Compute and display diff
-                let old_text = String::from_utf8_lossy(&head_content);
-                let new_text = String::from_utf8_lossy(&index_content);
+                let head_content = read_object_content(&head.hash)?;
 
-                let mut diff = diff_texts(&old_text, &new_text);
-                diff.old_path = format!("a/{}", entry.path);
-                diff.new_path = format!("b/{}", entry.path);
-
-                let output = format_unified_diff(&diff, &unified_opts);
-                print!("{}", output);
-
-                if options.show_stats {
-                    println!("{}", format_diff_stats(&diff, options.use_color));
-                }
+                printer.print(
+                    &entry.path,
+                    Some((&head_content, head.mode)),
+                    Some((&index_content, entry.mode)),
+                )?;
             }
             _ => {
                 // Unchanged
@@ -224,14 +258,19 @@ fn diff_index_vs_head(options: &DiffOptions) -> io::Result<()> {
     }
 
     // Check for deleted files
-    for (path, _) in &head_files {
+    for (path, head) in &head_files {
         if !options.paths.is_empty() && !options.paths.contains(path) {
             continue;
         }
 
         if !index_entries.iter().any(|e| &e.path == path) {
-            println!("deleted file: {}", path);
             any_changes = true;
+            if options.binary {
+                let head_content = read_object_content(&head.hash)?;
+                printer.print(path, Some((&head_content, head.mode)), None)?;
+            } else {
+                println!("deleted file: {}", path);
+            }
         }
     }
 
@@ -247,51 +286,38 @@ fn diff_index_vs_head(options: &DiffOptions) -> io::Result<()> {
 }
 
 /// Compare working directory vs specific commit
-fn diff_working_vs_commit(commit_hash: &str, options: &DiffOptions) -> io::Result<()> {
+fn diff_working_vs_commit(commit_hash: &str, options: &DiffOptions) -> Result<()> {
     let commit_files = get_commit_files(commit_hash)?;
-    let unified_opts = UnifiedDiffOptions {
-        use_color: options.use_color,
-        ..Default::default()
-    };
+    let printer = FileDiffPrinter::new(options)?;
 
     let mut any_changes = false;
 
     // Check modified files
-    for (path, commit_hash_val) in &commit_files {
+    for (path, commit_entry) in &commit_files {
         if !options.paths.is_empty() && !options.paths.contains(path) {
             continue;
         }
 
-        if !Path::new(path).exists() {
-            println!("deleted file: {}", path);
+        let Some((working_content, working_mode)) = read_working_file(path)? else {
             any_changes = true;
+            if options.binary {
+                let commit_content = read_object_content(&commit_entry.hash)?;
+                printer.print(path, Some((&commit_content, commit_entry.mode)), None)?;
+            } else {
+                println!("deleted file: {}", path);
+            }
             continue;
-        }
+        };
 
-        let working_content = fs::read(path)?;
-        let commit_content = read_object_content(commit_hash_val)?;
+        let commit_content = read_object_content(&commit_entry.hash)?;
 
-        if working_content[..] != commit_content[..] {
+        if working_content != commit_content || working_mode != commit_entry.mode {
             any_changes = true;
-
-            if is_binary(&working_content) || is_binary(&commit_content) {
-                println!("Binary files a/{} and b/{} differ", path, path);
-                continue;
-            }
-
-            let old_text = String::from_utf8_lossy(&commit_content);
-            let new_text = String::from_utf8_lossy(&working_content);
-
-            let mut diff = diff_texts(&old_text, &new_text);
-            diff.old_path = format!("a/{}", path);
-            diff.new_path = format!("b/{}", path);
-
-            let output = format_unified_diff(&diff, &unified_opts);
-            print!("{}", output);
-
-            if options.show_stats {
-                println!("{}", format_diff_stats(&diff, options.use_color));
-            }
+            printer.print(
+                path,
+                Some((&commit_content, commit_entry.mode)),
+                Some((&working_content, working_mode)),
+            )?;
         }
     }
 
@@ -307,13 +333,10 @@ fn diff_working_vs_commit(commit_hash: &str, options: &DiffOptions) -> io::Resul
 }
 
 /// Compare two commits
-fn diff_commit_vs_commit(commit1: &str, commit2: &str, options: &DiffOptions) -> io::Result<()> {
+fn diff_commit_vs_commit(commit1: &str, commit2: &str, options: &DiffOptions) -> Result<()> {
     let files1 = get_commit_files(commit1)?;
     let files2 = get_commit_files(commit2)?;
-    let unified_opts = UnifiedDiffOptions {
-        use_color: options.use_color,
-        ..Default::default()
-    };
+    let printer = FileDiffPrinter::new(options)?;
 
     let mut all_paths: std::collections::HashSet<String> = files1.keys().cloned().collect();
     all_paths.extend(files2.keys().cloned());
@@ -325,42 +348,35 @@ fn diff_commit_vs_commit(commit1: &str, commit2: &str, options: &DiffOptions) ->
             continue;
         }
 
-        let hash1 = files1.get(&path);
-        let hash2 = files2.get(&path);
+        let entry1 = files1.get(&path);
+        let entry2 = files2.get(&path);
 
-        match (hash1, hash2) {
-            (None, Some(_)) => {
-                println!("new file: {}", path);
+        match (entry1, entry2) {
+            (None, Some(e2)) => {
                 any_changes = true;
+                if options.binary {
+                    let content2 = read_object_content(&e2.hash)?;
+                    printer.print(&path, None, Some((&content2, e2.mode)))?;
+                } else {
+                    println!("new file: {}", path);
+                }
             }
-            (Some(_), None) => {
-                println!("deleted file: {}", path);
+            (Some(e1), None) => {
                 any_changes = true;
+                if options.binary {
+                    let content1 = read_object_content(&e1.hash)?;
+                    printer.print(&path, Some((&content1, e1.mode)), None)?;
+                } else {
+                    println!("deleted file: {}", path);
+                }
             }
-            (Some(h1), Some(h2)) if h1 != h2 => {
+            (Some(e1), Some(e2)) if e1 != e2 => {
                 any_changes = true;
 
-                let content1 = read_object_content(h1)?;
-                let content2 = read_object_content(h2)?;
-
-                if is_binary(&content1) || is_binary(&content2) {
-                    println!("Binary files a/{} and b/{} differ", path, path);
-                    continue;
-                }
-
-                let text1 = String::from_utf8_lossy(&content1);
-                let text2 = String::from_utf8_lossy(&content2);
-
-                let mut diff = diff_texts(&text1, &text2);
-                diff.old_path = format!("a/{}", path);
-                diff.new_path = format!("b/{}", path);
+                let content1 = read_object_content(&e1.hash)?;
+                let content2 = read_object_content(&e2.hash)?;
 
-                let output = format_unified_diff(&diff, &unified_opts);
-                print!("{}", output);
-
-                if options.show_stats {
-                    println!("{}", format_diff_stats(&diff, options.use_color));
-                }
+                printer.print(&path, Some((&content1, e1.mode)), Some((&content2, e2.mode)))?;
             }
             _ => {}
         }
@@ -377,9 +393,96 @@ fn diff_commit_vs_commit(commit1: &str, commit2: &str, options: &DiffOptions) ->
     Ok(())
 }
 
+/// Prints file diffs for one diff invocation
+struct FileDiffPrinter<'a> {
+    options: &'a DiffOptions,
+    unified: UnifiedDiffOptions,
+    drivers: DiffDrivers,
+}
+
+impl<'a> FileDiffPrinter<'a> {
+    /// Set up formatting options and load diff drivers
+    fn new(options: &'a DiffOptions) -> Result<Self> {
+        Ok(Self {
+            options,
+            unified: UnifiedDiffOptions {
+                use_color: options.use_color,
+                // Binary patches need full hashes so `apply` can verify the preimage
+                full_index: options.binary,
+                ..Default::default()
+            },
+            drivers: DiffDrivers::load()?,
+        })
+    }
+
+    /// Print the diff of one file, given each side's content and mode (`None` = file missing)
+    fn print(
+        &self,
+        path: &str,
+        old: Option<(&[u8], u32)>,
+        new: Option<(&[u8], u32)>,
+    ) -> Result<()> {
+        let driver = self.drivers.for_path(path);
+
+        if let Some(command) = &driver.command
+            && !self.options.no_ext_diff
+        {
+            return run_external_diff(command, path, old, new);
+        }
+
+        let (old_content, new_content) = (old.map(|(c, _)| c), new.map(|(c, _)| c));
+
+        if driver.binary {
+            println!("Binary files a/{} and b/{} differ", path, path);
+            return Ok(());
+        }
+
+        // Convert both sides to text first if the driver has a textconv filter
+        let (old_converted, new_converted) = match &driver.textconv {
+            Some(textconv) => (
+                old_content.map(|c| run_textconv(textconv, path, c)).transpose()?,
+                new_content.map(|c| run_textconv(textconv, path, c)).transpose()?,
+            ),
+            None => (None, None),
+        };
+        let mut diff = diff_blobs(
+            path,
+            old_converted.as_deref().or(old_content),
+            new_converted.as_deref().or(new_content),
+            self.options.binary,
+        );
+
+        diff.old_mode = old.map(|(_, mode)| mode);
+        diff.new_mode = new.map(|(_, mode)| mode);
+
+        // The index line names the real blobs, not the converted text
+        if driver.textconv.is_some() {
+            if let Some(content) = old_content {
+                diff.old_hash = Some(blob_hash(content));
+            }
+            if let Some(content) = new_content {
+                diff.new_hash = Some(blob_hash(content));
+            }
+        }
+
+        if diff.is_binary && diff.binary_patch.is_none() && !diff.mode_changed() {
+            println!("Binary files a/{} and b/{} differ", path, path);
+            return Ok(());
+        }
+
+        print!("{}", format_unified_diff(&diff, &self.unified));
+
+        if self.options.show_stats && !diff.is_binary {
+            println!("{}", format_diff_stats(&diff, self.options.use_color));
+        }
+
+        Ok(())
+    }
+}
+
 /// Get all files from HEAD commit
-fn get_head_files() -> io::Result<HashMap<String, String>> {
-    let head_content = read_head();
+fn get_head_files() -> Result<HashMap<String, FileEntry>> {
+    let head_content = read_head()?;
 
     let commit_hash = if head_content.starts_with("ref:") {
         let branch_name = head_content.trim_start_matches("ref: ").trim();
@@ -399,43 +502,16 @@ fn get_head_files() -> io::Result<HashMap<String, String>> {
     get_commit_files(&commit_hash)
 }
 
-/// Get all files from a specific commit
-fn get_commit_files(commit_hash: &str) -> io::Result<HashMap<String, String>> {
-    let tree_hash = get_commit_tree(commit_hash)?;
-    let mut files = HashMap::new();
-    collect_tree_files(&tree_hash, "", &mut files)?;
-    Ok(files)
-}
-
-/// Recursively collect all files from a tree
-fn collect_tree_files(
-    tree_hash: &str,
-    prefix: &str,
-    files: &mut HashMap<String, String>,
-) -> io::Result<()> {
-    let tree_entries = read_tree(tree_hash)?;
-
-    for entry in tree_entries {
-        let path = if prefix.is_empty() {
-            entry.name.clone()
-        } else {
-            format!("{}/{}", prefix, entry.name)
-        };
-
-        let hash_hex = bytes_to_hex(&entry.hash);
-
-        if entry.is_tree {
-            collect_tree_files(&hash_hex, &path, files)?;
-        } else {
-            files.insert(path, hash_hex);
-        }
-    }
-
-    Ok(())
+/// Read a working tree file's blob content and mode (None if it does not exist)
+fn read_working_file(path: &str) -> Result<Option<(Vec<u8>, u32)>> {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return Ok(None);
+    };
+    Ok(Some((read_worktree_file(path)?, file_mode(&metadata))))
 }
 
 /// Read object content from object store
-fn read_object_content(hash: &str) -> io::Result<Vec<u8>> {
+fn read_object_content(hash: &str) -> Result<Vec<u8>> {
     let obj_dir = &hash[0..2];
     let obj_file = &hash[2..];
     let obj_path = format!(".kitcat/objects/{}/{}", obj_dir, obj_file);
@@ -447,12 +523,7 @@ fn read_object_content(hash: &str) -> io::Result<Vec<u8>> {
     let null_pos = content
         .iter()
         .position(|&b| b == 0)
-        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid object"))?;
+        .ok_or_else(|| KitcatError::CorruptObject("Invalid object".to_string()))?;
 
     Ok(content[null_pos + 1..].to_vec())
 }
-
-/// Convert bytes to hex string
-fn bytes_to_hex(bytes: &[u8]) -> String {
-    bytes.iter().map(|b| format!("{:02x}", b)).collect()
-}
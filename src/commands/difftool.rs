@@ -0,0 +1,224 @@
+/// Difftool command implementation
+///
+/// Opens each changed file in an external diff tool:
+/// - No revisions: index vs working tree
+/// - One revision: that commit vs working tree
+/// - `A..B` or two revisions: commit vs commit
+///
+/// The tool comes from `--tool` or `diff.tool`. Its command line is
+/// `difftool.<name>.cmd`, or a built-in one for common tools, and runs with
+/// `$LOCAL`, `$REMOTE`, `$MERGED` and `$BASE` set. `--dir-diff` copies all
+/// changed files into two directories and launches the tool once.
+
+use crate::config::Config;
+use crate::diff::driver::{shell_command, TempFile};
+use crate::error::{KitcatError, Result};
+use crate::index::read_index_binary;
+use crate::merge::get_commit_files;
+use crate::object::read_blob_content;
+use crate::repo::resolve_revision;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Options for the difftool command
+#[derive(Debug, Clone, Default)]
+pub struct DifftoolOptions {
+    /// Zero, one or two revisions (or a single `A..B` range)
+    pub revisions: Vec<String>,
+    /// Tool to use instead of `diff.tool`
+    pub tool: Option<String>,
+    /// Compare whole directory snapshots in one tool invocation
+    pub dir_diff: bool,
+}
+
+/// A changed file: old and new content, and the working tree path if the new side is live
+struct ChangedFile {
+    path: String,
+    old: Option<Vec<u8>>,
+    new: Option<Vec<u8>>,
+    in_working_tree: bool,
+}
+
+/// Main difftool command
+pub fn difftool(options: DifftoolOptions) -> Result<()> {
+    let command = tool_command(options.tool.as_deref())?;
+    let changes = collect_changes(&options.revisions)?;
+
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    if options.dir_diff {
+        run_dir_diff(&command, &changes)
+    } else {
+        for change in &changes {
+            run_file_diff(&command, change)?;
+        }
+        Ok(())
+    }
+}
+
+/// Find the command line for the selected tool
+fn tool_command(tool: Option<&str>) -> Result<String> {
+    let config = Config::read()?;
+    let name = tool
+        .map(str::to_string)
+        .or_else(|| config.diff.tool.clone())
+        .ok_or_else(|| {
+            KitcatError::NotFound("No diff tool configured. Use --tool=<name> or set diff.tool".to_string())
+        })?;
+
+    if let Some(cmd) = config.difftool.get(&name).and_then(|t| t.cmd.clone()) {
+        return Ok(cmd);
+    }
+
+    let builtin = match name.as_str() {
+        "vimdiff" | "nvimdiff" | "meld" | "kdiff3" | "opendiff" => {
+            format!("{} \"$LOCAL\" \"$REMOTE\"", name)
+        }
+        "vscode" => "code --wait --diff \"$LOCAL\" \"$REMOTE\"".to_string(),
+        "diff" => "diff -u \"$LOCAL\" \"$REMOTE\"".to_string(),
+        _ => {
+            return Err(KitcatError::NotFound(format!(
+                    "Unknown diff tool '{}'. Set difftool.{}.cmd to use it",
+                    name, name
+                )));
+        }
+    };
+
+    Ok(builtin)
+}
+
+/// Work out the two sides to compare and the files that differ
+fn collect_changes(revisions: &[String]) -> Result<Vec<ChangedFile>> {
+    let (old_rev, new_rev) = match revisions {
+        [] => (None, None),
+        [range] => match range.split_once("..") {
+            Some((a, b)) => (Some(a.to_string()), Some(b.to_string())),
+            None => (Some(range.clone()), None),
+        },
+        [a, b] => (Some(a.clone()), Some(b.clone())),
+        _ => {
+            return Err(KitcatError::InvalidInput("difftool takes at most two revisions".to_string()));
+        }
+    };
+
+    let commit_files = |rev: &str| -> Result<HashMap<String, String>> {
+        let rev = if rev.is_empty() { "HEAD" } else { rev };
+        let files = get_commit_files(&resolve_revision(rev)?)?;
+        Ok(files.into_iter().map(|(path, entry)| (path, entry.hash)).collect())
+    };
+
+    let old_files = match &old_rev {
+        Some(rev) => commit_files(rev)?,
+        None => read_index_binary()?
+            .into_iter()
+            .map(|e| (e.path, e.hash))
+            .collect(),
+    };
+
+    let mut changes = Vec::new();
+
+    if let Some(rev) = &new_rev {
+        let new_files = commit_files(rev)?;
+        let paths: BTreeSet<&String> = old_files.keys().chain(new_files.keys()).collect();
+        for path in paths {
+            if old_files.get(path) == new_files.get(path) {
+                continue;
+            }
+            changes.push(ChangedFile {
+                path: path.clone(),
+                old: old_files.get(path).map(|h| read_blob_content(h)).transpose()?,
+                new: new_files.get(path).map(|h| read_blob_content(h)).transpose()?,
+                in_working_tree: false,
+            });
+        }
+        return Ok(changes);
+    }
+
+    // New side is the working tree, restricted to tracked files
+    let tracked: BTreeSet<String> = read_index_binary()?
+        .into_iter()
+        .map(|e| e.path)
+        .chain(old_files.keys().cloned())
+        .collect();
+    for path in tracked {
+        let old = old_files.get(&path).map(|h| read_blob_content(h)).transpose()?;
+        let new = if Path::new(&path).exists() {
+            Some(fs::read(&path)?)
+        } else {
+            None
+        };
+        if old != new {
+            changes.push(ChangedFile {
+                path,
+                old,
+                new,
+                in_working_tree: true,
+            });
+        }
+    }
+
+    Ok(changes)
+}
+
+/// Launch the tool for one file
+fn run_file_diff(command: &str, change: &ChangedFile) -> Result<()> {
+    let local = TempFile::new(&change.path, change.old.as_deref().unwrap_or_default())?;
+    let remote_temp;
+    let remote = if change.in_working_tree && change.new.is_some() {
+        PathBuf::from(&change.path)
+    } else {
+        remote_temp = TempFile::new(&change.path, change.new.as_deref().unwrap_or_default())?;
+        remote_temp.path().to_path_buf()
+    };
+
+    println!("Viewing: '{}'", change.path);
+    launch(command, local.path(), &remote, &change.path)
+}
+
+/// Copy all changed files into `left/` and `right/` and launch the tool once
+fn run_dir_diff(command: &str, changes: &[ChangedFile]) -> Result<()> {
+    let root = std::env::temp_dir().join(format!("kitcat-difftool-{}", std::process::id()));
+    let left = root.join("left");
+    let right = root.join("right");
+
+    let result = (|| {
+        for change in changes {
+            for (dir, content) in [(&left, &change.old), (&right, &change.new)] {
+                if let Some(content) = content {
+                    let target = dir.join(&change.path);
+                    if let Some(parent) = target.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    fs::write(target, content)?;
+                }
+            }
+        }
+        fs::create_dir_all(&left)?;
+        fs::create_dir_all(&right)?;
+
+        launch(command, &left, &right, ".")
+    })();
+
+    let _ = fs::remove_dir_all(&root);
+    result
+}
+
+/// Run the tool command with the usual difftool environment
+fn launch(command: &str, local: &Path, remote: &Path, merged: &str) -> Result<()> {
+    let status = shell_command(command, &[])
+        .env("LOCAL", local)
+        .env("REMOTE", remote)
+        .env("MERGED", merged)
+        .env("BASE", merged)
+        .status()?;
+
+    // Tools often exit non-zero just because the files differ
+    if status.code().is_none() {
+        return Err(KitcatError::CommandFailed("diff tool was terminated".to_string()));
+    }
+
+    Ok(())
+}
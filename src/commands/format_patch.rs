@@ -0,0 +1,274 @@
+/// Format-patch command implementation
+///
+/// Writes each commit in a range as a numbered mbox file that can be
+/// mailed and applied with `kitcat am`:
+/// - `From`/`Date`/`Subject` headers taken from the commit author
+/// - The commit message body, a diffstat and the full diff
+/// - An optional `0000-cover-letter.patch` summarising the series
+
+use crate::commands::pretty::{format_date_as, DateMode};
+use crate::diff::{
+    diff_file_maps, format_diffstat, format_unified_diff, FileDiff, UnifiedDiffOptions,
+};
+use crate::error::Result;
+use crate::merge::get_commit_files;
+use crate::models::Commit;
+use crate::object::{read_commit, Signature};
+use crate::repo::resolve_revision;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Fixed date on the mbox `From` line, as written by Git
+const MBOX_FROM_DATE: &str = "Mon Sep 17 00:00:00 2001";
+
+/// Maximum length of the subject part of a patch file name
+const MAX_SLUG_LEN: usize = 52;
+
+/// Options for the format-patch command
+#[derive(Debug, Clone, Default)]
+pub struct FormatPatchOptions {
+    /// `A..B` range, or a single revision meaning `rev..HEAD`
+    pub range: String,
+    /// Directory to write the patch files to
+    pub output_dir: Option<String>,
+    /// Also write a cover letter
+    pub cover_letter: bool,
+}
+
+/// A commit selected for the series
+struct SeriesCommit {
+    hash: String,
+    commit: Commit,
+    diffs: Vec<FileDiff>,
+}
+
+/// Main format-patch command
+pub fn format_patch(options: FormatPatchOptions) -> Result<()> {
+    let (from, to) = match options.range.split_once("..") {
+        Some((from, to)) => (from, if to.is_empty() { "HEAD" } else { to }),
+        None => (options.range.as_str(), "HEAD"),
+    };
+    let exclude = resolve_revision(if from.is_empty() { "HEAD" } else { from })?;
+    let include = resolve_revision(to)?;
+
+    let hashes = commits_in_range(&exclude, &include)?;
+    if hashes.is_empty() {
+        return Ok(());
+    }
+
+    let mut series = Vec::new();
+    for hash in hashes {
+        let commit = read_commit(&hash)?;
+        let old_files = match commit.parents.first() {
+            Some(parent) => get_commit_files(parent)?,
+            None => HashMap::new(),
+        };
+        let new_files = get_commit_files(&hash)?;
+        let diffs = diff_file_maps(&old_files, &new_files, true)?;
+        series.push(SeriesCommit {
+            hash,
+            commit,
+            diffs,
+        });
+    }
+
+    let output_dir = PathBuf::from(options.output_dir.as_deref().unwrap_or("."));
+    fs::create_dir_all(&output_dir)?;
+
+    let total = series.len();
+    let numbered = total > 1 || options.cover_letter;
+
+    if options.cover_letter {
+        let path = output_dir.join("0000-cover-letter.patch");
+        fs::write(&path, format_cover_letter(&series, &exclude, &include)?)?;
+        println!("{}", path.display());
+    }
+
+    for (i, item) in series.iter().enumerate() {
+        let prefix = if numbered {
+            format!("[PATCH {}/{}]", i + 1, total)
+        } else {
+            "[PATCH]".to_string()
+        };
+        let path = patch_file_path(&output_dir, i + 1, subject_line(&item.commit));
+        fs::write(&path, format_patch_email(item, &prefix))?;
+        println!("{}", path.display());
+    }
+
+    Ok(())
+}
+
+/// Non-merge commits reachable from `include` but not from `exclude`, oldest first
+fn commits_in_range(exclude: &str, include: &str) -> Result<Vec<String>> {
+    let mut excluded = HashSet::new();
+    let mut stack = vec![exclude.to_string()];
+    while let Some(hash) = stack.pop() {
+        if excluded.insert(hash.clone()) {
+            stack.extend(read_commit(&hash)?.parents);
+        }
+    }
+
+    // Post-order walk so parents are emitted before their children
+    let mut ordered = Vec::new();
+    let mut visited = HashSet::new();
+    let mut stack = vec![(include.to_string(), false)];
+    while let Some((hash, expanded)) = stack.pop() {
+        if expanded {
+            if read_commit(&hash)?.parents.len() <= 1 {
+                ordered.push(hash);
+            }
+            continue;
+        }
+        if excluded.contains(&hash) || !visited.insert(hash.clone()) {
+            continue;
+        }
+        let parents = read_commit(&hash)?.parents;
+        stack.push((hash, true));
+        for parent in parents.into_iter().rev() {
+            stack.push((parent, false));
+        }
+    }
+
+    Ok(ordered)
+}
+
+/// Format one commit as an mbox message
+fn format_patch_email(item: &SeriesCommit, prefix: &str) -> String {
+    let commit = &item.commit;
+    let author = Signature {
+        identity: commit.author.clone(),
+        time: commit.author_time,
+        timezone: commit.author_timezone.clone(),
+    };
+    let mut output = format_headers(&item.hash, &author);
+    output.push_str(&format!("Subject: {} {}\n\n", prefix, subject_line(commit)));
+
+    let body = message_body(commit);
+    if !body.is_empty() {
+        output.push_str(body);
+        output.push('\n');
+    }
+
+    output.push_str("---\n");
+    output.push_str(&format_diffstat(&item.diffs));
+    output.push('\n');
+
+    let unified = UnifiedDiffOptions {
+        use_color: false,
+        full_index: true,
+        ..UnifiedDiffOptions::default()
+    };
+    for diff in &item.diffs {
+        output.push_str(&format_unified_diff(diff, &unified));
+    }
+
+    output.push_str(&signature());
+    output
+}
+
+/// Format the `0000-cover-letter.patch` message
+fn format_cover_letter(series: &[SeriesCommit], exclude: &str, include: &str) -> Result<String> {
+    let mut output = format_headers(&"0".repeat(40), &Signature::author()?);
+    output.push_str(&format!(
+        "Subject: [PATCH 0/{}] *** SUBJECT HERE ***\n\n*** BLURB HERE ***\n\n",
+        series.len()
+    ));
+
+    // Shortlog grouped by author, in series order
+    let mut authors: Vec<(&str, Vec<&str>)> = Vec::new();
+    for item in series {
+        let name = author_name(&item.commit.author);
+        match authors.iter_mut().find(|(author, _)| *author == name) {
+            Some((_, subjects)) => subjects.push(subject_line(&item.commit)),
+            None => authors.push((name, vec![subject_line(&item.commit)])),
+        }
+    }
+    for (author, subjects) in &authors {
+        output.push_str(&format!("{} ({}):\n", author, subjects.len()));
+        for subject in subjects {
+            output.push_str(&format!("  {}\n", subject));
+        }
+        output.push('\n');
+    }
+
+    let diffs = diff_file_maps(&get_commit_files(exclude)?, &get_commit_files(include)?, false)?;
+    output.push_str(&format_diffstat(&diffs));
+    output.push('\n');
+    output.push_str(&signature());
+
+    Ok(output)
+}
+
+/// The mbox `From` line plus the `From:` and `Date:` headers
+fn format_headers(hash: &str, author: &Signature) -> String {
+    format!(
+        "From {} {}\nFrom: {}\nDate: {}\n",
+        hash,
+        MBOX_FROM_DATE,
+        author.identity,
+        format_date_as(author.time, &author.timezone, DateMode::Rfc)
+    )
+}
+
+/// Trailing mail signature
+fn signature() -> String {
+    format!("-- \nkitcat {}\n\n", env!("CARGO_PKG_VERSION"))
+}
+
+/// First line of the commit message
+fn subject_line(commit: &Commit) -> &str {
+    commit.message.lines().next().unwrap_or("").trim()
+}
+
+/// Commit message without the subject line
+fn message_body(commit: &Commit) -> &str {
+    match commit.message.split_once('\n') {
+        Some((_, body)) => body.trim(),
+        None => "",
+    }
+}
+
+/// Author name without the email address
+fn author_name(author: &str) -> &str {
+    author.split(" <").next().unwrap_or(author).trim()
+}
+
+/// Path of the Nth patch file, e.g. `0001-Fix-the-parser.patch`
+fn patch_file_path(dir: &Path, number: usize, subject: &str) -> PathBuf {
+    dir.join(format!("{:04}-{}.patch", number, subject_slug(subject)))
+}
+
+/// Turn a subject into a file-name friendly slug
+fn subject_slug(subject: &str) -> String {
+    let mut slug = String::new();
+    for c in subject.chars() {
+        if c.is_ascii_alphanumeric() || c == '.' || c == '_' {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    slug.truncate(MAX_SLUG_LEN);
+    slug.trim_end_matches(['-', '.']).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subject_slug() {
+        assert_eq!(subject_slug("Fix the parser"), "Fix-the-parser");
+        assert_eq!(subject_slug("  [core] Add: tests!!"), "core-Add-tests");
+        assert_eq!(subject_slug("Release v1.2."), "Release-v1.2");
+        assert!(subject_slug(&"word ".repeat(30)).len() <= MAX_SLUG_LEN);
+    }
+
+    #[test]
+    fn test_author_name() {
+        assert_eq!(author_name("Jane Doe <jane@example.com>"), "Jane Doe");
+        assert_eq!(author_name("Jane"), "Jane");
+    }
+}
@@ -4,12 +4,21 @@
 /// - Packing loose objects into packfiles
 /// - Removing unreachable objects
 /// - Compressing pack files
+/// - Writing the commit-graph used by history walks
+///
+/// With `--auto`, nothing happens until there are more than
+/// `AUTO_LOOSE_OBJECTS` loose objects, and the `pre-auto-gc` hook can
+/// still veto the collection.
 
+use crate::error::{KitcatError, Result};
+use crate::hooks::run_hook;
 use std::collections::HashSet;
 use std::fs;
-use std::io;
 use std::path::Path;
 
+/// Loose objects above which `gc --auto` collects (Git's `gc.auto` default)
+const AUTO_LOOSE_OBJECTS: usize = 6700;
+
 /// Garbage collection options
 #[derive(Debug, Clone)]
 pub struct GcOptions {
@@ -19,6 +28,8 @@ pub struct GcOptions {
     pub prune_days: Option<u32>,
     /// Dry run (don't actually delete anything)
     pub dry_run: bool,
+    /// Only collect when there are many loose objects
+    pub auto: bool,
 }
 
 impl Default for GcOptions {
@@ -27,12 +38,20 @@ impl Default for GcOptions {
             aggressive: false,
             prune_days: Some(14), // Default: prune after 14 days
             dry_run: false,
+            auto: false,
         }
     }
 }
 
 /// Main garbage collection command
-pub fn gc(options: GcOptions) -> io::Result<()> {
+pub fn gc(options: GcOptions) -> Result<()> {
+    if options.auto {
+        if count_loose_objects()? <= AUTO_LOOSE_OBJECTS || !run_hook("pre-auto-gc", &[])? {
+            return Ok(());
+        }
+        println!("Auto packing the repository for optimum performance.");
+    }
+
     println!("Running garbage collection...");
 
     if options.dry_run {
@@ -62,12 +81,33 @@ pub fn gc(options: GcOptions) -> io::Result<()> {
         repack_aggressive()?;
     }
 
+    // Step 5: Write the commit-graph
+    if !options.dry_run {
+        println!("Writing commit-graph...");
+        let commits = write_reachable_commit_graph()?;
+        println!("Wrote commit-graph with {} commits", commits);
+    }
+
     println!("Garbage collection complete!");
     Ok(())
 }
 
+/// Count the objects stored as loose files
+fn count_loose_objects() -> Result<usize> {
+    let mut count = 0;
+    for dir in fs::read_dir(".kitcat/objects")? {
+        let dir = dir?;
+        let name = dir.file_name();
+        let is_fanout = name.len() == 2 && name.to_str().is_some_and(|n| n.chars().all(|c| c.is_ascii_hexdigit()));
+        if is_fanout && dir.file_type()?.is_dir() {
+            count += fs::read_dir(dir.path())?.count();
+        }
+    }
+    Ok(count)
+}
+
 /// Find all reachable objects from refs
-fn find_reachable_objects() -> io::Result<HashSet<String>> {
+fn find_reachable_objects() -> Result<HashSet<String>> {
     let mut reachable = HashSet::new();
     let mut to_visit = Vec::new();
 
@@ -91,25 +131,49 @@ fn find_reachable_objects() -> io::Result<HashSet<String>> {
     Ok(reachable)
 }
 
+/// Write a commit-graph with every commit reachable from the refs
+pub fn write_reachable_commit_graph() -> Result<usize> {
+    let mut tips = Vec::new();
+    collect_refs(&mut tips)?;
+
+    // Annotated tags count with the commits they point at
+    for tip in &mut tips {
+        while let Ok(tag) = crate::object::read_tag(tip) {
+            *tip = tag.object;
+        }
+    }
+
+    crate::object::write_commit_graph(&tips)
+}
+
 /// Collect all refs (branches, HEAD, etc.)
-fn collect_refs(refs: &mut Vec<String>) -> io::Result<()> {
+fn collect_refs(refs: &mut Vec<String>) -> Result<()> {
     // Read HEAD
-    let head_content = crate::repo::read_head();
+    let head_content = crate::repo::read_head()?;
     if let Ok(commit_hash) = resolve_ref(&head_content) {
         refs.push(commit_hash);
     }
 
-    // Read all branches
-    let refs_dir = Path::new(".kitcat/refs/heads");
-    if refs_dir.exists() {
-        collect_refs_from_dir(refs_dir, refs)?;
+    // Read all branches and tags
+    for dir in [".kitcat/refs/heads", ".kitcat/refs/tags"] {
+        let refs_dir = Path::new(dir);
+        if refs_dir.exists() {
+            collect_refs_from_dir(refs_dir, refs)?;
+        }
+    }
+
+    // Stashed local changes (kept after a conflicting autostash, or during a merge)
+    for stash in [".kitcat/refs/stash", ".kitcat/MERGE_AUTOSTASH"] {
+        if let Ok(content) = fs::read_to_string(stash) {
+            refs.push(content.trim().to_string());
+        }
     }
 
     Ok(())
 }
 
 /// Recursively collect refs from directory
-fn collect_refs_from_dir(dir: &Path, refs: &mut Vec<String>) -> io::Result<()> {
+fn collect_refs_from_dir(dir: &Path, refs: &mut Vec<String>) -> Result<()> {
     for entry in fs::read_dir(dir)? {
         let entry = entry?;
         let path = entry.path();
@@ -130,7 +194,7 @@ fn collect_refs_from_dir(dir: &Path, refs: &mut Vec<String>) -> io::Result<()> {
 }
 
 /// Resolve a ref to a commit hash
-fn resolve_ref(ref_content: &str) -> io::Result<String> {
+fn resolve_ref(ref_content: &str) -> Result<String> {
     if ref_content.starts_with("ref:") {
         let branch_name = ref_content.trim_start_matches("ref: ").trim();
         let branch_path = format!(".kitcat/{}", branch_name);
@@ -138,10 +202,7 @@ fn resolve_ref(ref_content: &str) -> io::Result<String> {
         if Path::new(&branch_path).exists() {
             Ok(fs::read_to_string(&branch_path)?.trim().to_string())
         } else {
-            Err(io::Error::new(
-                io::ErrorKind::NotFound,
-                "Ref not found",
-            ))
+            Err(KitcatError::NotFound("Ref not found".to_string()))
         }
     } else {
         Ok(ref_content.trim().to_string())
@@ -149,7 +210,13 @@ fn resolve_ref(ref_content: &str) -> io::Result<String> {
 }
 
 /// Get children of an object (for graph traversal)
-fn get_object_children(hash: &str) -> io::Result<Vec<String>> {
+fn get_object_children(hash: &str) -> Result<Vec<String>> {
+    if let Some(commit) = crate::object::commit_graph::lookup(hash) {
+        let mut children = vec![commit.tree];
+        children.extend(commit.parents);
+        return Ok(children);
+    }
+
     let obj_dir = &hash[0..2];
     let obj_file = &hash[2..];
     let obj_path = format!(".kitcat/objects/{}/{}", obj_dir, obj_file);
@@ -165,7 +232,7 @@ fn get_object_children(hash: &str) -> io::Result<Vec<String>> {
     let null_pos = content
         .iter()
         .position(|&b| b == 0)
-        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid object"))?;
+        .ok_or_else(|| KitcatError::CorruptObject("Invalid object".to_string()))?;
 
     let header = String::from_utf8_lossy(&content[..null_pos]);
     let obj_type = header.split(' ').next().unwrap_or("");
@@ -195,6 +262,13 @@ fn get_object_children(hash: &str) -> io::Result<Vec<String>> {
                 children.push(entry_hash);
             }
         }
+        "tag" => {
+            // The tagged object
+            let text = String::from_utf8_lossy(data);
+            if let Some(object) = text.lines().find_map(|line| line.strip_prefix("object ")) {
+                children.push(object.to_string());
+            }
+        }
         "blob" => {
             // Blobs have no children
         }
@@ -209,7 +283,7 @@ fn prune_unreachable_objects(
     reachable: &HashSet<String>,
     _days: u32,
     dry_run: bool,
-) -> io::Result<usize> {
+) -> Result<usize> {
     let mut pruned_count = 0;
     let objects_dir = Path::new(".kitcat/objects");
 
@@ -259,7 +333,7 @@ fn prune_unreachable_objects(
 }
 
 /// Repack all objects aggressively
-fn repack_aggressive() -> io::Result<()> {
+fn repack_aggressive() -> Result<()> {
     // In aggressive mode, we would:
     // 1. Unpack all packfiles
     // 2. Repack everything with better delta compression
@@ -286,6 +360,34 @@ mod tests {
         assert!(!options.dry_run);
     }
 
+    #[test]
+    fn test_gc_keeps_objects_reachable_from_tags() {
+        use crate::commands::tag::{tag, TagOptions};
+        use crate::object::{read_commit, read_tag, verify_commit_graph};
+        use crate::utils::commit_files;
+
+        crate::utils::in_temp_repo("gc-tags", || {
+            let base = commit_files(&[("a.txt", "base\n")], "base");
+            let tagged = commit_files(&[("a.txt", "tagged\n")], "tagged");
+            tag(TagOptions {
+                name: Some("v1".to_string()),
+                message: Some("Release".to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+            // Only the tag still reaches the second commit
+            fs::write(".kitcat/refs/heads/master", &base).unwrap();
+
+            gc(GcOptions { prune_days: Some(0), ..Default::default() }).unwrap();
+
+            let tag_hash = fs::read_to_string(".kitcat/refs/tags/v1").unwrap();
+            assert_eq!(read_tag(tag_hash.trim()).unwrap().object, tagged);
+            assert_eq!(read_commit(&tagged).unwrap().message.trim(), "tagged");
+            // Both commits are in the commit-graph
+            assert_eq!(verify_commit_graph().unwrap(), 2);
+        });
+    }
+
     #[test]
     fn test_bytes_to_hex() {
         let bytes = vec![0xde, 0xad, 0xbe, 0xef];
@@ -0,0 +1,251 @@
+/// ASCII commit graph for `log --graph`
+///
+/// Each line of history is a lane, drawn as `|` in its own column and
+/// colored per lane. For every commit the graph prints:
+/// - the commit row: `*` in the commit's lane, `|` in the others
+/// - merge rows: a `\` opening a lane for each extra parent, shifting the
+///   lanes to its right
+/// - collapse rows: `/` moving lanes one column left per row, when lanes
+///   join (a parent already has a lane) or a root commit ends its lane
+///
+/// Commits must come in topological order (children before parents).
+
+/// ANSI colors cycled through by new lanes
+const LANE_COLORS: [&str; 6] = [
+    "\x1b[31m", "\x1b[32m", "\x1b[33m", "\x1b[34m", "\x1b[35m", "\x1b[36m",
+];
+const RESET: &str = "\x1b[0m";
+
+/// A character to draw: column, character and lane color
+type Cell = (usize, char, usize);
+
+/// A line of history waiting for its next commit
+#[derive(Debug, Clone)]
+struct Lane {
+    /// Commit expected next in this lane
+    hash: String,
+    /// Index into `LANE_COLORS`
+    color: usize,
+}
+
+/// The rows drawn for one commit
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphRows {
+    /// Row holding the `*`, printed before the commit's first line
+    pub commit: String,
+    /// Merge and collapse rows, printed before the following lines
+    pub transitions: Vec<String>,
+    /// Row for any further lines of the commit
+    pub padding: String,
+}
+
+/// Lane state of a graph being drawn
+#[derive(Debug, Default)]
+pub struct Graph {
+    lanes: Vec<Lane>,
+    next_color: usize,
+    use_color: bool,
+}
+
+impl Graph {
+    /// Create an empty graph
+    pub fn new(use_color: bool) -> Self {
+        Graph {
+            use_color,
+            ..Default::default()
+        }
+    }
+
+    /// Draw the next commit; `parents` are the parents that will be shown
+    pub fn draw_commit(&mut self, hash: &str, parents: &[String]) -> GraphRows {
+        let index = match self.lanes.iter().position(|lane| lane.hash == hash) {
+            Some(index) => index,
+            None => {
+                let lane = self.new_lane(hash);
+                self.lanes.push(lane);
+                self.lanes.len() - 1
+            }
+        };
+
+        let commit_cells: Vec<Cell> = self
+            .lanes
+            .iter()
+            .enumerate()
+            .map(|(i, lane)| (2 * i, if i == index { '*' } else { '|' }, lane.color))
+            .collect();
+
+        let mut transitions: Vec<Vec<Cell>> = Vec::new();
+
+        // The commit's lane continues with its first parent, or ends at a root
+        let mut lanes: Vec<(Lane, usize)> = self
+            .lanes
+            .iter()
+            .cloned()
+            .enumerate()
+            .map(|(i, lane)| (lane, i))
+            .collect();
+        match parents.first() {
+            Some(first) => lanes[index].0.hash = first.clone(),
+            None => {
+                lanes.remove(index);
+            }
+        }
+
+        // Extra parents open lanes right after the commit's lane
+        for (n, parent) in parents.iter().enumerate().skip(1) {
+            let at = index + n;
+            let mut cells = Vec::new();
+            for (lane, position) in &lanes {
+                if *position < at {
+                    cells.push((2 * position, '|', lane.color));
+                } else {
+                    cells.push((2 * position + 1, '\\', lane.color));
+                }
+            }
+            let lane = self.new_lane(parent);
+            cells.push((2 * at - 1, '\\', lane.color));
+            transitions.push(cells);
+
+            for (_, position) in lanes.iter_mut() {
+                if *position >= at {
+                    *position += 1;
+                }
+            }
+            lanes.insert(at, (lane, at));
+        }
+
+        // Lanes expecting the same commit join the leftmost one
+        let mut targets = Vec::new();
+        let mut merged: Vec<Lane> = Vec::new();
+        for (lane, _) in &lanes {
+            match merged.iter().position(|m| m.hash == lane.hash) {
+                Some(target) => targets.push(target),
+                None => {
+                    targets.push(merged.len());
+                    merged.push(lane.clone());
+                }
+            }
+        }
+        let mut positions: Vec<usize> = lanes.iter().map(|(_, position)| *position).collect();
+        while positions.iter().zip(&targets).any(|(position, target)| position > target) {
+            let mut cells = Vec::new();
+            for ((lane, _), (position, target)) in lanes.iter().zip(positions.iter_mut().zip(&targets)) {
+                if *position > *target {
+                    cells.push((2 * *position - 1, '/', lane.color));
+                    *position -= 1;
+                } else {
+                    cells.push((2 * *position, '|', lane.color));
+                }
+            }
+            transitions.push(cells);
+        }
+
+        self.lanes = merged;
+        let padding_cells: Vec<Cell> = self
+            .lanes
+            .iter()
+            .enumerate()
+            .map(|(i, lane)| (2 * i, '|', lane.color))
+            .collect();
+
+        // Every row gets the same width so the text lines up
+        let width = std::iter::once(&commit_cells)
+            .chain(&transitions)
+            .chain(std::iter::once(&padding_cells))
+            .flatten()
+            .map(|(column, _, _)| column + 1)
+            .max()
+            .unwrap_or(0);
+
+        GraphRows {
+            commit: self.render(&commit_cells, width),
+            transitions: transitions.iter().map(|cells| self.render(cells, width)).collect(),
+            padding: self.render(&padding_cells, width),
+        }
+    }
+
+    /// A lane with the next color
+    fn new_lane(&mut self, hash: &str) -> Lane {
+        let color = self.next_color;
+        self.next_color = (self.next_color + 1) % LANE_COLORS.len();
+        Lane {
+            hash: hash.to_string(),
+            color,
+        }
+    }
+
+    /// Draw characters at their columns, padded to `width` (`*` is never colored)
+    fn render(&self, cells: &[Cell], width: usize) -> String {
+        let mut row: Vec<Option<(char, usize)>> = vec![None; width];
+        for &(column, c, color) in cells {
+            // Keep the first character drawn in a column
+            if row[column].is_none() {
+                row[column] = Some((c, color));
+            }
+        }
+
+        let mut output = String::new();
+        for cell in row {
+            match cell {
+                Some((c, color)) if self.use_color && c != '*' => {
+                    output.push_str(LANE_COLORS[color]);
+                    output.push(c);
+                    output.push_str(RESET);
+                }
+                Some((c, _)) => output.push(c),
+                None => output.push(' '),
+            }
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parents(hashes: &[&str]) -> Vec<String> {
+        hashes.iter().map(|h| h.to_string()).collect()
+    }
+
+    #[test]
+    fn test_graph_merge_and_join() {
+        // m merges b into a; a and b fork from r
+        let mut graph = Graph::new(false);
+
+        let m = graph.draw_commit("m", &parents(&["a", "b"]));
+        assert_eq!(m.commit, "*  ");
+        assert_eq!(m.transitions, vec!["|\\ "]);
+        assert_eq!(m.padding, "| |");
+
+        let b = graph.draw_commit("b", &parents(&["r"]));
+        assert_eq!(b.commit, "| *");
+        assert!(b.transitions.is_empty());
+
+        let a = graph.draw_commit("a", &parents(&["r"]));
+        assert_eq!(a.commit, "* |");
+        assert_eq!(a.transitions, vec!["|/ "]);
+        assert_eq!(a.padding, "|  ");
+
+        let r = graph.draw_commit("r", &[]);
+        assert_eq!(r.commit, "*");
+        assert!(r.transitions.is_empty());
+        assert_eq!(r.padding, " ");
+    }
+
+    #[test]
+    fn test_graph_root_shifts_lanes() {
+        // Two unrelated histories: x (root) shown while y's lane is open
+        let mut graph = Graph::new(false);
+        graph.draw_commit("x", &[]);
+        let y = graph.draw_commit("y", &parents(&["z"]));
+        assert_eq!(y.commit, "*");
+
+        let mut graph = Graph::new(false);
+        graph.draw_commit("t", &parents(&["x", "y"]));
+        let x = graph.draw_commit("x", &[]);
+        assert_eq!(x.commit, "* |");
+        assert_eq!(x.transitions, vec![" / "]);
+        assert_eq!(x.padding, "|  ");
+    }
+}
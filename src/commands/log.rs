@@ -1,67 +1,131 @@
-use crate::object::{get_commit_parents, read_commit};
-use chrono::{Local, TimeZone};
-use std::collections::HashSet;
-use std::io;
+/// Log command implementation
+///
+/// Walks the history (see `repo::revision`), filters it (see `log_filter`),
+/// limits it to paths or line ranges (see `log_paths`) and prints each
+/// commit in a pretty format (see `pretty`), optionally followed by its
+/// changes and next to an ASCII graph of branches and merges (see `graph`).
+/// With `--show-signature`, signed commits are verified (see `signing`).
 
-/// Display commit history
-pub fn log(format: LogFormat, max_count: Option<usize>) -> io::Result<()> {
-    // Get starting commit from HEAD
-    let head_content = crate::repo::read_head();
-    let start_commit = if head_content.starts_with("ref:") {
-        // HEAD points to a branch
-        let branch_name = head_content.trim_start_matches("ref: ").trim();
-        let branch_path = format!(".kitcat/{}", branch_name);
-
-        if !std::path::Path::new(&branch_path).exists() {
-            println!("No commits yet.");
-            return Ok(());
-        }
-
-        std::fs::read_to_string(&branch_path)?.trim().to_string()
-    } else if head_content.len() == 40 {
-        // Detached HEAD
-        head_content
-    } else {
-        println!("No commits yet.");
-        return Ok(());
-    };
-
-    // Walk the commit history
-    walk_commits(&start_commit, format, max_count)?;
+use crate::commands::graph::Graph;
+use crate::commands::log_filter::{CommitFilter, LogFilterOptions};
+use crate::commands::log_paths::PathLimiter;
+use crate::commands::pretty::{format_commit_with, load_decorations, DateMode, FormatContext, PrettyFormat};
+use crate::diff::{diff_file_maps, format_diffstat, format_unified_diff, FileDiff, UnifiedDiffOptions};
+use crate::merge::get_commit_files;
+use crate::models::Commit;
+use crate::config::Config;
+use crate::error::{KitcatError, Result};
+use crate::object::{read_commit, read_commit_signature};
+use crate::repo::revision::{rev_walk, RevWalkOptions, SortOrder};
+use crate::signing::verify_payload;
+use std::collections::HashMap;
 
-    Ok(())
+/// Options for the log command
+#[derive(Debug, Clone)]
+pub struct LogOptions {
+    /// Commit format (`oneline`, `short`, `medium`, `full`, `fuller`, `raw`,
+    /// `reference` or `format:...`); `medium` when unset
+    pub format: Option<String>,
+    /// Date format (`relative`, `iso`, `short`, `unix`, ...)
+    pub date: Option<String>,
+    /// Show branch and tag names next to commits
+    pub decorate: bool,
+    /// Abbreviate commit hashes
+    pub abbrev_commit: bool,
+    /// Draw the history graph next to the commits
+    pub graph: bool,
+    /// Use color output
+    pub use_color: bool,
+    /// Maximum number of commits to show
+    pub max_count: Option<usize>,
+    /// Commits to walk and their order
+    pub walk: RevWalkOptions,
+    /// Author, message, date and change filters
+    pub filter: LogFilterOptions,
+    /// Show the selected commits oldest first
+    pub reverse: bool,
+    /// Only commits changing these paths
+    pub paths: Vec<String>,
+    /// Follow the single path across renames
+    pub follow: bool,
+    /// Only commits changing these line ranges (`<start>,<end>:<file>`)
+    pub line_ranges: Vec<String>,
+    /// Show the patch of each commit
+    pub patch: bool,
+    /// Show a diffstat for each commit
+    pub stat: bool,
+    /// Show the names and status of changed files
+    pub name_status: bool,
+    /// Verify signed commits and show the result
+    pub show_signature: bool,
 }
 
-/// Format options for log output
-#[derive(Debug, Clone, Copy)]
-pub enum LogFormat {
-    Full,
-    Oneline,
-    Short,
+impl Default for LogOptions {
+    fn default() -> Self {
+        LogOptions {
+            format: None,
+            date: None,
+            decorate: false,
+            abbrev_commit: false,
+            graph: false,
+            use_color: true,
+            max_count: None,
+            walk: RevWalkOptions::default(),
+            filter: LogFilterOptions::default(),
+            reverse: false,
+            paths: Vec::new(),
+            follow: false,
+            line_ranges: Vec::new(),
+            patch: false,
+            stat: false,
+            name_status: false,
+            show_signature: false,
+        }
+    }
 }
 
-/// Walk the commit history and display commits
-fn walk_commits(start: &str, format: LogFormat, max_count: Option<usize>) -> io::Result<()> {
-    let mut visited = HashSet::new();
-    let mut stack = vec![start.to_string()];
-    let mut count = 0;
+/// Display commit history
+pub fn log(mut options: LogOptions) -> Result<()> {
+    let format = match &options.format {
+        Some(spec) => PrettyFormat::parse(spec)?,
+        None => PrettyFormat::Medium,
+    };
+    let date_mode = match &options.date {
+        Some(spec) => DateMode::parse(spec)?,
+        None => DateMode::Default,
+    };
 
-    while let Some(hash) = stack.pop() {
-        // Skip if already visited
-        if visited.contains(&hash) {
-            continue;
+    if options.graph {
+        if options.reverse {
+            return Err(KitcatError::InvalidInput("--reverse and --graph cannot be used together".to_string()));
         }
-        visited.insert(hash.clone());
+        // The graph needs children before their parents
+        if options.walk.order == SortOrder::Default {
+            options.walk.order = SortOrder::Topo;
+        }
+    }
 
+    if options.walk.revisions.is_empty()
+        && !options.walk.all
+        && !options.walk.branches
+        && crate::repo::resolve_head()?.is_none()
+    {
+        println!("No commits yet.");
+        return Ok(());
+    }
+
+    let filter = CommitFilter::new(&options.filter)?;
+    let mut limiter = PathLimiter::new(&options.paths, options.follow, &options.line_ranges)?;
+    let show_changes = options.patch || options.stat || options.name_status || !options.line_ranges.is_empty();
+
+    let mut commits = Vec::new();
+    for hash in rev_walk(&options.walk)? {
         // Check max count
-        if let Some(max) = max_count {
-            if count >= max {
-                break;
-            }
+        if options.max_count.is_some_and(|max| commits.len() >= max) || limiter.is_exhausted() {
+            break;
         }
-        count += 1;
 
-        // Read the commit
+        let hash = hash?;
         let commit = match read_commit(&hash) {
             Ok(c) => c,
             Err(e) => {
@@ -69,174 +133,190 @@ fn walk_commits(start: &str, format: LogFormat, max_count: Option<usize>) -> io:
                 continue;
             }
         };
+        if !filter.matches(&hash, &commit)? {
+            continue;
+        }
 
-        // Display the commit
-        match format {
-            LogFormat::Full => {
-                println!("commit {}", hash);
-                if !commit.parents.is_empty() {
-                    if commit.parents.len() == 1 {
-                        println!("Parent: {}", commit.parents[0]);
-                    } else {
-                        println!("Merge:");
-                        for parent in &commit.parents {
-                            println!("  {}", parent);
-                        }
-                    }
-                }
-                println!("Author: {}", commit.author);
-                let dt = Local.timestamp_opt(commit.author_time, 0).unwrap();
-                println!("Date:   {}", dt.format("%a %b %d %H:%M:%S %Y %z"));
-                println!();
-                for line in commit.message.lines() {
-                    println!("    {}", line);
-                }
-                println!();
-            }
-            LogFormat::Oneline => {
-                let first_line = commit.message.lines().next().unwrap_or("");
-                println!("{} {}", &hash[0..7], first_line);
+        // Commits not touching the paths are simplified away
+        let changes = if limiter.is_active() {
+            match limiter.changes(&hash, &commit)? {
+                Some(diffs) => diffs,
+                None => continue,
             }
-            LogFormat::Short => {
-                println!("commit {}", hash);
-                println!("Author: {}", commit.author);
-                println!();
-                let first_line = commit.message.lines().next().unwrap_or("");
-                println!("    {}", first_line);
-                println!();
+        } else if show_changes {
+            commit_changes(&hash, &commit)?
+        } else {
+            Vec::new()
+        };
+        commits.push((hash, commit, changes));
+    }
+
+    // --reverse applies to the commits selected by --max-count
+    if options.reverse {
+        commits.reverse();
+    }
+
+    // Templates may ask for decorations with %d / %D
+    let decorations = if options.decorate || matches!(format, PrettyFormat::Format(_)) {
+        load_decorations()?
+    } else {
+        HashMap::new()
+    };
+    let mut context = FormatContext {
+        date_mode,
+        abbrev_commit: options.abbrev_commit,
+        decorate: options.decorate,
+        decorations: Vec::new(),
+        use_color: options.use_color,
+    };
+    let mut graph = options.graph.then(|| Graph::new(options.use_color));
+    let config = Config::read()?;
+
+    for (i, (hash, commit, changes)) in commits.iter().enumerate() {
+        context.decorations = decorations.get(hash).cloned().unwrap_or_default();
+        let mut text = format_commit_with(hash, commit, &format, &context);
+        if let Some(report) = signature_report(hash, &config, options.show_signature)? {
+            text = insert_signature_report(&text, &report, format.is_single_line());
+        }
+        if show_changes && !changes.is_empty() {
+            if !format.is_single_line() {
+                text.push('\n');
             }
+            text.push('\n');
+            text.push_str(format_changes(changes, &options).trim_end_matches('\n'));
+        }
+        // Named formats are separated by a blank line
+        if !format.is_single_line() && i + 1 < commits.len() {
+            text.push('\n');
         }
 
-        // Add parents to stack (in reverse order for correct traversal)
-        for parent in commit.parents.iter().rev() {
-            if !visited.contains(parent) {
-                stack.push(parent.clone());
+        match graph.as_mut() {
+            Some(graph) => {
+                let parents = shown_parents(commit, &options.walk);
+                print_with_graph(&text, graph, hash, &parents);
             }
+            None => println!("{}", text),
         }
     }
 
     Ok(())
 }
 
-/// Log with filtering options
-pub fn log_with_filter(
-    format: LogFormat,
-    max_count: Option<usize>,
-    author_filter: Option<&str>,
-    since: Option<i64>,
-    until: Option<i64>,
-) -> io::Result<()> {
-    // Get starting commit from HEAD
-    let head_content = crate::repo::read_head();
-    let start_commit = if head_content.starts_with("ref:") {
-        let branch_name = head_content.trim_start_matches("ref: ").trim();
-        let branch_path = format!(".kitcat/{}", branch_name);
-
-        if !std::path::Path::new(&branch_path).exists() {
-            println!("No commits yet.");
-            return Ok(());
-        }
+/// What verifying a commit's signature reported, if asked for and it is signed
+fn signature_report(hash: &str, config: &Config, show_signature: bool) -> Result<Option<String>> {
+    if !show_signature {
+        return Ok(None);
+    }
+    let Some((payload, signature)) = read_commit_signature(hash)? else {
+        return Ok(None);
+    };
+    Ok(Some(verify_payload(config, &payload, &signature)?.output))
+}
 
-        std::fs::read_to_string(&branch_path)?.trim().to_string()
-    } else if head_content.len() == 40 {
-        head_content
-    } else {
-        println!("No commits yet.");
-        return Ok(());
+/// Put a signature report after a commit's first line, or before a one-line format
+fn insert_signature_report(text: &str, report: &str, single_line: bool) -> String {
+    match text.split_once('\n') {
+        Some((first, rest)) if !single_line => format!("{}\n{}\n{}", first, report, rest),
+        _ if !single_line => format!("{}\n{}", text, report),
+        _ => format!("{}\n{}", report, text),
+    }
+}
+
+/// Changes of a commit against its first parent (none for merges)
+fn commit_changes(hash: &str, commit: &Commit) -> Result<Vec<FileDiff>> {
+    let parent_files = match commit.parents.as_slice() {
+        [] => HashMap::new(),
+        [parent] => get_commit_files(parent)?,
+        _ => return Ok(Vec::new()),
     };
+    diff_file_maps(&parent_files, &get_commit_files(hash)?, false)
+}
 
-    // Walk commits with filtering
-    let mut visited = HashSet::new();
-    let mut stack = vec![start_commit];
-    let mut count = 0;
+/// Format changes for `--name-status`, `--stat` and `-p`, in that order
+fn format_changes(diffs: &[FileDiff], options: &LogOptions) -> String {
+    let mut output = String::new();
 
-    while let Some(hash) = stack.pop() {
-        if visited.contains(&hash) {
-            continue;
+    if options.name_status {
+        for diff in diffs {
+            output.push_str(&format!("{}\n", name_status(diff)));
         }
-        visited.insert(hash.clone());
-
-        let commit = match read_commit(&hash) {
-            Ok(c) => c,
-            Err(_) => continue,
+    }
+    if options.stat {
+        if !output.is_empty() {
+            output.push('\n');
+        }
+        output.push_str(&format_diffstat(diffs));
+    }
+    if options.patch || !options.line_ranges.is_empty() {
+        if !output.is_empty() {
+            output.push('\n');
+        }
+        let unified = UnifiedDiffOptions {
+            use_color: options.use_color,
+            ..UnifiedDiffOptions::default()
         };
+        for diff in diffs {
+            output.push_str(&format_unified_diff(diff, &unified));
+        }
+    }
 
-        // Apply filters
-        let mut should_display = true;
+    output
+}
 
-        if let Some(author) = author_filter {
-            if !commit.author.contains(author) {
-                should_display = false;
-            }
-        }
+/// `<status>\t<path>` line of `--name-status` (`R<score>\t<old>\t<new>` for renames)
+fn name_status(diff: &FileDiff) -> String {
+    if let Some(source) = diff.rename_source() {
+        return format!(
+            "R{:03}\t{}\t{}",
+            diff.similarity.unwrap_or(0),
+            source,
+            diff.display_path()
+        );
+    }
 
-        if let Some(since_time) = since {
-            if commit.author_time < since_time {
-                should_display = false;
-            }
-        }
+    let status = if diff.old_path == "/dev/null" {
+        'A'
+    } else if diff.new_path == "/dev/null" {
+        'D'
+    } else {
+        'M'
+    };
+    format!("{}\t{}", status, diff.display_path())
+}
 
-        if let Some(until_time) = until {
-            if commit.author_time > until_time {
-                should_display = false;
-            }
-        }
+/// Parents drawn in the graph
+fn shown_parents(commit: &Commit, walk: &RevWalkOptions) -> Vec<String> {
+    if walk.first_parent {
+        commit.parents.iter().take(1).cloned().collect()
+    } else {
+        commit.parents.clone()
+    }
+}
 
-        if should_display {
-            if let Some(max) = max_count {
-                if count >= max {
-                    break;
-                }
-            }
-            count += 1;
-
-            // Display the commit
-            match format {
-                LogFormat::Full => {
-                    println!("commit {}", hash);
-                    if !commit.parents.is_empty() {
-                        if commit.parents.len() == 1 {
-                            println!("Parent: {}", commit.parents[0]);
-                        } else {
-                            println!("Merge:");
-                            for parent in &commit.parents {
-                                println!("  {}", parent);
-                            }
-                        }
-                    }
-                    println!("Author: {}", commit.author);
-                    let dt = Local.timestamp_opt(commit.author_time, 0).unwrap();
-                    println!("Date:   {}", dt.format("%a %b %d %H:%M:%S %Y %z"));
-                    println!();
-                    for line in commit.message.lines() {
-                        println!("    {}", line);
-                    }
-                    println!();
-                }
-                LogFormat::Oneline => {
-                    let first_line = commit.message.lines().next().unwrap_or("");
-                    println!("{} {}", &hash[0..7], first_line);
-                }
-                LogFormat::Short => {
-                    println!("commit {}", hash);
-                    println!("Author: {}", commit.author);
-                    println!();
-                    let first_line = commit.message.lines().next().unwrap_or("");
-                    println!("    {}", first_line);
-                    println!();
-                }
-            }
-        }
+/// Print a formatted commit with the graph rows in front of its lines
+///
+/// The first line gets the commit row, the next lines the merge and collapse
+/// rows (printed on their own when the text is shorter), the rest padding.
+fn print_with_graph(text: &str, graph: &mut Graph, hash: &str, parents: &[String]) {
+    let rows = graph.draw_commit(hash, parents);
+    let mut prefixes = std::iter::once(&rows.commit).chain(&rows.transitions);
 
-        // Add parents to stack
-        for parent in commit.parents.iter().rev() {
-            if !visited.contains(parent) {
-                stack.push(parent.clone());
-            }
-        }
+    for line in text.split('\n') {
+        let prefix = prefixes.next().unwrap_or(&rows.padding);
+        print_graph_line(prefix, line);
+    }
+    for prefix in prefixes {
+        print_graph_line(prefix, "");
     }
+}
 
-    Ok(())
+/// Print one graph row and its text, without trailing spaces
+fn print_graph_line(prefix: &str, line: &str) {
+    if line.is_empty() {
+        println!("{}", prefix.trim_end());
+    } else {
+        println!("{} {}", prefix, line);
+    }
 }
 
 #[cfg(test)]
@@ -244,9 +324,39 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_log_format() {
-        // Simple format enum test
-        let fmt = LogFormat::Oneline;
-        assert!(matches!(fmt, LogFormat::Oneline));
+    fn test_insert_signature_report() {
+        let report = "Good \"git\" signature for jane@example.com";
+        assert_eq!(
+            insert_signature_report("commit abc\nAuthor: Jane", report, false),
+            format!("commit abc\n{}\nAuthor: Jane", report)
+        );
+        assert_eq!(
+            insert_signature_report("abc1234 Subject", report, true),
+            format!("{}\nabc1234 Subject", report)
+        );
+    }
+
+    #[test]
+    fn test_shown_parents() {
+        let commit = Commit {
+            tree: "a".repeat(40),
+            parents: vec!["b".repeat(40), "c".repeat(40)],
+            author: "Jane Doe <jane@example.com>".to_string(),
+            author_time: 0,
+            author_timezone: "+0000".to_string(),
+            committer: "Jane Doe <jane@example.com>".to_string(),
+            committer_time: 0,
+            committer_timezone: "+0000".to_string(),
+            message: "Merge".to_string(),
+        };
+
+        let walk = RevWalkOptions::default();
+        assert_eq!(shown_parents(&commit, &walk).len(), 2);
+
+        let walk = RevWalkOptions {
+            first_parent: true,
+            ..Default::default()
+        };
+        assert_eq!(shown_parents(&commit, &walk), vec!["b".repeat(40)]);
     }
 }
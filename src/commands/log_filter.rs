@@ -0,0 +1,282 @@
+/// Commit filters for `log`
+///
+/// - `--author` / `--committer`: regular expressions matched against
+///   `Name <email>`; several patterns of one kind match any of them
+/// - `--grep`: regular expressions matched against the message; any of them,
+///   or all of them with `--all-match`
+/// - `--since` / `--until`: committer date bounds (see `date::parse_date`)
+/// - `-S<string>`: commits changing the number of occurrences of a string
+/// - `-G<regex>`: commits whose added or removed lines match a regex
+///
+/// `-i` makes the author, committer and message patterns case-insensitive.
+/// The pickaxe options (`-S`, `-G`) compare a commit with its parent (the
+/// empty tree for root commits) and never match merges.
+
+use crate::date::parse_date;
+use crate::diff::{DiffLineType, diff_file_maps};
+use crate::error::{KitcatError, Result};
+use crate::merge::get_commit_files;
+use crate::models::{Commit, FileEntry};
+use crate::object::read_blob_content;
+use regex::{Regex, RegexBuilder};
+use std::collections::HashMap;
+
+/// Filter options as given on the command line
+#[derive(Debug, Clone, Default)]
+pub struct LogFilterOptions {
+    /// Author patterns
+    pub authors: Vec<String>,
+    /// Committer patterns
+    pub committers: Vec<String>,
+    /// Message patterns
+    pub grep: Vec<String>,
+    /// Match patterns without regard to case
+    pub ignore_case: bool,
+    /// Require every `--grep` pattern to match
+    pub all_match: bool,
+    /// Only commits committed at or after this date
+    pub since: Option<String>,
+    /// Only commits committed at or before this date
+    pub until: Option<String>,
+    /// `-S`: string whose number of occurrences changes
+    pub pickaxe: Option<String>,
+    /// `-G`: regex matching an added or removed line
+    pub pickaxe_regex: Option<String>,
+}
+
+/// Compiled log filters
+#[derive(Debug, Default)]
+pub struct CommitFilter {
+    authors: Vec<Regex>,
+    committers: Vec<Regex>,
+    grep: Vec<Regex>,
+    all_match: bool,
+    since: Option<i64>,
+    until: Option<i64>,
+    pickaxe: Option<String>,
+    pickaxe_regex: Option<Regex>,
+}
+
+impl CommitFilter {
+    /// Compile filter options, rejecting invalid patterns and dates
+    pub fn new(options: &LogFilterOptions) -> Result<Self> {
+        let compile_all = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|pattern| compile(pattern, options.ignore_case))
+                .collect::<Result<Vec<_>>>()
+        };
+
+        Ok(CommitFilter {
+            authors: compile_all(&options.authors)?,
+            committers: compile_all(&options.committers)?,
+            grep: compile_all(&options.grep)?,
+            all_match: options.all_match,
+            since: options.since.as_deref().map(parse_date).transpose()?,
+            until: options.until.as_deref().map(parse_date).transpose()?,
+            pickaxe: options.pickaxe.clone().filter(|s| !s.is_empty()),
+            pickaxe_regex: options
+                .pickaxe_regex
+                .as_deref()
+                .map(|pattern| compile(pattern, false))
+                .transpose()?,
+        })
+    }
+
+    /// Whether a commit passes every filter
+    pub fn matches(&self, hash: &str, commit: &Commit) -> Result<bool> {
+        if self
+            .since
+            .is_some_and(|since| commit.committer_time < since)
+            || self
+                .until
+                .is_some_and(|until| commit.committer_time > until)
+        {
+            return Ok(false);
+        }
+
+        let any = |patterns: &[Regex], text: &str| {
+            patterns.is_empty() || patterns.iter().any(|re| re.is_match(text))
+        };
+        if !any(&self.authors, &commit.author) || !any(&self.committers, &commit.committer) {
+            return Ok(false);
+        }
+
+        let message_matches = if self.all_match {
+            self.grep.iter().all(|re| re.is_match(&commit.message))
+        } else {
+            any(&self.grep, &commit.message)
+        };
+        if !message_matches {
+            return Ok(false);
+        }
+
+        if self.pickaxe.is_none() && self.pickaxe_regex.is_none() {
+            return Ok(true);
+        }
+        self.matches_changes(hash, commit)
+    }
+
+    /// Whether the changes of a commit pass `-S` and `-G`
+    fn matches_changes(&self, hash: &str, commit: &Commit) -> Result<bool> {
+        let old_files = match commit.parents.as_slice() {
+            [] => HashMap::new(),
+            [parent] => get_commit_files(parent)?,
+            _ => return Ok(false),
+        };
+        let new_files = get_commit_files(hash)?;
+
+        if let Some(needle) = &self.pickaxe
+            && !changes_occurrences(&old_files, &new_files, needle)?
+        {
+            return Ok(false);
+        }
+
+        if let Some(regex) = &self.pickaxe_regex {
+            let diffs = diff_file_maps(&old_files, &new_files, false)?;
+            let changed_line_matches = diffs
+                .iter()
+                .flat_map(|diff| &diff.hunks)
+                .flat_map(|hunk| &hunk.lines)
+                .any(|line| {
+                    line.line_type != DiffLineType::Context && regex.is_match(&line.content)
+                });
+            if !changed_line_matches {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+/// Compile a pattern, optionally case-insensitive
+fn compile(pattern: &str, ignore_case: bool) -> Result<Regex> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(ignore_case)
+        .build()
+        .map_err(|e| {
+            KitcatError::InvalidInput(format!("Invalid pattern '{}': {}", pattern, e))
+        })
+}
+
+/// Whether any changed file has a different number of occurrences of `needle`
+fn changes_occurrences(
+    old_files: &HashMap<String, FileEntry>,
+    new_files: &HashMap<String, FileEntry>,
+    needle: &str,
+) -> Result<bool> {
+    let count = |entry: Option<&FileEntry>| -> Result<usize> {
+        Ok(match entry {
+            Some(entry) => count_occurrences(&read_blob_content(&entry.hash)?, needle.as_bytes()),
+            None => 0,
+        })
+    };
+
+    for path in old_files.keys().chain(new_files.keys()) {
+        let (old, new) = (old_files.get(path), new_files.get(path));
+        if old.map(|e| &e.hash) != new.map(|e| &e.hash) && count(old)? != count(new)? {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Non-overlapping occurrences of `needle` in `haystack`
+fn count_occurrences(haystack: &[u8], needle: &[u8]) -> usize {
+    let mut count = 0;
+    let mut at = 0;
+    while at + needle.len() <= haystack.len() {
+        if &haystack[at..at + needle.len()] == needle {
+            count += 1;
+            at += needle.len();
+        } else {
+            at += 1;
+        }
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(author: &str, time: i64, message: &str) -> Commit {
+        Commit {
+            tree: "a".repeat(40),
+            parents: Vec::new(),
+            author: author.to_string(),
+            author_time: time,
+            author_timezone: "+0000".to_string(),
+            committer: "Release Bot <bot@example.com>".to_string(),
+            committer_time: time,
+            committer_timezone: "+0000".to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_commit_filter() {
+        let fix = commit(
+            "Jane Doe <jane@example.com>",
+            2_000,
+            "Fix crash in parser\n\nIncident 42",
+        );
+        let filter = |options: LogFilterOptions| {
+            CommitFilter::new(&options)
+                .unwrap()
+                .matches(&"f".repeat(40), &fix)
+                .unwrap()
+        };
+
+        assert!(filter(LogFilterOptions {
+            authors: vec!["^Jane".into(), "nobody".into()],
+            ..Default::default()
+        }));
+        assert!(!filter(LogFilterOptions {
+            authors: vec!["jane doe".into()],
+            ..Default::default()
+        }));
+        assert!(filter(LogFilterOptions {
+            authors: vec!["jane doe".into()],
+            ignore_case: true,
+            ..Default::default()
+        }));
+        assert!(!filter(LogFilterOptions {
+            committers: vec!["Jane".into()],
+            ..Default::default()
+        }));
+        assert!(filter(LogFilterOptions {
+            grep: vec!["crash".into(), "feature".into()],
+            ..Default::default()
+        }));
+        assert!(!filter(LogFilterOptions {
+            grep: vec!["crash".into(), "feature".into()],
+            all_match: true,
+            ..Default::default()
+        }));
+        assert!(filter(LogFilterOptions {
+            since: Some("@1000".into()),
+            ..Default::default()
+        }));
+        assert!(!filter(LogFilterOptions {
+            until: Some("@1000".into()),
+            ..Default::default()
+        }));
+        assert!(
+            CommitFilter::new(&LogFilterOptions {
+                grep: vec!["(".into()],
+                ..Default::default()
+            })
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_count_occurrences() {
+        assert_eq!(count_occurrences(b"aaaa", b"aa"), 2);
+        assert_eq!(count_occurrences(b"fn main() { main() }", b"main"), 2);
+        assert_eq!(count_occurrences(b"", b"x"), 0);
+    }
+}
@@ -0,0 +1,346 @@
+/// Path and line range limiting for `log`
+///
+/// - `-- <path>...`: only commits changing a matching file (the path itself
+///   or anything below it) compared to their first parent. Merges are shown
+///   only when the paths differ from every parent.
+/// - `--follow`: with a single path, continue the history of a file under
+///   its old name when it was added by a rename (see `diff::rename`)
+/// - `-L <start>,<end>:<file>`: only commits changing lines in the range.
+///   The range is mapped to the parent's version of the file through the
+///   diff, and is dropped once all of its lines were introduced.
+///
+/// Commits must come newest first, as the followed names and line ranges
+/// are updated while walking back in history.
+
+use crate::diff::algorithm::{compute_edit_script, Edit};
+use crate::diff::rename::{diff_rename, find_rename_source};
+use crate::diff::{diff_blobs, diff_file_maps, FileDiff};
+use crate::error::{KitcatError, Result};
+use crate::merge::get_commit_files;
+use crate::models::{Commit, FileEntry};
+use crate::object::read_blob_content;
+use std::collections::HashMap;
+
+/// A line range of a file, as of the commit being looked at
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineRange {
+    /// File the lines belong to
+    pub path: String,
+    /// First line (1-indexed)
+    pub start: usize,
+    /// Last line (inclusive)
+    pub end: usize,
+}
+
+impl LineRange {
+    /// Parse `<start>,<end>:<file>`, where `<end>` may be `+<count>`
+    pub fn parse(spec: &str) -> Result<Self> {
+        let invalid = || {
+            KitcatError::InvalidInput(format!("Invalid line range '{}' (expected <start>,<end>:<file>)", spec))
+        };
+
+        let (range, path) = spec.split_once(':').ok_or_else(invalid)?;
+        let (start, end) = range.split_once(',').ok_or_else(invalid)?;
+        let start: usize = start.trim().parse().map_err(|_| invalid())?;
+        let end = match end.trim().strip_prefix('+') {
+            Some(count) => {
+                let count: usize = count.parse().map_err(|_| invalid())?;
+                start + count.saturating_sub(1)
+            }
+            None => end.trim().parse().map_err(|_| invalid())?,
+        };
+        if start == 0 || end < start || path.is_empty() {
+            return Err(invalid());
+        }
+
+        Ok(LineRange {
+            path: path.to_string(),
+            start,
+            end,
+        })
+    }
+}
+
+/// History limiting state for one `log` run
+#[derive(Debug, Default)]
+pub struct PathLimiter {
+    pathspecs: Vec<String>,
+    follow: bool,
+    ranges: Vec<LineRange>,
+}
+
+impl PathLimiter {
+    /// Check the pathspecs and line ranges given to `log`
+    pub fn new(pathspecs: &[String], follow: bool, line_ranges: &[String]) -> Result<Self> {
+        if follow && pathspecs.len() != 1 {
+            return Err(KitcatError::InvalidInput("--follow requires exactly one pathspec".to_string()));
+        }
+        if !line_ranges.is_empty() && !pathspecs.is_empty() {
+            return Err(KitcatError::InvalidInput("-L cannot be used with pathspecs".to_string()));
+        }
+
+        Ok(PathLimiter {
+            pathspecs: pathspecs
+                .iter()
+                .map(|path| path.trim_end_matches('/').to_string())
+                .collect(),
+            follow,
+            ranges: line_ranges
+                .iter()
+                .map(|spec| LineRange::parse(spec))
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    /// Whether any paths or line ranges were given
+    pub fn is_active(&self) -> bool {
+        !self.pathspecs.is_empty() || !self.ranges.is_empty()
+    }
+
+    /// Whether every line range has been traced back to where it was introduced
+    pub fn is_exhausted(&self) -> bool {
+        !self.ranges.is_empty() && self.ranges.iter().all(|range| range.end == 0)
+    }
+
+    /// The changes to show for a commit, or `None` when it is left out
+    pub fn changes(&mut self, hash: &str, commit: &Commit) -> Result<Option<Vec<FileDiff>>> {
+        if self.ranges.is_empty() {
+            self.path_changes(hash, commit)
+        } else {
+            self.range_changes(hash, commit)
+        }
+    }
+
+    /// Whether a path is selected by the pathspecs
+    fn matches(&self, path: &str) -> bool {
+        self.pathspecs.iter().any(|spec| {
+            spec == "." || path == spec || path.strip_prefix(spec.as_str()).is_some_and(|rest| rest.starts_with('/'))
+        })
+    }
+
+    /// Only the files selected by the pathspecs
+    fn limit(&self, files: &HashMap<String, FileEntry>) -> HashMap<String, FileEntry> {
+        files
+            .iter()
+            .filter(|(path, _)| self.matches(path))
+            .map(|(path, entry)| (path.clone(), entry.clone()))
+            .collect()
+    }
+
+    /// Changes to the selected paths against the first parent
+    fn path_changes(&mut self, hash: &str, commit: &Commit) -> Result<Option<Vec<FileDiff>>> {
+        let files = get_commit_files(hash)?;
+        let limited = self.limit(&files);
+
+        if commit.parents.len() > 1 {
+            for parent in &commit.parents {
+                if self.limit(&get_commit_files(parent)?) == limited {
+                    return Ok(None);
+                }
+            }
+            return Ok(Some(Vec::new()));
+        }
+
+        let parent_files = match commit.parents.first() {
+            Some(parent) => get_commit_files(parent)?,
+            None => HashMap::new(),
+        };
+        let mut diffs = diff_file_maps(&self.limit(&parent_files), &limited, false)?;
+
+        if self.follow {
+            let path = self.pathspecs[0].clone();
+            if let Some((source, score)) = find_rename_source(&path, &parent_files, &files)? {
+                diffs.retain(|diff| diff.display_path() != path);
+                diffs.push(diff_rename(&source, &parent_files[&source], &path, &files[&path], score)?);
+                self.pathspecs[0] = source;
+            }
+        }
+
+        Ok((!diffs.is_empty()).then_some(diffs))
+    }
+
+    /// Changes to the traced line ranges against the first parent
+    fn range_changes(&mut self, hash: &str, commit: &Commit) -> Result<Option<Vec<FileDiff>>> {
+        let files = get_commit_files(hash)?;
+        let parent_files = match commit.parents.first() {
+            Some(parent) => get_commit_files(parent)?,
+            None => HashMap::new(),
+        };
+
+        let mut diffs = Vec::new();
+        for range in self.ranges.iter_mut().filter(|range| range.end > 0) {
+            let Some(entry) = files.get(&range.path) else {
+                continue;
+            };
+
+            // The file may have had another name in the parent
+            let (old_path, score) = if parent_files.contains_key(&range.path) {
+                (Some(range.path.clone()), None)
+            } else {
+                match find_rename_source(&range.path, &parent_files, &files)? {
+                    Some((source, score)) => (Some(source), Some(score)),
+                    None => (None, None),
+                }
+            };
+            let old_entry = old_path.as_ref().map(|path| &parent_files[path]);
+            if old_entry.is_some_and(|old| old.hash == entry.hash) {
+                if let Some(old_path) = old_path {
+                    range.path = old_path;
+                }
+                continue;
+            }
+
+            let new_content = read_blob_content(&entry.hash)?;
+            let old_content = old_entry.map(|old| read_blob_content(&old.hash)).transpose()?;
+            let old_text = String::from_utf8_lossy(old_content.as_deref().unwrap_or_default()).into_owned();
+            let new_text = String::from_utf8_lossy(&new_content).into_owned();
+
+            let (touched, parent_range) = map_range(&old_text, &new_text, range.start, range.end);
+            if touched {
+                let mut diff = match (&old_path, old_entry, score) {
+                    (Some(source), Some(old), Some(score)) => diff_rename(source, old, &range.path, entry, score)?,
+                    _ => {
+                        let mut diff = diff_blobs(&range.path, old_content.as_deref(), Some(&new_content), false);
+                        diff.old_mode = old_entry.map(|old| old.mode);
+                        diff.new_mode = Some(entry.mode);
+                        diff
+                    }
+                };
+                let (start, end) = (range.start, range.end);
+                diff.hunks.retain(|hunk| {
+                    hunk.new_start <= end && hunk.new_start + hunk.new_count.max(1) > start
+                });
+                diffs.push(diff);
+            }
+
+            match (old_path, parent_range) {
+                (Some(old_path), Some((start, end))) => {
+                    *range = LineRange {
+                        path: old_path,
+                        start,
+                        end,
+                    }
+                }
+                // Every line of the range was introduced here
+                _ => range.end = 0,
+            }
+        }
+
+        Ok((!diffs.is_empty()).then_some(diffs))
+    }
+}
+
+/// Trace lines `start..=end` of `new_text` back to `old_text`
+///
+/// Returns whether any line of the range was added, removed or changed,
+/// and the old lines the range comes from: the lines kept in it and the
+/// lines replaced by it (none when every line of the range was added).
+fn map_range(old_text: &str, new_text: &str, start: usize, end: usize) -> (bool, Option<(usize, usize)>) {
+    let old_lines: Vec<&str> = old_text.lines().collect();
+    let new_lines: Vec<&str> = new_text.lines().collect();
+    let end = end.min(new_lines.len());
+    let in_range = |line: usize| line >= start && line <= end;
+
+    let mut old_line = 0;
+    let mut new_line = 0;
+    let mut touched = false;
+    let mut source: Option<(usize, usize)> = None;
+    let include = |source: &mut Option<(usize, usize)>, first: usize, last: usize| {
+        *source = Some(match *source {
+            Some((from, to)) => (from.min(first), to.max(last)),
+            None => (first, last),
+        });
+    };
+
+    // Each block of changes between kept lines: its removed old lines and
+    // whether it added lines to the range
+    let mut removed: Option<(usize, usize)> = None;
+    let mut added_in_range = false;
+
+    for edit in compute_edit_script(&old_lines, &new_lines)
+        .into_iter()
+        .map(Some)
+        .chain(std::iter::once(None))
+    {
+        match edit {
+            Some(Edit::Insert) => {
+                new_line += 1;
+                added_in_range |= in_range(new_line);
+            }
+            Some(Edit::Delete) => {
+                old_line += 1;
+                removed = Some((removed.map_or(old_line, |(first, _)| first), old_line));
+                // A removal between two lines of the range changes it
+                touched |= new_line >= start && new_line < end;
+            }
+            Some(Edit::Keep) | None => {
+                if added_in_range {
+                    touched = true;
+                    if let Some((first, last)) = removed {
+                        include(&mut source, first, last);
+                    }
+                }
+                removed = None;
+                added_in_range = false;
+
+                if edit.is_some() {
+                    old_line += 1;
+                    new_line += 1;
+                    if in_range(new_line) {
+                        include(&mut source, old_line, old_line);
+                    }
+                }
+            }
+        }
+    }
+
+    (touched, source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_line_range() {
+        assert_eq!(
+            LineRange::parse("3,5:src/main.rs").unwrap(),
+            LineRange {
+                path: "src/main.rs".to_string(),
+                start: 3,
+                end: 5
+            }
+        );
+        assert_eq!(LineRange::parse("10,+3:a.txt").unwrap().end, 12);
+        assert!(LineRange::parse("5,3:a.txt").is_err());
+        assert!(LineRange::parse("3,5").is_err());
+        assert!(LineRange::parse("0,5:a.txt").is_err());
+    }
+
+    #[test]
+    fn test_map_range() {
+        let old = "a\nb\nc\nd\ne\n";
+
+        // Unchanged lines move down by one
+        assert_eq!(map_range(old, "new\na\nb\nc\nd\ne\n", 3, 4), (false, Some((2, 3))));
+        // A changed line inside the range
+        assert_eq!(map_range(old, "a\nb\nC\nd\ne\n", 2, 4), (true, Some((2, 4))));
+        // A removed line inside the range
+        assert_eq!(map_range(old, "a\nb\nd\ne\n", 2, 3), (true, Some((2, 4))));
+        // A replaced line is traced to the line it replaced
+        assert_eq!(map_range(old, "a\nB\nc\nd\ne\n", 2, 2), (true, Some((2, 2))));
+        // Changes outside the range
+        assert_eq!(map_range(old, "a\nb\nc\nd\nE\n", 1, 2), (false, Some((1, 2))));
+        // Every line was added
+        assert_eq!(map_range("", "a\nb\n", 1, 2), (true, None));
+    }
+
+    #[test]
+    fn test_pathspec_matching() {
+        let limiter = PathLimiter::new(&["src/".to_string()], false, &[]).unwrap();
+        assert!(limiter.matches("src/main.rs"));
+        assert!(!limiter.matches("srcs/main.rs"));
+        assert!(PathLimiter::new(&[], true, &[]).is_err());
+        assert!(PathLimiter::new(&["a".to_string()], false, &["1,2:a".to_string()]).is_err());
+    }
+}
@@ -1,21 +1,50 @@
 /// Merge command implementation
 ///
-/// Combines branches using three-way merge algorithm
+/// Combines branches using three-way merge algorithm. Strategies (`-s`):
+/// - `ort`/`recursive` (default): merges several merge bases into a virtual base
+/// - `resolve`: plain three-way merge against a single merge base
+/// - `ours`/`theirs`: record the merge but keep one side's tree
+/// - `octopus` (default for several branches): merges all branches into one
+///   commit with N parents, refusing if any step would conflict
+///
+/// The `pre-merge-commit` and `commit-msg` hooks run before a merge commit
+/// is recorded, unless `--no-verify` is given. When one fails, the merged
+/// result is left in place as a merge in progress for `--continue`.
 
+use crate::error::{KitcatError, Result};
+use crate::hooks::{run_commit_msg_hook, verify_hook};
 use crate::index::{read_index_binary, write_index_binary};
-use crate::merge::{can_fast_forward, find_merge_base, get_commit_files, merge_trees};
-use crate::models::IndexEntry;
-use crate::object::{create_commit, get_commit_tree};
-use crate::repo::read_head;
+use crate::merge::recursive::apply_merge_result;
+use crate::merge::rerere;
+use crate::merge::worktree::{
+    apply_autostash, changed_paths, check_index_matches_head, check_paths, create_autostash,
+    has_autostash, touched_paths,
+};
+use crate::merge::{
+    can_fast_forward, find_merge_base, find_merge_bases, find_merge_bases_many, get_commit_files,
+    is_ancestor, merge_base_files, merge_trees, ConflictStyle, MarkerOptions, MergeStrategy,
+    StrategyOptions,
+};
+use crate::models::{FileEntry, IndexEntry, MODE_FILE};
+use crate::object::{
+    create_commit, read_blob_content, write_blob, write_tree_from_index,
+};
+use crate::repo::{read_head, resolve_revision};
+use crate::utils::{worktree_path_exists, write_worktree_file};
+use std::collections::HashMap;
 use std::fs;
-use std::io;
 use std::path::Path;
 
+/// Paths that conflicted in the merge in progress, one per line
+const CONFLICTS_FILE: &str = ".kitcat/merge/conflicts";
+/// Paths the merge in progress wrote or removed, one per line (`--abort` restores them)
+const WRITTEN_FILE: &str = ".kitcat/merge/written";
+
 /// Merge options
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct MergeOptions {
-    /// Branch or commit to merge
-    pub target: String,
+    /// Branches or commits to merge (several for an octopus merge)
+    pub targets: Vec<String>,
     /// Abort an in-progress merge
     pub abort: bool,
     /// Continue after resolving conflicts
@@ -26,33 +55,39 @@ pub struct MergeOptions {
     pub ff_only: bool,
     /// Commit message for merge commit
     pub message: Option<String>,
-}
-
-impl Default for MergeOptions {
-    fn default() -> Self {
-        MergeOptions {
-            target: String::new(),
-            abort: false,
-            r#continue: false,
-            no_ff: false,
-            ff_only: false,
-            message: None,
-        }
-    }
+    /// Merge strategy (`-s`)
+    pub strategy: MergeStrategy,
+    /// Strategy options (`-X`)
+    pub strategy_options: StrategyOptions,
+    /// Stash local changes before merging and reapply them afterwards
+    pub autostash: bool,
+    /// Conflict style overriding `merge.conflictStyle` (`--conflict`)
+    pub conflict_style: Option<String>,
+    /// Skip the `pre-merge-commit` and `commit-msg` hooks
+    pub no_verify: bool,
 }
 
 /// Main merge command
-pub fn merge(options: MergeOptions) -> io::Result<()> {
+pub fn merge(options: MergeOptions) -> Result<()> {
     if options.abort {
         return abort_merge();
     }
 
     if options.r#continue {
-        return continue_merge(options.message.as_deref());
+        return continue_merge(options.message.as_deref(), !options.no_verify);
+    }
+
+    if let Some(style) = &options.conflict_style {
+        ConflictStyle::parse(style)?;
+    }
+
+    if Path::new(".kitcat/MERGE_HEAD").exists() {
+        return Err(KitcatError::Conflict("You have not concluded your merge (MERGE_HEAD exists).\n\
+             Please, commit your changes before you merge.".to_string()));
     }
 
     // Get current HEAD commit
-    let head_content = read_head();
+    let head_content = read_head()?;
     let our_commit = resolve_head(&head_content)?;
     let our_branch = if head_content.starts_with("ref:") {
         head_content
@@ -63,8 +98,47 @@ pub fn merge(options: MergeOptions) -> io::Result<()> {
         "HEAD".to_string()
     };
 
+    if options.autostash {
+        create_autostash(&our_commit, &our_branch)?;
+    }
+
+    let result = merge_into_head(&options, &head_content, &our_commit, &our_branch);
+
+    // A conflicted merge applies the autostash on --continue or --abort
+    if Path::new(".kitcat/MERGE_HEAD").exists() {
+        if has_autostash() {
+            println!("Your local changes are stashed and will be reapplied when the merge is concluded.");
+        }
+        return result;
+    }
+    let applied = apply_autostash(&resolve_head(&read_head()?)?);
+    result?;
+    applied
+}
+
+/// Merge the target(s) into the checked out commit
+fn merge_into_head(
+    options: &MergeOptions,
+    head_content: &str,
+    our_commit: &str,
+    our_branch: &str,
+) -> Result<()> {
+    if options.targets.len() > 1 || options.strategy == MergeStrategy::Octopus {
+        return match options.strategy {
+            MergeStrategy::Ours => merge_ours_many(options, head_content, our_commit, our_branch),
+            MergeStrategy::Recursive | MergeStrategy::Octopus => {
+                octopus_merge(options, head_content, our_commit, our_branch)
+            }
+            _ => Err(KitcatError::InvalidInput("This merge strategy can only merge a single branch - use octopus".to_string())),
+        };
+    }
+
+    let target = options.targets.first().ok_or_else(|| {
+        KitcatError::InvalidInput("No branch or commit to merge".to_string())
+    })?;
+
     // Resolve target to commit hash
-    let their_commit = resolve_ref(&options.target)?;
+    let their_commit = resolve_revision(target)?;
 
     // Check if already up to date
     if our_commit == their_commit {
@@ -72,81 +146,305 @@ pub fn merge(options: MergeOptions) -> io::Result<()> {
         return Ok(());
     }
 
+    let our_files = get_commit_files(our_commit)?;
+
     // Check for fast-forward
-    let can_ff = can_fast_forward(&our_commit, &their_commit)?;
+    let can_ff = can_fast_forward(our_commit, &their_commit)?;
 
     if can_ff && !options.no_ff {
         // Fast-forward merge
+        let their_files = get_commit_files(&their_commit)?;
+        check_paths(&our_files, &changed_paths(&our_files, &their_files))?;
         println!("Fast-forwarding...");
-        return fast_forward_merge(&head_content, &their_commit);
+        return fast_forward_merge(head_content, our_commit, &their_commit, &our_files, &their_files);
     }
 
     if options.ff_only && !can_ff {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            "Cannot fast-forward - merge commit required",
-        ));
+        return Err(KitcatError::InvalidInput("Cannot fast-forward - merge commit required".to_string()));
+    }
+    check_index_matches_head(&our_files)?;
+
+    let message = options
+        .message
+        .clone()
+        .unwrap_or_else(|| format!("Merge {} into {}", target, our_branch));
+
+    // Strategies that take one side's tree as the result
+    match options.strategy {
+        MergeStrategy::Ours => {
+            let parents = [our_commit.to_string(), their_commit.clone()];
+            let merge_commit = commit_merge(head_content, &message, &parents, !options.no_verify)?;
+            println!("Merge made by the 'ours' strategy.");
+            println!("Merge commit: {}", merge_commit);
+            return Ok(());
+        }
+        MergeStrategy::Theirs => {
+            let their_files = get_commit_files(&their_commit)?;
+            check_paths(&our_files, &changed_paths(&our_files, &their_files))?;
+            apply_files_to_worktree(&our_files, &their_files)?;
+            let parents = [our_commit.to_string(), their_commit.clone()];
+            let merge_commit = commit_merge(head_content, &message, &parents, !options.no_verify)?;
+            println!("Merge made by the 'theirs' strategy.");
+            println!("Merge commit: {}", merge_commit);
+            return Ok(());
+        }
+        MergeStrategy::Recursive | MergeStrategy::ThreeWay | MergeStrategy::Octopus => {}
     }
 
-    // Find merge base
-    let base_commit = find_merge_base(&our_commit, &their_commit)?;
+    // Find merge bases
+    let bases = match options.strategy {
+        MergeStrategy::ThreeWay => find_merge_base(our_commit, &their_commit)?.into_iter().collect(),
+        _ => find_merge_bases(our_commit, &their_commit)?,
+    };
 
-    if base_commit.is_none() {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            "No common ancestor found - refusing to merge unrelated histories",
-        ));
+    if bases.is_empty() {
+        return Err(KitcatError::InvalidInput("No common ancestor found - refusing to merge unrelated histories".to_string()));
     }
 
-    let base_commit = base_commit.unwrap();
-
-    println!("Merge base: {}", base_commit);
-    println!("Merging {} into {}", options.target, our_branch);
+    if bases.len() == 1 {
+        println!("Merge base: {}", bases[0]);
+    } else {
+        println!("Merge bases: {} (using a virtual merge base)", bases.join(", "));
+    }
+    println!("Merging {} into {}", target, our_branch);
 
     // Get file trees for three-way merge
-    let base_files = get_commit_files(&base_commit)?;
-    let our_files = get_commit_files(&our_commit)?;
+    let base_files = merge_base_files(&bases, &options.strategy_options)?;
     let their_files = get_commit_files(&their_commit)?;
 
     // Perform three-way merge
-    let merge_result = merge_trees(&base_files, &our_files, &their_files)?;
+    let merge_result =
+        merge_trees(&base_files, &our_files, &their_files, &options.strategy_options)?;
+
+    // Nothing is written if that would lose local changes
+    let written = touched_paths(&our_files, &merge_result);
+    check_paths(&our_files, &written)?;
 
     if merge_result.has_conflicts() {
         // Save merge state
-        save_merge_state(&our_commit, &their_commit, &our_branch, &options.target)?;
+        save_merge_state(our_commit, &their_commit, our_branch, target)?;
+        fs::write(WRITTEN_FILE, written.into_iter().collect::<Vec<_>>().join("\n"))?;
 
         // Write conflicted files
+        let base_label = match bases.as_slice() {
+            [base] => base[..7].to_string(),
+            _ => "merged common ancestors".to_string(),
+        };
+        let marker_options = MarkerOptions::load(options.conflict_style.as_deref(), &base_label)?;
         for conflict in &merge_result.conflicts {
-            let content = conflict.generate_conflict_markers(&our_branch, &options.target);
-            fs::write(&conflict.path, content)?;
+            let content = conflict.generate_conflict_markers(our_branch, target, &marker_options);
+            write_worktree_file(&conflict.path, &content, conflict.worktree_mode())?;
             println!("CONFLICT in {}", conflict.path);
         }
 
         // Write successfully merged files
-        for (path, content) in &merge_result.merged_files {
-            fs::write(path, content)?;
-        }
+        write_merged_files(&merge_result)?;
 
-        println!("\nAutomatic merge failed; fix conflicts and then run 'kitcat merge --continue'");
-        return Ok(());
+        // Record merged files at stage 0 and conflicts as stages 1/2/3
+        update_index_with_merged_files(&merge_result)?;
+
+        // Remember the conflicted paths and reuse resolutions recorded for them
+        let paths: Vec<String> = merge_result.conflicts.iter().map(|c| c.path.clone()).collect();
+        fs::write(CONFLICTS_FILE, paths.join("\n"))?;
+        rerere::replay(&paths)?;
+
+        return Err(KitcatError::Conflict(
+            "Automatic merge failed; fix conflicts and then run 'kitcat merge --continue'".to_string(),
+        ));
     }
 
     // No conflicts - write merged files and create merge commit
-    for (path, content) in &merge_result.merged_files {
-        fs::write(path, content)?;
-    }
+    write_merged_files(&merge_result)?;
 
     // Update index with merged files
     update_index_with_merged_files(&merge_result)?;
 
-    // Create merge commit
-    let message = options.message.unwrap_or_else(|| {
-        format!("Merge {} into {}", options.target, our_branch)
-    });
+    let parents = [our_commit.to_string(), their_commit];
+    let merge_commit = commit_merge(head_content, &message, &parents, !options.no_verify)?;
+
+    println!("Merge completed successfully");
+    println!("Merge commit: {}", merge_commit);
+
+    Ok(())
+}
+
+/// Write merged files to the working tree and remove deleted ones
+fn write_merged_files(merge_result: &crate::merge::three_way::MergeResult) -> Result<()> {
+    for (path, content) in &merge_result.merged_files {
+        write_worktree_file(path, content, merge_result.merged_modes[path])?;
+    }
+
+    for path in &merge_result.deleted_files {
+        if worktree_path_exists(path) {
+            fs::remove_file(path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Merge several branches at once into a single commit with N parents
+///
+/// Each branch is merged in turn into the result so far, using the merge
+/// bases between it and every commit already merged. Everything happens in
+/// memory first: if any step conflicts, nothing is touched.
+fn octopus_merge(
+    options: &MergeOptions,
+    head_content: &str,
+    our_commit: &str,
+    our_branch: &str,
+) -> Result<()> {
+    let mut heads: Vec<(String, String)> = Vec::new();
+    for target in &options.targets {
+        let commit = resolve_revision(target)?;
+        if is_ancestor(&commit, our_commit)? || heads.iter().any(|(_, c)| *c == commit) {
+            println!("Already up to date with {}", target);
+        } else {
+            heads.push((target.clone(), commit));
+        }
+    }
+
+    if heads.is_empty() {
+        println!("Already up to date.");
+        return Ok(());
+    }
+    if options.ff_only {
+        return Err(KitcatError::InvalidInput("Cannot fast-forward - merge commit required".to_string()));
+    }
+
+    let our_files = get_commit_files(our_commit)?;
+    check_index_matches_head(&our_files)?;
+    let mut files = our_files.clone();
+    let mut merged_commits = vec![our_commit.to_string()];
+
+    for (target, commit) in &heads {
+        let bases = find_merge_bases_many(&merged_commits, commit)?;
+        if bases.is_empty() {
+            return Err(KitcatError::InvalidInput(format!("No common ancestor with {} - refusing to merge unrelated histories", target)));
+        }
+
+        println!("Trying simple merge with {}", target);
+        let base_files = merge_base_files(&bases, &options.strategy_options)?;
+        let their_files = get_commit_files(commit)?;
+        let result = merge_trees(&base_files, &files, &their_files, &options.strategy_options)?;
+
+        if result.has_conflicts() {
+            let paths: Vec<&str> = result.conflicts.iter().map(|c| c.path.as_str()).collect();
+            return Err(KitcatError::Conflict(format!(
+                "Merge with strategy octopus failed: merging {} conflicts in {}\n\
+                 Merge the branches one at a time to resolve the conflicts",
+                target,
+                paths.join(", ")
+            )));
+        }
+
+        files = apply_merge_result(&files, &result, our_branch, target)?;
+        merged_commits.push(commit.clone());
+    }
+
+    check_paths(&our_files, &changed_paths(&our_files, &files))?;
+    apply_files_to_worktree(&our_files, &files)?;
+
+    let names: Vec<String> = heads.iter().map(|(target, _)| format!("'{}'", target)).collect();
+    let message = options
+        .message
+        .clone()
+        .unwrap_or_else(|| format!("Merge branches {} into {}", join_names(&names), our_branch));
+    let merge_commit = commit_merge(head_content, &message, &merged_commits, !options.no_verify)?;
+
+    println!("Merge made by the 'octopus' strategy.");
+    println!("Merge commit: {}", merge_commit);
+
+    Ok(())
+}
+
+/// Record several branches as merged while keeping our tree
+fn merge_ours_many(
+    options: &MergeOptions,
+    head_content: &str,
+    our_commit: &str,
+    our_branch: &str,
+) -> Result<()> {
+    check_index_matches_head(&get_commit_files(our_commit)?)?;
+    let mut parents = vec![our_commit.to_string()];
+    for target in &options.targets {
+        parents.push(resolve_revision(target)?);
+    }
+
+    let names: Vec<String> = options.targets.iter().map(|t| format!("'{}'", t)).collect();
+    let message = options
+        .message
+        .clone()
+        .unwrap_or_else(|| format!("Merge branches {} into {}", join_names(&names), our_branch));
+    let merge_commit = commit_merge(head_content, &message, &parents, !options.no_verify)?;
+
+    println!("Merge made by the 'ours' strategy.");
+    println!("Merge commit: {}", merge_commit);
+
+    Ok(())
+}
+
+/// Join names as "a, b and c"
+fn join_names(names: &[String]) -> String {
+    match names {
+        [] => String::new(),
+        [only] => only.clone(),
+        [rest @ .., last] => format!("{} and {}", rest.join(", "), last),
+    }
+}
+
+/// Update the working tree and index from our files to the merged files
+fn apply_files_to_worktree(
+    our_files: &HashMap<String, FileEntry>,
+    merged_files: &HashMap<String, FileEntry>,
+) -> Result<()> {
+    let mut index = read_index_binary()?;
+
+    for (path, entry) in merged_files {
+        if our_files.get(path) == Some(entry) {
+            continue;
+        }
+        write_worktree_file(path, &read_blob_content(&entry.hash)?, entry.mode)?;
 
-    let merge_commit = create_merge_commit(&message, &our_commit, &their_commit)?;
+        let metadata = fs::symlink_metadata(path)?;
+        index.retain(|e| e.path != *path);
+        index.push(IndexEntry::from_file(path.clone(), entry.hash.clone(), &metadata));
+    }
+
+    for path in our_files.keys() {
+        if !merged_files.contains_key(path) {
+            if worktree_path_exists(path) {
+                fs::remove_file(path)?;
+            }
+            index.retain(|e| e.path != *path);
+        }
+    }
+
+    write_index_binary(&index)
+}
+
+/// Create the merge commit from the index and move HEAD to it
+///
+/// With `verify`, the `pre-merge-commit` and `commit-msg` hooks run first.
+fn commit_merge(head_content: &str, message: &str, parents: &[String], verify: bool) -> Result<String> {
+    let message = if verify {
+        match verify_hook("pre-merge-commit", &[]).and_then(|_| run_commit_msg_hook(message)) {
+            Ok(message) => message,
+            Err(e) => {
+                if !Path::new(".kitcat/MERGE_HEAD").exists() {
+                    save_pending_merge(parents, message)?;
+                }
+                return Err(KitcatError::Conflict(format!(
+                    "{}\nNot committing merge; use 'kitcat merge --continue' to complete the merge",
+                    e
+                )));
+            }
+        }
+    } else {
+        message.to_string()
+    };
+    let merge_commit = create_merge_commit(message.trim_end(), parents)?;
 
-    // Update HEAD
     if head_content.starts_with("ref:") {
         let branch_name = head_content.trim_start_matches("ref: ").trim();
         let branch_path = format!(".kitcat/{}", branch_name);
@@ -155,14 +453,22 @@ pub fn merge(options: MergeOptions) -> io::Result<()> {
         fs::write(".kitcat/HEAD", &merge_commit)?;
     }
 
-    println!("Merge completed successfully");
-    println!("Merge commit: {}", merge_commit);
-
-    Ok(())
+    Ok(merge_commit)
 }
 
 /// Fast-forward merge
-fn fast_forward_merge(head_content: &str, target_commit: &str) -> io::Result<()> {
+///
+/// Only the paths that differ between the two commits are updated, so local
+/// changes to other files (staged or not) are kept.
+fn fast_forward_merge(
+    head_content: &str,
+    our_commit: &str,
+    target_commit: &str,
+    our_files: &HashMap<String, FileEntry>,
+    their_files: &HashMap<String, FileEntry>,
+) -> Result<()> {
+    apply_files_to_worktree(our_files, their_files)?;
+
     if head_content.starts_with("ref:") {
         let branch_name = head_content.trim_start_matches("ref: ").trim();
         let branch_path = format!(".kitcat/{}", branch_name);
@@ -171,42 +477,31 @@ fn fast_forward_merge(head_content: &str, target_commit: &str) -> io::Result<()>
         fs::write(".kitcat/HEAD", target_commit)?;
     }
 
-    // Update working directory to match target
-    let tree_hash = get_commit_tree(target_commit)?;
-    checkout_tree(&tree_hash)?;
-
-    println!("Updating {}..{}", &target_commit[..7], &target_commit[..7]);
+    println!("Updating {}..{}", &our_commit[..7], &target_commit[..7]);
     println!("Fast-forward");
 
     Ok(())
 }
 
-/// Create a merge commit with two parents
-fn create_merge_commit(message: &str, parent1: &str, parent2: &str) -> io::Result<String> {
-    // First create tree from current index
-    let tree_hash = crate::commands::write_tree();
+/// Create a merge commit with the given parents
+fn create_merge_commit(message: &str, parents: &[String]) -> Result<String> {
+    // First create tree from current index (fails while conflicts are unresolved)
+    let tree_hash = write_tree_from_index(&read_index_binary()?)?;
 
-    // Create commit object with two parents
-    let commit_hash = create_commit(
-        &tree_hash,
-        &[parent1.to_string(), parent2.to_string()],
-        message,
-    )?;
+    // Create commit object with all parents
+    let commit_hash = create_commit(&tree_hash, parents, message)?;
 
     Ok(commit_hash)
 }
 
 /// Resolve HEAD to commit hash
-fn resolve_head(head_content: &str) -> io::Result<String> {
+fn resolve_head(head_content: &str) -> Result<String> {
     if head_content.starts_with("ref:") {
         let branch_name = head_content.trim_start_matches("ref: ").trim();
         let branch_path = format!(".kitcat/{}", branch_name);
 
         if !Path::new(&branch_path).exists() {
-            return Err(io::Error::new(
-                io::ErrorKind::NotFound,
-                "HEAD points to non-existent branch",
-            ));
+            return Err(KitcatError::NotFound("HEAD points to non-existent branch".to_string()));
         }
 
         Ok(fs::read_to_string(&branch_path)?.trim().to_string())
@@ -215,32 +510,13 @@ fn resolve_head(head_content: &str) -> io::Result<String> {
     }
 }
 
-/// Resolve a ref (branch name or commit hash) to commit hash
-fn resolve_ref(ref_name: &str) -> io::Result<String> {
-    // Check if it's a branch name
-    let branch_path = format!(".kitcat/refs/heads/{}", ref_name);
-    if Path::new(&branch_path).exists() {
-        return Ok(fs::read_to_string(&branch_path)?.trim().to_string());
-    }
-
-    // Assume it's a commit hash
-    if ref_name.len() == 40 {
-        Ok(ref_name.to_string())
-    } else {
-        Err(io::Error::new(
-            io::ErrorKind::NotFound,
-            format!("Cannot resolve '{}' to a commit", ref_name),
-        ))
-    }
-}
-
 /// Save merge state for conflict resolution
 fn save_merge_state(
     our_commit: &str,
     their_commit: &str,
     our_branch: &str,
     their_branch: &str,
-) -> io::Result<()> {
+) -> Result<()> {
     fs::create_dir_all(".kitcat/merge")?;
     fs::write(".kitcat/MERGE_HEAD", their_commit)?;
     fs::write(".kitcat/MERGE_MODE", "merge")?;
@@ -251,179 +527,316 @@ fn save_merge_state(
     Ok(())
 }
 
+/// Save a merge whose commit was vetoed by a hook, to be concluded later
+fn save_pending_merge(parents: &[String], message: &str) -> Result<()> {
+    fs::create_dir_all(".kitcat/merge")?;
+    fs::write(".kitcat/MERGE_HEAD", parents[1..].join("\n"))?;
+    fs::write(".kitcat/MERGE_MODE", "merge")?;
+    fs::write(".kitcat/MERGE_MSG", message)?;
+    fs::write(".kitcat/merge/our_commit", &parents[0])?;
+
+    // The index matched our commit before the merge, so it differs exactly where the merge wrote
+    let staged = read_index_binary()?
+        .into_iter()
+        .filter(|e| !e.is_unmerged())
+        .map(|e| (e.path.clone(), FileEntry::new(e.mode, e.hash)))
+        .collect();
+    let written = changed_paths(&get_commit_files(&parents[0])?, &staged);
+    fs::write(WRITTEN_FILE, written.into_iter().collect::<Vec<_>>().join("\n"))?;
+    Ok(())
+}
+
+/// Remove the state of a merge in progress
+fn clear_merge_state() -> Result<()> {
+    fs::remove_file(".kitcat/MERGE_HEAD")?;
+    fs::remove_file(".kitcat/MERGE_MODE")?;
+    if Path::new(".kitcat/MERGE_MSG").exists() {
+        fs::remove_file(".kitcat/MERGE_MSG")?;
+    }
+    fs::remove_dir_all(".kitcat/merge")?;
+    Ok(())
+}
+
 /// Continue merge after conflict resolution
-fn continue_merge(message: Option<&str>) -> io::Result<()> {
+fn continue_merge(message: Option<&str>, verify: bool) -> Result<()> {
     if !Path::new(".kitcat/MERGE_HEAD").exists() {
-        return Err(io::Error::new(
-            io::ErrorKind::NotFound,
-            "No merge in progress",
-        ));
+        return Err(KitcatError::NotFound("No merge in progress".to_string()));
     }
 
     // Check if there are still conflicts
     let index = read_index_binary()?;
-    for entry in &index {
-        if Path::new(&entry.path).exists() {
-            let content = fs::read_to_string(&entry.path)?;
-            if crate::merge::types::ConflictMarker::has_conflicts(&content) {
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidInput,
-                    format!("Unresolved conflict in {}", entry.path),
-                ));
-            }
+    if let Some(entry) = index.iter().find(|e| e.is_unmerged()) {
+        return Err(KitcatError::Conflict(format!(
+                "Unmerged path {} - use 'kitcat add' to mark the resolution",
+                entry.path
+            )));
+    }
+    // Markers left in a path that was conflicted (other files may be binary
+    // or contain marker-like lines on purpose)
+    let conflicted = fs::read_to_string(CONFLICTS_FILE).unwrap_or_default();
+    for path in conflicted.lines() {
+        if let Ok(content) = fs::read(path)
+            && crate::merge::types::ConflictMarker::has_conflicts(&String::from_utf8_lossy(&content))
+        {
+            return Err(KitcatError::Conflict(format!("Unresolved conflict in {}", path)));
         }
     }
 
-    // Read merge state
-    let their_commit = fs::read_to_string(".kitcat/MERGE_HEAD")?.trim().to_string();
+    // Read merge state (several heads for an octopus merge)
     let our_commit = fs::read_to_string(".kitcat/merge/our_commit")?.trim().to_string();
-    let our_branch = fs::read_to_string(".kitcat/merge/our_branch")?;
-    let their_branch = fs::read_to_string(".kitcat/merge/their_branch")?;
-
-    // Create merge commit
-    let default_msg = format!("Merge {} into {}", their_branch.trim(), our_branch.trim());
+    let mut parents = vec![our_commit];
+    parents.extend(
+        fs::read_to_string(".kitcat/MERGE_HEAD")?
+            .lines()
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty()),
+    );
+
+    // Create merge commit, with the message of a merge stopped by a hook
+    let default_msg = match fs::read_to_string(".kitcat/MERGE_MSG") {
+        Ok(saved) => saved,
+        Err(_) => {
+            let our_branch = fs::read_to_string(".kitcat/merge/our_branch")?;
+            let their_branch = fs::read_to_string(".kitcat/merge/their_branch")?;
+            format!("Merge {} into {}", their_branch.trim(), our_branch.trim())
+        }
+    };
     let msg = message.unwrap_or(&default_msg);
-    let merge_commit = create_merge_commit(msg, &our_commit, &their_commit)?;
+    let merge_commit = commit_merge(&read_head()?, msg, &parents, verify)?;
 
-    // Update HEAD
-    let head_content = read_head();
-    if head_content.starts_with("ref:") {
-        let branch_name = head_content.trim_start_matches("ref: ").trim();
-        let branch_path = format!(".kitcat/{}", branch_name);
-        fs::write(&branch_path, &merge_commit)?;
-    } else {
-        fs::write(".kitcat/HEAD", &merge_commit)?;
-    }
+    // Remember how the conflicts were resolved
+    rerere::record()?;
+    rerere::clear()?;
 
     // Clean up merge state
-    fs::remove_file(".kitcat/MERGE_HEAD")?;
-    fs::remove_file(".kitcat/MERGE_MODE")?;
-    fs::remove_dir_all(".kitcat/merge")?;
+    clear_merge_state()?;
 
     println!("Merge completed successfully");
     println!("Merge commit: {}", merge_commit);
 
-    Ok(())
+    apply_autostash(&merge_commit)
 }
 
 /// Abort merge and restore original state
-fn abort_merge() -> io::Result<()> {
+fn abort_merge() -> Result<()> {
     if !Path::new(".kitcat/MERGE_HEAD").exists() {
-        return Err(io::Error::new(
-            io::ErrorKind::NotFound,
-            "No merge in progress",
-        ));
+        return Err(KitcatError::NotFound("No merge in progress".to_string()));
     }
 
     let our_commit = fs::read_to_string(".kitcat/merge/our_commit")?.trim().to_string();
 
-    // Restore to our original commit
-    let tree_hash = get_commit_tree(&our_commit)?;
-    checkout_tree(&tree_hash)?;
+    // Restore the paths the merge wrote; other local changes are kept
+    let written = fs::read_to_string(WRITTEN_FILE).unwrap_or_default();
+    crate::commands::checkout::reset_paths_to_commit(&our_commit, written.lines())?;
 
     // Clean up merge state
-    fs::remove_file(".kitcat/MERGE_HEAD")?;
-    fs::remove_file(".kitcat/MERGE_MODE")?;
-    fs::remove_dir_all(".kitcat/merge")?;
+    rerere::clear()?;
+    clear_merge_state()?;
 
     println!("Merge aborted");
 
-    Ok(())
+    apply_autostash(&our_commit)
 }
 
-/// Checkout a tree to working directory
-fn checkout_tree(tree_hash: &str) -> io::Result<()> {
-    let files = get_tree_files(tree_hash)?;
+/// Update index with merged files
+///
+/// Cleanly merged files become stage 0 entries. Each conflicted path loses
+/// its stage 0 entry and gets stage 1 (base), 2 (ours) and 3 (theirs)
+/// entries for the sides that exist.
+fn update_index_with_merged_files(
+    merge_result: &crate::merge::three_way::MergeResult,
+) -> Result<()> {
+    let mut index = read_index_binary()?;
 
-    for (path, hash) in files {
-        let content = read_object_content(&hash)?;
+    index.retain(|e| !merge_result.deleted_files.contains(&e.path));
 
-        // Create parent directories
-        if let Some(parent) = Path::new(&path).parent() {
-            fs::create_dir_all(parent)?;
-        }
+    for (path, content) in &merge_result.merged_files {
+        // Store the merged content as a blob
+        let hash = write_blob(content)?;
+        let metadata = fs::symlink_metadata(path)?;
 
-        fs::write(&path, content)?;
+        index.retain(|e| e.path != *path);
+        index.push(IndexEntry::from_file(path.clone(), hash, &metadata));
     }
 
-    Ok(())
-}
-
-/// Get all files from a tree recursively
-fn get_tree_files(tree_hash: &str) -> io::Result<std::collections::HashMap<String, String>> {
-    let mut files = std::collections::HashMap::new();
-    collect_tree_files(tree_hash, "", &mut files)?;
-    Ok(files)
-}
-
-/// Recursively collect files from a tree
-fn collect_tree_files(
-    tree_hash: &str,
-    prefix: &str,
-    files: &mut std::collections::HashMap<String, String>,
-) -> io::Result<()> {
-    let tree_entries = crate::object::read_tree(tree_hash)?;
-
-    for entry in tree_entries {
-        let path = if prefix.is_empty() {
-            entry.name.clone()
-        } else {
-            format!("{}/{}", prefix, entry.name)
-        };
-
-        let hash_hex = bytes_to_hex(&entry.hash);
-
-        if entry.is_tree {
-            collect_tree_files(&hash_hex, &path, files)?;
-        } else {
-            files.insert(path, hash_hex);
+    for conflict in &merge_result.conflicts {
+        let default_mode = index
+            .iter()
+            .find(|e| e.path == conflict.path)
+            .map_or(MODE_FILE, |e| e.mode);
+        index.retain(|e| e.path != conflict.path);
+
+        let sides = [
+            (1, &conflict.base_content),
+            (2, &conflict.our_content),
+            (3, &conflict.their_content),
+        ];
+        for ((stage, content), mode) in sides.into_iter().zip(conflict.modes) {
+            if let Some(content) = content {
+                let hash = write_blob(content)?;
+                let mode = mode.unwrap_or(default_mode);
+                index.push(IndexEntry::conflict_stage(conflict.path.clone(), hash, mode, stage));
+            }
         }
     }
 
+    write_index_binary(&index)?;
     Ok(())
 }
 
-/// Read object content from object store
-fn read_object_content(hash: &str) -> io::Result<Vec<u8>> {
-    let obj_dir = &hash[0..2];
-    let obj_file = &hash[2..];
-    let obj_path = format!(".kitcat/objects/{}/{}", obj_dir, obj_file);
-
-    let compressed = fs::read(&obj_path)?;
-    let content = crate::utils::decompress(&compressed)?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::add_to_index;
+    use crate::object::get_commit_tree;
+    use crate::utils::{commit_files, in_temp_repo};
+
+    /// Make `master` and `side` diverge from a common base, returning both tips
+    fn diverged_branches() -> (String, String) {
+        let base = commit_files(&[("a.txt", "a1\n"), ("x.up", "x\n")], "base");
+        let theirs = commit_files(&[("a.txt", "theirs\n")], "theirs");
+        fs::write(".kitcat/refs/heads/side", &theirs).unwrap();
+        fs::write(".kitcat/refs/heads/master", &base).unwrap();
+        crate::commands::checkout::reset_to_commit(&base).unwrap();
+        let ours = commit_files(&[("a.txt", "ours\n")], "ours");
+        (ours, theirs)
+    }
 
-    let null_pos = content
-        .iter()
-        .position(|&b| b == 0)
-        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid object"))?;
+    #[test]
+    fn test_merge_commit_refuses_staged_changes() {
+        in_temp_repo("merge-ours-staged", || {
+            let (ours, _) = diverged_branches();
+            fs::write("b2.txt", "staged wip\n").unwrap();
+            add_to_index("b2.txt".to_string()).unwrap();
+
+            let options = MergeOptions {
+                targets: vec!["side".to_string()],
+                strategy: MergeStrategy::Ours,
+                ..Default::default()
+            };
+            let err = merge(options).unwrap_err();
+            assert!(err.to_string().contains("b2.txt"));
+            assert_eq!(resolve_head(&read_head().unwrap()).unwrap(), ours);
+        });
+    }
 
-    Ok(content[null_pos + 1..].to_vec())
-}
+    #[test]
+    fn test_theirs_strategy_keeps_unrelated_local_changes() {
+        in_temp_repo("merge-theirs", || {
+            let (_, theirs) = diverged_branches();
+            fs::write("x.up", "x local\n").unwrap();
+
+            let options = MergeOptions {
+                targets: vec!["side".to_string()],
+                strategy: MergeStrategy::Theirs,
+                ..Default::default()
+            };
+            merge(options).unwrap();
+
+            let merged = resolve_head(&read_head().unwrap()).unwrap();
+            assert_eq!(get_commit_tree(&merged).unwrap(), get_commit_tree(&theirs).unwrap());
+            assert_eq!(fs::read_to_string("a.txt").unwrap(), "theirs\n");
+            assert_eq!(fs::read_to_string("x.up").unwrap(), "x local\n");
+        });
+    }
 
-/// Convert bytes to hex string
-fn bytes_to_hex(bytes: &[u8]) -> String {
-    bytes.iter().map(|b| format!("{:02x}", b)).collect()
-}
+    #[test]
+    fn test_continue_checks_only_conflicted_paths() {
+        in_temp_repo("merge-continue", || {
+            fs::write("bin.dat", [0xff, 0xfe, 0x00, 0x80]).unwrap();
+            fs::write("markers.txt", "<<<<<<< documented\n=======\n>>>>>>> example\n").unwrap();
+            add_to_index("bin.dat".to_string()).unwrap();
+            add_to_index("markers.txt".to_string()).unwrap();
+            diverged_branches();
+
+            let err = merge(MergeOptions { targets: vec!["side".to_string()], ..Default::default() })
+                .unwrap_err();
+            assert!(matches!(err, KitcatError::Conflict(_)));
+
+            add_to_index("a.txt".to_string()).unwrap();
+            let continued = MergeOptions { r#continue: true, ..Default::default() };
+            let err = merge(continued.clone()).unwrap_err();
+            assert!(err.to_string().contains("Unresolved conflict in a.txt"));
+
+            fs::write("a.txt", "resolved\n").unwrap();
+            add_to_index("a.txt".to_string()).unwrap();
+            merge(continued).unwrap();
+            assert!(!Path::new(".kitcat/MERGE_HEAD").exists());
+        });
+    }
 
-/// Update index with merged files
-fn update_index_with_merged_files(
-    merge_result: &crate::merge::three_way::MergeResult,
-) -> io::Result<()> {
-    let mut index = read_index_binary()?;
+    #[test]
+    fn test_abort_keeps_unrelated_local_changes() {
+        in_temp_repo("merge-abort", || {
+            diverged_branches();
+            fs::write("x.up", "x local\n").unwrap();
+
+            let err = merge(MergeOptions { targets: vec!["side".to_string()], ..Default::default() })
+                .unwrap_err();
+            assert!(matches!(err, KitcatError::Conflict(_)));
+
+            merge(MergeOptions { abort: true, ..Default::default() }).unwrap();
+            assert!(!Path::new(".kitcat/MERGE_HEAD").exists());
+            assert_eq!(fs::read_to_string("a.txt").unwrap(), "ours\n");
+            assert_eq!(fs::read_to_string("x.up").unwrap(), "x local\n");
+            assert!(read_index_binary().unwrap().iter().all(|e| !e.is_unmerged()));
+        });
+    }
 
-    for (path, content) in &merge_result.merged_files {
-        // Hash the content
-        let hash = crate::utils::compute_hash(content);
+    #[test]
+    fn test_fast_forward_keeps_unrelated_local_changes() {
+        in_temp_repo("merge-ff", || {
+            let base = commit_files(&[("a.txt", "a1\n"), ("x.up", "x\n")], "base");
+            let ahead = commit_files(&[("a.txt", "a2\n")], "ahead");
+            fs::write(".kitcat/refs/heads/ff", &ahead).unwrap();
+            fs::write(".kitcat/refs/heads/master", &base).unwrap();
+            crate::commands::checkout::reset_to_commit(&base).unwrap();
+
+            // An unstaged edit and a staged new file the merge does not touch
+            fs::write("x.up", "x local\n").unwrap();
+            fs::write("b1.txt", "staged\n").unwrap();
+            add_to_index("b1.txt".to_string()).unwrap();
+
+            merge(MergeOptions { targets: vec!["ff".to_string()], ..Default::default() }).unwrap();
+
+            assert_eq!(resolve_head(&read_head().unwrap()).unwrap(), ahead);
+            assert_eq!(fs::read_to_string("a.txt").unwrap(), "a2\n");
+            assert_eq!(fs::read_to_string("x.up").unwrap(), "x local\n");
+            assert_eq!(fs::read_to_string("b1.txt").unwrap(), "staged\n");
+            assert!(read_index_binary().unwrap().iter().any(|e| e.path == "b1.txt"));
+        });
+    }
 
-        // Update or add to index
-        if let Some(entry) = index.iter_mut().find(|e| e.path == *path) {
-            entry.hash = hash.clone();
-        } else {
-            let metadata = fs::metadata(path)?;
-            index.push(IndexEntry::from_file(path.clone(), hash, &metadata));
-        }
+    #[test]
+    fn test_merge_resolves_revisions() {
+        in_temp_repo("merge-revisions", || {
+            let base = commit_files(&[("a.txt", "a1\n")], "base");
+            let ahead = commit_files(&[("a.txt", "a2\n")], "ahead");
+            commit_files(&[("a.txt", "a3\n")], "tip");
+            fs::create_dir_all(".kitcat/refs/tags").unwrap();
+            fs::write(".kitcat/refs/tags/v1", &ahead).unwrap();
+            fs::write(".kitcat/refs/heads/side", &base).unwrap();
+            fs::write(".kitcat/HEAD", "ref: refs/heads/side").unwrap();
+            crate::commands::checkout::reset_to_commit(&base).unwrap();
+
+            let merge_target = |target: &str| {
+                merge(MergeOptions { targets: vec![target.to_string()], ..Default::default() })
+            };
+            merge_target(&ahead[..7]).unwrap();
+            merge_target("v1").unwrap();
+            assert_eq!(resolve_head(&read_head().unwrap()).unwrap(), ahead);
+
+            merge_target("master~0").unwrap();
+            assert_eq!(fs::read_to_string("a.txt").unwrap(), "a3\n");
+            assert!(matches!(merge_target("nope"), Err(KitcatError::UnknownRevision(_))));
+        });
     }
 
-    write_index_binary(&index)?;
-    Ok(())
+    #[test]
+    fn test_join_names() {
+        let names: Vec<String> = ["'a'", "'b'", "'c'"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(join_names(&names[..1]), "'a'");
+        assert_eq!(join_names(&names[..2]), "'a' and 'b'");
+        assert_eq!(join_names(&names), "'a', 'b' and 'c'");
+    }
 }
@@ -0,0 +1,107 @@
+/// Merge-base command implementation
+///
+/// Prints the best common ancestor of two commits:
+/// - `--all`: print every best common ancestor, not just one
+/// - `--octopus`: common ancestor of all given commits
+/// - `--is-ancestor A B`: only answer, through the exit status, whether A is an ancestor of B
+/// - `--fork-point <ref> [<commit>]`: where `<commit>` forked from `<ref>`
+///
+/// Without reflogs, the past tips of `<ref>` used by `--fork-point` are
+/// approximated by its first-parent history.
+
+use crate::error::{KitcatError, Result};
+use crate::merge::CommitWalker;
+use crate::repo::resolve_revision;
+use std::collections::HashSet;
+
+/// Options for the merge-base command
+#[derive(Debug, Clone, Default)]
+pub struct MergeBaseOptions {
+    /// Commits (or refs) to inspect
+    pub commits: Vec<String>,
+    /// Print all best common ancestors
+    pub all: bool,
+    /// Check ancestry instead of printing
+    pub is_ancestor: bool,
+    /// Common ancestor of all commits
+    pub octopus: bool,
+    /// Find the fork point of a commit from a ref
+    pub fork_point: bool,
+}
+
+/// Main merge-base command
+///
+/// Returns `false` when there is nothing to report (no common ancestor, or
+/// not an ancestor for `--is-ancestor`), which should exit with status 1.
+pub fn merge_base(options: MergeBaseOptions) -> Result<bool> {
+    let mut walker = CommitWalker::new();
+
+    if options.is_ancestor {
+        let [ancestor, descendant] = options.commits.as_slice() else {
+            return Err(usage("--is-ancestor takes exactly two commits"));
+        };
+        return walker.is_ancestor(&resolve_revision(ancestor)?, &resolve_revision(descendant)?);
+    }
+
+    if options.fork_point {
+        let (upstream, commit) = match options.commits.as_slice() {
+            [upstream] => (upstream.as_str(), "HEAD"),
+            [upstream, commit] => (upstream.as_str(), commit.as_str()),
+            _ => return Err(usage("--fork-point takes a ref and an optional commit")),
+        };
+        return match fork_point(&mut walker, upstream, commit)? {
+            Some(hash) => {
+                println!("{}", hash);
+                Ok(true)
+            }
+            None => Ok(false),
+        };
+    }
+
+    let commits = options
+        .commits
+        .iter()
+        .map(|c| resolve_revision(c))
+        .collect::<Result<Vec<_>>>()?;
+
+    let bases = if options.octopus {
+        if commits.is_empty() {
+            return Err(usage("--octopus needs at least one commit"));
+        }
+        walker.octopus_merge_bases(&commits)?
+    } else {
+        let Some((first, rest)) = commits.split_first().filter(|(_, rest)| !rest.is_empty()) else {
+            return Err(usage("merge-base needs at least two commits"));
+        };
+        walker.merge_bases_many(first, rest)?
+    };
+
+    let shown = if options.all { bases.len() } else { 1 };
+    for base in bases.iter().take(shown) {
+        println!("{}", base);
+    }
+
+    Ok(!bases.is_empty())
+}
+
+/// The newest commit of `upstream`'s first-parent history that is also a merge base with `commit`
+fn fork_point(walker: &mut CommitWalker, upstream: &str, commit: &str) -> Result<Option<String>> {
+    let commit = resolve_revision(commit)?;
+
+    let mut tips = Vec::new();
+    let mut current = Some(resolve_revision(upstream)?);
+    while let Some(hash) = current {
+        current = walker.parents(&hash)?.into_iter().next();
+        tips.push(hash);
+    }
+
+    let tip_set: HashSet<&String> = tips.iter().collect();
+    let bases = walker.merge_bases_many(&commit, &tips)?;
+
+    Ok(bases.into_iter().find(|base| tip_set.contains(base)))
+}
+
+/// Error for invalid argument combinations
+fn usage(message: &str) -> KitcatError {
+    KitcatError::InvalidInput(message.to_string())
+}
@@ -0,0 +1,186 @@
+/// Mergetool command implementation
+///
+/// Runs a three-way merge tool on each conflicted path. The index stages of
+/// the conflict are written to temporary `$BASE`, `$LOCAL` and `$REMOTE`
+/// files, and the tool saves its result to `$MERGED` (the working tree file).
+///
+/// The tool comes from `--tool` or `merge.tool`. Its command line is
+/// `mergetool.<name>.cmd`, or a built-in one for common tools. A path counts
+/// as resolved when the tool exits successfully if
+/// `mergetool.<name>.trustExitCode` is set, otherwise when `$MERGED` was
+/// changed and holds no conflict markers. Resolved paths are staged.
+
+use crate::config::Config;
+use crate::diff::driver::{shell_command, TempFile};
+use crate::error::{KitcatError, Result};
+use crate::index::{add_file_to_index, read_index_binary};
+use crate::merge::ConflictMarker;
+use crate::object::{read_blob_content, write_blob};
+use crate::utils::read_worktree_file;
+use std::collections::BTreeMap;
+
+/// Options for the mergetool command
+#[derive(Debug, Clone, Default)]
+pub struct MergetoolOptions {
+    /// Tool to use instead of `merge.tool`
+    pub tool: Option<String>,
+    /// Only run the tool on these paths (or directories)
+    pub paths: Vec<String>,
+}
+
+/// Main mergetool command
+pub fn mergetool(options: MergetoolOptions) -> Result<()> {
+    let mut conflicts: BTreeMap<String, [Option<String>; 3]> = BTreeMap::new();
+    for entry in read_index_binary()? {
+        let stage = entry.stage();
+        if stage == 0 || !is_selected(&entry.path, &options.paths) {
+            continue;
+        }
+        conflicts.entry(entry.path).or_default()[stage as usize - 1] = Some(entry.hash);
+    }
+
+    if conflicts.is_empty() {
+        println!("No files need merging");
+        return Ok(());
+    }
+
+    let (command, trust_exit_code) = tool_command(options.tool.as_deref())?;
+
+    println!("Merging:");
+    for path in conflicts.keys() {
+        println!("{}", path);
+    }
+
+    let mut unresolved = Vec::new();
+    for (path, [base, local, remote]) in &conflicts {
+        println!();
+        if local.is_none() || remote.is_none() {
+            let side = if local.is_none() { "local" } else { "remote" };
+            println!("Deleted merge conflict for '{}': {} side deleted it", path, side);
+            println!("Keep one side with 'kitcat checkout --ours/--theirs' and 'kitcat add'");
+            unresolved.push(path.as_str());
+            continue;
+        }
+
+        println!("Normal merge conflict for '{}':", path);
+        if !run_tool(&command, trust_exit_code, path, [base, local, remote])? {
+            println!("merge of {} failed", path);
+            unresolved.push(path.as_str());
+            continue;
+        }
+
+        add_file_to_index(path, &write_blob(&read_worktree_file(path)?)?)?;
+    }
+
+    if !unresolved.is_empty() {
+        return Err(KitcatError::Conflict(format!(
+            "Unresolved conflicts remain in: {}",
+            unresolved.join(", ")
+        )));
+    }
+
+    Ok(())
+}
+
+/// Whether a path is one of the requested paths or lies below one
+fn is_selected(path: &str, filters: &[String]) -> bool {
+    filters.is_empty()
+        || filters.iter().any(|filter| {
+            let filter = filter.trim_end_matches('/');
+            filter == "." || path == filter || path.starts_with(&format!("{}/", filter))
+        })
+}
+
+/// Find the command line for the selected tool, and whether to trust its exit status
+fn tool_command(tool: Option<&str>) -> Result<(String, bool)> {
+    let config = Config::read()?;
+    let name = tool
+        .map(str::to_string)
+        .or_else(|| config.merge.tool.clone())
+        .ok_or_else(|| {
+            KitcatError::NotFound("No merge tool configured. Use --tool=<name> or set merge.tool".to_string())
+        })?;
+
+    let tool_config = config.mergetool.get(&name);
+    let trust_exit_code = tool_config.and_then(|t| t.trust_exit_code).unwrap_or(false);
+    if let Some(cmd) = tool_config.and_then(|t| t.cmd.clone()) {
+        return Ok((cmd, trust_exit_code));
+    }
+
+    let builtin = match name.as_str() {
+        "vimdiff" | "nvimdiff" => {
+            format!("{} -f -d \"$LOCAL\" \"$BASE\" \"$REMOTE\" \"$MERGED\"", name)
+        }
+        "meld" => "meld \"$LOCAL\" \"$BASE\" \"$REMOTE\" --output=\"$MERGED\"".to_string(),
+        "kdiff3" => "kdiff3 --auto \"$BASE\" \"$LOCAL\" \"$REMOTE\" -o \"$MERGED\"".to_string(),
+        "opendiff" => {
+            "opendiff \"$LOCAL\" \"$REMOTE\" -ancestor \"$BASE\" -merge \"$MERGED\" | cat"
+                .to_string()
+        }
+        "vscode" => "code --wait --merge \"$REMOTE\" \"$LOCAL\" \"$BASE\" \"$MERGED\"".to_string(),
+        _ => {
+            return Err(KitcatError::NotFound(format!(
+                    "Unknown merge tool '{}'. Set mergetool.{}.cmd to use it",
+                    name, name
+                )));
+        }
+    };
+
+    Ok((builtin, trust_exit_code))
+}
+
+/// Run the tool on one conflicted path and tell whether it was resolved
+///
+/// `stages` holds the base, local and remote blobs; a missing base (both
+/// sides added the file) is an empty file.
+fn run_tool(
+    command: &str,
+    trust_exit_code: bool,
+    path: &str,
+    stages: [&Option<String>; 3],
+) -> Result<bool> {
+    let mut temps = Vec::new();
+    for hash in stages {
+        let content = match hash {
+            Some(hash) => read_blob_content(hash)?,
+            None => Vec::new(),
+        };
+        temps.push(TempFile::new(path, &content)?);
+    }
+    let before = read_worktree_file(path).ok();
+
+    let status = shell_command(command, &[])
+        .env("BASE", temps[0].path())
+        .env("LOCAL", temps[1].path())
+        .env("REMOTE", temps[2].path())
+        .env("MERGED", path)
+        .status()?;
+
+    if status.code().is_none() {
+        return Err(KitcatError::CommandFailed("merge tool was terminated".to_string()));
+    }
+    if trust_exit_code {
+        return Ok(status.success());
+    }
+
+    let after = read_worktree_file(path).ok();
+    Ok(after.is_some()
+        && after != before
+        && !after
+            .as_deref()
+            .is_some_and(|content| ConflictMarker::has_conflicts(&String::from_utf8_lossy(content))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_selected() {
+        let filters = vec!["src/".to_string(), "README.md".to_string()];
+        assert!(is_selected("src/main.rs", &filters));
+        assert!(is_selected("README.md", &filters));
+        assert!(!is_selected("srcs/main.rs", &filters));
+        assert!(is_selected("anything", &[]));
+    }
+}
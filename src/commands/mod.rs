@@ -1,22 +1,52 @@
+pub mod am;
+pub mod apply;
+pub mod blame;
 pub mod branch;
 pub mod checkout;
 pub mod commands;
+pub mod commit;
+pub mod commit_graph;
 pub mod diff;
+pub mod difftool;
+pub mod format_patch;
 pub mod gc;
+pub mod graph;
 pub mod log;
+pub mod log_filter;
+pub mod log_paths;
 pub mod merge;
+pub mod merge_base;
+pub mod mergetool;
+pub mod pretty;
+pub mod rerere;
+pub mod show;
 pub mod status;
+pub mod tag;
+pub mod verify;
 
 // Re-export functions
+pub use am::{am, AmOptions};
+pub use apply::{apply, ApplyOptions};
+pub use blame::{blame, BlameOptions};
 pub use branch::{create_branch, delete_branch, list_branches, show_current_branch, switch_branch};
-pub use checkout::{checkout, checkout_file};
+pub use checkout::{checkout, checkout_conflict, checkout_file};
+pub use commit::{commit, CommitOptions};
+pub use commit_graph::commit_graph;
 pub use diff::{diff, DiffMode, DiffOptions};
+pub use difftool::{difftool, DifftoolOptions};
+pub use format_patch::{format_patch, FormatPatchOptions};
 pub use gc::{gc, GcOptions};
-pub use log::{log, log_with_filter, LogFormat};
+pub use log::{log, LogOptions};
+pub use log_filter::LogFilterOptions;
 pub use merge::{merge, MergeOptions};
+pub use merge_base::{merge_base, MergeBaseOptions};
+pub use mergetool::{mergetool, MergetoolOptions};
+pub use rerere::{rerere, RerereOptions};
+pub use show::{show, ShowOptions};
 pub use status::status;
+pub use tag::{tag, TagOptions};
+pub use verify::{verify_commit, verify_tag};
 pub use commands::add_to_index;
-pub use commands::commit;
 pub use commands::get_config_cmd;
 pub use commands::hash_file;
 pub use commands::init;
@@ -0,0 +1,611 @@
+/// Commit formatting shared by `show` and `log`
+///
+/// Supports Git's named formats (`oneline`, `short`, `medium`, `full`,
+/// `fuller`, `raw`, `reference`) and custom `format:<string>` templates with
+/// `%` placeholders. Dates follow a `--date` mode, and commits can be
+/// decorated with the branches and tags pointing at them.
+
+use crate::date::parse_offset;
+use crate::error::{KitcatError, Result};
+use crate::models::Commit;
+use chrono::{Local, TimeZone};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// ANSI color codes
+mod colors {
+    pub const RESET: &str = "\x1b[0m";
+    pub const YELLOW: &str = "\x1b[33m";
+    pub const BOLD_CYAN: &str = "\x1b[1;36m";
+    pub const BOLD_GREEN: &str = "\x1b[1;32m";
+    pub const BOLD_YELLOW: &str = "\x1b[1;33m";
+}
+
+/// How to print a commit
+#[derive(Debug, Clone, PartialEq)]
+pub enum PrettyFormat {
+    /// `<hash> <subject>`
+    Oneline,
+    /// Hash, author and subject
+    Short,
+    /// Hash, author, date and full message (the default)
+    Medium,
+    /// Hash, author, committer and full message
+    Full,
+    /// Hash, author and committer with their dates, and full message
+    Fuller,
+    /// The commit object headers and full message
+    Raw,
+    /// `<abbrev hash> (<subject>, <short date>)`, for quoting commits in prose
+    Reference,
+    /// Custom template, e.g. `format:%h %s`
+    Format(String),
+}
+
+impl PrettyFormat {
+    /// Parse a `--format`/`--pretty` value
+    pub fn parse(spec: &str) -> Result<Self> {
+        match spec {
+            "oneline" => Ok(PrettyFormat::Oneline),
+            "short" => Ok(PrettyFormat::Short),
+            "medium" => Ok(PrettyFormat::Medium),
+            "full" => Ok(PrettyFormat::Full),
+            "fuller" => Ok(PrettyFormat::Fuller),
+            "raw" => Ok(PrettyFormat::Raw),
+            "reference" => Ok(PrettyFormat::Reference),
+            _ => {
+                if let Some(template) = spec
+                    .strip_prefix("format:")
+                    .or_else(|| spec.strip_prefix("tformat:"))
+                {
+                    Ok(PrettyFormat::Format(template.to_string()))
+                } else if spec.contains('%') {
+                    Ok(PrettyFormat::Format(spec.to_string()))
+                } else {
+                    Err(KitcatError::InvalidInput(format!("Invalid pretty format: {}", spec)))
+                }
+            }
+        }
+    }
+
+    /// Whether commits are printed as a single line (no blank line between them)
+    pub fn is_single_line(&self) -> bool {
+        matches!(
+            self,
+            PrettyFormat::Oneline | PrettyFormat::Reference | PrettyFormat::Format(_)
+        )
+    }
+}
+
+/// How to print dates (`--date`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DateMode {
+    /// `Mon Jan 15 10:30:00 2024 +0200`
+    #[default]
+    Default,
+    /// `3 days ago`
+    Relative,
+    /// `2024-01-15 10:30:00 +0200`
+    Iso,
+    /// `2024-01-15T10:30:00+02:00`
+    IsoStrict,
+    /// `Mon, 15 Jan 2024 10:30:00 +0200`
+    Rfc,
+    /// `2024-01-15`
+    Short,
+    /// `1705307400`
+    Unix,
+    /// `1705307400 +0200`
+    Raw,
+    /// Like `Default`, but in the local timezone instead of the recorded one
+    Local,
+}
+
+impl DateMode {
+    /// Parse a `--date` value
+    pub fn parse(spec: &str) -> Result<Self> {
+        match spec {
+            "default" => Ok(DateMode::Default),
+            "relative" => Ok(DateMode::Relative),
+            "iso" | "iso8601" => Ok(DateMode::Iso),
+            "iso-strict" | "iso8601-strict" => Ok(DateMode::IsoStrict),
+            "rfc" | "rfc2822" => Ok(DateMode::Rfc),
+            "short" => Ok(DateMode::Short),
+            "unix" => Ok(DateMode::Unix),
+            "raw" => Ok(DateMode::Raw),
+            "local" | "default-local" => Ok(DateMode::Local),
+            _ => Err(KitcatError::InvalidInput(format!("Unknown date format '{}'", spec))),
+        }
+    }
+}
+
+/// Settings for formatting commits beyond the format itself
+#[derive(Debug, Clone, Default)]
+pub struct FormatContext {
+    /// How to print dates
+    pub date_mode: DateMode,
+    /// Abbreviate the commit hash in `commit <hash>` and `oneline`
+    pub abbrev_commit: bool,
+    /// Show decorations after the hash in named formats
+    pub decorate: bool,
+    /// Refs pointing at the commit (`HEAD -> master`, `tag: v1.0`, ...)
+    pub decorations: Vec<String>,
+    /// Color the hash and decorations
+    pub use_color: bool,
+}
+
+impl FormatContext {
+    /// Wrap text in a color when colors are enabled
+    fn paint(&self, color: &str, text: &str) -> String {
+        if self.use_color {
+            format!("{}{}{}", color, text, colors::RESET)
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// Decorations joined with `, ` (`%D`)
+    fn decoration_list(&self) -> String {
+        let names: Vec<String> = self
+            .decorations
+            .iter()
+            .map(|name| {
+                if let Some(branch) = name.strip_prefix("HEAD -> ") {
+                    format!(
+                        "{}{}",
+                        self.paint(colors::BOLD_CYAN, "HEAD -> "),
+                        self.paint(colors::BOLD_GREEN, branch)
+                    )
+                } else if name == "HEAD" {
+                    self.paint(colors::BOLD_CYAN, name)
+                } else if name.starts_with("tag: ") {
+                    self.paint(colors::BOLD_YELLOW, name)
+                } else {
+                    self.paint(colors::BOLD_GREEN, name)
+                }
+            })
+            .collect();
+        names.join(&self.paint(colors::YELLOW, ", "))
+    }
+
+    /// ` (<decorations>)`, or nothing for an undecorated commit (`%d`)
+    fn decoration(&self) -> String {
+        if self.decorations.is_empty() {
+            return String::new();
+        }
+        format!(
+            " {}{}{}",
+            self.paint(colors::YELLOW, "("),
+            self.decoration_list(),
+            self.paint(colors::YELLOW, ")")
+        )
+    }
+}
+
+/// Format a commit header and message
+///
+/// Named formats other than `oneline` end with the indented message;
+/// the result never ends with a newline.
+pub fn format_commit(hash: &str, commit: &Commit, format: &PrettyFormat) -> String {
+    format_commit_with(hash, commit, format, &FormatContext::default())
+}
+
+/// Format a commit with dates, decorations and colors from `context`
+pub fn format_commit_with(
+    hash: &str,
+    commit: &Commit,
+    format: &PrettyFormat,
+    context: &FormatContext,
+) -> String {
+    let shown_hash = if context.abbrev_commit { abbrev(hash) } else { hash };
+    let decoration = if context.decorate {
+        context.decoration()
+    } else {
+        String::new()
+    };
+
+    match format {
+        PrettyFormat::Oneline => format!(
+            "{}{} {}",
+            context.paint(colors::YELLOW, shown_hash),
+            decoration,
+            subject(commit)
+        ),
+        PrettyFormat::Reference => {
+            let date_mode = match context.date_mode {
+                DateMode::Default => DateMode::Short,
+                mode => mode,
+            };
+            format!(
+                "{} ({}, {})",
+                abbrev(hash),
+                subject(commit),
+                format_date_as(commit.author_time, &commit.author_timezone, date_mode)
+            )
+        }
+        PrettyFormat::Format(template) => expand_template(template, hash, commit, context),
+        PrettyFormat::Short
+        | PrettyFormat::Medium
+        | PrettyFormat::Full
+        | PrettyFormat::Fuller
+        | PrettyFormat::Raw => {
+            let mut output = format!(
+                "{}{}\n",
+                context.paint(colors::YELLOW, &format!("commit {}", shown_hash)),
+                decoration
+            );
+
+            if *format == PrettyFormat::Raw {
+                output.push_str(&format!("tree {}\n", commit.tree));
+                for parent in &commit.parents {
+                    output.push_str(&format!("parent {}\n", parent));
+                }
+                output.push_str(&format!(
+                    "author {} {}\n",
+                    commit.author,
+                    format_date_as(commit.author_time, &commit.author_timezone, DateMode::Raw)
+                ));
+                output.push_str(&format!(
+                    "committer {} {}\n",
+                    commit.committer,
+                    format_date_as(commit.committer_time, &commit.committer_timezone, DateMode::Raw)
+                ));
+            } else if commit.parents.len() > 1 {
+                let parents: Vec<&str> = commit.parents.iter().map(|p| abbrev(p)).collect();
+                output.push_str(&format!("Merge: {}\n", parents.join(" ")));
+            }
+
+            let author_date = format_date_as(commit.author_time, &commit.author_timezone, context.date_mode);
+            match format {
+                PrettyFormat::Short => {
+                    output.push_str(&format!("Author: {}\n", commit.author));
+                }
+                PrettyFormat::Medium => {
+                    output.push_str(&format!("Author: {}\n", commit.author));
+                    output.push_str(&format!("Date:   {}\n", author_date));
+                }
+                PrettyFormat::Full => {
+                    output.push_str(&format!("Author: {}\n", commit.author));
+                    output.push_str(&format!("Commit: {}\n", commit.committer));
+                }
+                PrettyFormat::Fuller => {
+                    output.push_str(&format!("Author:     {}\n", commit.author));
+                    output.push_str(&format!("AuthorDate: {}\n", author_date));
+                    output.push_str(&format!("Commit:     {}\n", commit.committer));
+                    output.push_str(&format!(
+                        "CommitDate: {}\n",
+                        format_date_as(commit.committer_time, &commit.committer_timezone, context.date_mode)
+                    ));
+                }
+                _ => {}
+            }
+
+            output.push('\n');
+            if *format == PrettyFormat::Short {
+                output.push_str(&format!("    {}", subject(commit)));
+            } else {
+                let lines: Vec<String> = commit
+                    .message
+                    .trim_end()
+                    .lines()
+                    .map(|line| format!("    {}", line).trim_end().to_string())
+                    .collect();
+                output.push_str(&lines.join("\n"));
+            }
+
+            output
+        }
+    }
+}
+
+/// Expand `%` placeholders in a custom format
+fn expand_template(template: &str, hash: &str, commit: &Commit, context: &FormatContext) -> String {
+    let mut output = String::new();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            output.push(c);
+            continue;
+        }
+
+        let mut code = String::new();
+        if let Some(&next) = chars.peek()
+            && matches!(next, 'a' | 'c')
+        {
+            code.push(next);
+            chars.next();
+        }
+        if let Some(next) = chars.next() {
+            code.push(next);
+        }
+
+        let expansion = match code.as_str() {
+            "H" => hash.to_string(),
+            "h" => abbrev(hash).to_string(),
+            "T" => commit.tree.clone(),
+            "t" => abbrev(&commit.tree).to_string(),
+            "P" => commit.parents.join(" "),
+            "p" => commit
+                .parents
+                .iter()
+                .map(|p| abbrev(p))
+                .collect::<Vec<_>>()
+                .join(" "),
+            "d" => context.decoration(),
+            "D" => context.decoration_list(),
+            "an" => person_name(&commit.author).to_string(),
+            "ae" => person_email(&commit.author).to_string(),
+            "ad" => format_date_as(commit.author_time, &commit.author_timezone, context.date_mode),
+            "ar" => format_date_as(commit.author_time, &commit.author_timezone, DateMode::Relative),
+            "ai" => format_date_as(commit.author_time, &commit.author_timezone, DateMode::Iso),
+            "aI" => format_date_as(commit.author_time, &commit.author_timezone, DateMode::IsoStrict),
+            "as" => format_date_as(commit.author_time, &commit.author_timezone, DateMode::Short),
+            "at" => commit.author_time.to_string(),
+            "cn" => person_name(&commit.committer).to_string(),
+            "ce" => person_email(&commit.committer).to_string(),
+            "cd" => format_date_as(commit.committer_time, &commit.committer_timezone, context.date_mode),
+            "cr" => format_date_as(commit.committer_time, &commit.committer_timezone, DateMode::Relative),
+            "ci" => format_date_as(commit.committer_time, &commit.committer_timezone, DateMode::Iso),
+            "cI" => format_date_as(commit.committer_time, &commit.committer_timezone, DateMode::IsoStrict),
+            "cs" => format_date_as(commit.committer_time, &commit.committer_timezone, DateMode::Short),
+            "ct" => commit.committer_time.to_string(),
+            "s" => subject(commit).to_string(),
+            "b" => body(commit).to_string(),
+            "B" => commit.message.trim_end().to_string(),
+            "n" => "\n".to_string(),
+            "%" => "%".to_string(),
+            // Unknown placeholders are printed as-is
+            _ => format!("%{}", code),
+        };
+        output.push_str(&expansion);
+    }
+
+    output
+}
+
+/// First seven characters of a hash
+pub fn abbrev(hash: &str) -> &str {
+    &hash[..hash.len().min(7)]
+}
+
+/// First line of the commit message
+pub fn subject(commit: &Commit) -> &str {
+    commit.message.lines().next().unwrap_or("").trim()
+}
+
+/// Commit message without the subject line
+fn body(commit: &Commit) -> &str {
+    match commit.message.split_once('\n') {
+        Some((_, body)) => body.trim(),
+        None => "",
+    }
+}
+
+/// Name part of "Name <email>"
+pub fn person_name(person: &str) -> &str {
+    person.split(" <").next().unwrap_or(person).trim()
+}
+
+/// Email part of "Name <email>"
+pub fn person_email(person: &str) -> &str {
+    person
+        .split_once('<')
+        .and_then(|(_, rest)| rest.split_once('>'))
+        .map(|(email, _)| email)
+        .unwrap_or("")
+}
+
+/// Format a timestamp like Git's default date format
+pub fn format_date(timestamp: i64, timezone: &str) -> String {
+    format_date_as(timestamp, timezone, DateMode::Default)
+}
+
+/// Format a timestamp in a `--date` mode
+///
+/// Dates are shown in their recorded `timezone` (e.g. `+0530`), or the
+/// local one for `DateMode::Local` and when none was recorded.
+pub fn format_date_as(timestamp: i64, timezone: &str, mode: DateMode) -> String {
+    let offset = match mode {
+        DateMode::Local => None,
+        _ => parse_offset(timezone),
+    }
+    .unwrap_or_else(|| *Local::now().offset());
+    let Some(date) = offset.timestamp_opt(timestamp, 0).single() else {
+        return timestamp.to_string();
+    };
+
+    match mode {
+        DateMode::Default | DateMode::Local => date.format("%a %b %-d %H:%M:%S %Y %z").to_string(),
+        DateMode::Relative => format_relative(timestamp, Local::now().timestamp()),
+        DateMode::Iso => date.format("%Y-%m-%d %H:%M:%S %z").to_string(),
+        DateMode::IsoStrict => date.to_rfc3339(),
+        DateMode::Rfc => date.to_rfc2822(),
+        DateMode::Short => date.format("%Y-%m-%d").to_string(),
+        DateMode::Unix => timestamp.to_string(),
+        DateMode::Raw => format!("{} {}", timestamp, date.format("%z")),
+    }
+}
+
+/// Describe how long before `now` a timestamp is, like Git's relative dates
+fn format_relative(timestamp: i64, now: i64) -> String {
+    let seconds = now - timestamp;
+    if seconds < 0 {
+        return "in the future".to_string();
+    }
+
+    let plural = |n: i64, unit: &str| format!("{} {}{} ago", n, unit, if n == 1 { "" } else { "s" });
+    let round = |value: i64, unit: i64| (value + unit / 2) / unit;
+
+    if seconds < 90 {
+        return plural(seconds, "second");
+    }
+    let minutes = round(seconds, 60);
+    if minutes < 90 {
+        return plural(minutes, "minute");
+    }
+    let hours = round(minutes, 60);
+    if hours < 36 {
+        return plural(hours, "hour");
+    }
+    let days = round(hours, 24);
+    if days < 14 {
+        return plural(days, "day");
+    }
+    if days < 70 {
+        return plural(round(days, 7), "week");
+    }
+    if days < 365 {
+        return plural(round(days, 30), "month");
+    }
+
+    let months = round(days * 12, 365);
+    let (years, months) = (months / 12, months % 12);
+    if years < 5 && months > 0 {
+        let year_part = format!("{} year{}", years, if years == 1 { "" } else { "s" });
+        format!("{}, {}", year_part, plural(months, "month"))
+    } else {
+        plural(years, "year")
+    }
+}
+
+/// Branch and tag names for each commit they point at
+///
+/// HEAD comes first (`HEAD -> <branch>`, or `HEAD` when detached), then
+/// tags (`tag: <name>`), then the other branches.
+pub fn load_decorations() -> Result<HashMap<String, Vec<String>>> {
+    let mut decorations: HashMap<String, Vec<String>> = HashMap::new();
+
+    let head = crate::repo::read_head()?;
+    let current_branch = head.strip_prefix("ref: refs/heads/").map(str::to_string);
+    if let Some(hash) = crate::repo::resolve_head()? {
+        let label = match &current_branch {
+            Some(branch) => format!("HEAD -> {}", branch),
+            None => "HEAD".to_string(),
+        };
+        decorations.entry(hash).or_default().push(label);
+    }
+
+    // Annotated tags decorate the commit they point at
+    for (name, _) in list_refs(Path::new(".kitcat/refs/tags"), "")? {
+        if let Ok(commit) = crate::repo::resolve_revision(&format!("refs/tags/{}", name)) {
+            decorations.entry(commit).or_default().push(format!("tag: {}", name));
+        }
+    }
+
+    for (name, hash) in list_refs(Path::new(".kitcat/refs/heads"), "")? {
+        if current_branch.as_deref() != Some(name.as_str()) {
+            decorations.entry(hash).or_default().push(name);
+        }
+    }
+
+    Ok(decorations)
+}
+
+/// Ref names below `dir` (relative to it) and the hashes they hold, sorted by name
+pub fn list_refs(dir: &Path, prefix: &str) -> Result<Vec<(String, String)>> {
+    let mut refs = Vec::new();
+    if !dir.is_dir() {
+        return Ok(refs);
+    }
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        let path = entry.path();
+        if path.is_dir() {
+            refs.extend(list_refs(&path, &format!("{}/", name))?);
+        } else {
+            refs.push((name, fs::read_to_string(&path)?.trim().to_string()));
+        }
+    }
+
+    refs.sort();
+    Ok(refs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_commit() -> Commit {
+        Commit {
+            tree: "a".repeat(40),
+            parents: vec!["b".repeat(40), "c".repeat(40)],
+            author: "Jane Doe <jane@example.com>".to_string(),
+            author_time: 1234567890,
+            author_timezone: "+0200".to_string(),
+            committer: "John Roe <john@example.com>".to_string(),
+            committer_time: 1234567890,
+            committer_timezone: "-0500".to_string(),
+            message: "Fix the parser\n\nHandle empty input.".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_format_template() {
+        let commit = sample_commit();
+        let format = PrettyFormat::parse("format:%h %an <%ae> %s%n%b %%").unwrap();
+        assert_eq!(
+            format_commit(&"d".repeat(40), &commit, &format),
+            "ddddddd Jane Doe <jane@example.com> Fix the parser\nHandle empty input. %"
+        );
+    }
+
+    #[test]
+    fn test_format_medium_merge() {
+        let commit = sample_commit();
+        let output = format_commit(&"d".repeat(40), &commit, &PrettyFormat::Medium);
+        assert!(output.contains("Merge: bbbbbbb ccccccc\n"));
+        assert!(output.ends_with("    Fix the parser\n\n    Handle empty input."));
+    }
+
+    #[test]
+    fn test_format_decorations_and_dates() {
+        let commit = sample_commit();
+        let context = FormatContext {
+            date_mode: DateMode::Unix,
+            abbrev_commit: true,
+            decorate: true,
+            decorations: vec!["HEAD -> main".to_string(), "tag: v1.0".to_string()],
+            use_color: false,
+        };
+        let hash = "d".repeat(40);
+
+        assert_eq!(
+            format_commit_with(&hash, &commit, &PrettyFormat::Oneline, &context),
+            "ddddddd (HEAD -> main, tag: v1.0) Fix the parser"
+        );
+        assert_eq!(
+            format_commit_with(&hash, &commit, &PrettyFormat::parse("%h%d %ad [%D]").unwrap(), &context),
+            "ddddddd (HEAD -> main, tag: v1.0) 1234567890 [HEAD -> main, tag: v1.0]"
+        );
+        let fuller = format_commit_with(&hash, &commit, &PrettyFormat::Fuller, &context);
+        assert!(fuller.starts_with("commit ddddddd (HEAD -> main, tag: v1.0)\n"));
+        assert!(fuller.contains("AuthorDate: 1234567890\nCommit:     John Roe <john@example.com>\n"));
+        let raw = format_commit(&hash, &commit, &PrettyFormat::Raw);
+        assert!(raw.contains(&format!("tree {}\nparent {}\nparent {}\n", "a".repeat(40), "b".repeat(40), "c".repeat(40))));
+        assert!(raw.contains("author Jane Doe <jane@example.com> 1234567890 +0200\n"));
+        assert!(raw.contains("committer John Roe <john@example.com> 1234567890 -0500\n"));
+        assert!(DateMode::parse("fortnightly").is_err());
+    }
+
+    #[test]
+    fn test_format_date_in_recorded_timezone() {
+        assert_eq!(format_date(1234567890, "+0200"), "Sat Feb 14 01:31:30 2009 +0200");
+        assert_eq!(format_date_as(1234567890, "-0500", DateMode::Iso), "2009-02-13 18:31:30 -0500");
+        assert_eq!(format_date_as(1234567890, "+0530", DateMode::Raw), "1234567890 +0530");
+    }
+
+    #[test]
+    fn test_format_relative() {
+        assert_eq!(format_relative(100, 130), "30 seconds ago");
+        assert_eq!(format_relative(0, 3600), "60 minutes ago");
+        assert_eq!(format_relative(0, 3 * 86400), "3 days ago");
+        assert_eq!(format_relative(0, 21 * 86400), "3 weeks ago");
+        assert_eq!(format_relative(0, 400 * 86400), "1 year, 1 month ago");
+        assert_eq!(format_relative(0, 3650 * 86400), "10 years ago");
+        assert_eq!(format_relative(10, 0), "in the future");
+    }
+}
@@ -0,0 +1,47 @@
+/// Rerere command implementation
+///
+/// Manages recorded conflict resolutions (see `merge::rerere`):
+/// - no subcommand: record the resolutions made so far in the merge in progress
+/// - `status`: list the paths whose conflicts are being tracked
+/// - `forget <path>...`: drop the recorded resolutions for the conflicts in those paths
+/// - `clear`: stop tracking the conflicts of the merge in progress
+
+use crate::error::{KitcatError, Result};
+use crate::merge::rerere;
+
+/// Options for the rerere command
+#[derive(Debug, Clone, Default)]
+pub struct RerereOptions {
+    /// `status`, `forget` or `clear`; records resolutions if absent
+    pub subcommand: Option<String>,
+    /// Paths for `forget`
+    pub paths: Vec<String>,
+}
+
+/// Main rerere command
+pub fn rerere(options: RerereOptions) -> Result<()> {
+    match options.subcommand.as_deref() {
+        None => rerere::record(),
+        Some("status") => {
+            for path in rerere::status()? {
+                println!("{}", path);
+            }
+            Ok(())
+        }
+        Some("forget") => {
+            if options.paths.is_empty() {
+                return Err(KitcatError::InvalidInput("rerere forget requires at least one path".to_string()));
+            }
+            for path in &options.paths {
+                if rerere::forget(path)? {
+                    println!("Forgot resolution for '{}'", path);
+                } else {
+                    println!("No remembered resolution for '{}'", path);
+                }
+            }
+            Ok(())
+        }
+        Some("clear") => rerere::clear(),
+        Some(other) => Err(KitcatError::InvalidInput(format!("Unknown rerere subcommand '{}'", other))),
+    }
+}
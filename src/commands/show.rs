@@ -0,0 +1,179 @@
+/// Show command implementation
+///
+/// Displays any object in a human-readable form:
+/// - Commits: header and message plus the patch against the first parent,
+///   or a combined diff for merges
+/// - Annotated tags: tag header and message, then the tagged object
+/// - Trees: a listing of entry names
+/// - Blobs (e.g. `HEAD:src/main.rs`): the raw content
+
+use crate::commands::pretty::{format_commit, format_date, PrettyFormat};
+use crate::diff::{
+    combined_diff_file_maps, diff_file_maps, format_combined_diff, format_diffstat,
+    format_unified_diff, UnifiedDiffOptions,
+};
+use crate::error::{KitcatError, Result};
+use crate::merge::get_commit_files;
+use crate::object::{read_commit, read_object_data, read_tag, read_tree};
+use crate::repo::resolve_object_name;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+/// Options for the show command
+#[derive(Debug, Clone)]
+pub struct ShowOptions {
+    /// Objects to show (defaults to HEAD)
+    pub objects: Vec<String>,
+    /// Show a diffstat instead of the patch
+    pub stat: bool,
+    /// Show only the names of changed files
+    pub name_only: bool,
+    /// Commit format (`oneline`, `short`, `medium`, `full` or `format:...`)
+    pub format: Option<String>,
+    /// Suppress the diff output
+    pub no_patch: bool,
+    /// Use color output
+    pub use_color: bool,
+}
+
+impl Default for ShowOptions {
+    fn default() -> Self {
+        ShowOptions {
+            objects: Vec::new(),
+            stat: false,
+            name_only: false,
+            format: None,
+            no_patch: false,
+            use_color: true,
+        }
+    }
+}
+
+/// Main show command
+pub fn show(options: ShowOptions) -> Result<()> {
+    let format = match &options.format {
+        Some(spec) => PrettyFormat::parse(spec)?,
+        None => PrettyFormat::Medium,
+    };
+
+    let objects = if options.objects.is_empty() {
+        vec!["HEAD".to_string()]
+    } else {
+        options.objects.clone()
+    };
+
+    for (i, name) in objects.iter().enumerate() {
+        if i > 0 && !format.is_single_line() {
+            println!();
+        }
+        let hash = resolve_object_name(name)?;
+        show_object(&hash, name, &format, &options)?;
+    }
+
+    Ok(())
+}
+
+/// Show one object according to its type
+fn show_object(hash: &str, name: &str, format: &PrettyFormat, options: &ShowOptions) -> Result<()> {
+    let (object_type, content) = read_object_data(hash)?;
+
+    match object_type.as_str() {
+        "commit" => show_commit_object(hash, format, options),
+        "tag" => {
+            let tag = read_tag(hash)?;
+            println!("tag {}", tag.tag);
+            println!("Tagger: {}", tag.tagger);
+            println!("Date:   {}", format_date(tag.tagger_time, &tag.tagger_timezone));
+            println!();
+            if !tag.message.is_empty() {
+                println!("{}", tag.message);
+                println!();
+            }
+            show_object(&tag.object, &tag.object, format, options)
+        }
+        "tree" => {
+            println!("tree {}", name);
+            println!();
+            for entry in read_tree(hash)? {
+                if entry.is_tree {
+                    println!("{}/", entry.name);
+                } else {
+                    println!("{}", entry.name);
+                }
+            }
+            Ok(())
+        }
+        "blob" => Ok(io::stdout().write_all(&content)?),
+        other => Err(KitcatError::CorruptObject(format!("Unknown object type '{}'", other))),
+    }
+}
+
+/// Show a commit and its changes against the first parent
+///
+/// Merges are shown as a combined diff against all parents, except for
+/// `--stat`, which like Git compares against the first parent.
+fn show_commit_object(hash: &str, format: &PrettyFormat, options: &ShowOptions) -> Result<()> {
+    let commit = read_commit(hash)?;
+    println!("{}", format_commit(hash, &commit, format));
+
+    if options.no_patch {
+        return Ok(());
+    }
+
+    let named_format = !format.is_single_line();
+
+    if commit.parents.len() > 1 && !options.stat {
+        let parent_files = commit
+            .parents
+            .iter()
+            .map(|p| get_commit_files(p))
+            .collect::<Result<Vec<_>>>()?;
+        let diffs = combined_diff_file_maps(&parent_files, &get_commit_files(hash)?)?;
+        if diffs.is_empty() {
+            return Ok(());
+        }
+
+        if named_format {
+            println!();
+        }
+        for diff in &diffs {
+            if options.name_only {
+                println!("{}", diff.path);
+            } else {
+                print!("{}", format_combined_diff(diff, options.use_color));
+            }
+        }
+        return Ok(());
+    }
+
+    let old_files = match commit.parents.first() {
+        Some(parent) => get_commit_files(parent)?,
+        None => HashMap::new(),
+    };
+    let diffs = diff_file_maps(&old_files, &get_commit_files(hash)?, false)?;
+    if diffs.is_empty() {
+        return Ok(());
+    }
+
+    if named_format {
+        println!();
+    }
+
+    if options.name_only {
+        for diff in &diffs {
+            println!("{}", diff.display_path());
+        }
+    } else if options.stat {
+        print!("{}", format_diffstat(&diffs));
+    } else {
+        let unified = UnifiedDiffOptions {
+            use_color: options.use_color,
+            ..UnifiedDiffOptions::default()
+        };
+        for diff in &diffs {
+            print!("{}", format_unified_diff(diff, &unified));
+        }
+    }
+
+    Ok(())
+}
@@ -1,17 +1,44 @@
+use crate::error::Result;
 use crate::index::read_index;
+use crate::models::FileEntry;
 use crate::object::{get_commit_tree, read_tree};
 use crate::repo::read_head;
-use std::collections::{HashMap, HashSet};
+use crate::utils::{file_mode, read_worktree_file};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
-use std::io;
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
-/// Display the status of the working directory
-pub fn status() -> io::Result<()> {
-    // Get current branch
-    let head_content = read_head();
-    let current_branch = if head_content.starts_with("ref: refs/heads/") {
+/// Differences between HEAD, the index and the working tree
+#[derive(Debug, Default)]
+pub struct WorktreeStatus {
+    /// Files in the index but not in HEAD
+    pub staged_new: Vec<String>,
+    /// Files whose index entry differs from HEAD
+    pub staged_modified: Vec<String>,
+    /// Files in HEAD but not in the index
+    pub staged_deleted: Vec<String>,
+    /// Conflicted paths and their stages
+    pub unmerged: BTreeMap<String, Vec<u16>>,
+    /// Tracked files whose working copy differs from the index
+    pub unstaged_modified: Vec<String>,
+    /// Tracked files missing from the working tree
+    pub unstaged_deleted: Vec<String>,
+    /// Files in neither the index nor HEAD
+    pub untracked: Vec<String>,
+}
+
+impl WorktreeStatus {
+    /// Whether tracked files differ from the index
+    pub fn has_unstaged(&self) -> bool {
+        !self.unstaged_modified.is_empty() || !self.unstaged_deleted.is_empty()
+    }
+}
+
+/// The checked out branch, or `None` for a detached HEAD
+pub fn current_branch() -> Option<String> {
+    let head_content = read_head().ok()?;
+    if head_content.starts_with("ref: refs/heads/") {
         Some(
             head_content
                 .trim_start_matches("ref: refs/heads/")
@@ -22,81 +49,108 @@ pub fn status() -> io::Result<()> {
         None // Detached HEAD
     } else {
         Some("master".to_string()) // No commits yet
-    };
-
-    // Display branch info
-    if let Some(branch) = &current_branch {
-        println!("On branch {}", branch);
-    } else {
-        println!("HEAD detached at {}", &head_content[0..7]);
     }
+}
+
+/// Compare HEAD, the index and the working tree
+pub fn collect_status() -> Result<WorktreeStatus> {
+    let mut status = WorktreeStatus::default();
 
     // Get HEAD commit tree if it exists
     let head_tree_entries = get_head_tree_entries()?;
 
-    // Get index entries
-    let index_entries = read_index();
-    let mut index_map: HashMap<String, String> = index_entries
+    // Get index entries, keeping conflicted paths apart
+    let index_entries = read_index()?;
+    for entry in index_entries.iter().filter(|e| e.is_unmerged()) {
+        status.unmerged.entry(entry.path.clone()).or_default().push(entry.stage());
+    }
+    let index_map: HashMap<String, FileEntry> = index_entries
         .iter()
-        .map(|e| (e.path.clone(), e.hash.clone()))
+        .filter(|e| !e.is_unmerged())
+        .map(|e| (e.path.clone(), FileEntry::new(e.mode, e.hash.clone())))
         .collect();
 
     // Get working directory files
     let working_files = get_working_files()?;
 
     // Calculate staged changes (index vs HEAD)
-    let mut staged_new = Vec::new();
-    let mut staged_modified = Vec::new();
-    let mut staged_deleted = Vec::new();
-
-    for (path, index_hash) in &index_map {
+    for (path, index_entry) in &index_map {
         match head_tree_entries.get(path) {
-            None => staged_new.push(path.clone()),
-            Some(head_hash) if head_hash != index_hash => staged_modified.push(path.clone()),
+            None => status.staged_new.push(path.clone()),
+            Some(head_entry) if head_entry != index_entry => status.staged_modified.push(path.clone()),
             _ => {}
         }
     }
 
     for (path, _) in &head_tree_entries {
-        if !index_map.contains_key(path) {
-            staged_deleted.push(path.clone());
+        if !index_map.contains_key(path) && !status.unmerged.contains_key(path) {
+            status.staged_deleted.push(path.clone());
         }
     }
 
     // Calculate unstaged changes (working tree vs index)
-    let mut unstaged_modified = Vec::new();
-    let mut unstaged_deleted = Vec::new();
-
-    for (path, index_hash) in &index_map {
+    for (path, index_entry) in &index_map {
         match working_files.get(path) {
-            None => unstaged_deleted.push(path.clone()),
-            Some(work_hash) if work_hash != index_hash => unstaged_modified.push(path.clone()),
+            None => status.unstaged_deleted.push(path.clone()),
+            Some(work_entry) if work_entry != index_entry => status.unstaged_modified.push(path.clone()),
             _ => {}
         }
     }
 
     // Calculate untracked files
-    let mut untracked = Vec::new();
     for (path, _) in &working_files {
-        if !index_map.contains_key(path) {
-            untracked.push(path.clone());
+        if !index_map.contains_key(path) && !status.unmerged.contains_key(path) {
+            status.untracked.push(path.clone());
         }
     }
 
     // Sort all lists
-    staged_new.sort();
-    staged_modified.sort();
-    staged_deleted.sort();
-    unstaged_modified.sort();
-    unstaged_deleted.sort();
-    untracked.sort();
+    status.staged_new.sort();
+    status.staged_modified.sort();
+    status.staged_deleted.sort();
+    status.unstaged_modified.sort();
+    status.unstaged_deleted.sort();
+    status.untracked.sort();
+
+    Ok(status)
+}
+
+/// Display the status of the working directory
+pub fn status() -> Result<()> {
+    // Display branch info
+    match current_branch() {
+        Some(branch) => println!("On branch {}", branch),
+        None => println!("HEAD detached at {}", &read_head()?[0..7]),
+    }
+
+    let WorktreeStatus {
+        staged_new,
+        staged_modified,
+        staged_deleted,
+        unmerged,
+        unstaged_modified,
+        unstaged_deleted,
+        untracked,
+    } = collect_status()?;
 
     // Display status
     let has_staged = !staged_new.is_empty() || !staged_modified.is_empty() || !staged_deleted.is_empty();
     let has_unstaged = !unstaged_modified.is_empty() || !unstaged_deleted.is_empty();
     let has_untracked = !untracked.is_empty();
+    let has_unmerged = !unmerged.is_empty();
 
-    if !has_staged && !has_unstaged && !has_untracked {
+    if Path::new(".kitcat/MERGE_HEAD").exists() {
+        if has_unmerged {
+            println!("\nYou have unmerged paths.");
+            println!("  (fix conflicts and run \"kitcat merge --continue\")");
+            println!("  (use \"kitcat merge --abort\" to abort the merge)");
+        } else {
+            println!("\nAll conflicts fixed but you are still merging.");
+            println!("  (use \"kitcat merge --continue\" to conclude merge)");
+        }
+    }
+
+    if !has_staged && !has_unstaged && !has_untracked && !has_unmerged {
         println!("\nnothing to commit, working tree clean");
         return Ok(());
     }
@@ -118,6 +172,17 @@ pub fn status() -> io::Result<()> {
         }
     }
 
+    // Unmerged paths
+    if has_unmerged {
+        println!("\nUnmerged paths:");
+        println!("  (use \"kitcat add <file>...\" to mark resolution)");
+        println!();
+
+        for (file, stages) in &unmerged {
+            println!("\t\x1b[31m{:<17}{}\x1b[0m", format!("{}:", unmerged_label(stages)), file);
+        }
+    }
+
     // Unstaged changes
     if has_unstaged {
         println!("\nChanges not staged for commit:");
@@ -149,9 +214,23 @@ pub fn status() -> io::Result<()> {
     Ok(())
 }
 
+/// Describe a conflict by which stages (1 = base, 2 = ours, 3 = theirs) are present
+fn unmerged_label(stages: &[u16]) -> &'static str {
+    let has = |stage| stages.contains(&stage);
+    match (has(1), has(2), has(3)) {
+        (true, true, true) => "both modified",
+        (false, true, true) => "both added",
+        (true, true, false) => "deleted by them",
+        (true, false, true) => "deleted by us",
+        (false, true, false) => "added by us",
+        (false, false, true) => "added by them",
+        _ => "both deleted",
+    }
+}
+
 /// Get entries from HEAD commit tree
-fn get_head_tree_entries() -> io::Result<HashMap<String, String>> {
-    let head_content = read_head();
+fn get_head_tree_entries() -> Result<HashMap<String, FileEntry>> {
+    let head_content = read_head()?;
 
     // Get commit hash
     let commit_hash = if head_content.starts_with("ref:") {
@@ -186,8 +265,8 @@ fn get_head_tree_entries() -> io::Result<HashMap<String, String>> {
 fn collect_tree_entries(
     tree_hash: &str,
     prefix: &str,
-    entries: &mut HashMap<String, String>,
-) -> io::Result<()> {
+    entries: &mut HashMap<String, FileEntry>,
+) -> Result<()> {
     let tree_entries = read_tree(tree_hash)?;
 
     for entry in tree_entries {
@@ -204,15 +283,15 @@ fn collect_tree_entries(
         } else {
             // Add blob entry
             let hash_hex = bytes_to_hex(&entry.hash);
-            entries.insert(path, hash_hex);
+            entries.insert(path, FileEntry::from_tree_mode(&entry.mode, hash_hex));
         }
     }
 
     Ok(())
 }
 
-/// Get all files in working directory with their hashes
-fn get_working_files() -> io::Result<HashMap<String, String>> {
+/// Get all files in working directory with their modes and hashes
+fn get_working_files() -> Result<HashMap<String, FileEntry>> {
     let mut files = HashMap::new();
 
     for entry in WalkDir::new(".")
@@ -226,8 +305,8 @@ fn get_working_files() -> io::Result<HashMap<String, String>> {
     {
         let path = entry.path();
 
-        // Skip directories and hidden files (but not in subdirectories)
-        if path.is_dir() || path == Path::new(".") {
+        // Skip directories (symlinks to directories are files) and the root
+        if entry.file_type().is_dir() || path == Path::new(".") {
             continue;
         }
 
@@ -239,17 +318,18 @@ fn get_working_files() -> io::Result<HashMap<String, String>> {
             .unwrap()
             .to_string();
 
-        // Hash the file
+        // Hash the file (or symlink target)
         let hash = hash_file_content(path)?;
-        files.insert(rel_path, hash);
+        let mode = file_mode(&fs::symlink_metadata(path)?);
+        files.insert(rel_path, FileEntry::new(mode, hash));
     }
 
     Ok(files)
 }
 
 /// Hash file content (blob format)
-fn hash_file_content(path: &Path) -> io::Result<String> {
-    let content = fs::read(path)?;
+fn hash_file_content(path: &Path) -> Result<String> {
+    let content = read_worktree_file(path)?;
     let header = format!("blob {}\0", content.len());
     let mut data = header.as_bytes().to_vec();
     data.extend_from_slice(&content);
@@ -272,6 +352,14 @@ mod tests {
         assert_eq!(bytes_to_hex(&bytes), "abcdef");
     }
 
+    #[test]
+    fn test_unmerged_label() {
+        assert_eq!(unmerged_label(&[1, 2, 3]), "both modified");
+        assert_eq!(unmerged_label(&[2, 3]), "both added");
+        assert_eq!(unmerged_label(&[1, 3]), "deleted by us");
+        assert_eq!(unmerged_label(&[1]), "both deleted");
+    }
+
     #[test]
     fn test_is_hidden() {
         // This test would require creating actual DirEntry objects
@@ -0,0 +1,88 @@
+/// Tag command implementation
+///
+/// Tags are refs in `.kitcat/refs/tags/`:
+/// - lightweight tags point straight at a commit
+/// - annotated tags (`-a`, `-m`) point at a tag object recording the
+///   tagger and a message
+/// - signed tags (`-s`, `-u <key>` or `tag.gpgSign`) are annotated tags
+///   carrying a signature (see `signing`)
+///
+/// Without a name the tags are listed; `-d` deletes one.
+
+use crate::commands::pretty::list_refs;
+use crate::config::Config;
+use crate::error::{KitcatError, Result};
+use crate::object::{create_tag, Signature};
+use crate::repo::resolve_revision;
+use crate::signing::SignRequest;
+use std::fs;
+use std::path::Path;
+
+/// Directory holding the tag refs
+const TAGS_DIR: &str = ".kitcat/refs/tags";
+
+/// Options for the tag command
+#[derive(Debug, Clone, Default)]
+pub struct TagOptions {
+    /// Tag to create or delete; lists the tags when unset
+    pub name: Option<String>,
+    /// Commit to tag (HEAD when unset)
+    pub target: Option<String>,
+    /// Make an annotated tag
+    pub annotate: bool,
+    /// Tag message (implies an annotated tag)
+    pub message: Option<String>,
+    /// Whether to sign (`-s`, `-u <key>`, `--no-sign` or `tag.gpgSign`)
+    pub sign: SignRequest,
+    /// Delete the tag
+    pub delete: bool,
+    /// Replace an existing tag
+    pub force: bool,
+}
+
+/// Main tag command
+pub fn tag(options: TagOptions) -> Result<()> {
+    let Some(name) = &options.name else {
+        for (name, _) in list_refs(Path::new(TAGS_DIR), "")? {
+            println!("{}", name);
+        }
+        return Ok(());
+    };
+
+    let path = Path::new(TAGS_DIR).join(name);
+    if options.delete {
+        if !path.is_file() {
+            return Err(KitcatError::NotFound(format!("tag '{}' not found", name)));
+        }
+        let hash = fs::read_to_string(&path)?;
+        fs::remove_file(&path)?;
+        println!("Deleted tag '{}' (was {})", name, &hash.trim()[..7]);
+        return Ok(());
+    }
+
+    if name.is_empty() || name.contains("..") || name.contains(' ') || name.starts_with('-') {
+        return Err(KitcatError::InvalidInput(format!("'{}' is not a valid tag name", name)));
+    }
+    if path.exists() && !options.force {
+        return Err(KitcatError::AlreadyExists(format!("tag '{}' already exists", name)));
+    }
+
+    let commit = resolve_revision(options.target.as_deref().unwrap_or("HEAD"))?;
+    let signed = options.sign.wants(Config::read()?.tag.gpg_sign);
+
+    let hash = if options.annotate || options.message.is_some() || signed {
+        let message = options.message.as_deref().ok_or_else(|| {
+            KitcatError::InvalidInput("Annotated and signed tags need a message (-m)".to_string())
+        })?;
+        let sign = if signed { options.sign.clone() } else { SignRequest::Never };
+        create_tag(&commit, "commit", name, &Signature::committer()?, message, &sign)?
+    } else {
+        commit
+    };
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, format!("{}\n", hash))?;
+    Ok(())
+}
@@ -0,0 +1,62 @@
+/// Verify-commit and verify-tag command implementations
+///
+/// Check the signatures of commits and annotated tags (see `signing`),
+/// printing what the signing program reports. The command fails when any
+/// object is unsigned or its signature is not good.
+
+use crate::config::Config;
+use crate::error::{KitcatError, Result};
+use crate::object::{read_commit_signature, read_tag_signature};
+use crate::repo::{resolve_object_name, resolve_revision};
+use crate::signing::verify_payload;
+
+/// Signed payload and signature of an object, if it is signed
+type SignatureReader = fn(&str) -> Result<Option<(Vec<u8>, String)>>;
+
+/// Check the signatures of commits
+pub fn verify_commit(revisions: &[String]) -> Result<()> {
+    verify_objects(revisions, "commit", resolve_revision, read_commit_signature)
+}
+
+/// Check the signatures of annotated tags
+pub fn verify_tag(names: &[String]) -> Result<()> {
+    verify_objects(names, "tag", resolve_object_name, read_tag_signature)
+}
+
+/// Verify each named object, reporting all of them before failing
+fn verify_objects(
+    names: &[String],
+    kind: &str,
+    resolve: fn(&str) -> Result<String>,
+    read_signature: SignatureReader,
+) -> Result<()> {
+    let config = Config::read()?;
+    let mut failed = Vec::new();
+
+    for name in names {
+        let hash = resolve(name)?;
+        let Some((payload, signature)) = read_signature(&hash)? else {
+            eprintln!("No signature found in {} {}", kind, name);
+            failed.push(name.as_str());
+            continue;
+        };
+
+        let verification = verify_payload(&config, &payload, &signature)?;
+        if !verification.output.is_empty() {
+            eprintln!("{}", verification.output);
+        }
+        if !verification.good {
+            failed.push(name.as_str());
+        }
+    }
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(KitcatError::BadSignature(format!(
+            "Could not verify the signature of {} {}",
+            kind,
+            failed.join(", ")
+        )))
+    }
+}
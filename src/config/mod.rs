@@ -1,17 +1,223 @@
+use crate::error::{KitcatError, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
-use std::io;
 use std::path::Path;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Config {
     pub user: User,
+    #[serde(default, skip_serializing_if = "CoreConfig::is_empty")]
+    pub core: CoreConfig,
+    #[serde(default, skip_serializing_if = "CommitConfig::is_empty")]
+    pub commit: CommitConfig,
+    #[serde(default, skip_serializing_if = "DiffConfig::is_empty")]
+    pub diff: DiffConfig,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub difftool: BTreeMap<String, DiffToolConfig>,
+    #[serde(default, skip_serializing_if = "MergeConfig::is_empty")]
+    pub merge: MergeConfig,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub mergetool: BTreeMap<String, MergeToolConfig>,
+    #[serde(default, skip_serializing_if = "RerereConfig::is_empty")]
+    pub rerere: RerereConfig,
+    #[serde(default, skip_serializing_if = "BlameConfig::is_empty")]
+    pub blame: BlameConfig,
+    #[serde(default, skip_serializing_if = "TagConfig::is_empty")]
+    pub tag: TagConfig,
+    #[serde(default, skip_serializing_if = "GpgConfig::is_empty")]
+    pub gpg: GpgConfig,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct User {
     pub name: String,
     pub email: String,
+    /// Key to sign commits and tags with (`user.signingKey`)
+    #[serde(rename = "signingKey", default, skip_serializing_if = "Option::is_none")]
+    pub signing_key: Option<String>,
+}
+
+/// `[core]` section
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct CoreConfig {
+    /// Editor for commit messages (`core.editor`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub editor: Option<String>,
+    /// Directory hooks are run from instead of `.kitcat/hooks` (`core.hooksPath`)
+    #[serde(rename = "hooksPath", skip_serializing_if = "Option::is_none")]
+    pub hooks_path: Option<String>,
+}
+
+impl CoreConfig {
+    fn is_empty(&self) -> bool {
+        self.editor.is_none() && self.hooks_path.is_none()
+    }
+}
+
+/// `[commit]` section
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct CommitConfig {
+    /// Default `--cleanup` mode (`commit.cleanup`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cleanup: Option<String>,
+    /// Sign every commit (`commit.gpgSign`)
+    #[serde(rename = "gpgSign", skip_serializing_if = "Option::is_none")]
+    pub gpg_sign: Option<bool>,
+}
+
+impl CommitConfig {
+    fn is_empty(&self) -> bool {
+        self.cleanup.is_none() && self.gpg_sign.is_none()
+    }
+}
+
+/// `[tag]` section
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TagConfig {
+    /// Sign every annotated tag (`tag.gpgSign`)
+    #[serde(rename = "gpgSign", skip_serializing_if = "Option::is_none")]
+    pub gpg_sign: Option<bool>,
+}
+
+impl TagConfig {
+    fn is_empty(&self) -> bool {
+        self.gpg_sign.is_none()
+    }
+}
+
+/// `[gpg]` section: how signatures are made and checked
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct GpgConfig {
+    /// `openpgp` (default) or `ssh` (`gpg.format`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    /// OpenPGP program instead of `gpg` (`gpg.program`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub program: Option<String>,
+    #[serde(default, skip_serializing_if = "GpgSshConfig::is_empty")]
+    pub ssh: GpgSshConfig,
+}
+
+impl GpgConfig {
+    fn is_empty(&self) -> bool {
+        self.format.is_none() && self.program.is_none() && self.ssh.is_empty()
+    }
+}
+
+/// `[gpg.ssh]` section
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct GpgSshConfig {
+    /// Program instead of `ssh-keygen` (`gpg.ssh.program`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub program: Option<String>,
+    /// File of trusted `<principal> <key>` lines (`gpg.ssh.allowedSignersFile`)
+    #[serde(rename = "allowedSignersFile", skip_serializing_if = "Option::is_none")]
+    pub allowed_signers_file: Option<String>,
+}
+
+impl GpgSshConfig {
+    fn is_empty(&self) -> bool {
+        self.program.is_none() && self.allowed_signers_file.is_none()
+    }
+}
+
+/// `[diff]` section: external diff program, default tool and named drivers
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct DiffConfig {
+    /// Program run instead of the built-in diff (`diff.external`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external: Option<String>,
+    /// Default tool for `difftool` (`diff.tool`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool: Option<String>,
+    /// Drivers selected by the `diff=<name>` attribute (`diff.<name>.*`)
+    #[serde(flatten)]
+    pub drivers: BTreeMap<String, DiffDriverConfig>,
+}
+
+impl DiffConfig {
+    fn is_empty(&self) -> bool {
+        self.external.is_none() && self.tool.is_none() && self.drivers.is_empty()
+    }
+}
+
+/// `[diff.<name>]` section
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct DiffDriverConfig {
+    /// External diff program for paths using this driver
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// Program converting file contents to text before diffing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub textconv: Option<String>,
+}
+
+/// `[difftool.<name>]` section
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct DiffToolConfig {
+    /// Command line run with `$LOCAL`, `$REMOTE` and `$MERGED` set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cmd: Option<String>,
+}
+
+/// `[merge]` section
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct MergeConfig {
+    /// Default tool for `mergetool` (`merge.tool`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool: Option<String>,
+    /// How conflicts are written: `merge`, `diff3` or `zdiff3` (`merge.conflictStyle`)
+    #[serde(rename = "conflictStyle", skip_serializing_if = "Option::is_none")]
+    pub conflict_style: Option<String>,
+}
+
+impl MergeConfig {
+    fn is_empty(&self) -> bool {
+        self.tool.is_none() && self.conflict_style.is_none()
+    }
+}
+
+/// `[mergetool.<name>]` section
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct MergeToolConfig {
+    /// Command line run with `$BASE`, `$LOCAL`, `$REMOTE` and `$MERGED` set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cmd: Option<String>,
+    /// Trust the tool's exit status to tell whether the merge was resolved
+    #[serde(rename = "trustExitCode", skip_serializing_if = "Option::is_none")]
+    pub trust_exit_code: Option<bool>,
+}
+
+/// `[rerere]` section
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct RerereConfig {
+    /// Record and replay conflict resolutions (`rerere.enabled`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    /// Stage files fully resolved from recorded resolutions (`rerere.autoUpdate`)
+    #[serde(rename = "autoUpdate", skip_serializing_if = "Option::is_none")]
+    pub auto_update: Option<bool>,
+}
+
+impl RerereConfig {
+    fn is_empty(&self) -> bool {
+        self.enabled.is_none() && self.auto_update.is_none()
+    }
+}
+
+/// `[blame]` section
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct BlameConfig {
+    /// File listing revisions `blame` passes over (`blame.ignoreRevsFile`)
+    #[serde(rename = "ignoreRevsFile", skip_serializing_if = "Option::is_none")]
+    pub ignore_revs_file: Option<String>,
+}
+
+impl BlameConfig {
+    fn is_empty(&self) -> bool {
+        self.ignore_revs_file.is_none()
+    }
 }
 
 impl Default for Config {
@@ -20,14 +226,25 @@ impl Default for Config {
             user: User {
                 name: String::from("Unknown"),
                 email: String::from("unknown@example.com"),
+                signing_key: None,
             },
+            core: CoreConfig::default(),
+            commit: CommitConfig::default(),
+            diff: DiffConfig::default(),
+            difftool: BTreeMap::new(),
+            merge: MergeConfig::default(),
+            mergetool: BTreeMap::new(),
+            rerere: RerereConfig::default(),
+            blame: BlameConfig::default(),
+            tag: TagConfig::default(),
+            gpg: GpgConfig::default(),
         }
     }
 }
 
 impl Config {
     /// Read config from .kitcat/config
-    pub fn read() -> io::Result<Self> {
+    pub fn read() -> Result<Self> {
         let config_path = Path::new(".kitcat/config");
 
         if !config_path.exists() {
@@ -37,21 +254,15 @@ impl Config {
 
         let content = fs::read_to_string(config_path)?;
         toml::from_str(&content).map_err(|e| {
-            io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!("Failed to parse config: {}", e),
-            )
+            KitcatError::InvalidInput(format!("Failed to parse config: {}", e))
         })
     }
 
     /// Write config to .kitcat/config
-    pub fn write(&self) -> io::Result<()> {
+    pub fn write(&self) -> Result<()> {
         let config_path = Path::new(".kitcat/config");
         let content = toml::to_string_pretty(self).map_err(|e| {
-            io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!("Failed to serialize config: {}", e),
-            )
+            KitcatError::InvalidInput(format!("Failed to serialize config: {}", e))
         })?;
 
         fs::write(config_path, content)?;
@@ -65,18 +276,57 @@ impl Config {
 }
 
 /// Set a config value
-pub fn set_config(key: &str, value: &str) -> io::Result<()> {
+pub fn set_config(key: &str, value: &str) -> Result<()> {
     let mut config = Config::read()?;
 
-    match key {
-        "user.name" => config.user.name = value.to_string(),
-        "user.email" => config.user.email = value.to_string(),
-        _ => {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                format!("Unknown config key: {}", key),
-            ));
+    let new_value = value.to_string();
+    let parts: Vec<&str> = key.split('.').collect();
+    match parts.as_slice() {
+        ["user", "name"] => config.user.name = new_value,
+        ["user", "email"] => config.user.email = new_value,
+        ["user", "signingKey"] => config.user.signing_key = Some(new_value),
+        ["core", "editor"] => config.core.editor = Some(new_value),
+        ["core", "hooksPath"] => config.core.hooks_path = Some(new_value),
+        ["commit", "gpgSign"] => config.commit.gpg_sign = Some(parse_bool(key, value)?),
+        ["tag", "gpgSign"] => config.tag.gpg_sign = Some(parse_bool(key, value)?),
+        ["gpg", "format"] => {
+            crate::signing::SignatureFormat::parse(value)?;
+            config.gpg.format = Some(new_value)
+        }
+        ["gpg", "program"] => config.gpg.program = Some(new_value),
+        ["gpg", "ssh", "program"] => config.gpg.ssh.program = Some(new_value),
+        ["gpg", "ssh", "allowedSignersFile"] => config.gpg.ssh.allowed_signers_file = Some(new_value),
+        ["commit", "cleanup"] => {
+            crate::commands::commit::CleanupMode::parse(value)?;
+            config.commit.cleanup = Some(new_value)
         }
+        ["diff", "external"] => config.diff.external = Some(new_value),
+        ["diff", "tool"] => config.diff.tool = Some(new_value),
+        ["diff", driver, "command"] => {
+            config.diff.drivers.entry(driver.to_string()).or_default().command = Some(new_value)
+        }
+        ["diff", driver, "textconv"] => {
+            config.diff.drivers.entry(driver.to_string()).or_default().textconv = Some(new_value)
+        }
+        ["difftool", tool, "cmd"] => {
+            config.difftool.entry(tool.to_string()).or_default().cmd = Some(new_value)
+        }
+        ["merge", "tool"] => config.merge.tool = Some(new_value),
+        ["merge", "conflictStyle"] => {
+            crate::merge::ConflictStyle::parse(value)?;
+            config.merge.conflict_style = Some(new_value)
+        }
+        ["mergetool", tool, "cmd"] => {
+            config.mergetool.entry(tool.to_string()).or_default().cmd = Some(new_value)
+        }
+        ["mergetool", tool, "trustExitCode"] => {
+            config.mergetool.entry(tool.to_string()).or_default().trust_exit_code =
+                Some(parse_bool(key, value)?)
+        }
+        ["rerere", "enabled"] => config.rerere.enabled = Some(parse_bool(key, value)?),
+        ["rerere", "autoUpdate"] => config.rerere.auto_update = Some(parse_bool(key, value)?),
+        ["blame", "ignoreRevsFile"] => config.blame.ignore_revs_file = Some(new_value),
+        _ => return Err(unknown_key(key)),
     }
 
     config.write()?;
@@ -85,21 +335,67 @@ pub fn set_config(key: &str, value: &str) -> io::Result<()> {
 }
 
 /// Get a config value
-pub fn get_config(key: &str) -> io::Result<String> {
+pub fn get_config(key: &str) -> Result<String> {
     let config = Config::read()?;
 
-    let value = match key {
-        "user.name" => &config.user.name,
-        "user.email" => &config.user.email,
-        _ => {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                format!("Unknown config key: {}", key),
-            ));
-        }
+    let parts: Vec<&str> = key.split('.').collect();
+    let value = match parts.as_slice() {
+        ["user", "name"] => Some(config.user.name.clone()),
+        ["user", "email"] => Some(config.user.email.clone()),
+        ["core", "editor"] => config.core.editor.clone(),
+        ["core", "hooksPath"] => config.core.hooks_path.clone(),
+        ["user", "signingKey"] => config.user.signing_key.clone(),
+        ["commit", "gpgSign"] => config.commit.gpg_sign.map(|b| b.to_string()),
+        ["tag", "gpgSign"] => config.tag.gpg_sign.map(|b| b.to_string()),
+        ["gpg", "format"] => config.gpg.format.clone(),
+        ["gpg", "program"] => config.gpg.program.clone(),
+        ["gpg", "ssh", "program"] => config.gpg.ssh.program.clone(),
+        ["gpg", "ssh", "allowedSignersFile"] => config.gpg.ssh.allowed_signers_file.clone(),
+        ["commit", "cleanup"] => config.commit.cleanup.clone(),
+        ["diff", "external"] => config.diff.external.clone(),
+        ["diff", "tool"] => config.diff.tool.clone(),
+        ["diff", driver, "command"] => config
+            .diff
+            .drivers
+            .get(*driver)
+            .and_then(|d| d.command.clone()),
+        ["diff", driver, "textconv"] => config
+            .diff
+            .drivers
+            .get(*driver)
+            .and_then(|d| d.textconv.clone()),
+        ["difftool", tool, "cmd"] => config.difftool.get(*tool).and_then(|t| t.cmd.clone()),
+        ["merge", "tool"] => config.merge.tool.clone(),
+        ["merge", "conflictStyle"] => config.merge.conflict_style.clone(),
+        ["mergetool", tool, "cmd"] => config.mergetool.get(*tool).and_then(|t| t.cmd.clone()),
+        ["mergetool", tool, "trustExitCode"] => config
+            .mergetool
+            .get(*tool)
+            .and_then(|t| t.trust_exit_code)
+            .map(|b| b.to_string()),
+        ["rerere", "enabled"] => config.rerere.enabled.map(|b| b.to_string()),
+        ["rerere", "autoUpdate"] => config.rerere.auto_update.map(|b| b.to_string()),
+        ["blame", "ignoreRevsFile"] => config.blame.ignore_revs_file.clone(),
+        _ => return Err(unknown_key(key)),
     };
 
-    Ok(value.clone())
+    value.ok_or_else(|| {
+        KitcatError::NotFound(format!("Config key {} is not set", key))
+    })
+}
+
+/// Parse a boolean config value
+fn parse_bool(key: &str, value: &str) -> Result<bool> {
+    match value {
+        "true" | "yes" | "on" | "1" => Ok(true),
+        "false" | "no" | "off" | "0" => Ok(false),
+        _ => Err(KitcatError::InvalidInput(format!("Invalid boolean value for {}: {}", key, value))),
+    }
+}
+
+/// Error for a config key we do not know about
+fn unknown_key(key: &str) -> KitcatError {
+    KitcatError::InvalidInput(format!("Unknown config key: {}", key))
 }
 
 #[cfg(test)]
@@ -119,8 +415,31 @@ mod tests {
             user: User {
                 name: "John Doe".to_string(),
                 email: "john@example.com".to_string(),
+                signing_key: None,
             },
+            ..Config::default()
         };
         assert_eq!(config.get_user_string(), "John Doe <john@example.com>");
     }
+
+    #[test]
+    fn test_diff_driver_roundtrip() {
+        let mut config = Config::default();
+        config.diff.external = Some("difft".to_string());
+        config.diff.drivers.insert(
+            "sqlite".to_string(),
+            DiffDriverConfig {
+                command: None,
+                textconv: Some("sqlite3 $1 .dump".to_string()),
+            },
+        );
+
+        let text = toml::to_string_pretty(&config).unwrap();
+        let parsed: Config = toml::from_str(&text).unwrap();
+        assert_eq!(parsed.diff.external.as_deref(), Some("difft"));
+        assert_eq!(
+            parsed.diff.drivers["sqlite"].textconv.as_deref(),
+            Some("sqlite3 $1 .dump")
+        );
+    }
 }
@@ -0,0 +1,214 @@
+/// Date parsing for `--since`, `--until` and friends
+///
+/// Accepts the forms people actually type:
+/// - relative: `now`, `today`, `yesterday`, `3 days ago`, `2.weeks.ago`
+/// - ISO 8601: `2024-01-15`, `2024-01-15 10:30`, `2024-01-15T10:30:00+02:00`
+/// - RFC 2822: `Mon, 15 Jan 2024 10:30:00 +0200`
+/// - raw: `@1705311000`, `@1705311000 +0200` or Git's internal `1705311000 +0200`
+///
+/// Dates without a timezone are local time.
+
+use crate::error::{KitcatError, Result};
+use chrono::{
+    DateTime, Duration, FixedOffset, Local, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeZone,
+};
+
+/// Parse a date into a Unix timestamp
+pub fn parse_date(spec: &str) -> Result<i64> {
+    parse_date_with_offset(spec).map(|(timestamp, _)| timestamp)
+}
+
+/// Parse a date into a Unix timestamp and its `+hhmm` timezone offset
+///
+/// The offset is the one written in the date, or the local one.
+pub fn parse_date_with_offset(spec: &str) -> Result<(i64, String)> {
+    parse_date_at(spec, Local::now().fixed_offset())
+        .map(|date| (date.timestamp(), date.format("%z").to_string()))
+        .ok_or_else(|| {
+            KitcatError::InvalidInput(format!("Invalid date '{}'", spec))
+        })
+}
+
+/// Parse a date relative to `now`
+fn parse_date_at(spec: &str, now: DateTime<FixedOffset>) -> Option<DateTime<FixedOffset>> {
+    let spec = spec.trim();
+    let lower = spec.to_ascii_lowercase();
+
+    match lower.as_str() {
+        "now" => return Some(now),
+        "today" => return start_of_day(now),
+        "yesterday" => return start_of_day(now - Duration::days(1)),
+        _ => {}
+    }
+
+    if let Some(date) = parse_relative(&lower, now) {
+        return Some(date);
+    }
+    if let Some(date) = parse_raw(spec) {
+        return Some(date);
+    }
+    if let Ok(date) = DateTime::parse_from_rfc3339(spec) {
+        return Some(date);
+    }
+    if let Ok(date) = DateTime::parse_from_rfc2822(spec) {
+        return Some(date);
+    }
+
+    parse_iso(spec)
+}
+
+/// Midnight (local to `date`) of the day of `date`
+fn start_of_day(date: DateTime<FixedOffset>) -> Option<DateTime<FixedOffset>> {
+    date.timezone()
+        .from_local_datetime(&date.date_naive().and_time(NaiveTime::MIN))
+        .single()
+}
+
+/// `<n> <unit>[s] ago`, also written with dots (`2.weeks.ago`)
+fn parse_relative(spec: &str, now: DateTime<FixedOffset>) -> Option<DateTime<FixedOffset>> {
+    let words: Vec<&str> = spec
+        .split(|c: char| c.is_whitespace() || c == '.')
+        .filter(|word| !word.is_empty())
+        .collect();
+    let [count, unit, "ago"] = words.as_slice() else {
+        return None;
+    };
+
+    let count: i64 = count.parse().ok()?;
+    let unit = unit.strip_suffix('s').unwrap_or(unit);
+    match unit {
+        "second" | "sec" => Some(now - Duration::seconds(count)),
+        "minute" | "min" => Some(now - Duration::minutes(count)),
+        "hour" => Some(now - Duration::hours(count)),
+        "day" => Some(now - Duration::days(count)),
+        "week" => Some(now - Duration::weeks(count)),
+        "month" => now.checked_sub_months(Months::new(u32::try_from(count).ok()?)),
+        "year" => now.checked_sub_months(Months::new(u32::try_from(count).ok()?.checked_mul(12)?)),
+        _ => None,
+    }
+}
+
+/// `@<timestamp>` with an optional offset, or `<timestamp> <+hhmm>` as stored in commit objects
+fn parse_raw(spec: &str) -> Option<DateTime<FixedOffset>> {
+    let spec = match spec.strip_prefix('@') {
+        Some(timestamp) if !timestamp.contains(' ') => {
+            return DateTime::from_timestamp(timestamp.parse().ok()?, 0)
+                .map(|date| date.fixed_offset());
+        }
+        Some(raw) => raw,
+        None => spec,
+    };
+
+    let (timestamp, offset) = spec.split_once(' ')?;
+    let timestamp: i64 = timestamp.parse().ok()?;
+    let offset = parse_offset(offset)?;
+    Some(DateTime::from_timestamp(timestamp, 0)?.with_timezone(&offset))
+}
+
+/// A `+hhmm` / `-hhmm` / `+hh:mm` / `Z` timezone offset
+pub fn parse_offset(offset: &str) -> Option<FixedOffset> {
+    if offset == "Z" {
+        return FixedOffset::east_opt(0);
+    }
+
+    let sign = match offset.as_bytes().first()? {
+        b'+' => 1,
+        b'-' => -1,
+        _ => return None,
+    };
+    let digits = offset[1..].replace(':', "");
+    if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let hours: i32 = digits[..2].parse().ok()?;
+    let minutes: i32 = digits[2..].parse().ok()?;
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+/// `YYYY-MM-DD` with an optional time (`T` or space separated) and offset
+fn parse_iso(spec: &str) -> Option<DateTime<FixedOffset>> {
+    let (date, rest) = match spec.find(['T', ' ']) {
+        Some(split) => (&spec[..split], spec[split + 1..].trim()),
+        None => (spec, ""),
+    };
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+
+    let (time, offset) = match rest.find(['+', '-', 'Z', ' ']) {
+        Some(split) => (&rest[..split], Some(parse_offset(rest[split..].trim())?)),
+        None => (rest, None),
+    };
+    let time = if time.is_empty() {
+        NaiveTime::MIN
+    } else {
+        NaiveTime::parse_from_str(time, "%H:%M:%S")
+            .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M"))
+            .ok()?
+    };
+
+    let local = NaiveDateTime::new(date, time);
+    match offset {
+        Some(offset) => offset.from_local_datetime(&local).single(),
+        None => Local
+            .from_local_datetime(&local)
+            .earliest()
+            .map(|date| date.fixed_offset()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_date() {
+        let now = DateTime::parse_from_rfc3339("2024-03-15T12:00:00+01:00").unwrap();
+        let parse = |spec| parse_date_at(spec, now).map(|date| date.to_rfc3339());
+
+        assert_eq!(parse("now").unwrap(), "2024-03-15T12:00:00+01:00");
+        assert_eq!(parse("yesterday").unwrap(), "2024-03-14T00:00:00+01:00");
+        assert_eq!(parse("2 weeks ago").unwrap(), "2024-03-01T12:00:00+01:00");
+        assert_eq!(parse("1.month.ago").unwrap(), "2024-02-15T12:00:00+01:00");
+        assert_eq!(
+            parse("2024-01-15 10:30 +0200").unwrap(),
+            "2024-01-15T10:30:00+02:00"
+        );
+        assert_eq!(
+            parse("2024-01-15T10:30:00Z").unwrap(),
+            "2024-01-15T10:30:00+00:00"
+        );
+        assert_eq!(
+            parse("1705311000 -0500").unwrap(),
+            "2024-01-15T04:30:00-05:00"
+        );
+        assert_eq!(parse("@0").unwrap(), "1970-01-01T00:00:00+00:00");
+        assert_eq!(
+            parse("@1600000000 +0100").unwrap(),
+            "2020-09-13T13:26:40+01:00"
+        );
+        assert!(parse("@1600000000 bogus").is_none());
+        assert_eq!(
+            parse("Mon, 15 Jan 2024 10:30:00 +0200").unwrap(),
+            "2024-01-15T10:30:00+02:00"
+        );
+        assert!(parse("2024-01-15").is_some());
+        assert!(parse("last tuesday-ish").is_none());
+        assert!(parse("3 fortnights ago").is_none());
+    }
+
+    #[test]
+    fn test_parse_date_with_offset() {
+        assert_eq!(
+            parse_date_with_offset("2024-01-15T10:30:00+05:30").unwrap(),
+            (1705294800, "+0530".to_string())
+        );
+        assert_eq!(
+            parse_date_with_offset("1705311000 -0500").unwrap(),
+            (1705311000, "-0500".to_string())
+        );
+        assert_eq!(
+            parse_date_with_offset("@1600000000 +0100").unwrap(),
+            (1600000000, "+0100".to_string())
+        );
+        assert_eq!(parse_offset("-0130"), FixedOffset::west_opt(5400));
+    }
+}
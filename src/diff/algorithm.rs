@@ -1,20 +1,56 @@
 /// Diff algorithms implementation
 ///
-/// Provides Myers diff algorithm for computing line-by-line diffs
+/// Provides Myers diff algorithm for computing line-by-line diffs, plus the
+/// patience and histogram algorithms, which anchor the diff on rare lines
+/// and tend to produce more readable results for reordered code.
 
 use crate::diff::types::{DiffHunk, DiffLine, FileDiff};
+use crate::error::{KitcatError, Result};
+use std::collections::HashMap;
+
+/// Lines occurring more often than this are never used as histogram anchors
+const HISTOGRAM_MAX_OCCURRENCES: usize = 64;
 
 /// Supported diff algorithms
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum DiffAlgorithm {
     /// Myers algorithm (default, efficient for most cases)
+    #[default]
     Myers,
+    /// Minimal edit script (our Myers implementation is already minimal)
+    Minimal,
+    /// Patience diff: anchor on lines that are unique on both sides
+    Patience,
+    /// Histogram diff: anchor on the least frequent common lines
+    Histogram,
+}
+
+impl DiffAlgorithm {
+    /// Parse an algorithm name (`myers`, `default`, `minimal`, `patience`, `histogram`)
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "myers" | "default" => Ok(DiffAlgorithm::Myers),
+            "minimal" => Ok(DiffAlgorithm::Minimal),
+            "patience" => Ok(DiffAlgorithm::Patience),
+            "histogram" => Ok(DiffAlgorithm::Histogram),
+            _ => Err(KitcatError::InvalidInput(format!("Unknown diff algorithm '{}'", name))),
+        }
+    }
 }
 
 /// Compute diff between two texts using specified algorithm
 pub fn compute_diff(old_text: &str, new_text: &str, algorithm: DiffAlgorithm) -> FileDiff {
     match algorithm {
-        DiffAlgorithm::Myers => myers_diff(old_text, new_text),
+        DiffAlgorithm::Myers | DiffAlgorithm::Minimal => myers_diff(old_text, new_text),
+        DiffAlgorithm::Patience | DiffAlgorithm::Histogram => {
+            let old_lines: Vec<&str> = old_text.lines().collect();
+            let new_lines: Vec<&str> = new_text.lines().collect();
+            let edits = compute_edit_script_with(&old_lines, &new_lines, algorithm);
+
+            let mut diff = FileDiff::new(String::new(), String::new());
+            diff.hunks = edits_to_hunks(&edits, &old_lines, &new_lines);
+            diff
+        }
     }
 }
 
@@ -32,24 +68,21 @@ fn myers_diff(old_text: &str, new_text: &str) -> FileDiff {
     // Convert edit script to hunks
     let hunks = edits_to_hunks(&edits, &old_lines, &new_lines);
 
-    FileDiff {
-        old_path: String::new(),
-        new_path: String::new(),
-        is_binary: false,
-        hunks,
-    }
+    let mut diff = FileDiff::new(String::new(), String::new());
+    diff.hunks = hunks;
+    diff
 }
 
 /// Edit operation
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum Edit {
+pub enum Edit {
     Keep,
     Delete,
     Insert,
 }
 
 /// Compute the shortest edit script using Myers algorithm
-fn compute_edit_script(old_lines: &[&str], new_lines: &[&str]) -> Vec<Edit> {
+pub fn compute_edit_script(old_lines: &[&str], new_lines: &[&str]) -> Vec<Edit> {
     let n = old_lines.len();
     let m = new_lines.len();
 
@@ -126,6 +159,162 @@ fn compute_edit_script(old_lines: &[&str], new_lines: &[&str]) -> Vec<Edit> {
     edits
 }
 
+/// Compute an edit script with the given algorithm
+pub fn compute_edit_script_with(
+    old_lines: &[&str],
+    new_lines: &[&str],
+    algorithm: DiffAlgorithm,
+) -> Vec<Edit> {
+    let mut edits = Vec::new();
+    match algorithm {
+        DiffAlgorithm::Myers | DiffAlgorithm::Minimal => {
+            return compute_edit_script(old_lines, new_lines);
+        }
+        DiffAlgorithm::Patience => patience_edits(old_lines, new_lines, &mut edits),
+        DiffAlgorithm::Histogram => histogram_edits(old_lines, new_lines, &mut edits),
+    }
+    edits
+}
+
+/// Length of the common prefix and (non-overlapping) common suffix
+fn common_ends(old_lines: &[&str], new_lines: &[&str]) -> (usize, usize) {
+    let prefix = old_lines
+        .iter()
+        .zip(new_lines)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = old_lines[prefix..]
+        .iter()
+        .rev()
+        .zip(new_lines[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    (prefix, suffix)
+}
+
+/// Patience diff: recurse between the longest increasing run of unique lines
+fn patience_edits(old_lines: &[&str], new_lines: &[&str], edits: &mut Vec<Edit>) {
+    let (prefix, suffix) = common_ends(old_lines, new_lines);
+    let old = &old_lines[prefix..old_lines.len() - suffix];
+    let new = &new_lines[prefix..new_lines.len() - suffix];
+    edits.extend(std::iter::repeat_n(Edit::Keep, prefix));
+
+    let anchors = unique_anchors(old, new);
+    if anchors.is_empty() {
+        edits.extend(compute_edit_script(old, new));
+    } else {
+        let (mut old_start, mut new_start) = (0, 0);
+        for (i, j) in anchors {
+            patience_edits(&old[old_start..i], &new[new_start..j], edits);
+            edits.push(Edit::Keep);
+            old_start = i + 1;
+            new_start = j + 1;
+        }
+        patience_edits(&old[old_start..], &new[new_start..], edits);
+    }
+
+    edits.extend(std::iter::repeat_n(Edit::Keep, suffix));
+}
+
+/// Lines unique on both sides, as the longest sequence increasing on both sides
+fn unique_anchors(old: &[&str], new: &[&str]) -> Vec<(usize, usize)> {
+    // line -> (count in old, count in new, position in old)
+    let mut counts: HashMap<&str, (usize, usize, usize)> = HashMap::new();
+    for (i, line) in old.iter().enumerate() {
+        let entry = counts.entry(line).or_insert((0, 0, i));
+        entry.0 += 1;
+    }
+    for line in new {
+        if let Some(entry) = counts.get_mut(line) {
+            entry.1 += 1;
+        }
+    }
+
+    let pairs: Vec<(usize, usize)> = new
+        .iter()
+        .enumerate()
+        .filter_map(|(j, line)| match counts.get(line) {
+            Some(&(1, 1, i)) => Some((i, j)),
+            _ => None,
+        })
+        .collect();
+
+    // Longest increasing subsequence of old positions (pairs are ordered by new position)
+    let mut tails: Vec<usize> = Vec::new();
+    let mut previous: Vec<Option<usize>> = vec![None; pairs.len()];
+    for (k, &(i, _)) in pairs.iter().enumerate() {
+        let slot = tails.partition_point(|&t| pairs[t].0 < i);
+        if slot > 0 {
+            previous[k] = Some(tails[slot - 1]);
+        }
+        if slot == tails.len() {
+            tails.push(k);
+        } else {
+            tails[slot] = k;
+        }
+    }
+
+    let mut anchors = Vec::new();
+    let mut current = tails.last().copied();
+    while let Some(k) = current {
+        anchors.push(pairs[k]);
+        current = previous[k];
+    }
+    anchors.reverse();
+    anchors
+}
+
+/// Histogram diff: split around the longest match of the least frequent common line
+fn histogram_edits(old_lines: &[&str], new_lines: &[&str], edits: &mut Vec<Edit>) {
+    let (prefix, suffix) = common_ends(old_lines, new_lines);
+    let old = &old_lines[prefix..old_lines.len() - suffix];
+    let new = &new_lines[prefix..new_lines.len() - suffix];
+    edits.extend(std::iter::repeat_n(Edit::Keep, prefix));
+
+    let mut positions: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, line) in old.iter().enumerate() {
+        positions.entry(line).or_default().push(i);
+    }
+
+    // Best region as (occurrences, length, old start, new start)
+    let mut best: Option<(usize, usize, usize, usize)> = None;
+    for (j, line) in new.iter().enumerate() {
+        let Some(starts) = positions.get(line) else {
+            continue;
+        };
+        if starts.len() > HISTOGRAM_MAX_OCCURRENCES {
+            continue;
+        }
+        for &i in starts {
+            let len = old[i..]
+                .iter()
+                .zip(&new[j..])
+                .take_while(|(a, b)| a == b)
+                .count();
+            let better = match best {
+                None => true,
+                Some((count, best_len, _, _)) => {
+                    starts.len() < count || (starts.len() == count && len > best_len)
+                }
+            };
+            if better {
+                best = Some((starts.len(), len, i, j));
+            }
+        }
+    }
+
+    match best {
+        Some((_, len, i, j)) => {
+            histogram_edits(&old[..i], &new[..j], edits);
+            edits.extend(std::iter::repeat_n(Edit::Keep, len));
+            histogram_edits(&old[i + len..], &new[j + len..], edits);
+        }
+        None => edits.extend(compute_edit_script(old, new)),
+    }
+
+    edits.extend(std::iter::repeat_n(Edit::Keep, suffix));
+}
+
 /// Convert edit script to diff hunks with context
 fn edits_to_hunks(edits: &[Edit], old_lines: &[&str], new_lines: &[&str]) -> Vec<DiffHunk> {
     const CONTEXT_LINES: usize = 3;
@@ -279,4 +468,40 @@ mod tests {
         assert!(edits.contains(&Edit::Delete));
         assert!(edits.contains(&Edit::Insert));
     }
+
+    /// Apply an edit script to `old`, checking it produces `new`
+    fn replay(edits: &[Edit], old: &[&str], new: &[&str]) -> Vec<String> {
+        let (mut i, mut j) = (0, 0);
+        let mut result = Vec::new();
+        for edit in edits {
+            match edit {
+                Edit::Keep => {
+                    assert_eq!(old[i], new[j]);
+                    result.push(old[i].to_string());
+                    i += 1;
+                    j += 1;
+                }
+                Edit::Delete => i += 1,
+                Edit::Insert => {
+                    result.push(new[j].to_string());
+                    j += 1;
+                }
+            }
+        }
+        assert_eq!((i, j), (old.len(), new.len()));
+        result
+    }
+
+    #[test]
+    fn test_anchored_algorithms_produce_valid_scripts() {
+        let old = vec!["fn a() {", "  one", "}", "", "fn b() {", "  two", "}"];
+        let new = vec!["fn b() {", "  two", "}", "", "fn a() {", "  one", "}", "x"];
+
+        for algorithm in [DiffAlgorithm::Patience, DiffAlgorithm::Histogram] {
+            let edits = compute_edit_script_with(&old, &new, algorithm);
+            assert_eq!(replay(&edits, &old, &new), new);
+        }
+        assert_eq!(DiffAlgorithm::parse("histogram").unwrap(), DiffAlgorithm::Histogram);
+        assert!(DiffAlgorithm::parse("fancy").is_err());
+    }
 }
@@ -0,0 +1,488 @@
+/// Git-style binary patches
+///
+/// Provides the pieces needed to carry binary files inside a patch:
+/// - Base85 encoding (Git alphabet) of zlib-compressed payloads
+/// - Delta encoding/decoding using Git's copy/insert instruction format
+/// - Formatting and parsing of `GIT binary patch` hunks
+
+use crate::error::{KitcatError, Result};
+use crate::utils::{compress_data, decompress};
+use std::collections::HashMap;
+
+/// Base85 alphabet used by Git
+const BASE85_ALPHABET: &[u8; 85] =
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz!#$%&()*+-;<=>?@^_`{|}~";
+
+/// Maximum number of raw bytes encoded on one patch line
+const BYTES_PER_LINE: usize = 52;
+
+/// Largest copy instruction emitted by the delta encoder
+const MAX_COPY_SIZE: usize = 0x10000;
+
+/// Largest insert instruction allowed by the delta format
+const MAX_INSERT_SIZE: usize = 0x7f;
+
+/// Size of the source blocks indexed when searching for copies
+const DELTA_BLOCK_SIZE: usize = 16;
+
+/// Kind of a binary hunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryHunkKind {
+    /// Payload is the complete target content
+    Literal,
+    /// Payload is a delta against the source content
+    Delta,
+}
+
+/// One `literal`/`delta` section of a binary patch
+#[derive(Debug, Clone)]
+pub struct BinaryHunk {
+    /// Literal or delta
+    pub kind: BinaryHunkKind,
+    /// Inflated payload size, as written in the hunk header
+    pub size: usize,
+    /// Inflated payload (target content or delta instructions)
+    pub data: Vec<u8>,
+}
+
+impl BinaryHunk {
+    /// Build the smallest hunk that turns `source` into `target`
+    pub fn encode(source: &[u8], target: &[u8]) -> Self {
+        let literal = BinaryHunk {
+            kind: BinaryHunkKind::Literal,
+            size: target.len(),
+            data: target.to_vec(),
+        };
+
+        if source.is_empty() {
+            return literal;
+        }
+
+        let delta = create_delta(source, target);
+        if delta.len() < target.len() {
+            BinaryHunk {
+                kind: BinaryHunkKind::Delta,
+                size: delta.len(),
+                data: delta,
+            }
+        } else {
+            literal
+        }
+    }
+
+    /// Produce the target content, given the source it was computed against
+    pub fn apply(&self, source: &[u8]) -> Result<Vec<u8>> {
+        match self.kind {
+            BinaryHunkKind::Literal => Ok(self.data.clone()),
+            BinaryHunkKind::Delta => apply_delta(source, &self.data),
+        }
+    }
+
+    /// Format this hunk as it appears in a patch (header, encoded lines, blank line)
+    pub fn format(&self) -> String {
+        let header = match self.kind {
+            BinaryHunkKind::Literal => "literal",
+            BinaryHunkKind::Delta => "delta",
+        };
+
+        let mut output = format!("{} {}\n", header, self.size);
+        let compressed = compress_data(&self.data);
+
+        for chunk in compressed.chunks(BYTES_PER_LINE) {
+            output.push(encode_line_length(chunk.len()));
+            output.push_str(&encode_base85(chunk));
+            output.push('\n');
+        }
+        output.push('\n');
+        output
+    }
+}
+
+/// A complete binary patch: forward hunk plus the reverse hunk used by `apply -R`
+#[derive(Debug, Clone)]
+pub struct BinaryPatch {
+    /// Turns the old content into the new content
+    pub forward: BinaryHunk,
+    /// Turns the new content back into the old content
+    pub reverse: Option<BinaryHunk>,
+}
+
+impl BinaryPatch {
+    /// Build a binary patch between two contents
+    pub fn new(old: &[u8], new: &[u8]) -> Self {
+        BinaryPatch {
+            forward: BinaryHunk::encode(old, new),
+            reverse: Some(BinaryHunk::encode(new, old)),
+        }
+    }
+
+    /// Format the patch body starting with the `GIT binary patch` line
+    pub fn format(&self) -> String {
+        let mut output = String::from("GIT binary patch\n");
+        output.push_str(&self.forward.format());
+        if let Some(reverse) = &self.reverse {
+            output.push_str(&reverse.format());
+        }
+        output
+    }
+
+    /// Parse the lines following a `GIT binary patch` line
+    ///
+    /// Returns the patch and the number of lines consumed.
+    pub fn parse(lines: &[&str]) -> Result<(Self, usize)> {
+        let (forward, mut consumed) = parse_hunk(lines)?;
+
+        let reverse = match lines.get(consumed) {
+            Some(line) if line.starts_with("literal ") || line.starts_with("delta ") => {
+                let (hunk, used) = parse_hunk(&lines[consumed..])?;
+                consumed += used;
+                Some(hunk)
+            }
+            _ => None,
+        };
+
+        Ok((BinaryPatch { forward, reverse }, consumed))
+    }
+
+    /// Swap forward and reverse hunks
+    pub fn reversed(&self) -> Result<Self> {
+        let reverse = self.reverse.clone().ok_or_else(|| {
+            KitcatError::InvalidInput("Binary patch has no reverse hunk, cannot apply in reverse".to_string())
+        })?;
+
+        Ok(BinaryPatch {
+            forward: reverse,
+            reverse: Some(self.forward.clone()),
+        })
+    }
+}
+
+/// Parse one binary hunk, returning it and the number of lines consumed
+fn parse_hunk(lines: &[&str]) -> Result<(BinaryHunk, usize)> {
+    let header = lines
+        .first()
+        .ok_or_else(|| KitcatError::InvalidInput("Missing binary hunk".to_string()))?;
+
+    let (kind, size) = if let Some(size) = header.strip_prefix("literal ") {
+        (BinaryHunkKind::Literal, size)
+    } else if let Some(size) = header.strip_prefix("delta ") {
+        (BinaryHunkKind::Delta, size)
+    } else {
+        return Err(KitcatError::InvalidInput(format!("Invalid binary hunk header: {}", header)));
+    };
+
+    let size: usize = size.trim().parse().map_err(|_| {
+        KitcatError::InvalidInput("Invalid binary hunk size".to_string())
+    })?;
+
+    let mut compressed = Vec::new();
+    let mut consumed = 1;
+
+    for line in &lines[1..] {
+        consumed += 1;
+        if line.is_empty() {
+            break;
+        }
+
+        let mut chars = line.chars();
+        let len_char = chars.next().unwrap_or('\0');
+        let len = decode_line_length(len_char)?;
+        let decoded = decode_base85(chars.as_str())?;
+        if decoded.len() < len {
+            return Err(KitcatError::InvalidInput("Corrupt binary patch line".to_string()));
+        }
+        compressed.extend_from_slice(&decoded[..len]);
+    }
+
+    let data = decompress(&compressed)?;
+    if data.len() != size {
+        return Err(KitcatError::InvalidInput(format!(
+                "Binary hunk size mismatch (expected {}, got {})",
+                size,
+                data.len()
+            )));
+    }
+
+    Ok((BinaryHunk { kind, size, data }, consumed))
+}
+
+/// Encode the length prefix of a base85 line ('A'-'Z' = 1-26, 'a'-'z' = 27-52)
+fn encode_line_length(len: usize) -> char {
+    if len <= 26 {
+        (b'A' + len as u8 - 1) as char
+    } else {
+        (b'a' + (len - 27) as u8) as char
+    }
+}
+
+/// Decode the length prefix of a base85 line
+fn decode_line_length(c: char) -> Result<usize> {
+    match c {
+        'A'..='Z' => Ok(c as usize - 'A' as usize + 1),
+        'a'..='z' => Ok(c as usize - 'a' as usize + 27),
+        _ => Err(KitcatError::InvalidInput(format!("Invalid binary patch line length '{}'", c))),
+    }
+}
+
+/// Encode bytes as base85, zero-padding the input to a multiple of 4 bytes
+pub fn encode_base85(data: &[u8]) -> String {
+    let mut output = String::with_capacity(data.len().div_ceil(4) * 5);
+
+    for chunk in data.chunks(4) {
+        let mut word = [0u8; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        let mut value = u32::from_be_bytes(word);
+
+        let mut encoded = [0u8; 5];
+        for slot in encoded.iter_mut().rev() {
+            *slot = BASE85_ALPHABET[(value % 85) as usize];
+            value /= 85;
+        }
+        output.extend(encoded.iter().map(|&b| b as char));
+    }
+
+    output
+}
+
+/// Decode base85 text produced by `encode_base85`
+pub fn decode_base85(text: &str) -> Result<Vec<u8>> {
+    let bytes = text.as_bytes();
+    if !bytes.len().is_multiple_of(5) {
+        return Err(KitcatError::InvalidInput("Base85 data length is not a multiple of 5".to_string()));
+    }
+
+    let mut output = Vec::with_capacity(bytes.len() / 5 * 4);
+
+    for chunk in bytes.chunks(5) {
+        let mut value: u64 = 0;
+        for &c in chunk {
+            let digit = BASE85_ALPHABET.iter().position(|&a| a == c).ok_or_else(|| {
+                KitcatError::InvalidInput(format!("Invalid base85 character '{}'", c as char))
+            })?;
+            value = value * 85 + digit as u64;
+        }
+
+        if value > u32::MAX as u64 {
+            return Err(KitcatError::InvalidInput("Base85 value out of range".to_string()));
+        }
+        output.extend_from_slice(&(value as u32).to_be_bytes());
+    }
+
+    Ok(output)
+}
+
+/// Create a Git delta that rebuilds `target` from `source`
+///
+/// Source blocks are indexed by content; the target is scanned for blocks
+/// that also occur in the source, matches are extended as far as possible
+/// and emitted as copies, everything else is inserted literally.
+pub fn create_delta(source: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::new();
+    write_varint(&mut delta, source.len());
+    write_varint(&mut delta, target.len());
+
+    let mut blocks: HashMap<&[u8], usize> = HashMap::new();
+    for (i, block) in source.chunks_exact(DELTA_BLOCK_SIZE).enumerate() {
+        blocks.entry(block).or_insert(i * DELTA_BLOCK_SIZE);
+    }
+
+    let mut insert_start = 0;
+    let mut pos = 0;
+
+    while pos + DELTA_BLOCK_SIZE <= target.len() {
+        let Some(&src_start) = blocks.get(&target[pos..pos + DELTA_BLOCK_SIZE]) else {
+            pos += 1;
+            continue;
+        };
+
+        // Extend the match backwards into pending literal data, then forwards
+        let mut src = src_start;
+        let mut tgt = pos;
+        while src > 0 && tgt > insert_start && source[src - 1] == target[tgt - 1] {
+            src -= 1;
+            tgt -= 1;
+        }
+        let len = source[src..]
+            .iter()
+            .zip(&target[tgt..])
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        push_insert(&mut delta, &target[insert_start..tgt]);
+        push_copy(&mut delta, src, len);
+
+        pos = tgt + len;
+        insert_start = pos;
+    }
+
+    push_insert(&mut delta, &target[insert_start..]);
+    delta
+}
+
+/// Apply a Git delta to `source`
+pub fn apply_delta(source: &[u8], delta: &[u8]) -> Result<Vec<u8>> {
+    let corrupt = || KitcatError::InvalidInput("Corrupt binary delta".to_string());
+
+    let mut pos = 0;
+    let source_size = read_varint(delta, &mut pos).ok_or_else(corrupt)?;
+    let target_size = read_varint(delta, &mut pos).ok_or_else(corrupt)?;
+
+    if source_size != source.len() {
+        return Err(KitcatError::Conflict("Binary delta does not match the preimage".to_string()));
+    }
+
+    let mut target = Vec::with_capacity(target_size);
+
+    while pos < delta.len() {
+        let op = delta[pos];
+        pos += 1;
+
+        if op & 0x80 != 0 {
+            let mut offset = 0usize;
+            let mut size = 0usize;
+            for i in 0..4 {
+                if op & (1 << i) != 0 {
+                    offset |= (*delta.get(pos).ok_or_else(corrupt)? as usize) << (8 * i);
+                    pos += 1;
+                }
+            }
+            for i in 0..3 {
+                if op & (0x10 << i) != 0 {
+                    size |= (*delta.get(pos).ok_or_else(corrupt)? as usize) << (8 * i);
+                    pos += 1;
+                }
+            }
+            if size == 0 {
+                size = 0x10000;
+            }
+            let chunk = source.get(offset..offset + size).ok_or_else(corrupt)?;
+            target.extend_from_slice(chunk);
+        } else if op != 0 {
+            let size = op as usize;
+            let chunk = delta.get(pos..pos + size).ok_or_else(corrupt)?;
+            target.extend_from_slice(chunk);
+            pos += size;
+        } else {
+            return Err(corrupt());
+        }
+    }
+
+    if target.len() != target_size {
+        return Err(corrupt());
+    }
+
+    Ok(target)
+}
+
+/// Emit insert instructions for literal data
+fn push_insert(delta: &mut Vec<u8>, data: &[u8]) {
+    for chunk in data.chunks(MAX_INSERT_SIZE) {
+        delta.push(chunk.len() as u8);
+        delta.extend_from_slice(chunk);
+    }
+}
+
+/// Emit copy instructions for `size` bytes starting at `offset`
+fn push_copy(delta: &mut Vec<u8>, mut offset: usize, mut size: usize) {
+    while size > 0 {
+        let chunk = size.min(MAX_COPY_SIZE);
+        let mut op = 0x80u8;
+        let mut args = Vec::new();
+
+        for i in 0..4 {
+            let byte = ((offset >> (8 * i)) & 0xff) as u8;
+            if byte != 0 {
+                op |= 1 << i;
+                args.push(byte);
+            }
+        }
+
+        // A size of 0x10000 is encoded by omitting all size bytes
+        if chunk != MAX_COPY_SIZE {
+            for i in 0..3 {
+                let byte = ((chunk >> (8 * i)) & 0xff) as u8;
+                if byte != 0 {
+                    op |= 0x10 << i;
+                    args.push(byte);
+                }
+            }
+        }
+
+        delta.push(op);
+        delta.extend_from_slice(&args);
+
+        offset += chunk;
+        size -= chunk;
+    }
+}
+
+/// Write a little-endian base-128 size
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    loop {
+        let mut byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value != 0 {
+            byte |= 0x80;
+        }
+        out.push(byte);
+        if value == 0 {
+            break;
+        }
+    }
+}
+
+/// Read a little-endian base-128 size
+fn read_varint(data: &[u8], pos: &mut usize) -> Option<usize> {
+    let mut value = 0usize;
+    let mut shift = 0;
+
+    loop {
+        let byte = *data.get(*pos)?;
+        *pos += 1;
+        value |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base85_roundtrip() {
+        let data = b"\x00\x01\x02\xffbinary payload";
+        let encoded = encode_base85(data);
+        let decoded = decode_base85(&encoded).unwrap();
+        assert_eq!(&decoded[..data.len()], data);
+    }
+
+    #[test]
+    fn test_delta_roundtrip() {
+        let source: Vec<u8> = (0..=255u8).cycle().take(70000).collect();
+        let mut target = source.clone();
+        target[40000] = 0x42;
+        target.extend_from_slice(b"tail");
+
+        let delta = create_delta(&source, &target);
+        assert!(delta.len() < 100);
+        assert_eq!(apply_delta(&source, &delta).unwrap(), target);
+    }
+
+    #[test]
+    fn test_binary_patch_format_and_parse() {
+        let old = b"\x89PNG\r\n\x1a\n\x00\x00old".to_vec();
+        let new = b"\x89PNG\r\n\x1a\n\x00\x00new image data".to_vec();
+
+        let patch = BinaryPatch::new(&old, &new);
+        let text = patch.format();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "GIT binary patch");
+
+        let (parsed, _) = BinaryPatch::parse(&lines[1..]).unwrap();
+        assert_eq!(parsed.forward.apply(&old).unwrap(), new);
+        assert_eq!(parsed.reversed().unwrap().forward.apply(&new).unwrap(), old);
+    }
+}
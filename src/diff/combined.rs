@@ -0,0 +1,330 @@
+/// Combined diffs for merges
+///
+/// Compares one result against several parents at once, like Git's
+/// `--cc` output. Every line carries one marker column per parent:
+/// `+` if the line is not in that parent, `-` if it was in that parent
+/// but is gone from the result. Changes where the result simply took one
+/// parent's version are dropped, leaving only the interesting ones.
+
+use crate::diff::algorithm::{compute_edit_script, Edit};
+use crate::diff::is_binary;
+
+/// Context lines around each change
+const CONTEXT_LINES: usize = 3;
+
+/// One line of a combined diff
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CombinedLine {
+    /// One marker per parent: ' ', '+' or '-'
+    pub markers: Vec<char>,
+    /// Line content (without newline)
+    pub content: String,
+}
+
+/// A hunk of a combined diff
+#[derive(Debug, Clone)]
+pub struct CombinedHunk {
+    /// Start line and line count in each parent
+    pub parent_ranges: Vec<(usize, usize)>,
+    /// Start line and line count in the result
+    pub result_range: (usize, usize),
+    /// Lines of the hunk
+    pub lines: Vec<CombinedLine>,
+}
+
+impl CombinedHunk {
+    /// Format the hunk header, e.g. `@@@ -1,3 -1,4 +1,5 @@@`
+    pub fn header(&self) -> String {
+        let marker = "@".repeat(self.parent_ranges.len() + 1);
+        let mut header = marker.clone();
+        for (start, count) in &self.parent_ranges {
+            header.push_str(&format!(" -{},{}", start, count));
+        }
+        header.push_str(&format!(
+            " +{},{} {}",
+            self.result_range.0, self.result_range.1, marker
+        ));
+        header
+    }
+}
+
+/// Combined diff of one file against all parents of a merge
+#[derive(Debug, Clone)]
+pub struct CombinedDiff {
+    /// File path
+    pub path: String,
+    /// Blob hash of the file in each parent (None if missing)
+    pub parent_hashes: Vec<Option<String>>,
+    /// Blob hash of the result (None if deleted)
+    pub result_hash: Option<String>,
+    /// Whether any version is binary
+    pub is_binary: bool,
+    /// Interesting hunks
+    pub hunks: Vec<CombinedHunk>,
+}
+
+/// An entry of the merged line list before hunking
+struct Entry {
+    line: CombinedLine,
+    /// Line number (0-based) in each parent, if present there
+    parent_lines: Vec<Option<usize>>,
+    /// Line number (0-based) in the result, if present there
+    result_line: Option<usize>,
+}
+
+/// Compute the combined diff of `result` against `parents`
+///
+/// Returns None when the result is identical to one of the parents or
+/// when no hunk is left after dropping the uninteresting ones.
+pub fn combined_diff(
+    path: &str,
+    parents: &[Option<&[u8]>],
+    result: Option<&[u8]>,
+) -> Option<CombinedDiff> {
+    if parents.contains(&result) {
+        return None;
+    }
+
+    let hash = |content: Option<&[u8]>| content.map(crate::diff::blob_hash);
+    let mut diff = CombinedDiff {
+        path: path.to_string(),
+        parent_hashes: parents.iter().map(|p| hash(*p)).collect(),
+        result_hash: hash(result),
+        is_binary: false,
+        hunks: Vec::new(),
+    };
+
+    if parents.iter().flatten().chain(result.iter()).any(|c| is_binary(c)) {
+        diff.is_binary = true;
+        return Some(diff);
+    }
+
+    let text = |content: Option<&[u8]>| String::from_utf8_lossy(content.unwrap_or(&[])).to_string();
+    let result_text = text(result);
+    let result_lines: Vec<&str> = result_text.lines().collect();
+    let parent_texts: Vec<String> = parents.iter().map(|p| text(*p)).collect();
+    let parent_lines: Vec<Vec<&str>> = parent_texts.iter().map(|t| t.lines().collect()).collect();
+
+    let entries = build_entries(&parent_lines, &result_lines);
+    diff.hunks = build_hunks(&entries, parents.len());
+
+    if diff.hunks.is_empty() {
+        None
+    } else {
+        Some(diff)
+    }
+}
+
+/// Merge the per-parent edit scripts into a single annotated line list
+fn build_entries(parents: &[Vec<&str>], result: &[&str]) -> Vec<Entry> {
+    let num_parents = parents.len();
+
+    // For each parent: lines lost before each result line, and which result lines it has
+    let mut lost: Vec<Vec<Vec<(usize, &str)>>> = vec![vec![Vec::new(); result.len() + 1]; num_parents];
+    let mut matched: Vec<Vec<Option<usize>>> = vec![vec![None; result.len()]; num_parents];
+
+    for (p, parent) in parents.iter().enumerate() {
+        let (mut i, mut j) = (0, 0);
+        for edit in compute_edit_script(parent, result) {
+            match edit {
+                Edit::Keep => {
+                    matched[p][j] = Some(i);
+                    i += 1;
+                    j += 1;
+                }
+                Edit::Delete => {
+                    lost[p][j].push((i, parent[i]));
+                    i += 1;
+                }
+                Edit::Insert => j += 1,
+            }
+        }
+    }
+
+    let mut entries = Vec::new();
+    for j in 0..=result.len() {
+        // Lost lines, sharing an entry when several parents lost the same line
+        let mut lost_entries: Vec<Entry> = Vec::new();
+        for (p, parent_lost) in lost.iter().enumerate() {
+            let mut cursor = 0;
+            for (idx, &(line_no, content)) in parent_lost[j].iter().enumerate() {
+                let free = |entry: &Entry, text: &str| {
+                    entry.line.content == text && entry.line.markers[p] == ' '
+                };
+                let found = (cursor..lost_entries.len()).find(|&k| free(&lost_entries[k], content));
+                let k = found.unwrap_or_else(|| {
+                    // Place unshared lines before the next entry this parent shares, or at the end
+                    let later = &parent_lost[j][idx + 1..];
+                    let position = (cursor..lost_entries.len())
+                        .find(|&k| later.iter().any(|&(_, text)| free(&lost_entries[k], text)))
+                        .unwrap_or(lost_entries.len());
+                    lost_entries.insert(
+                        position,
+                        Entry {
+                            line: CombinedLine {
+                                markers: vec![' '; num_parents],
+                                content: content.to_string(),
+                            },
+                            parent_lines: vec![None; num_parents],
+                            result_line: None,
+                        },
+                    );
+                    position
+                });
+                lost_entries[k].line.markers[p] = '-';
+                lost_entries[k].parent_lines[p] = Some(line_no);
+                cursor = k + 1;
+            }
+        }
+        entries.extend(lost_entries);
+
+        if j < result.len() {
+            let parent_lines: Vec<Option<usize>> = matched.iter().map(|m| m[j]).collect();
+            entries.push(Entry {
+                line: CombinedLine {
+                    markers: parent_lines
+                        .iter()
+                        .map(|l| if l.is_some() { ' ' } else { '+' })
+                        .collect(),
+                    content: result[j].to_string(),
+                },
+                parent_lines,
+                result_line: Some(j),
+            });
+        }
+    }
+
+    entries
+}
+
+/// Group changed entries into hunks, keeping only changes that differ from every parent
+///
+/// Each run of changed lines is checked on its own: one that did not change
+/// relative to some parent took that parent's side and is dropped. Context
+/// around the remaining runs stops short of dropped ones.
+fn build_hunks(entries: &[Entry], num_parents: usize) -> Vec<CombinedHunk> {
+    let is_changed = |e: &Entry| e.line.markers.iter().any(|&m| m != ' ');
+
+    // Runs of changed entries, and whether each differs from every parent
+    let mut groups: Vec<(usize, usize, bool)> = Vec::new();
+    for (i, entry) in entries.iter().enumerate() {
+        if !is_changed(entry) {
+            continue;
+        }
+        match groups.last_mut() {
+            Some(last) if last.1 == i => last.1 = i + 1,
+            _ => groups.push((i, i + 1, false)),
+        }
+    }
+    for group in &mut groups {
+        let run = &entries[group.0..group.1];
+        group.2 = (0..num_parents).all(|p| run.iter().any(|e| e.line.markers[p] != ' '));
+    }
+
+    // Ranges of entries with context, merging ranges that touch
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for (g, &(first, last, interesting)) in groups.iter().enumerate() {
+        if !interesting {
+            continue;
+        }
+        let lower = groups[..g].iter().rev().find(|group| !group.2).map_or(0, |group| group.1);
+        let upper = groups[g + 1..].iter().find(|group| !group.2).map_or(entries.len(), |group| group.0);
+        let start = first.saturating_sub(CONTEXT_LINES).max(lower);
+        let end = (last + CONTEXT_LINES).min(upper);
+        match ranges.last_mut() {
+            Some(range) if start <= range.1 => range.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    let mut hunks = Vec::new();
+    for (start, end) in ranges {
+        let slice = &entries[start..end];
+
+        let parent_ranges = (0..num_parents)
+            .map(|p| {
+                let lines: Vec<usize> = slice.iter().filter_map(|e| e.parent_lines[p]).collect();
+                let preceding = entries[..start].iter().rev().find_map(|e| e.parent_lines[p]);
+                hunk_range(&lines, preceding)
+            })
+            .collect();
+        let result_lines: Vec<usize> = slice.iter().filter_map(|e| e.result_line).collect();
+        let preceding = entries[..start].iter().rev().find_map(|e| e.result_line);
+
+        hunks.push(CombinedHunk {
+            parent_ranges,
+            result_range: hunk_range(&result_lines, preceding),
+            lines: slice.iter().map(|e| e.line.clone()).collect(),
+        });
+    }
+
+    hunks
+}
+
+/// 1-based start and count of a hunk side; empty sides point at the preceding line
+fn hunk_range(lines: &[usize], preceding: Option<usize>) -> (usize, usize) {
+    match lines.first() {
+        Some(&first) => (first + 1, lines.len()),
+        None => (preceding.map_or(0, |l| l + 1), 0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_combined_diff_conflict_resolution() {
+        let ours = b"a\nours\nc\n".as_slice();
+        let theirs = b"a\ntheirs\nc\n".as_slice();
+        let result = b"a\nresolved\nc\n".as_slice();
+
+        let diff = combined_diff("f.txt", &[Some(ours), Some(theirs)], Some(result)).unwrap();
+        assert_eq!(diff.hunks.len(), 1);
+
+        let hunk = &diff.hunks[0];
+        assert_eq!(hunk.header(), "@@@ -1,3 -1,3 +1,3 @@@");
+        let markers: Vec<String> = hunk
+            .lines
+            .iter()
+            .map(|l| l.markers.iter().collect::<String>() + &l.content)
+            .collect();
+        assert_eq!(markers, vec!["  a", "- ours", " -theirs", "++resolved", "  c"]);
+    }
+
+    #[test]
+    fn test_combined_diff_drops_one_sided_lines_near_conflicts() {
+        let ours = b"l1\nm\nours\n".as_slice();
+        let theirs = b"L1\nm\ntheirs\n".as_slice();
+        let result = b"L1\nm\nresolved\n".as_slice();
+
+        // The first line simply took theirs, so only the resolution is shown
+        let diff = combined_diff("f.txt", &[Some(ours), Some(theirs)], Some(result)).unwrap();
+        assert_eq!(diff.hunks.len(), 1);
+        let hunk = &diff.hunks[0];
+        assert_eq!(hunk.header(), "@@@ -2,2 -2,2 +2,2 @@@");
+        let markers: Vec<String> = hunk
+            .lines
+            .iter()
+            .map(|l| l.markers.iter().collect::<String>() + &l.content)
+            .collect();
+        assert_eq!(markers, vec!["  m", "- ours", " -theirs", "++resolved"]);
+    }
+
+    #[test]
+    fn test_combined_diff_drops_one_sided_changes() {
+        let ours = b"one\ntwo\n".as_slice();
+        let theirs = b"one\n2\n".as_slice();
+
+        // Result equal to one parent: nothing interesting
+        assert!(combined_diff("f", &[Some(ours), Some(theirs)], Some(theirs)).is_none());
+
+        // A line removed from both parents is shown once with both markers
+        let result = b"one\n".as_slice();
+        let parent = b"one\nx\n".as_slice();
+        let diff = combined_diff("f", &[Some(parent), Some(parent)], Some(result)).unwrap();
+        let lines = &diff.hunks[0].lines;
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1].markers, vec!['-', '-']);
+    }
+}
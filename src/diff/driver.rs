@@ -0,0 +1,212 @@
+/// Diff drivers: external diff programs and textconv filters
+///
+/// A path's driver comes from the `diff` attribute in `.kitcatattributes`:
+/// - `diff=<name>` uses `diff.<name>.command` and/or `diff.<name>.textconv`
+/// - `-diff` (or `binary`) always treats the file as binary
+///
+/// Paths without a driver command fall back to `diff.external` if set.
+
+use crate::attributes::{AttrValue, Attributes};
+use crate::config::Config;
+use crate::error::{KitcatError, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Counter making temporary file names unique within the process
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// How one path should be diffed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiffDriver {
+    /// External program replacing the built-in diff
+    pub command: Option<String>,
+    /// Program converting contents to text before diffing
+    pub textconv: Option<String>,
+    /// Always report the file as binary
+    pub binary: bool,
+}
+
+/// Attributes and config needed to pick a driver for each path
+#[derive(Debug, Clone, Default)]
+pub struct DiffDrivers {
+    attributes: Attributes,
+    config: Config,
+}
+
+impl DiffDrivers {
+    /// Load `.kitcatattributes` and the repository config
+    pub fn load() -> Result<Self> {
+        Ok(Self {
+            attributes: Attributes::read()?,
+            config: Config::read()?,
+        })
+    }
+
+    /// Resolve the driver for a path
+    pub fn for_path(&self, path: &str) -> DiffDriver {
+        let mut driver = DiffDriver::default();
+
+        match self.attributes.get(path, "diff") {
+            Some(AttrValue::Unset) => driver.binary = true,
+            Some(AttrValue::Value(name)) => {
+                if let Some(config) = self.config.diff.drivers.get(&name) {
+                    driver.command = config.command.clone();
+                    driver.textconv = config.textconv.clone();
+                }
+            }
+            _ => {}
+        }
+
+        if driver.command.is_none() && !driver.binary {
+            driver.command = self.config.diff.external.clone();
+        }
+        // An empty setting means no program, which turns one off
+        driver.command = driver.command.filter(|command| !command.trim().is_empty());
+        driver.textconv = driver.textconv.filter(|command| !command.trim().is_empty());
+
+        driver
+    }
+}
+
+/// A temporary file removed when dropped
+#[derive(Debug)]
+pub struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    /// Write `content` to a new temporary file whose name ends like `name`
+    pub fn new(name: &str, content: &[u8]) -> Result<Self> {
+        let file_name = Path::new(name)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let path = std::env::temp_dir().join(format!(
+            "kitcat-{}-{}-{}",
+            std::process::id(),
+            TEMP_COUNTER.fetch_add(1, Ordering::Relaxed),
+            file_name
+        ));
+        fs::write(&path, content)?;
+        Ok(Self { path })
+    }
+
+    /// Path of the temporary file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Run a shell command line with extra positional arguments (`$1`, `$2`, ...)
+pub fn shell_command(command: &str, args: &[String]) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c")
+        .arg(format!("{} \"$@\"", command))
+        .arg(command)
+        .args(args);
+    cmd
+}
+
+/// Run an external diff program like Git does:
+/// `command path old-file old-hex old-mode new-file new-hex new-mode`
+///
+/// Each side is its content and mode. A missing side is passed as
+/// `/dev/null` with `.` for hash and mode.
+pub fn run_external_diff(
+    command: &str,
+    path: &str,
+    old: Option<(&[u8], u32)>,
+    new: Option<(&[u8], u32)>,
+) -> Result<()> {
+    let old_file = old.map(|(c, _)| TempFile::new(path, c)).transpose()?;
+    let new_file = new.map(|(c, _)| TempFile::new(path, c)).transpose()?;
+
+    let side = |file: &Option<TempFile>, side: Option<(&[u8], u32)>| match (file, side) {
+        (Some(file), Some((content, mode))) => vec![
+            file.path().display().to_string(),
+            crate::diff::blob_hash(content),
+            format!("{:o}", mode),
+        ],
+        _ => vec!["/dev/null".to_string(), ".".to_string(), ".".to_string()],
+    };
+
+    let mut args = vec![path.to_string()];
+    args.extend(side(&old_file, old));
+    args.extend(side(&new_file, new));
+
+    let status = shell_command(command, &args).status()?;
+
+    // Like diff(1), exit status 1 only means "files differ"
+    match status.code() {
+        Some(0) | Some(1) => Ok(()),
+        _ => Err(KitcatError::CommandFailed(format!(
+            "external diff died, stopping at {}",
+            path
+        ))),
+    }
+}
+
+/// Convert content to text with a textconv program (receives the file as `$1`)
+pub fn run_textconv(command: &str, path: &str, content: &[u8]) -> Result<Vec<u8>> {
+    let file = TempFile::new(path, content)?;
+    let output = shell_command(command, &[file.path().display().to_string()]).output()?;
+
+    if !output.status.success() {
+        return Err(KitcatError::CommandFailed(format!(
+            "textconv '{}' failed for {}",
+            command, path
+        )));
+    }
+
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DiffDriverConfig;
+
+    #[test]
+    fn test_driver_for_path() {
+        let mut config = Config::default();
+        config.diff.external = Some("difft".to_string());
+        config.diff.drivers.insert(
+            "sqlite".to_string(),
+            DiffDriverConfig {
+                command: None,
+                textconv: Some("sqlite3 .dump".to_string()),
+            },
+        );
+        let drivers = DiffDrivers {
+            attributes: Attributes::parse("*.db diff=sqlite\n*.png -diff\n"),
+            config,
+        };
+
+        let db = drivers.for_path("data/app.db");
+        assert_eq!(db.textconv.as_deref(), Some("sqlite3 .dump"));
+        assert_eq!(db.command.as_deref(), Some("difft"));
+
+        assert!(drivers.for_path("logo.png").binary);
+        assert_eq!(drivers.for_path("logo.png").command, None);
+
+        // An empty `diff.external` counts as unset
+        let mut config = Config::default();
+        config.diff.external = Some(String::new());
+        let drivers = DiffDrivers { attributes: Attributes::parse(""), config };
+        assert_eq!(drivers.for_path("notes.txt").command, None);
+    }
+
+    #[test]
+    fn test_run_textconv() {
+        let output = run_textconv("tr a-z A-Z <", "notes.txt", b"hello\n").unwrap();
+        assert_eq!(output, b"HELLO\n");
+    }
+}
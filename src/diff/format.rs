@@ -1,5 +1,6 @@
 /// Unified diff format output
 
+use crate::diff::combined::CombinedDiff;
 use crate::diff::types::FileDiff;
 
 /// Options for formatting unified diffs
@@ -11,6 +12,8 @@ pub struct UnifiedDiffOptions {
     pub use_color: bool,
     /// Show line numbers
     pub show_line_numbers: bool,
+    /// Show full blob hashes in the `index` line instead of abbreviations
+    pub full_index: bool,
 }
 
 impl Default for UnifiedDiffOptions {
@@ -19,6 +22,7 @@ impl Default for UnifiedDiffOptions {
             context_lines: 3,
             use_color: true,
             show_line_numbers: false,
+            full_index: false,
         }
     }
 }
@@ -32,10 +36,65 @@ mod colors {
     pub const BOLD: &str = "\x1b[1m";
 }
 
+/// Mode lines of the extended header (`old mode`/`new mode`, `new file mode`, `deleted file mode`)
+fn mode_header(diff: &FileDiff) -> Option<String> {
+    match (diff.old_mode, diff.new_mode) {
+        (Some(old), Some(new)) if old != new => Some(format!("old mode {:o}\nnew mode {:o}", old, new)),
+        (None, Some(new)) if diff.old_path == "/dev/null" => Some(format!("new file mode {:o}", new)),
+        (Some(old), None) if diff.new_path == "/dev/null" => {
+            Some(format!("deleted file mode {:o}", old))
+        }
+        _ => None,
+    }
+}
+
 /// Format a file diff as unified diff output
 pub fn format_unified_diff(diff: &FileDiff, options: &UnifiedDiffOptions) -> String {
     let mut output = String::new();
 
+    // Git extended header, when blob hashes are known
+    if let (Some(old_hash), Some(new_hash)) = (&diff.old_hash, &diff.new_hash) {
+        let path = diff.display_path();
+        let mut header = match diff.rename_source() {
+            Some(source) => format!(
+                "diff --git a/{} b/{}\nsimilarity index {}%\nrename from {}\nrename to {}",
+                source,
+                path,
+                diff.similarity.unwrap_or(0),
+                source,
+                path
+            ),
+            None => format!("diff --git a/{} b/{}", path, path),
+        };
+        if let Some(mode_lines) = mode_header(diff) {
+            header.push('\n');
+            header.push_str(&mode_lines);
+        }
+        if old_hash != new_hash {
+            let (old_hash, new_hash) = if options.full_index {
+                (old_hash.as_str(), new_hash.as_str())
+            } else {
+                (&old_hash[..7], &new_hash[..7])
+            };
+            header.push_str(&format!("\nindex {}..{}", old_hash, new_hash));
+            if let (Some(old_mode), Some(new_mode)) = (diff.old_mode, diff.new_mode)
+                && old_mode == new_mode
+            {
+                header.push_str(&format!(" {:o}", new_mode));
+            }
+        }
+        if options.use_color {
+            output.push_str(&format!("{}{}{}\n", colors::BOLD, header, colors::RESET));
+        } else {
+            output.push_str(&format!("{}\n", header));
+        }
+
+        // A pure mode change has no content to show
+        if old_hash == new_hash {
+            return output;
+        }
+    }
+
     // File headers
     if options.use_color {
         output.push_str(&format!(
@@ -59,6 +118,12 @@ pub fn format_unified_diff(diff: &FileDiff, options: &UnifiedDiffOptions) -> Str
         output.push_str(&format!("+++ {}\n", diff.new_path));
     }
 
+    // Binary payload (diff --binary)
+    if let Some(patch) = &diff.binary_patch {
+        output.push_str(&patch.format());
+        return output;
+    }
+
     // Binary file check
     if diff.is_binary {
         output.push_str("Binary files differ\n");
@@ -112,6 +177,67 @@ pub fn format_unified_diff(diff: &FileDiff, options: &UnifiedDiffOptions) -> Str
     output
 }
 
+/// Format a combined diff (`diff --cc`) for a merge
+pub fn format_combined_diff(diff: &CombinedDiff, use_color: bool) -> String {
+    let paint = |text: &str, color: &str| {
+        if use_color {
+            format!("{}{}{}", color, text, colors::RESET)
+        } else {
+            text.to_string()
+        }
+    };
+
+    let abbrev = |hash: &Option<String>| match hash {
+        Some(hash) => hash[..7].to_string(),
+        None => "0000000".to_string(),
+    };
+    let parents: Vec<String> = diff.parent_hashes.iter().map(abbrev).collect();
+
+    let mut output = String::new();
+    output.push_str(&paint(&format!("diff --cc {}", diff.path), colors::BOLD));
+    output.push('\n');
+    output.push_str(&paint(
+        &format!("index {}..{}", parents.join(","), abbrev(&diff.result_hash)),
+        colors::BOLD,
+    ));
+    output.push('\n');
+
+    let old_path = format!("a/{}", diff.path);
+    let new_path = match diff.result_hash {
+        Some(_) => format!("b/{}", diff.path),
+        None => "/dev/null".to_string(),
+    };
+    output.push_str(&paint(&format!("--- {}", old_path), colors::RED));
+    output.push('\n');
+    output.push_str(&paint(&format!("+++ {}", new_path), colors::GREEN));
+    output.push('\n');
+
+    if diff.is_binary {
+        output.push_str("Binary files differ\n");
+        return output;
+    }
+
+    for hunk in &diff.hunks {
+        output.push_str(&paint(&hunk.header(), colors::CYAN));
+        output.push('\n');
+
+        for line in &hunk.lines {
+            let text = format!("{}{}", line.markers.iter().collect::<String>(), line.content);
+            let colored = if line.markers.contains(&'+') {
+                paint(&text, colors::GREEN)
+            } else if line.markers.contains(&'-') {
+                paint(&text, colors::RED)
+            } else {
+                text
+            };
+            output.push_str(&colored);
+            output.push('\n');
+        }
+    }
+
+    output
+}
+
 /// Format diff statistics (e.g., "3 insertions(+), 2 deletions(-)")
 pub fn format_diff_stats(diff: &FileDiff, use_color: bool) -> String {
     let additions = diff.additions();
@@ -161,6 +287,92 @@ pub fn format_diff_summary(diff: &FileDiff, use_color: bool) -> String {
     format!("{} | {} {}", diff.new_path, changes, bar)
 }
 
+/// Format a Git-style diffstat for several files
+///
+/// ```text
+///  README.md  | 5 +++--
+///  src/lib.rs | 2 +-
+///  2 files changed, 4 insertions(+), 3 deletions(-)
+/// ```
+pub fn format_diffstat(diffs: &[FileDiff]) -> String {
+    const MAX_BAR: usize = 50;
+
+    if diffs.is_empty() {
+        return String::new();
+    }
+
+    let names: Vec<String> = diffs
+        .iter()
+        .map(|d| match d.rename_source() {
+            Some(source) => format!("{} => {}", source, d.display_path()),
+            None => d.display_path().to_string(),
+        })
+        .collect();
+    let name_width = names.iter().map(|name| name.len()).max().unwrap_or(0);
+    let max_changes = diffs
+        .iter()
+        .map(|d| d.additions() + d.deletions())
+        .max()
+        .unwrap_or(0);
+    let count_width = max_changes.to_string().len().max(3);
+
+    let mut output = String::new();
+    let mut insertions = 0;
+    let mut deletions = 0;
+
+    for (diff, path) in diffs.iter().zip(&names) {
+
+        if diff.is_binary {
+            output.push_str(&format!(" {:<name_width$} | {:>count_width$}\n", path, "Bin"));
+            continue;
+        }
+
+        let additions = diff.additions();
+        let removals = diff.deletions();
+        insertions += additions;
+        deletions += removals;
+
+        // Scale the bar down when the largest change does not fit
+        let (plus, minus) = if max_changes > MAX_BAR {
+            let scale = |n: usize| if n == 0 { 0 } else { (n * MAX_BAR / max_changes).max(1) };
+            (scale(additions), scale(removals))
+        } else {
+            (additions, removals)
+        };
+
+        output.push_str(&format!(
+            " {:<name_width$} | {:>count_width$} {}{}\n",
+            path,
+            additions + removals,
+            "+".repeat(plus),
+            "-".repeat(minus)
+        ));
+    }
+
+    output.push_str(&format!(
+        " {} file{} changed",
+        diffs.len(),
+        if diffs.len() == 1 { "" } else { "s" }
+    ));
+    if insertions > 0 || deletions == 0 {
+        output.push_str(&format!(
+            ", {} insertion{}(+)",
+            insertions,
+            if insertions == 1 { "" } else { "s" }
+        ));
+    }
+    if deletions > 0 || insertions == 0 {
+        output.push_str(&format!(
+            ", {} deletion{}(-)",
+            deletions,
+            if deletions == 1 { "" } else { "s" }
+        ));
+    }
+    output.push('\n');
+
+    output
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -199,6 +411,20 @@ mod tests {
         assert!(summary.contains("-"));
     }
 
+    #[test]
+    fn test_format_diffstat() {
+        let mut diff = FileDiff::new("a/src/main.rs".to_string(), "b/src/main.rs".to_string());
+        let mut hunk = DiffHunk::new(1, 1);
+        hunk.add_line(DiffLine::deletion(1, "old".to_string()));
+        hunk.add_line(DiffLine::addition(1, "new".to_string()));
+        hunk.add_line(DiffLine::addition(2, "more".to_string()));
+        diff.hunks.push(hunk);
+
+        let stat = format_diffstat(&[diff]);
+        assert!(stat.contains(" src/main.rs |   3 ++-"));
+        assert!(stat.contains("1 file changed, 2 insertions(+), 1 deletion(-)"));
+    }
+
     #[test]
     fn test_format_unified_diff() {
         let mut diff = FileDiff::new("a/file.txt".to_string(), "b/file.txt".to_string());
@@ -223,4 +449,49 @@ mod tests {
         assert!(output.contains("-line 2"));
         assert!(output.contains("+line 2 modified"));
     }
+
+    #[test]
+    fn test_format_mode_changes() {
+        let options = UnifiedDiffOptions {
+            use_color: false,
+            ..Default::default()
+        };
+        let hash = "e69de29bb2d1d6434b8b29ae775ad8c2e48c5391".to_string();
+
+        let mut diff = FileDiff::new("a/run.sh".to_string(), "b/run.sh".to_string());
+        diff.old_hash = Some(hash.clone());
+        diff.new_hash = Some(hash.clone());
+        diff.old_mode = Some(0o100644);
+        diff.new_mode = Some(0o100755);
+        assert_eq!(
+            format_unified_diff(&diff, &options),
+            "diff --git a/run.sh b/run.sh\nold mode 100644\nnew mode 100755\n"
+        );
+
+        let mut diff = FileDiff::new("/dev/null".to_string(), "b/link".to_string());
+        diff.old_hash = Some("0".repeat(40));
+        diff.new_hash = Some(hash);
+        diff.new_mode = Some(0o120000);
+        let output = format_unified_diff(&diff, &options);
+        assert!(output.starts_with("diff --git a/link b/link\nnew file mode 120000\nindex 0000000..e69de29\n"));
+    }
+
+    #[test]
+    fn test_format_renames() {
+        let hash = "e69de29bb2d1d6434b8b29ae775ad8c2e48c5391".to_string();
+        let mut diff = FileDiff::new("a/old.txt".to_string(), "b/docs/new.txt".to_string());
+        diff.old_hash = Some(hash.clone());
+        diff.new_hash = Some(hash);
+        diff.similarity = Some(100);
+
+        let options = UnifiedDiffOptions {
+            use_color: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            format_unified_diff(&diff, &options),
+            "diff --git a/old.txt b/docs/new.txt\nsimilarity index 100%\nrename from old.txt\nrename to docs/new.txt\n"
+        );
+        assert!(format_diffstat(&[diff]).starts_with(" old.txt => docs/new.txt |   0 \n"));
+    }
 }
@@ -4,19 +4,33 @@
 /// - Computing diffs between two text files (Myers algorithm)
 /// - Generating unified diff format output
 /// - Detecting binary files
+/// - Encoding binary files as Git binary patches
+/// - Parsing and applying unified diffs
+/// - Detecting renamed files by content similarity
 /// - Comparing working tree, index, and commit states
 
 pub mod algorithm;
+pub mod binary;
+pub mod combined;
+pub mod driver;
 pub mod format;
+pub mod patch;
+pub mod rename;
 pub mod types;
 
 // Re-export main types and functions
-pub use algorithm::{compute_diff, DiffAlgorithm};
-pub use format::{format_unified_diff, UnifiedDiffOptions};
+pub use algorithm::{compute_diff, compute_edit_script_with, DiffAlgorithm};
+pub use binary::BinaryPatch;
+pub use combined::{combined_diff, CombinedDiff};
+pub use format::{
+    format_combined_diff, format_diffstat, format_unified_diff, UnifiedDiffOptions,
+};
 pub use types::{DiffHunk, DiffLine, DiffLineType, FileDiff};
 
+use crate::error::Result;
+use crate::models::FileEntry;
+use std::collections::{BTreeSet, HashMap};
 use std::fs;
-use std::io;
 use std::path::Path;
 
 /// Compare two text contents and return a structured diff
@@ -25,18 +39,18 @@ pub fn diff_texts(old_content: &str, new_content: &str) -> FileDiff {
 }
 
 /// Compare two files and return a structured diff
-pub fn diff_files(old_path: &Path, new_path: &Path) -> io::Result<FileDiff> {
+pub fn diff_files(old_path: &Path, new_path: &Path) -> Result<FileDiff> {
     let old_content = fs::read(old_path)?;
     let new_content = fs::read(new_path)?;
 
     // Check if files are binary
     if is_binary(&old_content) || is_binary(&new_content) {
-        return Ok(FileDiff {
-            old_path: old_path.to_string_lossy().to_string(),
-            new_path: new_path.to_string_lossy().to_string(),
-            is_binary: true,
-            hunks: vec![],
-        });
+        let mut diff = FileDiff::new(
+            old_path.to_string_lossy().to_string(),
+            new_path.to_string_lossy().to_string(),
+        );
+        diff.is_binary = true;
+        return Ok(diff);
     }
 
     let old_text = String::from_utf8_lossy(&old_content);
@@ -49,11 +63,149 @@ pub fn diff_files(old_path: &Path, new_path: &Path) -> io::Result<FileDiff> {
     Ok(diff)
 }
 
+/// Compare two blob contents and return a diff with `a/`/`b/` paths and blob hashes
+///
+/// A `None` side is a missing file and is shown as `/dev/null`. Binary
+/// contents carry a `BinaryPatch` when `with_binary` is set.
+pub fn diff_blobs(
+    path: &str,
+    old_content: Option<&[u8]>,
+    new_content: Option<&[u8]>,
+    with_binary: bool,
+) -> FileDiff {
+    let old = old_content.unwrap_or_default();
+    let new = new_content.unwrap_or_default();
+
+    let mut diff = if is_binary(old) || is_binary(new) {
+        let mut diff = FileDiff::new(String::new(), String::new());
+        diff.is_binary = true;
+        if with_binary {
+            diff.binary_patch = Some(BinaryPatch::new(old, new));
+        }
+        diff
+    } else {
+        diff_texts(&String::from_utf8_lossy(old), &String::from_utf8_lossy(new))
+    };
+
+    diff.old_path = match old_content {
+        Some(_) => format!("a/{}", path),
+        None => "/dev/null".to_string(),
+    };
+    diff.new_path = match new_content {
+        Some(_) => format!("b/{}", path),
+        None => "/dev/null".to_string(),
+    };
+    diff.old_hash = Some(old_content.map(blob_hash).unwrap_or_else(null_hash));
+    diff.new_hash = Some(new_content.map(blob_hash).unwrap_or_else(null_hash));
+
+    diff
+}
+
+/// Diff two flattened trees (path -> mode and blob hash), reading blobs from the object store
+///
+/// Returns one `FileDiff` per changed path (content or mode), sorted by path.
+pub fn diff_file_maps(
+    old_files: &HashMap<String, FileEntry>,
+    new_files: &HashMap<String, FileEntry>,
+    with_binary: bool,
+) -> Result<Vec<FileDiff>> {
+    let all_paths: BTreeSet<&String> = old_files.keys().chain(new_files.keys()).collect();
+    let mut diffs = Vec::new();
+
+    for path in all_paths {
+        let old_entry = old_files.get(path);
+        let new_entry = new_files.get(path);
+        if old_entry == new_entry {
+            continue;
+        }
+
+        let old_content = old_entry
+            .map(|e| crate::object::read_blob_content(&e.hash))
+            .transpose()?;
+        let new_content = new_entry
+            .map(|e| crate::object::read_blob_content(&e.hash))
+            .transpose()?;
+
+        let mut diff = diff_blobs(
+            path,
+            old_content.as_deref(),
+            new_content.as_deref(),
+            with_binary,
+        );
+        diff.old_mode = old_entry.map(|e| e.mode);
+        diff.new_mode = new_entry.map(|e| e.mode);
+        diffs.push(diff);
+    }
+
+    Ok(diffs)
+}
+
+/// Combined diffs of a merge result against its parents (path -> mode and blob hash maps)
+///
+/// Only paths whose result differs from every parent are included.
+pub fn combined_diff_file_maps(
+    parent_files: &[HashMap<String, FileEntry>],
+    result_files: &HashMap<String, FileEntry>,
+) -> Result<Vec<CombinedDiff>> {
+    let all_paths: BTreeSet<&String> = parent_files
+        .iter()
+        .flat_map(|files| files.keys())
+        .chain(result_files.keys())
+        .collect();
+    let mut diffs = Vec::new();
+
+    for path in all_paths {
+        let result_entry = result_files.get(path);
+        if parent_files.iter().any(|files| files.get(path) == result_entry) {
+            continue;
+        }
+
+        let read = |entry: Option<&FileEntry>| {
+            entry
+                .map(|e| crate::object::read_blob_content(&e.hash))
+                .transpose()
+        };
+        let parents = parent_files
+            .iter()
+            .map(|files| read(files.get(path)))
+            .collect::<Result<Vec<_>>>()?;
+        let result = read(result_entry)?;
+
+        let parent_refs: Vec<Option<&[u8]>> = parents.iter().map(|p| p.as_deref()).collect();
+        if let Some(diff) = combined_diff(path, &parent_refs, result.as_deref()) {
+            diffs.push(diff);
+        }
+    }
+
+    Ok(diffs)
+}
+
+/// Compute the blob object hash of some content
+pub fn blob_hash(content: &[u8]) -> String {
+    let mut data = format!("blob {}\0", content.len()).into_bytes();
+    data.extend_from_slice(content);
+    crate::utils::compute_hash(&data)
+}
+
+/// Hash used for the missing side of an added or deleted file
+fn null_hash() -> String {
+    "0".repeat(40)
+}
+
 /// Check if content appears to be binary
 pub fn is_binary(content: &[u8]) -> bool {
-    // Simple heuristic: if we find a null byte in the first 8KB, it's likely binary
+    // Null bytes or invalid UTF-8 in the first 8KB mean the content is binary
     let check_size = content.len().min(8192);
-    content[..check_size].contains(&0)
+    let sample = &content[..check_size];
+    if sample.contains(&0) {
+        return true;
+    }
+
+    // A multi-byte character cut off by the sample boundary is still text
+    match std::str::from_utf8(sample) {
+        Ok(_) => false,
+        Err(e) => e.error_len().is_some(),
+    }
 }
 
 #[cfg(test)]
@@ -68,6 +220,25 @@ mod tests {
         assert!(is_binary(&[0xFF, 0xD8, 0xFF, 0xE0])); // JPEG header
     }
 
+    #[test]
+    fn test_diff_blobs_new_file() {
+        let diff = diff_blobs("file.txt", None, Some(b"hello\n"), false);
+        assert_eq!(diff.old_path, "/dev/null");
+        assert_eq!(diff.new_path, "b/file.txt");
+        assert_eq!(diff.additions(), 1);
+        assert_eq!(diff.display_path(), "file.txt");
+    }
+
+    #[test]
+    fn test_diff_blobs_binary() {
+        let diff = diff_blobs("img.png", Some(b"\x00old"), Some(b"\x00new"), true);
+        assert!(diff.is_binary);
+        assert!(diff.binary_patch.is_some());
+
+        let diff = diff_blobs("img.png", Some(b"\x00old"), Some(b"\x00new"), false);
+        assert!(diff.binary_patch.is_none());
+    }
+
     #[test]
     fn test_diff_texts_simple() {
         let old = "line 1\nline 2\nline 3\n";
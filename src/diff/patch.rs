@@ -0,0 +1,604 @@
+/// Unified diff parsing and application
+///
+/// Parses patches produced by `kitcat diff` (or Git), including
+/// `GIT binary patch` sections, and applies text hunks with offset
+/// and fuzz matching like `patch(1)`.
+
+use crate::diff::binary::BinaryPatch;
+use crate::diff::types::{DiffHunk, DiffLine, DiffLineType};
+use crate::error::{KitcatError, Result};
+
+/// Maximum number of context lines ignored at each end of a hunk
+pub const MAX_FUZZ: usize = 2;
+
+/// Changes to a single file parsed from a patch
+#[derive(Debug, Clone, Default)]
+pub struct FilePatch {
+    /// Path before the change (None for a new file)
+    pub old_path: Option<String>,
+    /// Path after the change (None for a deleted file)
+    pub new_path: Option<String>,
+    /// Blob hash of the preimage from the `index` line (may be abbreviated)
+    pub old_hash: Option<String>,
+    /// Blob hash of the postimage from the `index` line (may be abbreviated)
+    pub new_hash: Option<String>,
+    /// Text hunks
+    pub hunks: Vec<DiffHunk>,
+    /// Binary payload, if this is a binary patch
+    pub binary: Option<BinaryPatch>,
+    /// Preimage does not end with a newline
+    pub old_no_newline: bool,
+    /// Postimage does not end with a newline
+    pub new_no_newline: bool,
+    /// Mode before the change (`old mode`, `deleted file mode`)
+    pub old_mode: Option<u32>,
+    /// Mode after the change (`new mode`, `new file mode`)
+    pub new_mode: Option<u32>,
+    /// A `---`/`+++` header pair has been seen for this file
+    has_file_header: bool,
+}
+
+impl FilePatch {
+    /// Path this patch applies to
+    pub fn path(&self) -> &str {
+        self.new_path
+            .as_deref()
+            .or(self.old_path.as_deref())
+            .unwrap_or("")
+    }
+
+    /// Whether this patch creates a new file
+    pub fn is_creation(&self) -> bool {
+        self.old_path.is_none() && self.new_path.is_some()
+    }
+
+    /// Whether this patch deletes a file
+    pub fn is_deletion(&self) -> bool {
+        self.new_path.is_none() && self.old_path.is_some()
+    }
+
+    /// Build the patch that undoes this one (`apply -R`)
+    pub fn reversed(&self) -> Result<Self> {
+        let hunks = self
+            .hunks
+            .iter()
+            .map(|hunk| {
+                let mut reversed = DiffHunk::new(hunk.new_start, hunk.old_start);
+                for line in &hunk.lines {
+                    let line = match line.line_type {
+                        DiffLineType::Context => line.clone(),
+                        DiffLineType::Addition => DiffLine::deletion(0, line.content.clone()),
+                        DiffLineType::Deletion => DiffLine::addition(0, line.content.clone()),
+                    };
+                    reversed.add_line(line);
+                }
+                reversed
+            })
+            .collect();
+
+        let binary = self.binary.as_ref().map(|b| b.reversed()).transpose()?;
+
+        Ok(FilePatch {
+            old_path: self.new_path.clone(),
+            new_path: self.old_path.clone(),
+            old_hash: self.new_hash.clone(),
+            new_hash: self.old_hash.clone(),
+            hunks,
+            binary,
+            old_no_newline: self.new_no_newline,
+            new_no_newline: self.old_no_newline,
+            old_mode: self.new_mode,
+            new_mode: self.old_mode,
+            has_file_header: self.has_file_header,
+        })
+    }
+
+    /// Whether anything was parsed into this patch
+    fn has_content(&self) -> bool {
+        !self.hunks.is_empty() || self.binary.is_some() || self.is_mode_change()
+    }
+
+    /// Whether this patch changes the mode of an existing file
+    pub fn is_mode_change(&self) -> bool {
+        matches!((self.old_mode, self.new_mode), (Some(old), Some(new)) if old != new)
+    }
+}
+
+/// Where a hunk ended up when applied
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HunkReport {
+    /// 1-based hunk number
+    pub number: usize,
+    /// 1-based line in the result where the hunk was applied
+    pub line: usize,
+    /// Distance from the line recorded in the hunk header
+    pub offset: isize,
+    /// Context lines ignored at each end to make the hunk match
+    pub fuzz: usize,
+}
+
+impl HunkReport {
+    /// Describe a hunk that did not apply exactly, `patch(1)` style
+    pub fn describe(&self) -> Option<String> {
+        if self.offset == 0 && self.fuzz == 0 {
+            return None;
+        }
+
+        let mut message = format!("Hunk #{} succeeded at {}", self.number, self.line);
+        if self.fuzz > 0 {
+            message.push_str(&format!(" with fuzz {}", self.fuzz));
+        }
+        if self.offset != 0 {
+            let lines = if self.offset.abs() == 1 { "line" } else { "lines" };
+            message.push_str(&format!(" (offset {} {})", self.offset, lines));
+        }
+        message.push('.');
+        Some(message)
+    }
+}
+
+/// A hunk that could not be located in the target content
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HunkFailure {
+    /// 1-based hunk number
+    pub number: usize,
+    /// Line recorded in the hunk header
+    pub line: usize,
+}
+
+/// Parse a unified diff into per-file patches
+///
+/// Lines that are not part of a file patch (commit messages, mail headers,
+/// diffstats) are ignored.
+pub fn parse_patch(text: &str) -> Result<Vec<FilePatch>> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut patches = Vec::new();
+    let mut current: Option<FilePatch> = None;
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+
+        if let Some(rest) = line.strip_prefix("diff --git ") {
+            flush_patch(&mut current, &mut patches);
+            let mut patch = FilePatch::default();
+            if let Some((old, new)) = split_git_header(rest) {
+                patch.old_path = Some(check_patch_path(old)?);
+                patch.new_path = Some(check_patch_path(new)?);
+            }
+            current = Some(patch);
+            i += 1;
+        } else if line.starts_with("--- ")
+            && lines.get(i + 1).is_some_and(|l| l.starts_with("+++ "))
+        {
+            if current
+                .as_ref()
+                .is_some_and(|p| p.has_file_header || p.has_content())
+            {
+                flush_patch(&mut current, &mut patches);
+            }
+            let patch = current.get_or_insert_with(FilePatch::default);
+            patch.old_path = parse_file_header(&line[4..]).map(check_patch_path).transpose()?;
+            patch.new_path = parse_file_header(&lines[i + 1][4..]).map(check_patch_path).transpose()?;
+            patch.has_file_header = true;
+            i += 2;
+        } else if let Some(patch) = current.as_mut() {
+            if let Some(rest) = line.strip_prefix("index ") {
+                let range = rest.split_whitespace().next().unwrap_or("");
+                if let Some((old, new)) = range.split_once("..") {
+                    patch.old_hash = Some(old.to_string());
+                    patch.new_hash = Some(new.to_string());
+                }
+                i += 1;
+            } else if let Some(mode) = line.strip_prefix("new file mode ") {
+                patch.old_path = None;
+                patch.new_mode = Some(parse_mode(mode)?);
+                i += 1;
+            } else if let Some(mode) = line.strip_prefix("deleted file mode ") {
+                patch.new_path = None;
+                patch.old_mode = Some(parse_mode(mode)?);
+                i += 1;
+            } else if let Some(mode) = line.strip_prefix("old mode ") {
+                patch.old_mode = Some(parse_mode(mode)?);
+                i += 1;
+            } else if let Some(mode) = line.strip_prefix("new mode ") {
+                patch.new_mode = Some(parse_mode(mode)?);
+                i += 1;
+            } else if line == "GIT binary patch" {
+                let (binary, consumed) = BinaryPatch::parse(&lines[i + 1..])?;
+                patch.binary = Some(binary);
+                i += 1 + consumed;
+            } else if line.starts_with("@@ ") {
+                i = parse_hunk(&lines, i, patch)?;
+            } else {
+                i += 1;
+            }
+        } else {
+            i += 1;
+        }
+    }
+
+    flush_patch(&mut current, &mut patches);
+    Ok(patches)
+}
+
+/// Push the current patch if it carries any changes
+fn flush_patch(current: &mut Option<FilePatch>, patches: &mut Vec<FilePatch>) {
+    if let Some(patch) = current.take()
+        && (patch.has_content() || patch.is_creation() || patch.is_deletion())
+    {
+        patches.push(patch);
+    }
+}
+
+/// Split the `a/old b/new` part of a `diff --git` line
+fn split_git_header(rest: &str) -> Option<(String, String)> {
+    let (old, new) = rest.split_once(" b/")?;
+    let old = old.strip_prefix("a/")?;
+    Some((old.to_string(), new.to_string()))
+}
+
+/// Parse a `---`/`+++` path, stripping the `a/`/`b/` prefix and timestamps
+fn parse_file_header(header: &str) -> Option<String> {
+    let path = header.split('\t').next().unwrap_or("").trim_end();
+    if path == "/dev/null" {
+        return None;
+    }
+    let path = path
+        .strip_prefix("a/")
+        .or_else(|| path.strip_prefix("b/"))
+        .unwrap_or(path);
+    Some(path.to_string())
+}
+
+/// Refuse a patch path outside the work tree or inside the repository
+///
+/// Absolute paths, `..` components and `.kitcat` components are rejected,
+/// so a patch cannot write elsewhere or install hooks.
+fn check_patch_path(path: String) -> Result<String> {
+    let escapes = path.starts_with('/')
+        || path
+            .split('/')
+            .any(|component| component == ".." || component.eq_ignore_ascii_case(".kitcat"));
+    if escapes || path.is_empty() {
+        return Err(KitcatError::InvalidInput(format!("Invalid path '{}' in patch", path)));
+    }
+    Ok(path)
+}
+
+/// Parse an octal file mode from an extended header line
+fn parse_mode(mode: &str) -> Result<u32> {
+    u32::from_str_radix(mode.trim(), 8)
+        .map_err(|_| KitcatError::InvalidInput(format!("Invalid file mode: {}", mode)))
+}
+
+/// Parse a `-start,count` or `+start,count` range
+fn parse_range(range: &str) -> Result<(usize, usize)> {
+    let invalid = || {
+        KitcatError::InvalidInput(format!("Invalid hunk range: {}", range))
+    };
+    let range = &range[1..];
+    match range.split_once(',') {
+        Some((start, count)) => Ok((
+            start.parse().map_err(|_| invalid())?,
+            count.parse().map_err(|_| invalid())?,
+        )),
+        None => Ok((range.parse().map_err(|_| invalid())?, 1)),
+    }
+}
+
+/// Parse one hunk starting at the `@@` line, returning the index of the next line
+fn parse_hunk(lines: &[&str], start: usize, patch: &mut FilePatch) -> Result<usize> {
+    let header = lines[start];
+    let mut parts = header.split_whitespace().skip(1);
+    let old_range = parts.next().unwrap_or("");
+    let new_range = parts.next().unwrap_or("");
+    if !old_range.starts_with('-') || !new_range.starts_with('+') {
+        return Err(KitcatError::InvalidInput(format!("Invalid hunk header: {}", header)));
+    }
+
+    let (old_start, old_count) = parse_range(old_range)?;
+    let (new_start, new_count) = parse_range(new_range)?;
+
+    let mut hunk = DiffHunk::new(old_start, new_start);
+    let mut i = start + 1;
+    let mut last_type = None;
+
+    while i < lines.len() {
+        let line = lines[i];
+
+        if line.starts_with('\\') {
+            match last_type {
+                Some(DiffLineType::Deletion) => patch.old_no_newline = true,
+                Some(DiffLineType::Addition) => patch.new_no_newline = true,
+                _ => {
+                    patch.old_no_newline = true;
+                    patch.new_no_newline = true;
+                }
+            }
+            i += 1;
+            continue;
+        }
+
+        if hunk.old_count >= old_count && hunk.new_count >= new_count {
+            break;
+        }
+
+        let diff_line = match line.chars().next() {
+            Some('+') => DiffLine::addition(0, line[1..].to_string()),
+            Some('-') => DiffLine::deletion(0, line[1..].to_string()),
+            Some(' ') => DiffLine::context(0, 0, line[1..].to_string()),
+            // Some tools strip the space from empty context lines
+            None => DiffLine::context(0, 0, String::new()),
+            Some(_) => {
+                return Err(KitcatError::InvalidInput(format!("Corrupt patch at line: {}", line)));
+            }
+        };
+
+        last_type = Some(diff_line.line_type);
+        hunk.add_line(diff_line);
+        i += 1;
+    }
+
+    if hunk.old_count != old_count || hunk.new_count != new_count {
+        return Err(KitcatError::InvalidInput(format!("Truncated hunk: {}", header)));
+    }
+
+    patch.hunks.push(hunk);
+    Ok(i)
+}
+
+/// Apply text hunks to `content`
+///
+/// Each hunk is searched for near the line recorded in its header; if the
+/// exact context is not found, up to `MAX_FUZZ` context lines are ignored at
+/// each end. Returns the new content and a report per hunk.
+pub fn apply_hunks(
+    content: &str,
+    patch: &FilePatch,
+) -> Result<(String, Vec<HunkReport>), HunkFailure> {
+    let had_trailing_newline = content.is_empty() || content.ends_with('\n');
+    let mut lines: Vec<String> = content.lines().map(String::from).collect();
+    let mut reports = Vec::new();
+
+    // Net line count change of the hunks applied so far
+    let mut delta: isize = 0;
+    // Offset at which the previous hunk was found
+    let mut last_offset: isize = 0;
+    // Hunks must apply in order, never before the end of the previous one
+    let mut min_pos = 0usize;
+
+    for (idx, hunk) in patch.hunks.iter().enumerate() {
+        let number = idx + 1;
+        let header_pos = if hunk.old_count == 0 {
+            hunk.old_start
+        } else {
+            hunk.old_start.saturating_sub(1)
+        };
+        let expected = (header_pos as isize + delta).max(0) as usize;
+
+        let mut applied = None;
+        for fuzz in 0..=MAX_FUZZ {
+            let (lead, trail) = trimmed_context(hunk, fuzz);
+            if fuzz > 0 && lead == 0 && trail == 0 {
+                break;
+            }
+
+            let body = &hunk.lines[lead..hunk.lines.len() - trail];
+            let preimage: Vec<&str> = body
+                .iter()
+                .filter(|l| l.line_type != DiffLineType::Addition)
+                .map(|l| l.content.as_str())
+                .collect();
+            let postimage: Vec<String> = body
+                .iter()
+                .filter(|l| l.line_type != DiffLineType::Deletion)
+                .map(|l| l.content.clone())
+                .collect();
+
+            let guess = ((expected + lead) as isize + last_offset).max(0) as usize;
+            if let Some(pos) = find_preimage(&lines, &preimage, guess, min_pos) {
+                applied = Some((pos, lead, fuzz, preimage.len(), postimage));
+                break;
+            }
+        }
+
+        let (pos, lead, fuzz, old_len, postimage) = applied.ok_or(HunkFailure {
+            number,
+            line: hunk.old_start,
+        })?;
+
+        let offset = pos as isize - (expected + lead) as isize;
+        let new_len = postimage.len();
+        lines.splice(pos..pos + old_len, postimage);
+
+        reports.push(HunkReport {
+            number,
+            line: pos.saturating_sub(lead) + 1,
+            offset,
+            fuzz,
+        });
+
+        delta += new_len as isize - old_len as isize;
+        last_offset = offset;
+        min_pos = pos + new_len;
+    }
+
+    let mut result = lines.join("\n");
+    let trailing_newline = if patch.new_no_newline {
+        false
+    } else if patch.old_no_newline {
+        true
+    } else {
+        had_trailing_newline
+    };
+    if trailing_newline && !lines.is_empty() {
+        result.push('\n');
+    }
+
+    Ok((result, reports))
+}
+
+/// Number of leading and trailing context lines to ignore at a fuzz level
+fn trimmed_context(hunk: &DiffHunk, fuzz: usize) -> (usize, usize) {
+    let leading = hunk
+        .lines
+        .iter()
+        .take_while(|l| l.line_type == DiffLineType::Context)
+        .count();
+    let trailing = hunk
+        .lines
+        .iter()
+        .rev()
+        .take_while(|l| l.line_type == DiffLineType::Context)
+        .count();
+
+    // Never trim a hunk down to nothing but context
+    if leading == hunk.lines.len() {
+        return (0, 0);
+    }
+
+    // Always keep at least one line of context on each side that has some
+    let trim = |context: usize| fuzz.min(context.saturating_sub(1));
+    (trim(leading), trim(trailing))
+}
+
+/// Find the position closest to `guess` where `preimage` matches `lines`
+fn find_preimage(lines: &[String], preimage: &[&str], guess: usize, min_pos: usize) -> Option<usize> {
+    if preimage.len() > lines.len() {
+        return None;
+    }
+
+    let max_pos = lines.len() - preimage.len();
+    let matches_at = |pos: usize| {
+        pos >= min_pos
+            && lines[pos..pos + preimage.len()]
+                .iter()
+                .zip(preimage)
+                .all(|(a, b)| a == b)
+    };
+
+    let guess = guess.min(max_pos);
+    for distance in 0..=max_pos {
+        if distance <= guess && matches_at(guess - distance) {
+            return Some(guess - distance);
+        }
+        if distance > 0 && guess + distance <= max_pos && matches_at(guess + distance) {
+            return Some(guess + distance);
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::{diff_blobs, format_unified_diff, UnifiedDiffOptions};
+
+    fn make_patch(old: &str, new: &str) -> String {
+        let diff = diff_blobs("file.txt", Some(old.as_bytes()), Some(new.as_bytes()), false);
+        let options = UnifiedDiffOptions {
+            use_color: false,
+            ..Default::default()
+        };
+        format_unified_diff(&diff, &options)
+    }
+
+    #[test]
+    fn test_parse_rejects_unsafe_paths() {
+        for path in ["../escape.txt", "/tmp/escape.txt", "src/../../escape.txt", ".kitcat/hooks/pre-commit"] {
+            let patch = format!("--- /dev/null\n+++ b/{}\n@@ -0,0 +1 @@\n+x\n", path);
+            let err = parse_patch(&patch).unwrap_err();
+            assert!(matches!(err, KitcatError::InvalidInput(_)), "{}", path);
+
+            let patch = format!("diff --git a/{0} b/{0}\nnew file mode 100644\n", path);
+            assert!(parse_patch(&patch).is_err(), "{}", path);
+        }
+        assert!(parse_patch("--- /dev/null\n+++ b/src/ok..txt\n@@ -0,0 +1 @@\n+x\n").is_ok());
+    }
+
+    #[test]
+    fn test_parse_roundtrip() {
+        let patch = make_patch("a\nb\nc\n", "a\nB\nc\n");
+        let patches = parse_patch(&patch).unwrap();
+        assert_eq!(patches.len(), 1);
+        assert_eq!(patches[0].path(), "file.txt");
+        assert_eq!(patches[0].hunks.len(), 1);
+        assert!(patches[0].old_hash.is_some());
+
+        let (result, reports) = apply_hunks("a\nb\nc\n", &patches[0]).unwrap();
+        assert_eq!(result, "a\nB\nc\n");
+        assert_eq!(reports[0].describe(), None);
+    }
+
+    #[test]
+    fn test_apply_with_offset() {
+        let patch = make_patch("1\n2\n3\n4\n5\n", "1\n2\nthree\n4\n5\n");
+        let patches = parse_patch(&patch).unwrap();
+
+        let (result, reports) = apply_hunks("0\n0\n1\n2\n3\n4\n5\n", &patches[0]).unwrap();
+        assert_eq!(result, "0\n0\n1\n2\nthree\n4\n5\n");
+        assert_eq!(reports[0].offset, 2);
+        assert!(reports[0].describe().unwrap().contains("offset 2 lines"));
+    }
+
+    #[test]
+    fn test_apply_with_fuzz() {
+        let patch = make_patch("1\n2\n3\n4\n5\n6\n7\n", "1\n2\n3\nfour\n5\n6\n7\n");
+        let patches = parse_patch(&patch).unwrap();
+
+        let (result, reports) = apply_hunks("x\n2\n3\n4\n5\n6\ny\n", &patches[0]).unwrap();
+        assert_eq!(result, "x\n2\n3\nfour\n5\n6\ny\n");
+        assert_eq!(reports[0].fuzz, 1);
+    }
+
+    #[test]
+    fn test_apply_failure_and_reverse() {
+        let patch = make_patch("a\nb\nc\n", "a\nB\nc\n");
+        let patches = parse_patch(&patch).unwrap();
+        assert!(apply_hunks("x\ny\nz\n", &patches[0]).is_err());
+
+        let reversed = patches[0].reversed().unwrap();
+        let (result, _) = apply_hunks("a\nB\nc\n", &reversed).unwrap();
+        assert_eq!(result, "a\nb\nc\n");
+    }
+
+    #[test]
+    fn test_parse_new_and_deleted_files() {
+        let mut text = make_patch("", "");
+        let diff = diff_blobs("new.txt", None, Some(b"hello\n"), false);
+        let options = UnifiedDiffOptions {
+            use_color: false,
+            ..Default::default()
+        };
+        text.push_str(&format_unified_diff(&diff, &options));
+        let diff = diff_blobs("old.txt", Some(b"bye\n"), None, false);
+        text.push_str(&format_unified_diff(&diff, &options));
+
+        let patches = parse_patch(&text).unwrap();
+        assert_eq!(patches.len(), 2);
+        assert!(patches[0].is_creation());
+        assert_eq!(patches[0].path(), "new.txt");
+        assert!(patches[1].is_deletion());
+
+        let (result, _) = apply_hunks("", &patches[0]).unwrap();
+        assert_eq!(result, "hello\n");
+    }
+
+    #[test]
+    fn test_parse_binary_patch() {
+        let diff = diff_blobs("img.bin", Some(b"\x00\x01"), Some(b"\x00\x02\x03"), true);
+        let options = UnifiedDiffOptions {
+            use_color: false,
+            full_index: true,
+            ..Default::default()
+        };
+        let text = format_unified_diff(&diff, &options);
+
+        let patches = parse_patch(&text).unwrap();
+        assert_eq!(patches.len(), 1);
+        let binary = patches[0].binary.as_ref().unwrap();
+        assert_eq!(binary.forward.apply(b"\x00\x01").unwrap(), b"\x00\x02\x03");
+    }
+}
@@ -0,0 +1,114 @@
+/// Rename detection by content similarity
+///
+/// Two blobs are compared line by line: the similarity is the number of
+/// bytes in lines common to both (counting repeated lines as often as they
+/// occur on both sides) divided by the size of the larger blob. Like Git,
+/// a file added in a commit is a rename of a file deleted in the same
+/// commit when they are at least 50% similar.
+
+use crate::diff::diff_blobs;
+use crate::diff::types::FileDiff;
+use crate::error::Result;
+use crate::models::FileEntry;
+use crate::object::read_blob_content;
+use std::collections::HashMap;
+
+/// Minimum similarity (in percent) for a rename
+pub const RENAME_THRESHOLD: usize = 50;
+
+/// Similarity of two contents in percent
+pub fn similarity(old: &[u8], new: &[u8]) -> usize {
+    let larger = old.len().max(new.len());
+    if larger == 0 {
+        return 100;
+    }
+
+    let mut old_lines: HashMap<&[u8], usize> = HashMap::new();
+    for line in old.split_inclusive(|&b| b == b'\n') {
+        *old_lines.entry(line).or_default() += 1;
+    }
+
+    let mut common = 0;
+    for line in new.split_inclusive(|&b| b == b'\n') {
+        if let Some(count) = old_lines.get_mut(line)
+            && *count > 0
+        {
+            *count -= 1;
+            common += line.len();
+        }
+    }
+
+    common * 100 / larger
+}
+
+/// Find the file `path` was renamed from between two flattened trees
+///
+/// Candidates are files of `old_files` missing from `new_files`. Returns
+/// the most similar one and its score, if it reaches `RENAME_THRESHOLD`.
+pub fn find_rename_source(
+    path: &str,
+    old_files: &HashMap<String, FileEntry>,
+    new_files: &HashMap<String, FileEntry>,
+) -> Result<Option<(String, usize)>> {
+    let Some(entry) = new_files.get(path) else {
+        return Ok(None);
+    };
+    if old_files.contains_key(path) {
+        return Ok(None);
+    }
+
+    let mut candidates: Vec<&String> = old_files
+        .keys()
+        .filter(|old_path| !new_files.contains_key(*old_path))
+        .collect();
+    candidates.sort();
+
+    // An unchanged blob is a perfect match
+    if let Some(exact) = candidates.iter().find(|old_path| old_files[**old_path].hash == entry.hash) {
+        return Ok(Some(((*exact).clone(), 100)));
+    }
+
+    let content = read_blob_content(&entry.hash)?;
+    let mut best: Option<(String, usize)> = None;
+    for old_path in candidates {
+        let score = similarity(&read_blob_content(&old_files[old_path].hash)?, &content);
+        if score >= RENAME_THRESHOLD && best.as_ref().is_none_or(|(_, best)| score > *best) {
+            best = Some((old_path.clone(), score));
+        }
+    }
+
+    Ok(best)
+}
+
+/// Diff a renamed file between its old and new path
+pub fn diff_rename(
+    old_path: &str,
+    old_entry: &FileEntry,
+    new_path: &str,
+    new_entry: &FileEntry,
+    score: usize,
+) -> Result<FileDiff> {
+    let old_content = read_blob_content(&old_entry.hash)?;
+    let new_content = read_blob_content(&new_entry.hash)?;
+
+    let mut diff = diff_blobs(new_path, Some(&old_content), Some(&new_content), false);
+    diff.old_path = format!("a/{}", old_path);
+    diff.old_mode = Some(old_entry.mode);
+    diff.new_mode = Some(new_entry.mode);
+    diff.similarity = Some(score);
+    Ok(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_similarity() {
+        assert_eq!(similarity(b"a\nb\nc\nd\n", b"a\nb\nc\nd\n"), 100);
+        assert_eq!(similarity(b"a\nb\nc\nd\n", b"a\nb\nx\ny\n"), 50);
+        assert_eq!(similarity(b"a\na\n", b"a\nb\nc\nd\n"), 25);
+        assert_eq!(similarity(b"one\n", b"two\n"), 0);
+        assert_eq!(similarity(b"", b""), 100);
+    }
+}
@@ -1,5 +1,6 @@
 /// Type definitions for diff operations
 
+use crate::diff::binary::BinaryPatch;
 use std::fmt;
 
 /// Type of a diff line
@@ -118,10 +119,17 @@ impl DiffHunk {
     }
 
     /// Get the hunk header (e.g., "@@ -1,3 +1,4 @@")
+    ///
+    /// Like Git, an empty side is given by the line before it, so a new
+    /// file starts with `@@ -0,0 +1,N @@`.
     pub fn header(&self) -> String {
+        let start = |start: usize, count: usize| if count == 0 { start.saturating_sub(1) } else { start };
         format!(
             "@@ -{},{} +{},{} @@",
-            self.old_start, self.old_count, self.new_start, self.new_count
+            start(self.old_start, self.old_count),
+            self.old_count,
+            start(self.new_start, self.new_count),
+            self.new_count
         )
     }
 }
@@ -147,6 +155,18 @@ pub struct FileDiff {
     pub is_binary: bool,
     /// Hunks of changes
     pub hunks: Vec<DiffHunk>,
+    /// Blob hash of the old content (shown in the `index` line)
+    pub old_hash: Option<String>,
+    /// Blob hash of the new content (shown in the `index` line)
+    pub new_hash: Option<String>,
+    /// Encoded binary payload (only for binary files diffed with `--binary`)
+    pub binary_patch: Option<BinaryPatch>,
+    /// File mode of the old side (None if unknown or the file is new)
+    pub old_mode: Option<u32>,
+    /// File mode of the new side (None if unknown or the file is deleted)
+    pub new_mode: Option<u32>,
+    /// Similarity in percent when the file was renamed from `old_path`
+    pub similarity: Option<usize>,
 }
 
 impl FileDiff {
@@ -157,15 +177,44 @@ impl FileDiff {
             new_path,
             is_binary: false,
             hunks: Vec::new(),
+            old_hash: None,
+            new_hash: None,
+            binary_patch: None,
+            old_mode: None,
+            new_mode: None,
+            similarity: None,
         }
     }
 
+    /// Path of the file without the `a/`/`b/` prefix
+    pub fn display_path(&self) -> &str {
+        let path = if self.new_path == "/dev/null" {
+            &self.old_path
+        } else {
+            &self.new_path
+        };
+        path.strip_prefix("a/")
+            .or_else(|| path.strip_prefix("b/"))
+            .unwrap_or(path)
+    }
+
+    /// Path the file was renamed from, without the `a/` prefix
+    pub fn rename_source(&self) -> Option<&str> {
+        self.similarity?;
+        Some(self.old_path.strip_prefix("a/").unwrap_or(&self.old_path))
+    }
+
     /// Check if there are any changes
     pub fn has_changes(&self) -> bool {
         if self.is_binary {
             return true; // Binary files are always considered changed
         }
-        !self.hunks.is_empty()
+        !self.hunks.is_empty() || self.mode_changed()
+    }
+
+    /// Whether both sides exist with different file modes
+    pub fn mode_changed(&self) -> bool {
+        matches!((self.old_mode, self.new_mode), (Some(old), Some(new)) if old != new)
     }
 
     /// Count total additions
@@ -192,6 +241,11 @@ impl fmt::Display for FileDiff {
         writeln!(f, "--- {}", self.old_path)?;
         writeln!(f, "+++ {}", self.new_path)?;
 
+        if let Some(patch) = &self.binary_patch {
+            write!(f, "{}", patch.format())?;
+            return Ok(());
+        }
+
         if self.is_binary {
             writeln!(f, "Binary files differ")?;
             return Ok(());
@@ -230,6 +284,11 @@ mod tests {
             lines: vec![],
         };
         assert_eq!(hunk.header(), "@@ -1,3 +1,4 @@");
+
+        // A new file has an empty old side
+        let mut hunk = DiffHunk::new(1, 1);
+        hunk.add_line(DiffLine::addition(1, "line".to_string()));
+        assert_eq!(hunk.header(), "@@ -0,0 +1,1 @@");
     }
 
     #[test]
@@ -0,0 +1,116 @@
+/// Errors returned by kitcat operations
+///
+/// Every fallible function returns `error::Result`, so callers can match on
+/// what went wrong instead of parsing messages. Filesystem errors convert
+/// into `KitcatError::Io` with `?`. Only `main` turns errors into exit codes.
+
+use std::fmt;
+use std::io;
+
+/// Result of a kitcat operation
+pub type Result<T, E = KitcatError> = std::result::Result<T, E>;
+
+/// What went wrong in a kitcat operation
+#[derive(Debug)]
+pub enum KitcatError {
+    /// The current directory holds no `.kitcat` repository
+    NotARepository,
+    /// No object with this hash
+    ObjectNotFound(String),
+    /// An object, the index or another repository file cannot be parsed
+    CorruptObject(String),
+    /// A name that resolves to no commit
+    UnknownRevision(String),
+    /// A short hash matching several objects
+    AmbiguousRevision(String),
+    /// Unmerged paths, an unfinished merge or changes that do not apply
+    Conflict(String),
+    /// A lock file held by another kitcat process
+    Locked(String),
+    /// Invalid arguments, configuration values or patch input
+    InvalidInput(String),
+    /// A ref, path, setting or operation state that does not exist
+    NotFound(String),
+    /// A ref or file that already exists
+    AlreadyExists(String),
+    /// An external program (hook, editor, tool, gpg) failed
+    CommandFailed(String),
+    /// A missing or bad signature
+    BadSignature(String),
+    /// Reading or writing files failed
+    Io(io::Error),
+}
+
+impl fmt::Display for KitcatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KitcatError::NotARepository => {
+                write!(
+                    f,
+                    "Not a kitcat repository (no .kitcat directory); run 'kitcat init' first"
+                )
+            }
+            KitcatError::ObjectNotFound(hash) => write!(f, "Object {} not found", hash),
+            KitcatError::UnknownRevision(rev) => write!(f, "Unknown revision '{}'", rev),
+            KitcatError::AmbiguousRevision(prefix) => {
+                write!(f, "Short object ID {} is ambiguous", prefix)
+            }
+            KitcatError::Locked(path) => write!(
+                f,
+                "Unable to lock '{}': another kitcat process seems to be running",
+                path
+            ),
+            KitcatError::CorruptObject(message)
+            | KitcatError::Conflict(message)
+            | KitcatError::InvalidInput(message)
+            | KitcatError::NotFound(message)
+            | KitcatError::AlreadyExists(message)
+            | KitcatError::CommandFailed(message)
+            | KitcatError::BadSignature(message) => f.write_str(message),
+            KitcatError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for KitcatError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            KitcatError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for KitcatError {
+    fn from(e: io::Error) -> Self {
+        KitcatError::Io(e)
+    }
+}
+
+impl KitcatError {
+    /// Prefix a filesystem error with what was being accessed
+    pub fn context(self, context: impl fmt::Display) -> Self {
+        match self {
+            KitcatError::Io(e) => {
+                KitcatError::Io(io::Error::new(e.kind(), format!("{}: {}", context, e)))
+            }
+            other => other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_prefixes_io_errors_only() {
+        let err = KitcatError::from(io::Error::new(io::ErrorKind::NotFound, "missing"));
+        let err = err.context("Cannot read 'a.txt'");
+        assert!(matches!(&err, KitcatError::Io(e) if e.kind() == io::ErrorKind::NotFound));
+        assert_eq!(err.to_string(), "Cannot read 'a.txt': missing");
+
+        let err = KitcatError::ObjectNotFound("abc1234".to_string()).context("ignored");
+        assert_eq!(err.to_string(), "Object abc1234 not found");
+    }
+}
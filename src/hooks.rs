@@ -0,0 +1,145 @@
+/// Client-side hooks
+///
+/// Executables in `.kitcat/hooks/` (or the directory set by
+/// `core.hooksPath`) run at fixed points, with the repository root as the
+/// working directory and `KITCAT_DIR` / `KITCAT_INDEX_FILE` set:
+/// - `pre-commit`: before a commit is made; failing aborts it
+/// - `commit-msg <file>`: with the message file, which it may edit;
+///   failing aborts the commit
+/// - `post-commit`: after a commit; its exit status is ignored
+/// - `pre-merge-commit`: before `merge` records a merge commit; failing
+///   aborts the merge
+/// - `post-checkout <old> <new> <flag>`: after `checkout`, with the old and
+///   new HEAD and 1 for a branch checkout or 0 for files; status ignored
+/// - `pre-auto-gc`: before `gc --auto`; failing skips the collection
+///
+/// Hooks that can abort are skipped with `--no-verify`. Hook files that are
+/// not executable are ignored with a hint, like Git does.
+
+use crate::config::Config;
+use crate::error::{KitcatError, Result};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Default hooks directory
+const HOOKS_DIR: &str = ".kitcat/hooks";
+
+/// File `commit-msg` hooks get the message in
+pub const COMMIT_EDITMSG: &str = ".kitcat/COMMIT_EDITMSG";
+
+/// The executable for a hook, if there is one
+fn find_hook(name: &str) -> Result<Option<PathBuf>> {
+    let dir = Config::read()?
+        .core
+        .hooks_path
+        .unwrap_or_else(|| HOOKS_DIR.to_string());
+    let path = PathBuf::from(dir).join(name);
+
+    let Ok(metadata) = fs::metadata(&path) else {
+        return Ok(None);
+    };
+    if !metadata.is_file() || metadata.permissions().mode() & 0o111 == 0 {
+        eprintln!(
+            "hint: The '{}' hook was ignored because it's not set as executable.",
+            path.display()
+        );
+        return Ok(None);
+    }
+
+    Ok(Some(path))
+}
+
+/// Run a hook if it exists, returning whether it succeeded
+///
+/// A missing hook counts as a success.
+pub fn run_hook(name: &str, args: &[&str]) -> Result<bool> {
+    let Some(path) = find_hook(name)? else {
+        return Ok(true);
+    };
+
+    let status = Command::new(&path)
+        .args(args)
+        .env("KITCAT_DIR", ".kitcat")
+        .env("KITCAT_INDEX_FILE", ".kitcat/index")
+        .status()
+        .map_err(|e| KitcatError::CommandFailed(format!("Cannot run hook '{}': {}", path.display(), e)))?;
+
+    Ok(status.success())
+}
+
+/// Run a hook that may veto an operation
+pub fn verify_hook(name: &str, args: &[&str]) -> Result<()> {
+    if run_hook(name, args)? {
+        Ok(())
+    } else {
+        Err(KitcatError::CommandFailed(format!("{} hook failed; use --no-verify to bypass it", name)))
+    }
+}
+
+/// Run the `commit-msg` hook on a message, returning the message it leaves
+pub fn run_commit_msg_hook(message: &str) -> Result<String> {
+    if find_hook("commit-msg")?.is_none() {
+        return Ok(message.to_string());
+    }
+
+    let mut content = message.to_string();
+    if !content.ends_with('\n') {
+        content.push('\n');
+    }
+    fs::write(COMMIT_EDITMSG, content)?;
+    verify_hook("commit-msg", &[COMMIT_EDITMSG])?;
+    Ok(fs::read_to_string(COMMIT_EDITMSG)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::in_temp_repo;
+
+    /// Point `core.hooksPath` at a fresh directory and write a hook script there
+    fn write_hook(name: &str, script: &str, mode: u32) {
+        let mut config = Config::read().unwrap();
+        config.core.hooks_path = Some("custom-hooks".to_string());
+        config.write().unwrap();
+
+        fs::create_dir_all("custom-hooks").unwrap();
+        let path = PathBuf::from("custom-hooks").join(name);
+        fs::write(&path, script).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+    }
+
+    #[test]
+    fn test_find_hook_ignores_non_executable_files() {
+        in_temp_repo("hooks-find", || {
+            write_hook("pre-commit", "#!/bin/sh\nexit 0\n", 0o644);
+            assert_eq!(find_hook("pre-commit").unwrap(), None);
+
+            write_hook("pre-commit", "#!/bin/sh\nexit 0\n", 0o755);
+            assert_eq!(find_hook("pre-commit").unwrap(), Some(PathBuf::from("custom-hooks/pre-commit")));
+            assert_eq!(find_hook("post-commit").unwrap(), None);
+        });
+    }
+
+    #[test]
+    fn test_verify_hook_failure_is_command_failed() {
+        in_temp_repo("hooks-verify", || {
+            write_hook("pre-commit", "#!/bin/sh\nexit 1\n", 0o755);
+            assert!(matches!(verify_hook("pre-commit", &[]), Err(KitcatError::CommandFailed(_))));
+
+            write_hook("pre-commit", "#!/bin/sh\nexit 0\n", 0o755);
+            assert!(verify_hook("pre-commit", &[]).is_ok());
+        });
+    }
+
+    #[test]
+    fn test_commit_msg_hook_edits_message() {
+        in_temp_repo("hooks-commit-msg", || {
+            assert_eq!(run_commit_msg_hook("Unchanged").unwrap(), "Unchanged");
+
+            write_hook("commit-msg", "#!/bin/sh\necho \"TICKET-1: $(cat \"$1\")\" > \"$1\"\n", 0o755);
+            assert_eq!(run_commit_msg_hook("Fix typo").unwrap(), "TICKET-1: Fix typo\n");
+        });
+    }
+}
@@ -1,18 +1,15 @@
+use crate::error::Result;
 use crate::index::write_index::add_file_to_index;
 
 /// Add a file to the index
-pub fn add_to_index(file: String) {
-    // Hash the file content and create the blob object
-    let hash = crate::object::hash_object(file.clone());
+pub fn add_to_index(file: String) -> Result<()> {
+    // Hash the file content (or symlink target) and create the blob object
+    let data = crate::utils::read_worktree_file(&file)
+        .map_err(|e| e.context(format!("Cannot read '{}'", file)))?;
+    let hash = crate::object::write_blob(&data)?;
 
     // Add the file to the index with its hash
-    match add_file_to_index(&file, &hash) {
-        Ok(_) => {
-            println!("Added {} with hash {}", file, hash);
-        }
-        Err(e) => {
-            eprintln!("Error adding file to index: {}", e);
-            std::process::exit(1);
-        }
-    }
+    add_file_to_index(&file, &hash)?;
+    println!("Added {} with hash {}", file, hash);
+    Ok(())
 }
@@ -4,4 +4,6 @@ pub mod write_index;
 
 pub use add_to_index::add_to_index;
 pub use read_index::{read_index, read_index_binary};
-pub use write_index::{add_file_to_index, write_index, write_index as write_index_binary};
+pub use write_index::{
+    add_file_to_index, remove_file_from_index, write_index, write_index as write_index_binary,
+};
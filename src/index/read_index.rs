@@ -1,10 +1,10 @@
+use crate::error::{KitcatError, Result};
 use crate::models::IndexEntry;
 use std::fs;
-use std::io::{self, Read};
 use std::path::Path;
 
 /// Read the index from binary format
-pub fn read_index_binary() -> io::Result<Vec<IndexEntry>> {
+pub fn read_index_binary() -> Result<Vec<IndexEntry>> {
     let index_path = Path::new(".kitcat/index");
 
     if !index_path.exists() {
@@ -16,12 +16,9 @@ pub fn read_index_binary() -> io::Result<Vec<IndexEntry>> {
 }
 
 /// Parse the binary index data
-fn parse_index(data: &[u8]) -> io::Result<Vec<IndexEntry>> {
+fn parse_index(data: &[u8]) -> Result<Vec<IndexEntry>> {
     if data.len() < 12 {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidData,
-            "Index file too small",
-        ));
+        return Err(KitcatError::CorruptObject("Index file too small".to_string()));
     }
 
     // Check header
@@ -33,10 +30,7 @@ fn parse_index(data: &[u8]) -> io::Result<Vec<IndexEntry>> {
     // Read version
     let version = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
     if version != 2 {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidData,
-            format!("Unsupported index version: {}", version),
-        ));
+        return Err(KitcatError::CorruptObject(format!("Unsupported index version: {}", version)));
     }
 
     // Read number of entries
@@ -56,12 +50,9 @@ fn parse_index(data: &[u8]) -> io::Result<Vec<IndexEntry>> {
 }
 
 /// Parse a single index entry
-fn parse_entry(data: &[u8]) -> io::Result<(IndexEntry, usize)> {
+fn parse_entry(data: &[u8]) -> Result<(IndexEntry, usize)> {
     if data.len() < 62 {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidData,
-            "Entry data too small",
-        ));
+        return Err(KitcatError::CorruptObject("Entry data too small".to_string()));
     }
 
     let mut offset = 0;
@@ -109,7 +100,7 @@ fn parse_entry(data: &[u8]) -> io::Result<(IndexEntry, usize)> {
     let path_end = data[offset..]
         .iter()
         .position(|&b| b == 0)
-        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Path not null-terminated"))?;
+        .ok_or_else(|| KitcatError::CorruptObject("Path not null-terminated".to_string()))?;
 
     let path = String::from_utf8_lossy(&data[offset..offset + path_end]).to_string();
     offset += path_end + 1; // +1 for null terminator
@@ -144,7 +135,7 @@ fn bytes_to_hex(bytes: &[u8]) -> String {
 }
 
 /// Legacy text format reader (for backward compatibility)
-fn read_text_format(data: &[u8]) -> io::Result<Vec<IndexEntry>> {
+fn read_text_format(data: &[u8]) -> Result<Vec<IndexEntry>> {
     let text = String::from_utf8_lossy(data);
     let entries = text
         .lines()
@@ -175,6 +166,6 @@ fn read_text_format(data: &[u8]) -> io::Result<Vec<IndexEntry>> {
 }
 
 /// Read the index (wrapper function for compatibility)
-pub fn read_index() -> Vec<IndexEntry> {
-    read_index_binary().unwrap_or_default()
+pub fn read_index() -> Result<Vec<IndexEntry>> {
+    read_index_binary()
 }
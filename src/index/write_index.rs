@@ -1,8 +1,9 @@
+use crate::error::{KitcatError, Result};
 use crate::models::IndexEntry;
 use crate::utils::compute_hash;
 use fs2::FileExt;
 use std::fs::{File, OpenOptions};
-use std::io::{self, Write};
+use std::io::Write;
 use std::path::Path;
 
 /// Write the index to disk in binary DIRC format
@@ -13,21 +14,24 @@ use std::path::Path;
 /// - Number of entries (4 bytes, big-endian)
 /// - Entries (sorted by path)
 /// - SHA-1 checksum of entire index (20 bytes)
-pub fn write_index(entries: &[IndexEntry]) -> io::Result<()> {
+pub fn write_index(entries: &[IndexEntry]) -> Result<()> {
     let index_path = Path::new(".kitcat/index");
 
     // Create a temporary file first for atomic write
     let temp_path = Path::new(".kitcat/index.lock");
 
-    // Open the lock file with exclusive access
+    // Open the lock file without truncating it: another process may hold it
     let mut file = OpenOptions::new()
         .write(true)
         .create(true)
-        .truncate(true)
+        .truncate(false)
         .open(temp_path)?;
 
-    // Lock the file for exclusive access
-    file.lock_exclusive()?;
+    // Lock the file for exclusive access, failing if another process has it
+    if file.try_lock_exclusive().is_err() {
+        return Err(KitcatError::Locked(temp_path.display().to_string()));
+    }
+    file.set_len(0)?;
 
     let mut buffer = Vec::new();
 
@@ -40,9 +44,9 @@ pub fn write_index(entries: &[IndexEntry]) -> io::Result<()> {
     // Write number of entries
     buffer.extend_from_slice(&(entries.len() as u32).to_be_bytes());
 
-    // Sort entries by path (Git requirement)
+    // Sort entries by path, then stage (Git requirement)
     let mut sorted_entries = entries.to_vec();
-    sorted_entries.sort_by(|a, b| a.path.cmp(&b.path));
+    sorted_entries.sort_by(|a, b| a.path.cmp(&b.path).then(a.stage().cmp(&b.stage())));
 
     // Write each entry
     for entry in &sorted_entries {
@@ -69,7 +73,7 @@ pub fn write_index(entries: &[IndexEntry]) -> io::Result<()> {
 }
 
 /// Write a single index entry to the buffer
-fn write_entry(buffer: &mut Vec<u8>, entry: &IndexEntry) -> io::Result<()> {
+fn write_entry(buffer: &mut Vec<u8>, entry: &IndexEntry) -> Result<()> {
     // ctime seconds
     buffer.extend_from_slice(&entry.ctime_sec.to_be_bytes());
     // ctime nanoseconds
@@ -127,7 +131,10 @@ fn hex_to_bytes(hex: &str) -> Vec<u8> {
 }
 
 /// Add or update a single file in the index
-pub fn add_file_to_index(file_path: &str, hash: &str) -> io::Result<()> {
+///
+/// Adding a conflicted path replaces its stage 1/2/3 entries with a single
+/// stage 0 entry, marking the conflict as resolved.
+pub fn add_file_to_index(file_path: &str, hash: &str) -> Result<()> {
     use crate::index::read_index::read_index_binary;
 
     // Read existing index
@@ -136,8 +143,8 @@ pub fn add_file_to_index(file_path: &str, hash: &str) -> io::Result<()> {
         Err(_) => Vec::new(), // No existing index
     };
 
-    // Get file metadata
-    let metadata = std::fs::metadata(file_path)?;
+    // Get file metadata (of the link itself for symlinks)
+    let metadata = std::fs::symlink_metadata(file_path)?;
 
     // Normalize path to be relative to repository root
     let repo_root = std::env::current_dir()?;
@@ -152,7 +159,7 @@ pub fn add_file_to_index(file_path: &str, hash: &str) -> io::Result<()> {
     // Create new entry
     let new_entry = IndexEntry::from_file(rel_path.clone(), hash.to_string(), &metadata);
 
-    // Remove existing entry for this path if it exists
+    // Remove existing entries for this path, collapsing any conflict stages to stage 0
     entries.retain(|e| e.path != rel_path);
 
     // Add new entry
@@ -163,3 +170,12 @@ pub fn add_file_to_index(file_path: &str, hash: &str) -> io::Result<()> {
 
     Ok(())
 }
+
+/// Remove a file from the index
+pub fn remove_file_from_index(file_path: &str) -> Result<()> {
+    use crate::index::read_index::read_index_binary;
+
+    let mut entries = read_index_binary().unwrap_or_default();
+    entries.retain(|e| e.path != file_path);
+    write_index(&entries)
+}
@@ -1,15 +1,25 @@
 mod commands;
+mod attributes;
 mod config;
+mod date;
 mod diff;
+mod error;
+mod hooks;
 mod index;
 mod merge;
 mod models;
 mod object;
 mod repo;
+mod signing;
 mod utils;
 
 use clap::{Parser, Subcommand};
 use commands::*;
+use merge::{MergeStrategy, StrategyOptions};
+use repo::revision::{RevWalkOptions, SortOrder};
+use signing::SignRequest;
+use error::{KitcatError, Result};
+use std::io::IsTerminal;
 
 /// Command line interface for KitCat VCS
 #[derive(Parser)]
@@ -64,9 +74,51 @@ enum Commands {
     },
     /// Create a commit
     Commit {
-        /// Commit message
+        /// Commit message (repeat for more paragraphs); opens the editor if omitted
         #[arg(short = 'm', long = "message")]
-        message: String,
+        message: Vec<String>,
+        /// Read the commit message from a file ("-" for stdin)
+        #[arg(short = 'F', long = "file", conflicts_with = "message")]
+        file: Option<String>,
+        /// Edit the message even when given with -m, -F or --amend
+        #[arg(short = 'e', long = "edit")]
+        edit: bool,
+        /// Reuse the amended commit's message without editing it
+        #[arg(long = "no-edit", conflicts_with = "edit")]
+        no_edit: bool,
+        /// Stage modified and deleted tracked files first
+        #[arg(short = 'a', long = "all")]
+        all: bool,
+        /// Replace the current commit, keeping its parents and author
+        #[arg(long)]
+        amend: bool,
+        /// Allow a commit that changes nothing
+        #[arg(long = "allow-empty")]
+        allow_empty: bool,
+        /// Make a "fixup!" commit to be folded into <commit>
+        #[arg(long, value_name = "COMMIT", conflicts_with_all = ["squash", "amend"])]
+        fixup: Option<String>,
+        /// Make a "squash!" commit to be folded into <commit>
+        #[arg(long, value_name = "COMMIT", conflicts_with = "amend")]
+        squash: Option<String>,
+        /// Message cleanup: strip, whitespace, verbatim, scissors or default
+        #[arg(long)]
+        cleanup: Option<String>,
+        /// Override the author ("Name <email>")
+        #[arg(long)]
+        author: Option<String>,
+        /// Override the author date (e.g. "2024-01-15T10:30:00+05:30")
+        #[arg(long)]
+        date: Option<String>,
+        /// Skip the pre-commit and commit-msg hooks
+        #[arg(short = 'n', long = "no-verify")]
+        no_verify: bool,
+        /// Sign the commit, with the given key or user.signingKey
+        #[arg(short = 'S', long = "gpg-sign", value_name = "KEYID", num_args = 0..=1, require_equals = true)]
+        gpg_sign: Option<Option<String>>,
+        /// Do not sign, even with commit.gpgSign set
+        #[arg(long = "no-gpg-sign", conflicts_with = "gpg_sign")]
+        no_gpg_sign: bool,
     },
     /// Show commit details
     ShowCommit {
@@ -91,14 +143,146 @@ enum Commands {
         #[arg(short = 'D', long = "force-delete")]
         force_delete: bool,
     },
+    /// List, create or delete tags
+    Tag {
+        /// Tag name (lists tags if omitted)
+        name: Option<String>,
+        /// Commit to tag (defaults to HEAD)
+        target: Option<String>,
+        /// Make an annotated tag
+        #[arg(short = 'a', long = "annotate")]
+        annotate: bool,
+        /// Tag message (makes an annotated tag)
+        #[arg(short = 'm', long = "message")]
+        message: Option<String>,
+        /// Make a signed tag with the default key
+        #[arg(short = 's', long = "sign")]
+        sign: bool,
+        /// Make a signed tag with this key
+        #[arg(short = 'u', long = "local-user", value_name = "KEYID")]
+        local_user: Option<String>,
+        /// Do not sign, even with tag.gpgSign set
+        #[arg(long = "no-sign", conflicts_with_all = ["sign", "local_user"])]
+        no_sign: bool,
+        /// Delete the tag
+        #[arg(short = 'd', long = "delete")]
+        delete: bool,
+        /// Replace an existing tag
+        #[arg(short = 'f', long = "force")]
+        force: bool,
+    },
+    /// Check the signatures of commits
+    VerifyCommit {
+        /// Commits to check
+        #[arg(required = true)]
+        commits: Vec<String>,
+    },
+    /// Check the signatures of annotated tags
+    VerifyTag {
+        /// Tags to check
+        #[arg(required = true)]
+        tags: Vec<String>,
+    },
     /// Show commit history
     Log {
-        /// Show in oneline format
-        #[arg(long = "oneline")]
+        /// Revisions to show: `B`, `^A`, `A..B` or `A...B` (defaults to HEAD)
+        revisions: Vec<String>,
+        /// Only commits changing these paths (after `--`)
+        #[arg(last = true)]
+        paths: Vec<String>,
+        /// Continue the history of a single file across renames
+        #[arg(long = "follow")]
+        follow: bool,
+        /// Trace the history of a line range: <start>,<end>:<file> (repeatable)
+        #[arg(short = 'L')]
+        line_range: Vec<String>,
+        /// Show the patch of each commit
+        #[arg(short = 'p', long = "patch")]
+        patch: bool,
+        /// Show a diffstat for each commit
+        #[arg(long = "stat")]
+        stat: bool,
+        /// Show the names and status of changed files
+        #[arg(long = "name-status")]
+        name_status: bool,
+        /// Verify signed commits and show the result
+        #[arg(long = "show-signature")]
+        show_signature: bool,
+        /// Show in oneline format (same as --pretty=oneline --abbrev-commit)
+        #[arg(long = "oneline", conflicts_with = "format")]
         oneline: bool,
+        /// Pretty format: oneline, short, medium, full, fuller, raw, reference or format:<string>
+        #[arg(long = "format", visible_alias = "pretty")]
+        format: Option<String>,
+        /// Date format: default, relative, iso, iso-strict, rfc, short, unix, raw or local
+        #[arg(long = "date")]
+        date: Option<String>,
+        /// Show branch and tag names next to commits
+        #[arg(long = "decorate")]
+        decorate: bool,
+        /// Show abbreviated commit hashes
+        #[arg(long = "abbrev-commit")]
+        abbrev_commit: bool,
+        /// Draw an ASCII graph of branches and merges next to the commits
+        #[arg(long = "graph")]
+        graph: bool,
+        /// Disable colored output
+        #[arg(long = "no-color")]
+        no_color: bool,
         /// Maximum number of commits to show
         #[arg(short = 'n', long = "max-count")]
         max_count: Option<usize>,
+        /// Start from HEAD and every ref
+        #[arg(long = "all")]
+        all: bool,
+        /// Start from every branch
+        #[arg(long = "branches")]
+        branches: bool,
+        /// Show no parent before all its children, otherwise by commit date
+        #[arg(long = "date-order", conflicts_with = "topo_order")]
+        date_order: bool,
+        /// Show no parent before all its children, without interleaving lines of history
+        #[arg(long = "topo-order")]
+        topo_order: bool,
+        /// Show the selected commits oldest first
+        #[arg(long = "reverse")]
+        reverse: bool,
+        /// Follow only the first parent of merge commits
+        #[arg(long = "first-parent")]
+        first_parent: bool,
+        /// Show only merge commits
+        #[arg(long = "merges", conflicts_with = "no_merges")]
+        merges: bool,
+        /// Hide merge commits
+        #[arg(long = "no-merges")]
+        no_merges: bool,
+        /// Only commits whose author matches this regex (repeatable)
+        #[arg(long = "author")]
+        author: Vec<String>,
+        /// Only commits whose committer matches this regex (repeatable)
+        #[arg(long = "committer")]
+        committer: Vec<String>,
+        /// Only commits whose message matches this regex (repeatable)
+        #[arg(long = "grep")]
+        grep: Vec<String>,
+        /// Match --author, --committer and --grep patterns case-insensitively
+        #[arg(short = 'i', long = "regexp-ignore-case")]
+        ignore_case: bool,
+        /// Require all --grep patterns to match instead of any
+        #[arg(long = "all-match")]
+        all_match: bool,
+        /// Only commits more recent than a date, e.g. "2 weeks ago" or 2024-01-15
+        #[arg(long = "since", visible_alias = "after")]
+        since: Option<String>,
+        /// Only commits older than a date
+        #[arg(long = "until", visible_alias = "before")]
+        until: Option<String>,
+        /// Only commits that change the number of occurrences of a string
+        #[arg(short = 'S')]
+        pickaxe: Option<String>,
+        /// Only commits with an added or removed line matching a regex
+        #[arg(short = 'G')]
+        pickaxe_regex: Option<String>,
     },
     /// Show working tree status
     Status,
@@ -112,6 +296,9 @@ enum Commands {
         /// Restore file from index (use with -- before filename)
         #[arg(long = "file")]
         file: bool,
+        /// Recreate the conflicted file from the index in this style: merge, diff3 or zdiff3
+        #[arg(long = "conflict")]
+        conflict: Option<String>,
     },
     /// Show changes between commits, commit and working tree, etc
     Diff {
@@ -128,11 +315,125 @@ enum Commands {
         /// Disable color output
         #[arg(long = "no-color")]
         no_color: bool,
+        /// Output binary files as applicable binary patches (implies --no-color)
+        #[arg(long = "binary")]
+        binary: bool,
+        /// Do not run external diff programs (diff.external, diff drivers)
+        #[arg(long = "no-ext-diff")]
+        no_ext_diff: bool,
+    },
+    /// Show changes using an external diff tool
+    Difftool {
+        /// Revisions to compare (none: index vs working tree, one: commit vs working tree)
+        revisions: Vec<String>,
+        /// Diff tool to use (overrides diff.tool)
+        #[arg(short = 't', long = "tool")]
+        tool: Option<String>,
+        /// Compare all changed files at once as two directories
+        #[arg(short = 'd', long = "dir-diff")]
+        dir_diff: bool,
+    },
+    /// Apply a patch to files and/or to the index
+    Apply {
+        /// Patch file to apply (reads standard input if omitted or "-")
+        patch: Option<String>,
+        /// Check whether the patch applies without modifying anything
+        #[arg(long = "check")]
+        check: bool,
+        /// Apply the patch to the index as well as the working tree
+        #[arg(long = "index")]
+        index: bool,
+        /// Fall back to a three-way merge if the patch does not apply cleanly
+        #[arg(short = '3', long = "3way")]
+        three_way: bool,
+        /// Apply the patch in reverse
+        #[arg(short = 'R', long = "reverse")]
+        reverse: bool,
+    },
+    /// Prepare each commit in a range as a patch email
+    FormatPatch {
+        /// Commit range (`A..B`, or `A` for `A..HEAD`)
+        range: String,
+        /// Write the patch files to this directory
+        #[arg(short = 'o', long = "output-directory")]
+        output_dir: Option<String>,
+        /// Also write a cover letter describing the series
+        #[arg(long = "cover-letter")]
+        cover_letter: bool,
+    },
+    /// Apply a series of patches from mailbox files as commits
+    Am {
+        /// Mbox files to apply (reads standard input if omitted)
+        files: Vec<String>,
+        /// Commit the resolved patch and continue with the rest
+        #[arg(long = "continue")]
+        r#continue: bool,
+        /// Skip the current patch
+        #[arg(long = "skip")]
+        skip: bool,
+        /// Stop applying patches and restore the original branch
+        #[arg(long = "abort")]
+        abort: bool,
+        /// Fall back to a three-way merge if a patch does not apply cleanly
+        #[arg(short = '3', long = "3way")]
+        three_way: bool,
+    },
+    /// Show commits, tags, trees and blobs
+    Show {
+        /// Objects to show, e.g. `HEAD~2`, `v1.0` or `HEAD:src/main.rs` (defaults to HEAD)
+        objects: Vec<String>,
+        /// Show a diffstat instead of the patch
+        #[arg(long = "stat")]
+        stat: bool,
+        /// Show only the names of changed files
+        #[arg(long = "name-only")]
+        name_only: bool,
+        /// Pretty format: oneline, short, medium, full or format:<string>
+        #[arg(long = "format", visible_alias = "pretty")]
+        format: Option<String>,
+        /// Suppress diff output
+        #[arg(short = 's', long = "no-patch")]
+        no_patch: bool,
+        /// Disable colored output
+        #[arg(long = "no-color")]
+        no_color: bool,
+    },
+    /// Show what revision and author last modified each line of a file
+    Blame {
+        /// `[<rev>] <file>`, or the revision when the file follows `--`
+        args: Vec<String>,
+        /// File to blame (after `--`)
+        #[arg(last = true)]
+        path: Vec<String>,
+        /// Only blame lines <start>,<end>, <start>,+<count> or <start> (repeatable)
+        #[arg(short = 'L')]
+        ranges: Vec<String>,
+        /// Machine-readable output
+        #[arg(long = "porcelain", conflicts_with = "line_porcelain")]
+        porcelain: bool,
+        /// Machine-readable output with the commit headers on every line
+        #[arg(long = "line-porcelain")]
+        line_porcelain: bool,
+        /// Ignore whitespace when comparing lines
+        #[arg(short = 'w')]
+        ignore_whitespace: bool,
+        /// Detect lines moved within the file
+        #[arg(short = 'M')]
+        detect_moves: bool,
+        /// Detect lines copied from files changed in the same commit (twice: from any file)
+        #[arg(short = 'C', action = clap::ArgAction::Count)]
+        detect_copies: u8,
+        /// Pass the changes of a revision on to its parent (repeatable)
+        #[arg(long = "ignore-rev")]
+        ignore_rev: Vec<String>,
+        /// Ignore the revisions listed in a file (repeatable)
+        #[arg(long = "ignore-revs-file")]
+        ignore_revs_file: Vec<String>,
     },
     /// Join two or more development histories together
     Merge {
-        /// Branch or commit to merge
-        target: Option<String>,
+        /// Branches or commits to merge (several make an octopus merge)
+        targets: Vec<String>,
         /// Abort the current merge
         #[arg(long = "abort")]
         abort: bool,
@@ -148,6 +449,53 @@ enum Commands {
         /// Commit message for merge commit
         #[arg(short = 'm', long = "message")]
         message: Option<String>,
+        /// Merge strategy: ort, recursive, resolve, ours, theirs or octopus
+        #[arg(short = 's', long = "strategy")]
+        strategy: Option<String>,
+        /// Strategy option: ours, theirs, ignore-space-change, diff-algorithm=<name>
+        #[arg(short = 'X', long = "strategy-option")]
+        strategy_options: Vec<String>,
+        /// Stash local changes before the merge and reapply them afterwards
+        #[arg(long = "autostash")]
+        autostash: bool,
+        /// Conflict style: merge, diff3 or zdiff3 (overrides merge.conflictStyle)
+        #[arg(long = "conflict")]
+        conflict: Option<String>,
+        /// Skip the pre-merge-commit and commit-msg hooks
+        #[arg(long = "no-verify")]
+        no_verify: bool,
+    },
+    /// Run a three-way merge tool on conflicted files
+    Mergetool {
+        /// Only resolve these paths
+        paths: Vec<String>,
+        /// Merge tool to use (overrides merge.tool)
+        #[arg(short = 't', long = "tool")]
+        tool: Option<String>,
+    },
+    /// Reuse recorded resolutions of conflicted merges
+    Rerere {
+        /// status, forget or clear (records resolutions if omitted)
+        subcommand: Option<String>,
+        /// Paths for forget
+        paths: Vec<String>,
+    },
+    /// Find as good common ancestors as possible for a merge
+    MergeBase {
+        /// Commits to find the merge base of
+        commits: Vec<String>,
+        /// Output all merge bases instead of just one
+        #[arg(short = 'a', long = "all")]
+        all: bool,
+        /// Check if the first commit is an ancestor of the second (exit status 0 or 1)
+        #[arg(long = "is-ancestor")]
+        is_ancestor: bool,
+        /// Compute the best common ancestor of all given commits
+        #[arg(long = "octopus")]
+        octopus: bool,
+        /// Find where a commit forked from a ref: <ref> [<commit>]
+        #[arg(long = "fork-point")]
+        fork_point: bool,
     },
     /// Cleanup unnecessary files and optimize the local repository
     Gc {
@@ -160,42 +508,118 @@ enum Commands {
         /// Dry run - show what would be deleted without deleting
         #[arg(long = "dry-run")]
         dry_run: bool,
+        /// Only collect when there are many loose objects (runs the pre-auto-gc hook)
+        #[arg(long = "auto")]
+        auto: bool,
+    },
+    /// Write or verify the commit-graph file
+    CommitGraph {
+        /// write or verify
+        action: String,
     },
 }
 
 fn main() {
     let cli = Cli::parse();
 
-    match cli.command {
-        Commands::Init => init(),
+    if let Err(e) = run(cli.command) {
+        eprintln!("Error: {}", e);
+        std::process::exit(exit_code(&e));
+    }
+}
+
+/// Exit status for an error, stable across releases
+///
+/// Argument errors found by clap exit with 2 as well.
+fn exit_code(error: &KitcatError) -> i32 {
+    match error {
+        KitcatError::Io(_)
+        | KitcatError::NotFound(_)
+        | KitcatError::AlreadyExists(_)
+        | KitcatError::CommandFailed(_) => 1,
+        KitcatError::InvalidInput(_) => 2,
+        KitcatError::Conflict(_) => 3,
+        KitcatError::Locked(_) => 4,
+        KitcatError::UnknownRevision(_) | KitcatError::AmbiguousRevision(_) => 5,
+        KitcatError::ObjectNotFound(_) => 6,
+        KitcatError::CorruptObject(_) => 7,
+        KitcatError::BadSignature(_) => 8,
+        KitcatError::NotARepository => 128,
+    }
+}
+
+/// Whether to colour output: not with `--no-color` or when stdout is not a terminal
+fn use_color(no_color: bool) -> bool {
+    !no_color && std::io::stdout().is_terminal()
+}
+
+/// Run a command against the repository in the current directory
+fn run(command: Commands) -> Result<()> {
+    if !matches!(command, Commands::Init) {
+        repo::ensure_repository()?;
+    }
+
+    match command {
+        Commands::Init => init()?,
         Commands::HashObject { file } => {
-            let hash = hash_file(file);
+            let hash = hash_file(file)?;
             println!("{}", hash);
         }
-        Commands::ReadFile { pretty, hash } => read_file(hash, pretty),
-        Commands::Add { file } => add_to_index(file),
+        Commands::ReadFile { pretty, hash } => read_file(hash, pretty)?,
+        Commands::Add { file } => add_to_index(file)?,
         Commands::ReadIndex => {
-            let index = read_index();
-            for entry in index {
+            for entry in read_index()? {
                 println!("{} {}", entry.hash, entry.path);
             }
         }
-        Commands::WriteHead { value } => write_head(&value),
-        Commands::ReadHead => {
-            let head = read_head();
-            println!("{}", head);
-        }
+        Commands::WriteHead { value } => write_head(&value)?,
+        Commands::ReadHead => println!("{}", read_head()?),
         Commands::WriteTree => {
-            write_tree();
+            write_tree()?;
+        }
+        Commands::ListTree { hash } => list_tree(hash)?,
+        Commands::Commit {
+            message,
+            file,
+            edit,
+            no_edit,
+            all,
+            amend,
+            allow_empty,
+            fixup,
+            squash,
+            cleanup,
+            author,
+            date,
+            no_verify,
+            gpg_sign,
+            no_gpg_sign,
+        } => {
+            let options = CommitOptions {
+                messages: message,
+                file,
+                edit,
+                no_edit,
+                all,
+                amend,
+                allow_empty,
+                fixup,
+                squash,
+                cleanup,
+                author,
+                date,
+                no_verify,
+                sign: SignRequest::from_flags(gpg_sign, no_gpg_sign),
+            };
+
+            commit(options)?;
         }
-        Commands::ListTree { hash } => list_tree(hash),
-        Commands::Commit { message } => commit(message),
-        Commands::ShowCommit { hash } => show_commit_cmd(hash),
+        Commands::ShowCommit { hash } => show_commit_cmd(hash)?,
         Commands::Config { key, value } => {
             if let Some(val) = value {
-                set_config_cmd(key, val);
+                set_config_cmd(key, val)?;
             } else {
-                get_config_cmd(key);
+                get_config_cmd(key)?;
             }
         }
         Commands::Branch {
@@ -206,72 +630,165 @@ fn main() {
             if delete || force_delete {
                 // Delete branch
                 if let Some(branch_name) = name {
-                    if let Err(e) = delete_branch(&branch_name, force_delete) {
-                        eprintln!("Error: {}", e);
-                        std::process::exit(1);
-                    }
+                    delete_branch(&branch_name, force_delete)?;
                 } else {
-                    eprintln!("Error: branch name required for deletion");
-                    std::process::exit(1);
+                    return Err(KitcatError::InvalidInput(
+                        "branch name required for deletion".to_string(),
+                    ));
                 }
             } else if let Some(branch_name) = name {
                 // Create or switch branch
                 // Try to switch first, if it exists
                 if std::path::Path::new(&format!(".kitcat/refs/heads/{}", branch_name)).exists() {
-                    if let Err(e) = switch_branch(&branch_name) {
-                        eprintln!("Error: {}", e);
-                        std::process::exit(1);
-                    }
+                    switch_branch(&branch_name)?;
                 } else {
                     // Create new branch
-                    if let Err(e) = create_branch(&branch_name) {
-                        eprintln!("Error: {}", e);
-                        std::process::exit(1);
-                    }
+                    create_branch(&branch_name)?;
                 }
             } else {
                 // List branches
-                if let Err(e) = list_branches() {
-                    eprintln!("Error: {}", e);
-                    std::process::exit(1);
-                }
+                list_branches()?;
             }
         }
-        Commands::Log { oneline, max_count } => {
+        Commands::Log {
+            revisions,
+            paths,
+            follow,
+            line_range,
+            patch,
+            stat,
+            name_status,
+            show_signature,
+            oneline,
+            format,
+            date,
+            decorate,
+            abbrev_commit,
+            graph,
+            no_color,
+            max_count,
+            all,
+            branches,
+            date_order,
+            topo_order,
+            reverse,
+            first_parent,
+            merges,
+            no_merges,
+            author,
+            committer,
+            grep,
+            ignore_case,
+            all_match,
+            since,
+            until,
+            pickaxe,
+            pickaxe_regex,
+        } => {
             let format = if oneline {
-                LogFormat::Oneline
+                Some("oneline".to_string())
+            } else {
+                format
+            };
+            let order = if topo_order {
+                SortOrder::Topo
+            } else if date_order {
+                SortOrder::Date
             } else {
-                LogFormat::Full
+                SortOrder::Default
             };
 
-            if let Err(e) = log(format, max_count) {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
-            }
+            let options = LogOptions {
+                format,
+                date,
+                decorate,
+                abbrev_commit: abbrev_commit || oneline,
+                graph,
+                use_color: use_color(no_color),
+                max_count,
+                walk: RevWalkOptions {
+                    revisions,
+                    all,
+                    branches,
+                    order,
+                    first_parent,
+                    merges,
+                    no_merges,
+                },
+                filter: LogFilterOptions {
+                    authors: author,
+                    committers: committer,
+                    grep,
+                    ignore_case,
+                    all_match,
+                    since,
+                    until,
+                    pickaxe,
+                    pickaxe_regex,
+                },
+                reverse,
+                paths,
+                follow,
+                line_ranges: line_range,
+                patch,
+                stat,
+                name_status,
+                show_signature,
+            };
+
+            log(options)?;
+        }
+        Commands::Tag {
+            name,
+            target,
+            annotate,
+            message,
+            sign,
+            local_user,
+            no_sign,
+            delete,
+            force,
+        } => {
+            let sign = match local_user {
+                Some(key) => SignRequest::Key(Some(key)),
+                None => SignRequest::from_flags(sign.then_some(None), no_sign),
+            };
+            let options = TagOptions {
+                name,
+                target,
+                annotate,
+                message,
+                sign,
+                delete,
+                force,
+            };
+
+            tag(options)?;
+        }
+        Commands::VerifyCommit { commits } => {
+            verify_commit(&commits)?;
+        }
+        Commands::VerifyTag { tags } => {
+            verify_tag(&tags)?;
         }
         Commands::Status => {
-            if let Err(e) = status() {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
-            }
+            status()?;
         }
         Commands::Checkout {
             target,
             force,
             file,
+            conflict,
         } => {
-            if file {
+            if let Some(style) = conflict {
+                // Recreate conflict markers for an unmerged file
+                checkout_conflict(&target, &style)?;
+            } else if file {
                 // Restore file from index
-                if let Err(e) = checkout_file(&target) {
-                    eprintln!("Error: {}", e);
-                    std::process::exit(1);
-                }
+                checkout_file(&target)?;
             } else {
                 // Checkout branch or commit
-                if let Err(e) = checkout(&target, force) {
-                    eprintln!("Error: {}", e);
-                    std::process::exit(1);
-                }
+                checkout(&target, force)?;
             }
         }
         Commands::Diff {
@@ -280,6 +797,8 @@ fn main() {
             commit2,
             stat,
             no_color,
+            binary,
+            no_ext_diff,
         } => {
             let mode = if cached {
                 DiffMode::IndexVsHead
@@ -296,39 +815,202 @@ fn main() {
                 commit1: commit1.clone(),
                 commit2: commit2.clone(),
                 paths: vec![],
-                use_color: !no_color,
+                use_color: use_color(no_color || binary),
                 show_stats: stat,
+                binary,
+                no_ext_diff,
             };
 
-            if let Err(e) = diff(options) {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
+            diff(options)?;
+        }
+        Commands::Difftool {
+            revisions,
+            tool,
+            dir_diff,
+        } => {
+            let options = DifftoolOptions {
+                revisions,
+                tool,
+                dir_diff,
+            };
+
+            difftool(options)?;
+        }
+        Commands::Apply {
+            patch,
+            check,
+            index,
+            three_way,
+            reverse,
+        } => {
+            let options = ApplyOptions {
+                patch,
+                check,
+                index,
+                three_way,
+                reverse,
+            };
+
+            apply(options)?;
+        }
+        Commands::FormatPatch {
+            range,
+            output_dir,
+            cover_letter,
+        } => {
+            let options = FormatPatchOptions {
+                range,
+                output_dir,
+                cover_letter,
+            };
+
+            format_patch(options)?;
+        }
+        Commands::Am {
+            files,
+            r#continue,
+            skip,
+            abort,
+            three_way,
+        } => {
+            let options = AmOptions {
+                mbox_files: files,
+                r#continue,
+                skip,
+                abort,
+                three_way,
+            };
+
+            am(options)?;
+        }
+        Commands::Show {
+            objects,
+            stat,
+            name_only,
+            format,
+            no_patch,
+            no_color,
+        } => {
+            let options = ShowOptions {
+                objects,
+                stat,
+                name_only,
+                format,
+                no_patch,
+                use_color: use_color(no_color),
+            };
+
+            show(options)?;
+        }
+        Commands::Blame {
+            mut args,
+            path,
+            ranges,
+            porcelain,
+            line_porcelain,
+            ignore_whitespace,
+            detect_moves,
+            detect_copies,
+            ignore_rev,
+            ignore_revs_file,
+        } => {
+            let path = match path.into_iter().next() {
+                Some(path) => path,
+                None => args
+                    .pop()
+                    .ok_or_else(|| KitcatError::InvalidInput("no file to blame given".to_string()))?,
+            };
+            if args.len() > 1 {
+                return Err(KitcatError::InvalidInput("blame takes a single revision".to_string()));
             }
+
+            let options = BlameOptions {
+                revision: args.pop(),
+                path,
+                ranges,
+                porcelain,
+                line_porcelain,
+                ignore_whitespace,
+                detect_moves,
+                detect_copies,
+                ignore_revs: ignore_rev,
+                ignore_revs_files: ignore_revs_file,
+            };
+
+            blame(options)?;
         }
         Commands::Merge {
-            target,
+            targets,
             abort,
             r#continue,
             no_ff,
             ff_only,
             message,
+            strategy,
+            strategy_options,
+            autostash,
+            conflict,
+            no_verify,
         } => {
-            if !abort && !r#continue && target.is_none() {
-                eprintln!("Error: branch or commit to merge is required");
-                std::process::exit(1);
+            if !abort && !r#continue && targets.is_empty() {
+                return Err(KitcatError::InvalidInput(
+                    "branch or commit to merge is required".to_string(),
+                ));
+            }
+
+            let strategy = strategy
+                .as_deref()
+                .map(MergeStrategy::parse)
+                .transpose()?
+                .unwrap_or_default();
+            let mut parsed_options = StrategyOptions::default();
+            for option in &strategy_options {
+                parsed_options.apply(option)?;
             }
 
             let options = MergeOptions {
-                target: target.unwrap_or_default(),
+                targets,
                 abort,
                 r#continue,
                 no_ff,
                 ff_only,
                 message,
+                strategy,
+                strategy_options: parsed_options,
+                autostash,
+                conflict_style: conflict,
+                no_verify,
             };
 
-            if let Err(e) = merge(options) {
-                eprintln!("Error: {}", e);
+            merge(options)?;
+        }
+        Commands::Mergetool { paths, tool } => {
+            let options = MergetoolOptions { tool, paths };
+
+            mergetool(options)?;
+        }
+        Commands::Rerere { subcommand, paths } => {
+            let options = RerereOptions { subcommand, paths };
+
+            rerere(options)?;
+        }
+        Commands::MergeBase {
+            commits,
+            all,
+            is_ancestor,
+            octopus,
+            fork_point,
+        } => {
+            let options = MergeBaseOptions {
+                commits,
+                all,
+                is_ancestor,
+                octopus,
+                fork_point,
+            };
+
+            // --is-ancestor answers with the exit status alone
+            if !merge_base(options)? {
                 std::process::exit(1);
             }
         }
@@ -336,17 +1018,21 @@ fn main() {
             aggressive,
             prune_days,
             dry_run,
+            auto,
         } => {
             let options = GcOptions {
                 aggressive,
                 prune_days,
                 dry_run,
+                auto,
             };
 
-            if let Err(e) = gc(options) {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
-            }
+            gc(options)?;
+        }
+        Commands::CommitGraph { action } => {
+            commit_graph(&action)?;
         }
     }
+
+    Ok(())
 }
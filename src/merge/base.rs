@@ -1,138 +1,306 @@
 /// Merge base detection algorithm
 ///
-/// Finds the common ancestor of two commits using a graph traversal approach
+/// Finds the best common ancestors of commits with Git's "paint down" walk:
+/// commits are visited newest first from both sides, each marked with the
+/// side(s) it is reachable from. A commit reachable from both sides is a
+/// merge base candidate and everything below it is marked stale, so the
+/// walk stops as soon as only stale commits remain instead of listing the
+/// full history of both commits.
+///
+/// Commit data comes from the commit-graph when available. Its generation
+/// numbers order the walk and let it stop early: a commit can only reach
+/// commits with a smaller generation.
 
-use std::collections::{HashMap, HashSet, VecDeque};
-use std::io;
+use crate::error::Result;
+use crate::object::commit_graph;
+use crate::object::read_commit;
+use std::collections::{BinaryHeap, HashMap, HashSet};
 
-/// Find the merge base (common ancestor) of two commits
-///
-/// Uses a breadth-first search to find the lowest common ancestor
-pub fn find_merge_base(commit1: &str, commit2: &str) -> io::Result<Option<String>> {
-    if commit1 == commit2 {
-        return Ok(Some(commit1.to_string()));
-    }
+/// Reachable from the first commit
+const PARENT1: u8 = 1;
+/// Reachable from one of the other commits
+const PARENT2: u8 = 2;
+/// Below a merge base candidate
+const STALE: u8 = 4;
+/// Already collected as a candidate
+const RESULT: u8 = 8;
+
+/// Generation of commits missing from the commit-graph
+const GENERATION_INFINITY: u32 = u32::MAX;
 
-    // Build ancestor chains for both commits
-    let ancestors1 = get_all_ancestors(commit1)?;
-    let ancestors2 = get_all_ancestors(commit2)?;
+/// Walk queue entry: generation, committer time, hash (largest pops first)
+type QueueEntry = (u32, i64, String);
 
-    // Find common ancestors
-    let common: HashSet<_> = ancestors1.intersection(&ancestors2).collect();
+/// A commit as seen by the walker
+#[derive(Debug, Clone)]
+struct WalkCommit {
+    time: i64,
+    generation: u32,
+    parents: Vec<String>,
+}
 
-    if common.is_empty() {
-        return Ok(None);
+/// Walks commit history, caching parents, commit times and generations
+#[derive(Debug, Default)]
+pub struct CommitWalker {
+    /// Commit hash -> walk data
+    commits: HashMap<String, WalkCommit>,
+}
+
+impl CommitWalker {
+    /// Create a walker with an empty cache
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parents of a commit
+    pub fn parents(&mut self, hash: &str) -> Result<Vec<String>> {
+        Ok(self.load(hash)?.parents.clone())
+    }
+
+    /// Add a commit without reading it from the repository (for tests)
+    #[cfg(test)]
+    pub(crate) fn insert(&mut self, hash: &str, time: i64, parents: &[&str]) {
+        self.commits.insert(
+            hash.to_string(),
+            WalkCommit {
+                time,
+                generation: GENERATION_INFINITY,
+                parents: parents.iter().map(|p| p.to_string()).collect(),
+            },
+        );
     }
 
-    // Find the closest common ancestor
-    // This is the one with the shortest path from both commits
-    let mut best_base = None;
-    let mut best_distance = usize::MAX;
+    /// Committer time of a commit
+    pub fn commit_time(&mut self, hash: &str) -> Result<i64> {
+        Ok(self.load(hash)?.time)
+    }
 
-    for ancestor in common {
-        let dist1 = get_distance(commit1, ancestor)?;
-        let dist2 = get_distance(commit2, ancestor)?;
-        let total_dist = dist1 + dist2;
+    /// Generation number of a commit (`u32::MAX` when it is not in the commit-graph)
+    pub fn generation(&mut self, hash: &str) -> Result<u32> {
+        Ok(self.load(hash)?.generation)
+    }
 
-        if total_dist < best_distance {
-            best_distance = total_dist;
-            best_base = Some(ancestor.to_string());
+    /// Walk data of a commit, read on first use
+    fn load(&mut self, hash: &str) -> Result<&WalkCommit> {
+        if !self.commits.contains_key(hash) {
+            let commit = match commit_graph::lookup(hash) {
+                Some(graph) => WalkCommit {
+                    time: graph.commit_time,
+                    generation: graph.generation,
+                    parents: graph.parents,
+                },
+                None => {
+                    let commit = read_commit(hash)?;
+                    WalkCommit {
+                        time: commit.committer_time,
+                        generation: GENERATION_INFINITY,
+                        parents: commit.parents,
+                    }
+                }
+            };
+            self.commits.insert(hash.to_string(), commit);
         }
+        Ok(&self.commits[hash])
     }
 
-    Ok(best_base)
-}
+    /// Queue entry for a commit
+    fn entry(&mut self, hash: &str) -> Result<QueueEntry> {
+        let commit = self.load(hash)?;
+        Ok((commit.generation, commit.time, hash.to_string()))
+    }
+
+    /// Walk down from `one` and `twos`, returning the commits reachable from both sides
+    ///
+    /// The result can contain redundant candidates (ancestors of other candidates).
+    /// Commits with a generation below `min_generation` are not walked.
+    fn paint_down_to_common(
+        &mut self,
+        one: &str,
+        twos: &[String],
+        min_generation: u32,
+    ) -> Result<Vec<String>> {
+        if twos.iter().any(|two| two == one) {
+            return Ok(vec![one.to_string()]);
+        }
 
-/// Get all ancestors of a commit
-fn get_all_ancestors(commit_hash: &str) -> io::Result<HashSet<String>> {
-    let mut ancestors = HashSet::new();
-    let mut to_visit = VecDeque::new();
-    to_visit.push_back(commit_hash.to_string());
+        let mut flags: HashMap<String, u8> = HashMap::new();
+        let mut queue: BinaryHeap<QueueEntry> = BinaryHeap::new();
 
-    while let Some(current) = to_visit.pop_front() {
-        if ancestors.contains(&current) {
-            continue;
+        flags.insert(one.to_string(), PARENT1);
+        queue.push(self.entry(one)?);
+        for two in twos {
+            *flags.entry(two.clone()).or_default() |= PARENT2;
+            queue.push(self.entry(two)?);
         }
 
-        ancestors.insert(current.clone());
+        let mut result = Vec::new();
+        let has_nonstale = |queue: &BinaryHeap<QueueEntry>, flags: &HashMap<String, u8>| {
+            queue.iter().any(|(_, _, hash)| flags[hash] & STALE == 0)
+        };
+
+        while has_nonstale(&queue, &flags) {
+            let Some((generation, _, commit)) = queue.pop() else {
+                break;
+            };
+            // Everything left in the queue is too old to matter
+            if generation < min_generation {
+                break;
+            }
+
+            let mut paint = flags[&commit] & (PARENT1 | PARENT2 | STALE);
+            if paint == PARENT1 | PARENT2 {
+                if flags[&commit] & RESULT == 0 {
+                    *flags.get_mut(&commit).unwrap() |= RESULT;
+                    result.push(commit.clone());
+                }
+                // Everything below a common commit is no longer interesting
+                paint |= STALE;
+            }
 
-        let parents = get_commit_parents(&current)?;
-        for parent in parents {
-            to_visit.push_back(parent);
+            for parent in self.parents(&commit)? {
+                let parent_flags = flags.entry(parent.clone()).or_default();
+                if *parent_flags & paint == paint {
+                    continue;
+                }
+                *parent_flags |= paint;
+                queue.push(self.entry(&parent)?);
+            }
         }
+
+        Ok(result)
     }
 
-    Ok(ancestors)
-}
+    /// Best common ancestors of `one` and any of `twos`, newest first
+    pub fn merge_bases_many(&mut self, one: &str, twos: &[String]) -> Result<Vec<String>> {
+        let candidates = self.paint_down_to_common(one, twos, 0)?;
+        let mut bases = self.remove_redundant(candidates)?;
 
-/// Get distance from start commit to target commit
-fn get_distance(start: &str, target: &str) -> io::Result<usize> {
-    if start == target {
-        return Ok(0);
-    }
+        let mut keyed = Vec::new();
+        for base in bases.drain(..) {
+            keyed.push((self.load(&base)?.time, base));
+        }
+        keyed.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
 
-    let mut distances: HashMap<String, usize> = HashMap::new();
-    let mut queue = VecDeque::new();
+        Ok(keyed.into_iter().map(|(_, hash)| hash).collect())
+    }
 
-    queue.push_back((start.to_string(), 0));
-    distances.insert(start.to_string(), 0);
+    /// Best common ancestors of two commits, newest first
+    pub fn merge_bases(&mut self, one: &str, two: &str) -> Result<Vec<String>> {
+        self.merge_bases_many(one, &[two.to_string()])
+    }
 
-    while let Some((current, dist)) = queue.pop_front() {
-        if current == target {
-            return Ok(dist);
+    /// Check whether `ancestor` is reachable from `descendant` (or equal to it)
+    pub fn is_ancestor(&mut self, ancestor: &str, descendant: &str) -> Result<bool> {
+        if ancestor == descendant {
+            return Ok(true);
         }
 
-        let parents = get_commit_parents(&current)?;
-        for parent in parents {
-            if !distances.contains_key(&parent) {
-                distances.insert(parent.clone(), dist + 1);
-                queue.push_back((parent, dist + 1));
-            }
+        // A commit only reaches commits with a smaller generation
+        let min_generation = self.load(ancestor)?.generation;
+        if min_generation != GENERATION_INFINITY
+            && min_generation >= self.load(descendant)?.generation
+        {
+            return Ok(false);
         }
-    }
 
-    Ok(usize::MAX)
-}
+        let common =
+            self.paint_down_to_common(ancestor, &[descendant.to_string()], min_generation)?;
+        Ok(common.iter().any(|c| c == ancestor))
+    }
 
-/// Get parent commits of a commit
-fn get_commit_parents(commit_hash: &str) -> io::Result<Vec<String>> {
-    use std::fs;
+    /// Best common ancestors of all `commits` (for octopus merges)
+    pub fn octopus_merge_bases(&mut self, commits: &[String]) -> Result<Vec<String>> {
+        let Some((first, rest)) = commits.split_first() else {
+            return Ok(Vec::new());
+        };
 
-    let obj_dir = &commit_hash[0..2];
-    let obj_file = &commit_hash[2..];
-    let obj_path = format!(".kitcat/objects/{}/{}", obj_dir, obj_file);
+        let mut bases = vec![first.clone()];
+        for next in rest {
+            let mut next_bases = Vec::new();
+            for base in &bases {
+                for found in self.merge_bases(base, next)? {
+                    if !next_bases.contains(&found) {
+                        next_bases.push(found);
+                    }
+                }
+            }
+            bases = next_bases;
+        }
 
-    if !std::path::Path::new(&obj_path).exists() {
-        return Ok(Vec::new());
+        self.remove_redundant(bases)
     }
 
-    let compressed = fs::read(&obj_path)?;
-    let content = crate::utils::decompress(&compressed)?;
+    /// Drop candidates that are ancestors of another candidate
+    fn remove_redundant(&mut self, candidates: Vec<String>) -> Result<Vec<String>> {
+        if candidates.len() < 2 {
+            return Ok(candidates);
+        }
 
-    // Find null byte
-    let null_pos = content
-        .iter()
-        .position(|&b| b == 0)
-        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid commit object"))?;
+        let mut redundant = HashSet::new();
+        for candidate in &candidates {
+            for other in &candidates {
+                if other != candidate
+                    && !redundant.contains(other)
+                    && self.is_ancestor(candidate, other)?
+                {
+                    redundant.insert(candidate.clone());
+                    break;
+                }
+            }
+        }
 
-    let commit_data = String::from_utf8_lossy(&content[null_pos + 1..]);
-    let mut parents = Vec::new();
+        Ok(candidates
+            .into_iter()
+            .filter(|c| !redundant.contains(c))
+            .collect())
+    }
+}
 
-    for line in commit_data.lines() {
-        if line.starts_with("parent ") {
-            parents.push(line[7..].to_string());
-        } else if line.is_empty() {
-            break;
-        }
+/// Find the merge base (common ancestor) of two commits
+///
+/// When there are several best common ancestors, the newest one is returned.
+pub fn find_merge_base(commit1: &str, commit2: &str) -> Result<Option<String>> {
+    if commit1 == commit2 {
+        return Ok(Some(commit1.to_string()));
     }
 
-    Ok(parents)
+    Ok(CommitWalker::new().merge_bases(commit1, commit2)?.into_iter().next())
+}
+
+/// Find all best common ancestors of two commits
+///
+/// A common ancestor is "best" when it is not an ancestor of another common
+/// ancestor. Criss-cross histories can have several.
+pub fn find_merge_bases(commit1: &str, commit2: &str) -> Result<Vec<String>> {
+    CommitWalker::new().merge_bases(commit1, commit2)
+}
+
+/// Find the best common ancestors of `commit` and a virtual commit whose parents are `ones`
+pub fn find_merge_bases_many(ones: &[String], commit: &str) -> Result<Vec<String>> {
+    CommitWalker::new().merge_bases_many(commit, ones)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Build a walker over an in-memory history of (hash, time, parents)
+    fn walker(history: &[(&str, i64, &[&str])]) -> CommitWalker {
+        let mut walker = CommitWalker::new();
+        for (hash, time, parents) in history {
+            walker.insert(hash, *time, parents);
+        }
+        walker
+    }
+
+    /// Give every commit of the walker its generation number
+    fn with_generations(mut walker: CommitWalker, generations: &[(&str, u32)]) -> CommitWalker {
+        for (hash, generation) in generations {
+            walker.commits.get_mut(*hash).unwrap().generation = *generation;
+        }
+        walker
+    }
+
     #[test]
     fn test_same_commit() {
         // If commits are the same, they are their own merge base
@@ -147,4 +315,51 @@ mod tests {
         let ancestors: HashSet<String> = HashSet::new();
         assert_eq!(ancestors.len(), 0);
     }
+
+    #[test]
+    fn test_criss_cross_merge_bases() {
+        // root - a1 - m1 - a2
+        //     \     X
+        //      b1 - m2 - b2
+        let mut walker = walker(&[
+            ("root", 1, &[]),
+            ("a1", 2, &["root"]),
+            ("b1", 3, &["root"]),
+            ("m1", 4, &["a1", "b1"]),
+            ("m2", 5, &["b1", "a1"]),
+            ("a2", 6, &["m1"]),
+            ("b2", 7, &["m2"]),
+        ]);
+
+        assert_eq!(walker.merge_bases("a2", "b2").unwrap(), vec!["b1", "a1"]);
+        assert_eq!(walker.merge_bases("a1", "b1").unwrap(), vec!["root"]);
+        assert!(walker.is_ancestor("a1", "b2").unwrap());
+        assert!(!walker.is_ancestor("a2", "b2").unwrap());
+        assert_eq!(
+            walker
+                .octopus_merge_bases(&["a2".to_string(), "b2".to_string(), "b1".to_string()])
+                .unwrap(),
+            vec!["b1"]
+        );
+    }
+
+    #[test]
+    fn test_generation_cutoff() {
+        // root - a - b - c, with a clock-skewed "b" older than its parent
+        let history: &[(&str, i64, &[&str])] = &[
+            ("root", 10, &[]),
+            ("a", 20, &["root"]),
+            ("b", 5, &["a"]),
+            ("c", 30, &["b"]),
+        ];
+        let mut walker = with_generations(
+            walker(history),
+            &[("root", 1), ("a", 2), ("b", 3), ("c", 4)],
+        );
+
+        assert!(walker.is_ancestor("a", "c").unwrap());
+        assert!(walker.is_ancestor("root", "c").unwrap());
+        assert!(!walker.is_ancestor("c", "a").unwrap());
+        assert_eq!(walker.merge_bases("c", "a").unwrap(), vec!["a"]);
+    }
 }
@@ -3,16 +3,24 @@
 /// Implements three-way merge algorithm with conflict detection
 
 pub mod base;
+pub mod recursive;
+pub mod rerere;
 pub mod three_way;
 pub mod types;
+pub mod worktree;
 
-pub use base::find_merge_base;
+pub use base::{find_merge_base, find_merge_bases, find_merge_bases_many, CommitWalker};
+pub use recursive::merge_base_files;
 pub use three_way::{merge_trees, MergeResult};
-pub use types::{ConflictMarker, FileConflict, MergeStrategy};
+pub use types::{
+    ConflictMarker, ConflictStyle, FileConflict, MarkerOptions, MergeStrategy,
+    StrategyOptions,
+};
 
+use crate::error::Result;
+use crate::models::FileEntry;
 use crate::object::{get_commit_tree, read_tree};
 use std::collections::HashMap;
-use std::io;
 
 /// Represents the outcome of a merge operation
 #[derive(Debug)]
@@ -34,72 +42,18 @@ pub enum MergeOutcome {
 pub fn can_fast_forward(
     our_commit: &str,
     their_commit: &str,
-) -> io::Result<bool> {
+) -> Result<bool> {
     // Check if our_commit is an ancestor of their_commit
     is_ancestor(our_commit, their_commit)
 }
 
 /// Check if commit1 is an ancestor of commit2
-fn is_ancestor(ancestor: &str, descendant: &str) -> io::Result<bool> {
-    if ancestor == descendant {
-        return Ok(true);
-    }
-
-    // Walk back from descendant to see if we reach ancestor
-    let mut to_visit = vec![descendant.to_string()];
-    let mut visited = std::collections::HashSet::new();
-
-    while let Some(current) = to_visit.pop() {
-        if current == ancestor {
-            return Ok(true);
-        }
-
-        if visited.contains(&current) {
-            continue;
-        }
-        visited.insert(current.clone());
-
-        // Get parents of current commit
-        let parents = get_commit_parents(&current)?;
-        to_visit.extend(parents);
-    }
-
-    Ok(false)
-}
-
-/// Get parent commits of a commit
-fn get_commit_parents(commit_hash: &str) -> io::Result<Vec<String>> {
-    use std::fs;
-
-    let obj_dir = &commit_hash[0..2];
-    let obj_file = &commit_hash[2..];
-    let obj_path = format!(".kitcat/objects/{}/{}", obj_dir, obj_file);
-
-    let compressed = fs::read(&obj_path)?;
-    let content = crate::utils::decompress(&compressed)?;
-
-    // Find null byte
-    let null_pos = content
-        .iter()
-        .position(|&b| b == 0)
-        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid commit object"))?;
-
-    let commit_data = String::from_utf8_lossy(&content[null_pos + 1..]);
-    let mut parents = Vec::new();
-
-    for line in commit_data.lines() {
-        if line.starts_with("parent ") {
-            parents.push(line[7..].to_string());
-        } else if line.is_empty() {
-            break;
-        }
-    }
-
-    Ok(parents)
+pub fn is_ancestor(ancestor: &str, descendant: &str) -> Result<bool> {
+    CommitWalker::new().is_ancestor(ancestor, descendant)
 }
 
-/// Get all files from a commit tree
-pub fn get_commit_files(commit_hash: &str) -> io::Result<HashMap<String, String>> {
+/// Get all files (path -> mode and blob hash) from a commit tree
+pub fn get_commit_files(commit_hash: &str) -> Result<HashMap<String, FileEntry>> {
     let tree_hash = get_commit_tree(commit_hash)?;
     let mut files = HashMap::new();
     collect_tree_files(&tree_hash, "", &mut files)?;
@@ -110,8 +64,8 @@ pub fn get_commit_files(commit_hash: &str) -> io::Result<HashMap<String, String>
 fn collect_tree_files(
     tree_hash: &str,
     prefix: &str,
-    files: &mut HashMap<String, String>,
-) -> io::Result<()> {
+    files: &mut HashMap<String, FileEntry>,
+) -> Result<()> {
     let tree_entries = read_tree(tree_hash)?;
 
     for entry in tree_entries {
@@ -126,7 +80,7 @@ fn collect_tree_files(
         if entry.is_tree {
             collect_tree_files(&hash_hex, &path, files)?;
         } else {
-            files.insert(path, hash_hex);
+            files.insert(path, FileEntry::from_tree_mode(&entry.mode, hash_hex));
         }
     }
 
@@ -0,0 +1,80 @@
+/// Recursive merge strategy
+///
+/// When two commits have several best common ancestors (criss-cross
+/// history), the ancestors are first merged with each other into a virtual
+/// merge base, recursively, and that tree is used as the base of the real
+/// merge. Conflicts inside the virtual base are kept as conflict markers,
+/// so they only resurface if the real merge touches those lines.
+
+use crate::error::Result;
+use super::base::find_merge_bases_many;
+use super::three_way::{merge_trees, MergeResult};
+use super::types::{MarkerOptions, StrategyOptions};
+use super::get_commit_files;
+use crate::models::FileEntry;
+use crate::object::write_blob;
+use std::collections::HashMap;
+
+/// Files (path -> mode and blob hash) of the merge base built from `bases`
+///
+/// No bases gives an empty tree, one base its own tree, and several bases
+/// a virtual merge base.
+pub fn merge_base_files(
+    bases: &[String],
+    options: &StrategyOptions,
+) -> Result<HashMap<String, FileEntry>> {
+    match bases {
+        [] => Ok(HashMap::new()),
+        [base] => get_commit_files(base),
+        _ => virtual_merge_base(bases, options),
+    }
+}
+
+/// Merge several merge bases into one virtual tree
+fn virtual_merge_base(
+    bases: &[String],
+    options: &StrategyOptions,
+) -> Result<HashMap<String, FileEntry>> {
+    let mut merged_commits = vec![bases[0].clone()];
+    let mut files = get_commit_files(&bases[0])?;
+
+    for next in &bases[1..] {
+        // The virtual commit so far has all merged bases as parents
+        let sub_bases = find_merge_bases_many(&merged_commits, next)?;
+        let base_files = merge_base_files(&sub_bases, options)?;
+        let next_files = get_commit_files(next)?;
+
+        let result = merge_trees(&base_files, &files, &next_files, options)?;
+        files = apply_merge_result(&files, &result, "Temporary merge branch 1", "Temporary merge branch 2")?;
+        merged_commits.push(next.clone());
+    }
+
+    Ok(files)
+}
+
+/// Apply a merge result to our files, storing merged contents as blobs
+///
+/// Conflicted files are stored with conflict markers.
+pub fn apply_merge_result(
+    our_files: &HashMap<String, FileEntry>,
+    result: &MergeResult,
+    our_label: &str,
+    their_label: &str,
+) -> Result<HashMap<String, FileEntry>> {
+    let mut files = our_files.clone();
+
+    for (path, content) in &result.merged_files {
+        let mode = result.merged_modes[path];
+        files.insert(path.clone(), FileEntry::new(mode, write_blob(content)?));
+    }
+    for conflict in &result.conflicts {
+        let content = conflict.generate_conflict_markers(our_label, their_label, &MarkerOptions::default());
+        let mode = conflict.worktree_mode();
+        files.insert(conflict.path.clone(), FileEntry::new(mode, write_blob(&content)?));
+    }
+    for path in &result.deleted_files {
+        files.remove(path);
+    }
+
+    Ok(files)
+}
@@ -0,0 +1,418 @@
+/// Reuse recorded resolutions of conflicted hunks ("rerere")
+///
+/// Every conflict hunk a merge writes is identified by a hash of its two
+/// sides, sorted so that the same conflict with the sides swapped (as in a
+/// rebase) gets the same id. When the merge is concluded, the resolution of
+/// each hunk is stored as `.kitcat/rr-cache/<id>/postimage`; the next time
+/// the same hunk appears it is replaced by that resolution.
+///
+/// While a merge is in progress, `.kitcat/MERGE_RR` lists each conflicted
+/// path with the id of its conflicted contents, which are kept as
+/// `rr-cache/<id>/preimage` so the resolutions can be extracted later.
+///
+/// Enabled by `rerere.enabled`, or by the existence of `rr-cache` if that is
+/// not set. With `rerere.autoUpdate`, files resolved completely are staged.
+
+use super::types::ConflictMarker;
+use crate::config::Config;
+use crate::diff::algorithm::{compute_edit_script, Edit};
+use crate::error::Result;
+use crate::index::add_file_to_index;
+use crate::object::write_blob;
+use crate::utils::compute_hash;
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Database of recorded conflicts and resolutions
+pub const RR_CACHE: &str = ".kitcat/rr-cache";
+/// Conflicted paths of the merge in progress
+const MERGE_RR: &str = ".kitcat/MERGE_RR";
+
+/// Whether conflict resolutions are recorded and replayed
+pub fn is_enabled() -> bool {
+    match Config::read().ok().and_then(|c| c.rerere.enabled) {
+        Some(enabled) => enabled,
+        None => Path::new(RR_CACHE).is_dir(),
+    }
+}
+
+/// Replay recorded resolutions into freshly conflicted files and remember their conflicts
+pub fn replay(paths: &[String]) -> Result<()> {
+    if !is_enabled() {
+        return Ok(());
+    }
+    let auto_update = Config::read()?.rerere.auto_update.unwrap_or(false);
+
+    let mut merge_rr = String::new();
+    for path in paths {
+        let Ok(content) = fs::read_to_string(path) else {
+            continue;
+        };
+        let segments = parse(&content);
+        if !segments.iter().any(Segment::is_conflict) {
+            continue;
+        }
+
+        let preimage = normalize(&segments);
+        let id = compute_hash(preimage.as_bytes());
+        write_entry(&id, "preimage", &preimage)?;
+        merge_rr.push_str(&format!("{}\t{}\n", id, path));
+
+        let mut replayed = String::new();
+        let mut remaining = 0;
+        for segment in &segments {
+            match segment {
+                Segment::Text(text) => replayed.push_str(text),
+                Segment::Conflict { ours, theirs, raw } => {
+                    match fs::read_to_string(entry_path(&hunk_id(ours, theirs), "postimage")) {
+                        Ok(resolution) => replayed.push_str(&resolution),
+                        Err(_) => {
+                            remaining += 1;
+                            replayed.push_str(raw);
+                        }
+                    }
+                }
+            }
+        }
+        if replayed == content {
+            continue;
+        }
+        fs::write(path, &replayed)?;
+
+        if remaining > 0 {
+            println!("Partially resolved '{}' using previous resolution.", path);
+        } else if auto_update {
+            add_file_to_index(path, &write_blob(replayed.as_bytes())?)?;
+            println!("Staged '{}' using previous resolution.", path);
+        } else {
+            println!("Resolved '{}' using previous resolution.", path);
+        }
+    }
+
+    if !merge_rr.is_empty() {
+        fs::write(MERGE_RR, merge_rr)?;
+    }
+    Ok(())
+}
+
+/// Record the resolutions of the conflicts listed in `MERGE_RR`
+///
+/// Hunks whose resolution cannot be told apart (still containing markers,
+/// or with no unchanged line separating them) are left unrecorded.
+pub fn record() -> Result<()> {
+    for (id, path) in merge_rr()? {
+        let (Ok(preimage), Ok(resolved)) = (
+            fs::read_to_string(entry_path(&id, "preimage")),
+            fs::read_to_string(&path),
+        ) else {
+            continue;
+        };
+
+        let mut recorded = false;
+        for (hunk, resolution) in extract_resolutions(&preimage, &resolved) {
+            let Segment::Conflict { ours, theirs, raw } = hunk else {
+                continue;
+            };
+            let hunk_id = hunk_id(&ours, &theirs);
+            if fs::read_to_string(entry_path(&hunk_id, "postimage")).ok() == Some(resolution.clone()) {
+                continue;
+            }
+            write_entry(&hunk_id, "preimage", &raw)?;
+            write_entry(&hunk_id, "postimage", &resolution)?;
+            recorded = true;
+        }
+        if recorded {
+            println!("Recorded resolution for '{}'.", path);
+        }
+    }
+    Ok(())
+}
+
+/// Forget the conflicts of the merge in progress (recorded resolutions are kept)
+pub fn clear() -> Result<()> {
+    let ids: BTreeSet<String> = merge_rr()?.into_iter().map(|(id, _)| id).collect();
+    for id in ids {
+        let _ = fs::remove_dir_all(Path::new(RR_CACHE).join(id));
+    }
+    if Path::new(MERGE_RR).exists() {
+        fs::remove_file(MERGE_RR)?;
+    }
+    Ok(())
+}
+
+/// Paths with conflicts tracked for the merge in progress
+pub fn status() -> Result<Vec<String>> {
+    Ok(merge_rr()?.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Drop the recorded resolutions for the conflicts in `path`
+pub fn forget(path: &str) -> Result<bool> {
+    let Some((id, _)) = merge_rr()?.into_iter().find(|(_, p)| p == path) else {
+        return Ok(false);
+    };
+    let preimage = fs::read_to_string(entry_path(&id, "preimage"))?;
+
+    let mut forgotten = false;
+    for segment in parse(&preimage) {
+        if let Segment::Conflict { ours, theirs, .. } = segment {
+            let postimage = entry_path(&hunk_id(&ours, &theirs), "postimage");
+            if postimage.exists() {
+                fs::remove_file(postimage)?;
+                forgotten = true;
+            }
+        }
+    }
+    Ok(forgotten)
+}
+
+/// Entries of `MERGE_RR` as (conflict id, path)
+fn merge_rr() -> Result<Vec<(String, String)>> {
+    let content = match fs::read_to_string(MERGE_RR) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(content
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .map(|(id, path)| (id.to_string(), path.to_string()))
+        .collect())
+}
+
+/// Path of a file kept for a conflict id
+fn entry_path(id: &str, name: &str) -> PathBuf {
+    Path::new(RR_CACHE).join(id).join(name)
+}
+
+/// Write a file kept for a conflict id
+fn write_entry(id: &str, name: &str, content: &str) -> Result<()> {
+    let path = entry_path(id, name);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, content)?;
+    Ok(())
+}
+
+/// A piece of a conflicted file
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    /// Lines outside any conflict
+    Text(String),
+    /// A conflict hunk: both sides and the hunk as written, markers included
+    Conflict {
+        ours: String,
+        theirs: String,
+        raw: String,
+    },
+}
+
+impl Segment {
+    fn is_conflict(&self) -> bool {
+        matches!(self, Segment::Conflict { .. })
+    }
+}
+
+/// Part of a conflict hunk being read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Section {
+    Ours,
+    Base,
+    Theirs,
+}
+
+/// Split file content into text and conflict hunks
+///
+/// A `|||||||` base section is skipped, so all conflict styles give the
+/// same hunks. An unterminated hunk is treated as text.
+fn parse(content: &str) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut text = String::new();
+    let mut open: Option<(Section, String, String, String, usize)> = None;
+
+    for line in content.split_inclusive('\n') {
+        let Some((section, ours, theirs, raw, size)) = open.as_mut() else {
+            if let Some(size) = ConflictMarker::marker_size(line, '<') {
+                if !text.is_empty() {
+                    segments.push(Segment::Text(std::mem::take(&mut text)));
+                }
+                open = Some((Section::Ours, String::new(), String::new(), line.to_string(), size));
+            } else {
+                text.push_str(line);
+            }
+            continue;
+        };
+        let is_marker = |c: char| ConflictMarker::marker_size(line, c) == Some(*size);
+
+        raw.push_str(line);
+        match *section {
+            Section::Ours if is_marker('|') => *section = Section::Base,
+            Section::Ours | Section::Base if is_marker('=') => *section = Section::Theirs,
+            Section::Theirs if is_marker('>') => {
+                if let Some((_, ours, theirs, raw, _)) = open.take() {
+                    segments.push(Segment::Conflict { ours, theirs, raw });
+                }
+            }
+            Section::Ours => ours.push_str(line),
+            Section::Base => {}
+            Section::Theirs => theirs.push_str(line),
+        }
+    }
+
+    if let Some((_, _, _, raw, _)) = open {
+        match segments.last_mut() {
+            Some(Segment::Text(previous)) => previous.push_str(&raw),
+            _ => text = raw,
+        }
+    }
+    if !text.is_empty() {
+        segments.push(Segment::Text(text));
+    }
+    segments
+}
+
+/// Id of a conflict hunk, independent of which side is ours
+fn hunk_id(ours: &str, theirs: &str) -> String {
+    let (first, second) = if ours <= theirs { (ours, theirs) } else { (theirs, ours) };
+    compute_hash(format!("{}\0{}\0", first, second).as_bytes())
+}
+
+/// Conflicted content without labels or base sections, and with the sides sorted
+fn normalize(segments: &[Segment]) -> String {
+    segments
+        .iter()
+        .map(|segment| match segment {
+            Segment::Text(text) => text.clone(),
+            Segment::Conflict { ours, theirs, .. } => {
+                let (first, second) = if ours <= theirs { (ours, theirs) } else { (theirs, ours) };
+                format!("<<<<<<<\n{}=======\n{}>>>>>>>\n", first, second)
+            }
+        })
+        .collect()
+}
+
+/// Pair each conflict hunk of `preimage` with the lines that replaced it in `resolved`
+///
+/// Lines outside the hunks are matched between the two files; a hunk's
+/// resolution is whatever lies between the matched lines around it.
+fn extract_resolutions(preimage: &str, resolved: &str) -> Vec<(Segment, String)> {
+    let segments = parse(preimage);
+
+    // Preimage lines, each tagged with the hunk it belongs to
+    let mut pre_lines: Vec<&str> = Vec::new();
+    let mut owners: Vec<Option<usize>> = Vec::new();
+    let mut hunk_ranges = Vec::new();
+    for segment in &segments {
+        match segment {
+            Segment::Text(text) => {
+                for line in text.split_inclusive('\n') {
+                    pre_lines.push(line);
+                    owners.push(None);
+                }
+            }
+            Segment::Conflict { raw, .. } => {
+                let start = pre_lines.len();
+                for line in raw.split_inclusive('\n') {
+                    pre_lines.push(line);
+                    owners.push(Some(hunk_ranges.len()));
+                }
+                hunk_ranges.push((start, pre_lines.len()));
+            }
+        }
+    }
+    let res_lines: Vec<&str> = resolved.split_inclusive('\n').collect();
+
+    // Where each unchanged text line ended up
+    let mut matched: Vec<Option<usize>> = vec![None; pre_lines.len()];
+    let (mut i, mut j) = (0, 0);
+    for edit in compute_edit_script(&pre_lines, &res_lines) {
+        match edit {
+            Edit::Keep => {
+                if owners[i].is_none() {
+                    matched[i] = Some(j);
+                }
+                i += 1;
+                j += 1;
+            }
+            Edit::Delete => i += 1,
+            Edit::Insert => j += 1,
+        }
+    }
+
+    let spans: Vec<(usize, usize)> = hunk_ranges
+        .iter()
+        .map(|&(start, end)| {
+            let from = matched[..start].iter().rev().flatten().next().map_or(0, |j| j + 1);
+            let to = matched[end..].iter().flatten().next().copied().unwrap_or(res_lines.len());
+            (from, to.max(from))
+        })
+        .collect();
+
+    segments
+        .into_iter()
+        .filter(Segment::is_conflict)
+        .enumerate()
+        .filter_map(|(k, hunk)| {
+            let (from, to) = spans[k];
+            // Two hunks claiming the same lines cannot be told apart
+            let overlaps = (k > 0 && spans[k - 1].1 > from)
+                || spans.get(k + 1).is_some_and(|next| next.0 < to);
+            let resolution = res_lines[from..to].concat();
+            let unresolved = resolution.split_inclusive('\n').any(|line| {
+                ConflictMarker::marker_size(line, '<').is_some()
+                    || ConflictMarker::marker_size(line, '>').is_some()
+            });
+            (!overlaps && !unresolved).then_some((hunk, resolution))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFLICTED: &str = "a\n<<<<<<< HEAD\nours\n||||||| base\nbase\n=======\ntheirs\n>>>>>>> topic\nb\n";
+
+    #[test]
+    fn test_parse_conflict_hunks() {
+        let segments = parse(CONFLICTED);
+        assert_eq!(segments.len(), 3);
+        assert_eq!(segments[0], Segment::Text("a\n".to_string()));
+        let Segment::Conflict { ours, theirs, raw } = &segments[1] else {
+            panic!("expected a conflict");
+        };
+        assert_eq!(ours, "ours\n");
+        assert_eq!(theirs, "theirs\n");
+        assert!(raw.starts_with("<<<<<<< HEAD\n") && raw.ends_with(">>>>>>> topic\n"));
+
+        // Unterminated hunks are plain text
+        let broken = "x\n<<<<<<< HEAD\ny\n";
+        assert_eq!(parse(broken), vec![Segment::Text(broken.to_string())]);
+    }
+
+    #[test]
+    fn test_hunk_id_ignores_side_order() {
+        assert_eq!(hunk_id("ours\n", "theirs\n"), hunk_id("theirs\n", "ours\n"));
+        assert_ne!(hunk_id("ours\n", "theirs\n"), hunk_id("ours\n", "other\n"));
+        assert_eq!(
+            normalize(&parse(CONFLICTED)),
+            "a\n<<<<<<<\nours\n=======\ntheirs\n>>>>>>>\nb\n"
+        );
+    }
+
+    #[test]
+    fn test_extract_resolutions() {
+        let preimage = "a\n<<<<<<<\n1\n=======\n2\n>>>>>>>\nb\nc\n<<<<<<<\n3\n=======\n4\n>>>>>>>\nd\n";
+        let resolved = "a\n1\n2\nb\nc\nd\n";
+        let resolutions: Vec<String> = extract_resolutions(preimage, resolved)
+            .into_iter()
+            .map(|(_, resolution)| resolution)
+            .collect();
+        assert_eq!(resolutions, vec!["1\n2\n", ""]);
+
+        // A hunk still holding markers is not recorded
+        assert!(extract_resolutions(preimage, preimage).is_empty());
+    }
+}
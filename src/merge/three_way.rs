@@ -2,16 +2,20 @@
 ///
 /// Merges two versions of a file given their common ancestor
 
-use super::types::{FileConflict, FileMergeResult};
+use super::types::{Favor, FileConflict, FileMergeResult, MergeHunk, StrategyOptions};
+use crate::models::{FileEntry, MODE_SYMLINK};
+use crate::diff::algorithm::{compute_edit_script_with, Edit};
+use crate::error::{KitcatError, Result};
 use std::collections::HashMap;
 use std::fs;
-use std::io;
 
 /// Result of merging two trees
 #[derive(Debug)]
 pub struct MergeResult {
     /// Successfully merged files (path -> content)
     pub merged_files: HashMap<String, Vec<u8>>,
+    /// Modes of the merged files (path -> mode)
+    pub merged_modes: HashMap<String, u32>,
     /// Files with conflicts
     pub conflicts: Vec<FileConflict>,
     /// Files that were deleted
@@ -23,6 +27,7 @@ impl MergeResult {
     pub fn new() -> Self {
         MergeResult {
             merged_files: HashMap::new(),
+            merged_modes: HashMap::new(),
             conflicts: Vec::new(),
             deleted_files: Vec::new(),
         }
@@ -34,12 +39,17 @@ impl MergeResult {
     }
 }
 
-/// Merge two file trees given a common base
+/// Merge two file trees given a common base, with `-X` strategy options
+///
+/// Contents and modes are merged separately: a mode changed on one side
+/// only is taken from that side, and modes changed differently on both
+/// sides make the path conflict even if its content merges cleanly.
 pub fn merge_trees(
-    base_files: &HashMap<String, String>,
-    our_files: &HashMap<String, String>,
-    their_files: &HashMap<String, String>,
-) -> io::Result<MergeResult> {
+    base_files: &HashMap<String, FileEntry>,
+    our_files: &HashMap<String, FileEntry>,
+    their_files: &HashMap<String, FileEntry>,
+    options: &StrategyOptions,
+) -> Result<MergeResult> {
     let mut result = MergeResult::new();
 
     // Collect all file paths
@@ -49,18 +59,38 @@ pub fn merge_trees(
     all_paths.extend(their_files.keys().cloned());
 
     for path in all_paths {
-        let base_hash = base_files.get(&path);
-        let our_hash = our_files.get(&path);
-        let their_hash = their_files.get(&path);
+        let base = base_files.get(&path);
+        let ours = our_files.get(&path);
+        let theirs = their_files.get(&path);
+        let modes = [base, ours, theirs].map(|entry| entry.map(|e| e.mode));
+        let mode = merge_mode(modes[0], modes[1], modes[2]);
+
+        let file_result = match (merge_file(&path, base, ours, theirs, options)?, ours) {
+            // Same content on both sides, but they may have changed the mode
+            (FileMergeResult::Unchanged, Some(ours)) if mode != Some(ours.mode) => {
+                FileMergeResult::Success {
+                    content: read_object_content(&ours.hash)?,
+                }
+            }
+            (file_result, _) => file_result,
+        };
 
-        match merge_file(&path, base_hash, our_hash, their_hash)? {
-            FileMergeResult::Success { content } => {
+        match (file_result, mode) {
+            (FileMergeResult::Success { content }, Some(mode)) => {
+                result.merged_modes.insert(path.clone(), mode);
                 result.merged_files.insert(path, content);
             }
-            FileMergeResult::Conflict { conflict } => {
+            (FileMergeResult::Success { .. } | FileMergeResult::Unchanged, None) => {
+                result.conflicts.push(create_mode_conflict(&path, base, ours, theirs)?);
+            }
+            (FileMergeResult::Conflict { mut conflict }, _) => {
+                conflict.modes = modes;
                 result.conflicts.push(conflict);
             }
-            FileMergeResult::Unchanged => {
+            (FileMergeResult::Deleted, _) => {
+                result.deleted_files.push(path);
+            }
+            (FileMergeResult::Unchanged, Some(_)) => {
                 // File unchanged, no action needed
             }
         }
@@ -69,13 +99,37 @@ pub fn merge_trees(
     Ok(result)
 }
 
-/// Merge a single file using three-way merge
+/// Merge the modes of a path: a change made by one side only wins
+///
+/// Returns `None` when both sides changed the mode differently. A side
+/// without the file does not take part.
+fn merge_mode(base: Option<u32>, ours: Option<u32>, theirs: Option<u32>) -> Option<u32> {
+    match (ours, theirs) {
+        (Some(ours), Some(theirs)) if ours == theirs => Some(ours),
+        (Some(ours), Some(theirs)) if base == Some(ours) => Some(theirs),
+        (Some(ours), Some(theirs)) if base == Some(theirs) => Some(ours),
+        (Some(_), Some(_)) => None,
+        (Some(mode), None) | (None, Some(mode)) => Some(mode),
+        (None, None) => base,
+    }
+}
+
+/// Merge a single file's content using three-way merge
 fn merge_file(
     path: &str,
-    base_hash: Option<&String>,
-    our_hash: Option<&String>,
-    their_hash: Option<&String>,
-) -> io::Result<FileMergeResult> {
+    base: Option<&FileEntry>,
+    ours: Option<&FileEntry>,
+    theirs: Option<&FileEntry>,
+    options: &StrategyOptions,
+) -> Result<FileMergeResult> {
+    let base_hash = base.map(|e| &e.hash);
+    let our_hash = ours.map(|e| &e.hash);
+    let their_hash = theirs.map(|e| &e.hash);
+    let symlink = [base, ours, theirs]
+        .iter()
+        .flatten()
+        .any(|e| e.mode == MODE_SYMLINK);
+
     match (base_hash, our_hash, their_hash) {
         // All three versions exist
         (Some(base), Some(ours), Some(theirs)) => {
@@ -97,7 +151,7 @@ fn merge_file(
             }
 
             // Both modified differently - need to merge content
-            merge_file_contents(path, base, ours, theirs)
+            merge_file_contents(path, base, ours, theirs, symlink, options)
         }
 
         // File added by both sides
@@ -113,12 +167,20 @@ fn merge_file(
         }
 
         // File deleted by one side
-        (Some(_base), None, Some(theirs)) => {
+        (Some(base), None, Some(theirs)) => {
+            if theirs == base {
+                // We deleted, they left it alone
+                return Ok(FileMergeResult::Deleted);
+            }
             // We deleted, they modified - conflict
             create_delete_modify_conflict(path, None, Some(theirs))
         }
 
-        (Some(_base), Some(ours), None) => {
+        (Some(base), Some(ours), None) => {
+            if ours == base {
+                // They deleted, we left it alone
+                return Ok(FileMergeResult::Deleted);
+            }
             // They deleted, we modified - conflict
             create_delete_modify_conflict(path, Some(ours), None)
         }
@@ -152,13 +214,21 @@ fn merge_file_contents(
     base_hash: &str,
     our_hash: &str,
     their_hash: &str,
-) -> io::Result<FileMergeResult> {
+    symlink: bool,
+    options: &StrategyOptions,
+) -> Result<FileMergeResult> {
     let base_content = read_object_content(base_hash)?;
     let our_content = read_object_content(our_hash)?;
     let their_content = read_object_content(their_hash)?;
 
-    // Check if any version is binary
-    if is_binary(&base_content) || is_binary(&our_content) || is_binary(&their_content) {
+    // Binary files and symlink targets cannot be merged line by line
+    if symlink
+        || is_binary(&base_content) || is_binary(&our_content) || is_binary(&their_content) {
+        match options.favor {
+            Some(Favor::Ours) => return Ok(FileMergeResult::Success { content: our_content }),
+            Some(Favor::Theirs) => return Ok(FileMergeResult::Success { content: their_content }),
+            None => {}
+        }
         return create_binary_conflict(path, &base_content, &our_content, &their_content);
     }
 
@@ -167,83 +237,271 @@ fn merge_file_contents(
     let our_text = String::from_utf8_lossy(&our_content);
     let their_text = String::from_utf8_lossy(&their_content);
 
-    // Try to merge line by line
-    match merge_text_contents(&base_text, &our_text, &their_text) {
-        Some(merged) => Ok(FileMergeResult::Success {
+    // Merge line by line; conflicts only cover the regions both sides changed
+    let hunks = merge_text_hunks(&base_text, &our_text, &their_text, options);
+    if let Some(merged) = resolved_text(&hunks) {
+        return Ok(FileMergeResult::Success {
             content: merged.into_bytes(),
-        }),
-        None => {
-            // Content merge failed - create conflict
-            let mut conflict = FileConflict::new(path.to_string());
-            conflict.base_content = Some(base_content);
-            conflict.our_content = Some(our_content);
-            conflict.their_content = Some(their_content);
-            conflict.is_binary = false;
-            Ok(FileMergeResult::Conflict { conflict })
-        }
+        });
     }
+
+    let mut conflict = FileConflict::new(path.to_string());
+    conflict.base_content = Some(base_content);
+    conflict.our_content = Some(our_content);
+    conflict.their_content = Some(their_content);
+    conflict.is_binary = false;
+    conflict.hunks = hunks;
+    Ok(FileMergeResult::Conflict { conflict })
 }
 
 /// Merge text contents line by line
-fn merge_text_contents(base: &str, ours: &str, theirs: &str) -> Option<String> {
-    let base_lines: Vec<&str> = base.lines().collect();
-    let our_lines: Vec<&str> = ours.lines().collect();
-    let their_lines: Vec<&str> = theirs.lines().collect();
-
-    // Simple line-by-line merge
-    // If both sides changed the same line differently, return None (conflict)
-    let mut merged_lines = Vec::new();
-    let max_len = base_lines.len().max(our_lines.len()).max(their_lines.len());
-
-    for i in 0..max_len {
-        let base_line = base_lines.get(i).copied();
-        let our_line = our_lines.get(i).copied();
-        let their_line = their_lines.get(i).copied();
-
-        match (base_line, our_line, their_line) {
-            (Some(b), Some(o), Some(t)) => {
-                if o == t {
-                    // Same on both sides
-                    merged_lines.push(o);
-                } else if o == b {
-                    // Only they changed it
-                    merged_lines.push(t);
-                } else if t == b {
-                    // Only we changed it
-                    merged_lines.push(o);
-                } else {
-                    // Both changed differently - conflict
-                    return None;
-                }
-            }
-            (None, Some(o), Some(t)) => {
-                if o == t {
-                    merged_lines.push(o);
-                } else {
-                    // Both added different lines
-                    return None;
-                }
-            }
-            (Some(_b), None, None) => {
-                // Both deleted - ok
-            }
-            (None, Some(o), None) => {
-                merged_lines.push(o);
-            }
-            (Some(_b), Some(o), None) => {
-                merged_lines.push(o);
-            }
-            (None, None, Some(t)) => {
-                merged_lines.push(t);
+pub fn merge_text_contents(base: &str, ours: &str, theirs: &str) -> Option<String> {
+    merge_text_with_options(base, ours, theirs, &StrategyOptions::default())
+}
+
+/// Merge text contents, or None if any region conflicts
+pub fn merge_text_with_options(
+    base: &str,
+    ours: &str,
+    theirs: &str,
+    options: &StrategyOptions,
+) -> Option<String> {
+    resolved_text(&merge_text_hunks(base, ours, theirs, options))
+}
+
+/// The merged text, or None if any hunk conflicts
+pub fn resolved_text(hunks: &[MergeHunk]) -> Option<String> {
+    hunks
+        .iter()
+        .map(|hunk| match hunk {
+            MergeHunk::Resolved(text) => Some(text.as_str()),
+            MergeHunk::Conflict { .. } => None,
+        })
+        .collect()
+}
+
+/// Merge text contents into merged regions and conflicts, aligning both sides with the base
+///
+/// Regions changed on one side only are taken from that side, and regions
+/// changed identically on both sides are taken once. Regions both sides
+/// changed differently are resolved by `options.favor` or returned as
+/// conflict hunks. Consecutive merged lines
+/// form a single `Resolved` hunk.
+///
+/// Lines keep their endings, so a missing newline at the end of the file
+/// survives the merge, and a change to that newline is merged like any
+/// other change.
+pub fn merge_text_hunks(
+    base: &str,
+    ours: &str,
+    theirs: &str,
+    options: &StrategyOptions,
+) -> Vec<MergeHunk> {
+    let base_lines: Vec<&str> = base.split_inclusive('\n').collect();
+    let our_lines: Vec<&str> = ours.split_inclusive('\n').collect();
+    let their_lines: Vec<&str> = theirs.split_inclusive('\n').collect();
+
+    let mut hunks = Vec::new();
+    for chunk in merge_chunks(&base_lines, &our_lines, &their_lines, options) {
+        match chunk {
+            MergeChunk::Stable(line) => push_resolved(&mut hunks, line),
+            MergeChunk::Changed { base, ours, theirs } => {
+                resolve_chunk(&mut hunks, base, ours, theirs, options);
             }
-            (Some(_b), None, Some(t)) => {
-                merged_lines.push(t);
+        }
+    }
+
+    hunks
+}
+
+/// Append merged lines, extending the last hunk if it is merged too
+fn push_resolved(hunks: &mut Vec<MergeHunk>, text: &str) {
+    match hunks.last_mut() {
+        Some(MergeHunk::Resolved(merged)) => merged.push_str(text),
+        _ => hunks.push(MergeHunk::Resolved(text.to_string())),
+    }
+}
+
+/// Append a conflict, extending the last hunk if it is a conflict too
+fn push_conflict(hunks: &mut Vec<MergeHunk>, base: &[&str], ours: &[&str], theirs: &[&str]) {
+    match hunks.last_mut() {
+        Some(MergeHunk::Conflict {
+            base: b,
+            ours: o,
+            theirs: t,
+        }) => {
+            b.push_str(&base.concat());
+            o.push_str(&ours.concat());
+            t.push_str(&theirs.concat());
+        }
+        _ => hunks.push(MergeHunk::Conflict {
+            base: base.concat(),
+            ours: ours.concat(),
+            theirs: theirs.concat(),
+        }),
+    }
+}
+
+/// A region of a three-way text merge
+enum MergeChunk<'a> {
+    /// A base line kept unchanged by both sides
+    Stable(&'a str),
+    /// A region changed by at least one side
+    Changed {
+        base: &'a [&'a str],
+        ours: &'a [&'a str],
+        theirs: &'a [&'a str],
+    },
+}
+
+/// Split a three-way merge into stable lines and changed regions
+fn merge_chunks<'a>(
+    base: &'a [&'a str],
+    ours: &'a [&'a str],
+    theirs: &'a [&'a str],
+    options: &StrategyOptions,
+) -> Vec<MergeChunk<'a>> {
+    let our_match = match_base_lines(base, ours, options);
+    let their_match = match_base_lines(base, theirs, options);
+
+    let mut chunks = Vec::new();
+    let (mut i, mut j, mut k) = (0, 0, 0);
+
+    while i < base.len() || j < ours.len() || k < theirs.len() {
+        if i < base.len() && our_match[i] == Some(j) && their_match[i] == Some(k) {
+            chunks.push(MergeChunk::Stable(ours[j]));
+            i += 1;
+            j += 1;
+            k += 1;
+            continue;
+        }
+
+        // The changed region ends at the next base line both sides kept
+        let end = (i..base.len()).find(|&b| our_match[b].is_some() && their_match[b].is_some());
+        let (next_i, next_j, next_k) = match end {
+            Some(b) => (b, our_match[b].unwrap(), their_match[b].unwrap()),
+            None => (base.len(), ours.len(), theirs.len()),
+        };
+
+        chunks.push(MergeChunk::Changed {
+            base: &base[i..next_i],
+            ours: &ours[j..next_j],
+            theirs: &theirs[k..next_k],
+        });
+        i = next_i;
+        j = next_j;
+        k = next_k;
+    }
+
+    chunks
+}
+
+/// For each base line, its position in `side` if the diff kept it
+fn match_base_lines(base: &[&str], side: &[&str], options: &StrategyOptions) -> Vec<Option<usize>> {
+    let normalize = |lines: &[&str]| -> Vec<String> {
+        lines.iter().map(|l| comparable_line(l, options)).collect()
+    };
+    let base_keys = normalize(base);
+    let side_keys = normalize(side);
+    let base_refs: Vec<&str> = base_keys.iter().map(String::as_str).collect();
+    let side_refs: Vec<&str> = side_keys.iter().map(String::as_str).collect();
+
+    let mut matches = vec![None; base.len()];
+    let (mut i, mut j) = (0, 0);
+    for edit in compute_edit_script_with(&base_refs, &side_refs, options.diff_algorithm) {
+        match edit {
+            Edit::Keep => {
+                matches[i] = Some(j);
+                i += 1;
+                j += 1;
             }
-            (None, None, None) => {}
+            Edit::Delete => i += 1,
+            Edit::Insert => j += 1,
         }
     }
+    matches
+}
 
-    Some(merged_lines.join("\n") + "\n")
+/// Resolve a changed region into merged lines, or a conflict if both sides changed it differently
+fn resolve_chunk(
+    hunks: &mut Vec<MergeHunk>,
+    base: &[&str],
+    ours: &[&str],
+    theirs: &[&str],
+    options: &StrategyOptions,
+) {
+    let same = |a: &[&str], b: &[&str]| {
+        a.len() == b.len() && a.iter().zip(b).all(|(x, y)| lines_equal(x, y, options))
+    };
+    let push_all = |hunks: &mut Vec<MergeHunk>, lines: &[&str]| {
+        lines.iter().for_each(|line| push_resolved(hunks, line));
+    };
+
+    if same(ours, base) {
+        return push_all(hunks, theirs);
+    }
+    if same(theirs, base) || same(ours, theirs) {
+        return push_all(hunks, ours);
+    }
+    if let Some(lines) = merge_final_newline(base, ours, theirs) {
+        return push_all(hunks, lines);
+    }
+
+    match options.favor {
+        Some(Favor::Ours) => push_all(hunks, ours),
+        Some(Favor::Theirs) => push_all(hunks, theirs),
+        None => push_conflict(hunks, base, ours, theirs),
+    }
+}
+
+/// Both sides made the same change except for the newline at the end of the file
+///
+/// The newline is merged on its own: if one side kept the base's choice,
+/// the other side's choice wins.
+fn merge_final_newline<'a, 'b>(
+    base: &[&str],
+    ours: &'b [&'a str],
+    theirs: &'b [&'a str],
+) -> Option<&'b [&'a str]> {
+    let (our_last, our_rest) = ours.split_last()?;
+    let (their_last, their_rest) = theirs.split_last()?;
+    if our_rest != their_rest || our_last.trim_end_matches('\n') != their_last.trim_end_matches('\n') {
+        return None;
+    }
+
+    let base_newline = base.last().is_none_or(|line| line.ends_with('\n'));
+    if our_last.ends_with('\n') == base_newline {
+        Some(theirs)
+    } else {
+        Some(ours)
+    }
+}
+
+/// Compare two lines, honoring `ignore_space_change`
+fn lines_equal(a: &str, b: &str, options: &StrategyOptions) -> bool {
+    a == b || (options.ignore_space_change && comparable_line(a, options) == comparable_line(b, options))
+}
+
+/// The form of a line used for matching: whitespace runs collapse to one space
+fn comparable_line(line: &str, options: &StrategyOptions) -> String {
+    if !options.ignore_space_change {
+        return line.to_string();
+    }
+
+    let mut result = String::with_capacity(line.len());
+    let mut in_space = false;
+    for c in line.trim_end().chars() {
+        if c.is_whitespace() {
+            if !in_space {
+                result.push(' ');
+            }
+            in_space = true;
+        } else {
+            result.push(c);
+            in_space = false;
+        }
+    }
+    result
 }
 
 /// Create conflict for files added by both sides with different content
@@ -251,7 +509,7 @@ fn create_add_add_conflict(
     path: &str,
     our_hash: &str,
     their_hash: &str,
-) -> io::Result<FileMergeResult> {
+) -> Result<FileMergeResult> {
     let our_content = read_object_content(our_hash)?;
     let their_content = read_object_content(their_hash)?;
 
@@ -271,7 +529,7 @@ fn create_delete_modify_conflict(
     path: &str,
     our_hash: Option<&String>,
     their_hash: Option<&String>,
-) -> io::Result<FileMergeResult> {
+) -> Result<FileMergeResult> {
     let our_content = our_hash.map(|h| read_object_content(h)).transpose()?;
     let their_content = their_hash.map(|h| read_object_content(h)).transpose()?;
 
@@ -296,7 +554,7 @@ fn create_binary_conflict(
     base_content: &[u8],
     our_content: &[u8],
     their_content: &[u8],
-) -> io::Result<FileMergeResult> {
+) -> Result<FileMergeResult> {
     let mut conflict = FileConflict::new(path.to_string());
     conflict.base_content = Some(base_content.to_vec());
     conflict.our_content = Some(our_content.to_vec());
@@ -306,8 +564,30 @@ fn create_binary_conflict(
     Ok(FileMergeResult::Conflict { conflict })
 }
 
+/// Create conflict for a path whose mode was changed differently on both sides
+fn create_mode_conflict(
+    path: &str,
+    base: Option<&FileEntry>,
+    ours: Option<&FileEntry>,
+    theirs: Option<&FileEntry>,
+) -> Result<FileConflict> {
+    let read = |entry: Option<&FileEntry>| entry.map(|e| read_object_content(&e.hash)).transpose();
+
+    let mut conflict = FileConflict::new(path.to_string());
+    conflict.base_content = read(base)?;
+    conflict.our_content = read(ours)?;
+    conflict.their_content = read(theirs)?;
+    conflict.is_binary = [&conflict.our_content, &conflict.their_content]
+        .into_iter()
+        .flatten()
+        .any(|c| is_binary(c));
+    conflict.modes = [base, ours, theirs].map(|entry| entry.map(|e| e.mode));
+
+    Ok(conflict)
+}
+
 /// Read object content from object store
-fn read_object_content(hash: &str) -> io::Result<Vec<u8>> {
+fn read_object_content(hash: &str) -> Result<Vec<u8>> {
     let obj_dir = &hash[0..2];
     let obj_file = &hash[2..];
     let obj_path = format!(".kitcat/objects/{}/{}", obj_dir, obj_file);
@@ -319,7 +599,7 @@ fn read_object_content(hash: &str) -> io::Result<Vec<u8>> {
     let null_pos = content
         .iter()
         .position(|&b| b == 0)
-        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid object"))?;
+        .ok_or_else(|| KitcatError::CorruptObject("Invalid object".to_string()))?;
 
     Ok(content[null_pos + 1..].to_vec())
 }
@@ -337,14 +617,14 @@ mod tests {
     #[test]
     fn test_merge_text_no_conflict() {
         let base = "line 1\nline 2\nline 3\n";
-        let ours = "line 1\nline 2 modified\nline 3\n";
+        let ours = "line 1 modified\nline 2\nline 3\n";
         let theirs = "line 1\nline 2\nline 3 modified\n";
 
         let result = merge_text_contents(base, ours, theirs);
         assert!(result.is_some());
 
         let merged = result.unwrap();
-        assert!(merged.contains("line 2 modified"));
+        assert!(merged.contains("line 1 modified"));
         assert!(merged.contains("line 3 modified"));
     }
 
@@ -358,6 +638,109 @@ mod tests {
         assert!(result.is_none()); // Should conflict
     }
 
+    #[test]
+    fn test_merge_text_overlapping_edits_conflict() {
+        // Both sides rewrote the same region; lining the lines up by position would merge it
+        assert!(merge_text_contents("a\nb\n", "b\nc\n", "a\nc\n").is_none());
+
+        let merged = merge_text_contents("a\nb\nc\n", "A\nb\nc\n", "a\nb\nC\n").unwrap();
+        assert_eq!(merged, "A\nb\nC\n");
+    }
+
+    #[test]
+    fn test_merge_mode() {
+        const FILE: u32 = 0o100644;
+        const EXEC: u32 = 0o100755;
+        const LINK: u32 = 0o120000;
+
+        assert_eq!(merge_mode(Some(FILE), Some(FILE), Some(EXEC)), Some(EXEC));
+        assert_eq!(merge_mode(Some(FILE), Some(EXEC), Some(FILE)), Some(EXEC));
+        assert_eq!(merge_mode(Some(FILE), Some(EXEC), Some(EXEC)), Some(EXEC));
+        assert_eq!(merge_mode(Some(FILE), Some(EXEC), Some(LINK)), None);
+        assert_eq!(merge_mode(None, Some(EXEC), Some(FILE)), None);
+        assert_eq!(merge_mode(Some(FILE), None, Some(EXEC)), Some(EXEC));
+    }
+
+    #[test]
+    fn test_merge_text_aligns_insertions() {
+        let base = "a\nb\nc\nd\n";
+        let ours = "new first\na\nb\nc\nd\n";
+        let theirs = "a\nb\nc\nd changed\n";
+
+        let merged = merge_text_contents(base, ours, theirs).unwrap();
+        assert_eq!(merged, "new first\na\nb\nc\nd changed\n");
+    }
+
+    #[test]
+    fn test_merge_text_strategy_options() {
+        let base = "x\nkeep\n";
+        let ours = "ours\nkeep\nmore\n";
+        let theirs = "theirs\nkeep\n";
+        assert!(merge_text_contents(base, ours, theirs).is_none());
+
+        let options = StrategyOptions {
+            favor: Some(Favor::Theirs),
+            ..StrategyOptions::default()
+        };
+        let merged = merge_text_with_options(base, ours, theirs, &options).unwrap();
+        assert_eq!(merged, "theirs\nkeep\nmore\n");
+
+        // A whitespace-only edit does not conflict with a real change
+        let base = "a b\nc\n";
+        let ours = "a  b\nc\n";
+        let theirs = "a b!\nC\n";
+        assert!(merge_text_contents(base, ours, theirs).is_none());
+
+        let options = StrategyOptions {
+            ignore_space_change: true,
+            ..StrategyOptions::default()
+        };
+        let merged = merge_text_with_options(base, ours, theirs, &options);
+        assert_eq!(merged.unwrap(), "a b!\nC\n");
+    }
+
+    #[test]
+    fn test_merge_text_hunks() {
+        let base = "fn a() {\n    1\n}\n\nfn b() {\n    2\n}\n\nfn c() {\n    3\n}\n";
+        let ours = "fn a() {\n    10\n}\n\nfn b() {\n    20\n}\n\nfn c() {\n    3\n}\n";
+        let theirs = "fn a() {\n    1\n}\n\nfn b() {\n    200\n}\n\nfn c() {\n    30\n}\n";
+
+        // Distant edits merge; only the line both sides changed conflicts
+        let hunks = merge_text_hunks(base, ours, theirs, &StrategyOptions::default());
+        assert_eq!(
+            hunks,
+            vec![
+                MergeHunk::Resolved("fn a() {\n    10\n}\n\nfn b() {\n".to_string()),
+                MergeHunk::Conflict {
+                    base: "    2\n".to_string(),
+                    ours: "    20\n".to_string(),
+                    theirs: "    200\n".to_string(),
+                },
+                MergeHunk::Resolved("}\n\nfn c() {\n    30\n}\n".to_string()),
+            ]
+        );
+        assert_eq!(resolved_text(&hunks), None);
+
+        // The same change on both sides is taken once
+        let both = "fn a() {\n    10\n}\n\nfn b() {\n    2\n}\n\nfn c() {\n    3\n}\n";
+        assert_eq!(merge_text_contents(base, both, both).as_deref(), Some(both));
+    }
+
+    #[test]
+    fn test_merge_text_final_newline() {
+        // A missing newline at the end survives a merge elsewhere
+        let merged = merge_text_contents("a\nb\nc", "A\nb\nc", "a\nb\nC");
+        assert_eq!(merged.as_deref(), Some("A\nb\nC"));
+
+        // Adding the newline is a change like any other
+        let merged = merge_text_contents("a\nb\nc", "A\nb\nc", "a\nb\nc\n");
+        assert_eq!(merged.as_deref(), Some("A\nb\nc\n"));
+
+        // Same new line on both sides, one also dropping the newline
+        let merged = merge_text_contents("a\nb\n", "a\nx\n", "a\nx");
+        assert_eq!(merged.as_deref(), Some("a\nx"));
+    }
+
     #[test]
     fn test_is_binary() {
         let text = b"Hello, world!";
@@ -1,24 +1,232 @@
 /// Types for merge operations
 
+use crate::attributes::{AttrValue, Attributes};
+use crate::config::Config;
+use crate::diff::DiffAlgorithm;
+use crate::error::{KitcatError, Result};
+use crate::models::{MODE_FILE, MODE_SYMLINK};
 use std::fmt;
 
 /// Merge strategy
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum MergeStrategy {
-    /// Standard three-way merge
+    /// Recursive three-way merge: several merge bases are first merged into a virtual base
+    #[default]
+    Recursive,
+    /// Standard three-way merge against a single merge base (`resolve`)
     ThreeWay,
-    /// Ours: in conflict, take our version
+    /// Ours: keep our tree as is, recording the other side as merged
+    Ours,
+    /// Theirs: take their tree as is
+    Theirs,
+    /// Octopus: merge several branches at once, refusing on any conflict
+    Octopus,
+}
+
+impl MergeStrategy {
+    /// Parse a strategy name as given to `merge -s`
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "recursive" | "ort" => Ok(MergeStrategy::Recursive),
+            "resolve" => Ok(MergeStrategy::ThreeWay),
+            "ours" => Ok(MergeStrategy::Ours),
+            "theirs" => Ok(MergeStrategy::Theirs),
+            "octopus" => Ok(MergeStrategy::Octopus),
+            _ => Err(KitcatError::InvalidInput(format!(
+                    "Could not find merge strategy '{}'. Available strategies are: ort recursive resolve ours theirs octopus",
+                    name
+                ))),
+        }
+    }
+}
+
+/// Side that wins when both sides changed the same lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Favor {
+    /// Take our lines (`-X ours`)
     Ours,
-    /// Theirs: in conflict, take their version
+    /// Take their lines (`-X theirs`)
     Theirs,
 }
 
-impl Default for MergeStrategy {
-    fn default() -> Self {
-        MergeStrategy::ThreeWay
+/// Options for the content merge (`merge -X <option>`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StrategyOptions {
+    /// Resolve conflicting lines in favor of one side
+    pub favor: Option<Favor>,
+    /// Treat lines differing only in the amount of whitespace as equal
+    pub ignore_space_change: bool,
+    /// Algorithm used to align each side with the merge base
+    pub diff_algorithm: DiffAlgorithm,
+}
+
+impl StrategyOptions {
+    /// Apply one `-X` option
+    pub fn apply(&mut self, option: &str) -> Result<()> {
+        match option.split_once('=') {
+            Some(("diff-algorithm", name)) => self.diff_algorithm = DiffAlgorithm::parse(name)?,
+            None if option == "ours" => self.favor = Some(Favor::Ours),
+            None if option == "theirs" => self.favor = Some(Favor::Theirs),
+            None if option == "ignore-space-change" => self.ignore_space_change = true,
+            None if option == "patience" => self.diff_algorithm = DiffAlgorithm::Patience,
+            _ => {
+                return Err(KitcatError::InvalidInput(format!("Unknown strategy option: -X{}", option)));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Default length of conflict marker lines
+pub const DEFAULT_MARKER_SIZE: usize = 7;
+
+/// Shortest conflict marker recognised in files (`conflict-marker-size` may
+/// be below the default)
+const MIN_MARKER_SIZE: usize = 3;
+
+/// How conflict hunks are written (`merge.conflictStyle`, `--conflict`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConflictStyle {
+    /// Our and their lines, with lines common to both moved out of the hunk
+    #[default]
+    Merge,
+    /// Our lines, the base lines (after `|||||||`) and their lines
+    Diff3,
+    /// Like diff3, with lines common to both sides moved out of the hunk
+    Zdiff3,
+}
+
+impl ConflictStyle {
+    /// Parse a style name
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "merge" => Ok(ConflictStyle::Merge),
+            "diff3" => Ok(ConflictStyle::Diff3),
+            "zdiff3" => Ok(ConflictStyle::Zdiff3),
+            _ => Err(KitcatError::InvalidInput(format!("Unknown conflict style '{}'. Use merge, diff3 or zdiff3", name))),
+        }
+    }
+}
+
+/// How conflict markers are written into files
+///
+/// The marker length is `DEFAULT_MARKER_SIZE` unless a
+/// `conflict-marker-size=<n>` attribute applies to the path.
+#[derive(Debug, Clone, Default)]
+pub struct MarkerOptions {
+    /// Conflict style
+    pub style: ConflictStyle,
+    /// Label after the `|||||||` marker of the base section
+    pub base_label: String,
+    /// Attributes giving per-path marker sizes
+    pub attributes: Attributes,
+}
+
+impl MarkerOptions {
+    /// Style from `style` (as given to `--conflict`) or `merge.conflictStyle`,
+    /// with marker sizes from `.kitcatattributes`
+    pub fn load(style: Option<&str>, base_label: &str) -> Result<Self> {
+        let style = match style {
+            Some(style) => ConflictStyle::parse(style)?,
+            None => match Config::read()?.merge.conflict_style {
+                Some(style) => ConflictStyle::parse(&style)?,
+                None => ConflictStyle::default(),
+            },
+        };
+        Ok(MarkerOptions {
+            style,
+            base_label: base_label.to_string(),
+            attributes: Attributes::read()?,
+        })
+    }
+
+    /// Length of the conflict markers for a path
+    pub fn marker_size(&self, path: &str) -> usize {
+        match self.attributes.get(path, "conflict-marker-size") {
+            Some(AttrValue::Value(size)) => size.parse().unwrap_or(DEFAULT_MARKER_SIZE),
+            _ => DEFAULT_MARKER_SIZE,
+        }
+    }
+
+    /// Append a conflict hunk between the base, our and their lines to `out`
+    ///
+    /// Lines keep their line endings; a newline is added after the last line
+    /// of a side lacking one, so each marker starts its own line.
+    pub fn write_hunk(
+        &self,
+        out: &mut String,
+        path: &str,
+        [base, ours, theirs]: [&[&str]; 3],
+        [our_label, their_label]: [&str; 2],
+    ) {
+        let size = self.marker_size(path);
+        let (mut ours, mut theirs) = (ours, theirs);
+        let (mut prefix, mut suffix): (&[&str], &[&str]) = (&[], &[]);
+
+        if self.style != ConflictStyle::Diff3 {
+            let common = ours.iter().zip(theirs).take_while(|(o, t)| o == t).count();
+            prefix = &ours[..common];
+            ours = &ours[common..];
+            theirs = &theirs[common..];
+
+            let common = ours.iter().rev().zip(theirs.iter().rev()).take_while(|(o, t)| o == t).count();
+            suffix = &ours[ours.len() - common..];
+            ours = &ours[..ours.len() - common];
+            theirs = &theirs[..theirs.len() - common];
+        }
+
+        out.extend(prefix.iter().copied());
+        push_marker(out, '<', size, our_label);
+        push_lines(out, ours);
+        if self.style != ConflictStyle::Merge {
+            push_marker(out, '|', size, &self.base_label);
+            push_lines(out, base);
+        }
+        push_marker(out, '=', size, "");
+        push_lines(out, theirs);
+        push_marker(out, '>', size, their_label);
+        out.extend(suffix.iter().copied());
+    }
+}
+
+/// Append a marker line, with its label if any
+fn push_marker(out: &mut String, marker: char, size: usize, label: &str) {
+    out.extend(std::iter::repeat_n(marker, size));
+    if !label.is_empty() {
+        out.push(' ');
+        out.push_str(label);
+    }
+    out.push('\n');
+}
+
+/// Split text into lines, keeping their line endings
+fn split_lines(text: &str) -> Vec<&str> {
+    text.split_inclusive('\n').collect()
+}
+
+/// Append lines inside a conflict hunk, ending the last one
+fn push_lines(out: &mut String, lines: &[&str]) {
+    for line in lines {
+        out.push_str(line);
+        if !line.ends_with('\n') {
+            out.push('\n');
+        }
     }
 }
 
+/// A region of a line-by-line text merge
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeHunk {
+    /// Merged lines
+    Resolved(String),
+    /// Lines both sides changed differently (each side's lines, with line endings)
+    Conflict {
+        base: String,
+        ours: String,
+        theirs: String,
+    },
+}
+
 /// Represents a file with merge conflicts
 #[derive(Debug, Clone)]
 pub struct FileConflict {
@@ -32,6 +240,10 @@ pub struct FileConflict {
     pub their_content: Option<Vec<u8>>,
     /// Whether the file is binary
     pub is_binary: bool,
+    /// Base, our and their file modes (for the index stages)
+    pub modes: [Option<u32>; 3],
+    /// Merged regions and conflicts of a text merge (empty: the whole file conflicts)
+    pub hunks: Vec<MergeHunk>,
 }
 
 impl FileConflict {
@@ -43,11 +255,31 @@ impl FileConflict {
             our_content: None,
             their_content: None,
             is_binary: false,
+            modes: [None; 3],
+            hunks: Vec::new(),
+        }
+    }
+
+    /// Mode for the conflicted working tree file
+    ///
+    /// Our mode is kept (theirs if we deleted the file), except that
+    /// conflict markers are never written into a symlink.
+    pub fn worktree_mode(&self) -> u32 {
+        let mode = self.modes[1].or(self.modes[2]).unwrap_or(MODE_FILE);
+        if mode == MODE_SYMLINK && self.our_content != self.their_content {
+            MODE_FILE
+        } else {
+            mode
         }
     }
 
     /// Generate content with conflict markers
-    pub fn generate_conflict_markers(&self, our_branch: &str, their_branch: &str) -> Vec<u8> {
+    pub fn generate_conflict_markers(
+        &self,
+        our_branch: &str,
+        their_branch: &str,
+        options: &MarkerOptions,
+    ) -> Vec<u8> {
         if self.is_binary {
             return format!(
                 "Binary file conflict in {}\n\
@@ -57,34 +289,46 @@ impl FileConflict {
             .into_bytes();
         }
 
-        let mut result = Vec::new();
-
-        let our_text = self
-            .our_content
-            .as_ref()
-            .map(|c| String::from_utf8_lossy(c).to_string())
-            .unwrap_or_default();
-
-        let their_text = self
-            .their_content
-            .as_ref()
-            .map(|c| String::from_utf8_lossy(c).to_string())
-            .unwrap_or_default();
-
-        // Generate conflict markers
-        result.extend_from_slice(format!("<<<<<<< {}\n", our_branch).as_bytes());
-        result.extend_from_slice(our_text.as_bytes());
-        if !our_text.ends_with('\n') {
-            result.push(b'\n');
+        // Only the modes conflict: the content needs no markers
+        if self.our_content.is_some() && self.our_content == self.their_content {
+            return self.our_content.clone().unwrap_or_default();
         }
-        result.extend_from_slice(b"=======\n");
-        result.extend_from_slice(their_text.as_bytes());
-        if !their_text.ends_with('\n') {
-            result.push(b'\n');
+
+        // Markers only around the regions that conflict
+        if !self.hunks.is_empty() {
+            let mut result = String::new();
+            for hunk in &self.hunks {
+                match hunk {
+                    MergeHunk::Resolved(text) => result.push_str(text),
+                    MergeHunk::Conflict { base, ours, theirs } => options.write_hunk(
+                        &mut result,
+                        &self.path,
+                        [&split_lines(base), &split_lines(ours), &split_lines(theirs)],
+                        [our_branch, their_branch],
+                    ),
+                }
+            }
+            return result.into_bytes();
         }
-        result.extend_from_slice(format!(">>>>>>> {}\n", their_branch).as_bytes());
 
-        result
+        let text = |content: &Option<Vec<u8>>| {
+            content
+                .as_ref()
+                .map(|c| String::from_utf8_lossy(c).to_string())
+                .unwrap_or_default()
+        };
+        let base_text = text(&self.base_content);
+        let our_text = text(&self.our_content);
+        let their_text = text(&self.their_content);
+
+        let mut result = String::new();
+        options.write_hunk(
+            &mut result,
+            &self.path,
+            [&split_lines(&base_text), &split_lines(&our_text), &split_lines(&their_text)],
+            [our_branch, their_branch],
+        );
+        result.into_bytes()
     }
 }
 
@@ -107,6 +351,8 @@ pub struct ConflictMarker {
     pub end_line: usize,
     /// Our version lines
     pub our_lines: Vec<String>,
+    /// Base version lines (diff3 and zdiff3 styles only)
+    pub base_lines: Vec<String>,
     /// Their version lines
     pub their_lines: Vec<String>,
 }
@@ -118,13 +364,58 @@ impl ConflictMarker {
             start_line,
             end_line: 0,
             our_lines: Vec::new(),
+            base_lines: Vec::new(),
             their_lines: Vec::new(),
         }
     }
 
+    /// Length of a conflict marker line made of `marker` characters: at least
+    /// `MIN_MARKER_SIZE` of them, then nothing or a space and a label
+    pub fn marker_size(line: &str, marker: char) -> Option<usize> {
+        let line = line.trim_end_matches(['\n', '\r']);
+        let rest = line.trim_start_matches(marker);
+        let size = line.len() - rest.len();
+        (size >= MIN_MARKER_SIZE && (rest.is_empty() || rest.starts_with(' '))).then_some(size)
+    }
+
+    /// Find the complete conflict hunks in content, in any conflict style
+    ///
+    /// The opening marker sets the marker size (which `conflict-marker-size`
+    /// may change); the other markers of the hunk must have the same size.
+    pub fn parse(content: &str) -> Vec<ConflictMarker> {
+        let mut markers = Vec::new();
+        let mut current: Option<(ConflictMarker, char, usize)> = None;
+
+        for (number, line) in content.lines().enumerate() {
+            let Some((marker, section, size)) = current.as_mut() else {
+                if let Some(size) = Self::marker_size(line, '<') {
+                    current = Some((ConflictMarker::new(number), '<', size));
+                }
+                continue;
+            };
+            let is_marker = |c: char| Self::marker_size(line, c) == Some(*size);
+
+            match *section {
+                '<' if is_marker('|') => *section = '|',
+                '<' | '|' if is_marker('=') => *section = '=',
+                '=' if is_marker('>') => {
+                    if let Some((mut marker, _, _)) = current.take() {
+                        marker.end_line = number;
+                        markers.push(marker);
+                    }
+                }
+                '<' => marker.our_lines.push(line.to_string()),
+                '|' => marker.base_lines.push(line.to_string()),
+                _ => marker.their_lines.push(line.to_string()),
+            }
+        }
+
+        markers
+    }
+
     /// Check if content has unresolved conflicts
     pub fn has_conflicts(content: &str) -> bool {
-        content.contains("<<<<<<<") && content.contains(">>>>>>>")
+        !Self::parse(content).is_empty()
     }
 }
 
@@ -135,6 +426,8 @@ pub enum FileMergeResult {
     Success { content: Vec<u8> },
     /// File has conflicts
     Conflict { conflict: FileConflict },
+    /// File was deleted on one side and left unchanged on the other
+    Deleted,
     /// No changes needed
     Unchanged,
 }
@@ -149,7 +442,7 @@ mod tests {
         conflict.our_content = Some(b"our version\n".to_vec());
         conflict.their_content = Some(b"their version\n".to_vec());
 
-        let result = conflict.generate_conflict_markers("HEAD", "feature");
+        let result = conflict.generate_conflict_markers("HEAD", "feature", &MarkerOptions::default());
         let result_str = String::from_utf8(result).unwrap();
 
         assert!(result_str.contains("<<<<<<< HEAD"));
@@ -159,6 +452,54 @@ mod tests {
         assert!(result_str.contains("their version"));
     }
 
+    #[test]
+    fn test_conflict_styles() {
+        let mut conflict = FileConflict::new("test.txt".to_string());
+        conflict.base_content = Some(b"a\nb\nz\n".to_vec());
+        conflict.our_content = Some(b"a\nours\nz\n".to_vec());
+        conflict.their_content = Some(b"a\ntheirs\nz".to_vec());
+
+        let mut options = MarkerOptions {
+            style: ConflictStyle::Diff3,
+            base_label: "base".to_string(),
+            attributes: Attributes::parse("*.txt conflict-marker-size=9\n"),
+        };
+        let generate = |options: &MarkerOptions| {
+            String::from_utf8(conflict.generate_conflict_markers("HEAD", "topic", options)).unwrap()
+        };
+
+        assert_eq!(
+            generate(&options),
+            "<<<<<<<<< HEAD\na\nours\nz\n||||||||| base\na\nb\nz\n=========\na\ntheirs\nz\n>>>>>>>>> topic\n"
+        );
+
+        options.style = ConflictStyle::Zdiff3;
+        assert_eq!(
+            generate(&options),
+            "a\n<<<<<<<<< HEAD\nours\nz\n||||||||| base\na\nb\nz\n=========\ntheirs\nz\n>>>>>>>>> topic\n"
+        );
+
+        options.style = ConflictStyle::Merge;
+        let merged = generate(&options);
+        assert!(!merged.contains("|||||||"));
+        assert!(ConflictMarker::has_conflicts(&merged));
+        assert!(ConflictStyle::parse("bogus").is_err());
+    }
+
+    #[test]
+    fn test_strategy_options() {
+        let mut options = StrategyOptions::default();
+        options.apply("theirs").unwrap();
+        options.apply("ignore-space-change").unwrap();
+        options.apply("diff-algorithm=histogram").unwrap();
+
+        assert_eq!(options.favor, Some(Favor::Theirs));
+        assert!(options.ignore_space_change);
+        assert_eq!(options.diff_algorithm, DiffAlgorithm::Histogram);
+        assert!(options.apply("bogus").is_err());
+        assert_eq!(MergeStrategy::parse("ort").unwrap(), MergeStrategy::Recursive);
+    }
+
     #[test]
     fn test_has_conflicts() {
         let content = "<<<<<<< HEAD\nour\n=======\ntheir\n>>>>>>> branch";
@@ -166,5 +507,19 @@ mod tests {
 
         let clean = "no conflicts here";
         assert!(!ConflictMarker::has_conflicts(clean));
+
+        let diff3 = "<<<<<<<<<< HEAD\nour\n|||||||||| base\nbase\n==========\ntheir\n>>>>>>>>>> branch\n";
+        let markers = ConflictMarker::parse(diff3);
+        assert_eq!(markers.len(), 1);
+        assert_eq!(markers[0].base_lines, vec!["base"]);
+        assert_eq!(markers[0].their_lines, vec!["their"]);
+
+        // Markers must start a line and be complete
+        assert!(!ConflictMarker::has_conflicts("x <<<<<<< y\n=======\n>>>>>>> z\n"));
+        assert!(!ConflictMarker::has_conflicts("<<<<<<< HEAD\nour\n=======\n"));
+
+        // Smaller markers from `conflict-marker-size`, but not mixed sizes
+        assert!(ConflictMarker::has_conflicts("<<<< HEAD\nour\n====\ntheir\n>>>> branch\n"));
+        assert!(!ConflictMarker::has_conflicts("<<<< HEAD\nour\n=======\ntheir\n>>>> branch\n"));
     }
 }
@@ -0,0 +1,296 @@
+/// Working tree safety checks for merges
+///
+/// A merge rewrites every path whose result differs from HEAD. Before
+/// anything is written, those paths must not have local changes (staged or
+/// not) and must not be untracked files that would be overwritten. A merge
+/// that records a commit also needs an index matching HEAD.
+///
+/// `--autostash` is the alternative: all local changes are saved as a
+/// commit on top of HEAD (recorded in `.kitcat/MERGE_AUTOSTASH`), the
+/// working tree is reset, and the changes are merged back once the merge
+/// is done. If that conflicts, the stash is kept as `refs/stash`.
+
+use super::get_commit_files;
+use super::three_way::{merge_trees, MergeResult};
+use super::types::{MarkerOptions, StrategyOptions};
+use crate::diff::blob_hash;
+use crate::error::{KitcatError, Result};
+use crate::index::{add_file_to_index, read_index_binary};
+use crate::models::{FileEntry, IndexEntry};
+use crate::object::{create_commit, read_commit, write_blob, write_tree_from_files};
+use crate::utils::{file_mode, read_worktree_file, worktree_path_exists, write_worktree_file};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+
+/// Where an autostash is recorded while a merge runs
+const AUTOSTASH_FILE: &str = ".kitcat/MERGE_AUTOSTASH";
+/// Where an autostash that could not be applied cleanly is kept
+const STASH_REF: &str = ".kitcat/refs/stash";
+
+/// Paths whose entry differs between two flattened trees
+pub fn changed_paths(
+    old_files: &HashMap<String, FileEntry>,
+    new_files: &HashMap<String, FileEntry>,
+) -> BTreeSet<String> {
+    old_files
+        .keys()
+        .chain(new_files.keys())
+        .filter(|path| old_files.get(*path) != new_files.get(*path))
+        .cloned()
+        .collect()
+}
+
+/// Paths a merge result would write or remove, compared with our files
+pub fn touched_paths(our_files: &HashMap<String, FileEntry>, result: &MergeResult) -> BTreeSet<String> {
+    let mut paths: BTreeSet<String> = result
+        .merged_files
+        .iter()
+        .filter(|(path, content)| {
+            let merged = FileEntry::new(result.merged_modes[*path], blob_hash(content));
+            our_files.get(*path) != Some(&merged)
+        })
+        .map(|(path, _)| path.clone())
+        .collect();
+    paths.extend(result.conflicts.iter().map(|c| c.path.clone()));
+    paths.extend(result.deleted_files.iter().cloned());
+    paths
+}
+
+/// Refuse to merge if any of `paths` has local changes or is an untracked file in the way
+///
+/// Every offending path is listed in the error.
+pub fn check_paths(head_files: &HashMap<String, FileEntry>, paths: &BTreeSet<String>) -> Result<()> {
+    let index = stage0_index()?;
+
+    let mut modified = Vec::new();
+    let mut untracked = Vec::new();
+    for path in paths {
+        match path_state(path, head_files.get(path), index.get(path))? {
+            PathState::Clean => {}
+            PathState::Modified => modified.push(path.as_str()),
+            PathState::Untracked => untracked.push(path.as_str()),
+        }
+    }
+
+    if modified.is_empty() && untracked.is_empty() {
+        return Ok(());
+    }
+
+    let mut message = String::new();
+    if !modified.is_empty() {
+        message.push_str(&format!(
+            "Your local changes to the following files would be overwritten by merge:\n\t{}\n\
+             Please commit your changes or stash them before you merge (or use --autostash).\n",
+            modified.join("\n\t")
+        ));
+    }
+    if !untracked.is_empty() {
+        message.push_str(&format!(
+            "The following untracked working tree files would be overwritten by merge:\n\t{}\n\
+             Please move or remove them before you merge.\n",
+            untracked.join("\n\t")
+        ));
+    }
+    message.push_str("Aborting");
+
+    Err(KitcatError::InvalidInput(message))
+}
+
+/// Refuse to record a merge commit while the index differs from HEAD
+///
+/// The merge commit is written from the index, so staged changes would
+/// otherwise end up in it.
+pub fn check_index_matches_head(head_files: &HashMap<String, FileEntry>) -> Result<()> {
+    let index = stage0_index()?;
+
+    let staged: BTreeSet<&str> = head_files
+        .keys()
+        .chain(index.keys())
+        .filter(|path| {
+            let staged = index.get(*path).map(|e| FileEntry::new(e.mode, e.hash.clone()));
+            head_files.get(*path) != staged.as_ref()
+        })
+        .map(|path| path.as_str())
+        .collect();
+
+    if staged.is_empty() {
+        return Ok(());
+    }
+
+    Err(KitcatError::InvalidInput(format!(
+        "Your local changes to the following files would be overwritten by merge:\n\t{}\n\
+         Please commit your changes or stash them before you merge (or use --autostash).\n\
+         Aborting",
+        staged.into_iter().collect::<Vec<_>>().join("\n\t")
+    )))
+}
+
+/// Save all local changes to tracked files and reset them to HEAD
+///
+/// Returns the stash commit, or `None` if there was nothing to save.
+pub fn create_autostash(our_commit: &str, our_branch: &str) -> Result<Option<String>> {
+    let head_files = get_commit_files(our_commit)?;
+    let index = stage0_index()?;
+
+    let mut candidates: BTreeSet<&String> = head_files.keys().collect();
+    candidates.extend(index.keys());
+
+    let mut stashed = head_files.clone();
+    let mut any_changes = false;
+    for path in candidates {
+        if path_state(path, head_files.get(path), index.get(path))? != PathState::Modified {
+            continue;
+        }
+        any_changes = true;
+        match fs::symlink_metadata(path) {
+            Ok(metadata) => {
+                let hash = write_blob(&read_worktree_file(path)?)?;
+                stashed.insert(path.clone(), FileEntry::new(file_mode(&metadata), hash));
+            }
+            Err(_) => {
+                stashed.remove(path);
+            }
+        }
+    }
+
+    if !any_changes {
+        return Ok(None);
+    }
+
+    let tree = write_tree_from_files(&stashed)?;
+    let message = format!("On {}: autostash", our_branch);
+    let stash = create_commit(&tree, &[our_commit.to_string()], &message)?;
+    fs::write(AUTOSTASH_FILE, &stash)?;
+
+    crate::commands::checkout::reset_to_commit(our_commit)?;
+    println!("Created autostash: {}", &stash[..7]);
+
+    Ok(Some(stash))
+}
+
+/// Whether an autostash is waiting to be applied
+pub fn has_autostash() -> bool {
+    Path::new(AUTOSTASH_FILE).exists()
+}
+
+/// Merge a pending autostash back into the working tree (HEAD must be checked out cleanly)
+///
+/// Does nothing without an autostash. Conflicts are written with markers
+/// and the stash is kept as `refs/stash`.
+pub fn apply_autostash(head_commit: &str) -> Result<()> {
+    if !has_autostash() {
+        return Ok(());
+    }
+    let stash = fs::read_to_string(AUTOSTASH_FILE)?.trim().to_string();
+    fs::remove_file(AUTOSTASH_FILE)?;
+
+    let stash_commit = read_commit(&stash)?;
+    let base_files = match stash_commit.parents.first() {
+        Some(parent) => get_commit_files(parent)?,
+        None => HashMap::new(),
+    };
+    let our_files = get_commit_files(head_commit)?;
+    let stash_files = get_commit_files(&stash)?;
+
+    let result = merge_trees(&base_files, &our_files, &stash_files, &StrategyOptions::default())?;
+
+    for path in touched_paths(&our_files, &result) {
+        if let Some(content) = result.merged_files.get(&path) {
+            write_worktree_file(&path, content, result.merged_modes[&path])?;
+            // Files the stash added stay tracked
+            if !our_files.contains_key(&path) {
+                add_file_to_index(&path, &write_blob(content)?)?;
+            }
+        } else if result.deleted_files.contains(&path) && worktree_path_exists(&path) {
+            fs::remove_file(&path)?;
+        }
+    }
+    let marker_options = MarkerOptions::load(None, "Stash base")?;
+    for conflict in &result.conflicts {
+        let content =
+            conflict.generate_conflict_markers("Updated upstream", "Stashed changes", &marker_options);
+        write_worktree_file(&conflict.path, &content, conflict.worktree_mode())?;
+    }
+
+    if result.has_conflicts() {
+        fs::create_dir_all(".kitcat/refs")?;
+        fs::write(STASH_REF, &stash)?;
+        println!("Applying autostash resulted in conflicts.");
+        println!("Your changes are safe in the stash ({}).", &stash[..7]);
+    } else {
+        println!("Applied autostash.");
+    }
+
+    Ok(())
+}
+
+/// State of one path compared with HEAD, the index and the working tree
+#[derive(Debug, PartialEq, Eq)]
+enum PathState {
+    /// Working tree, index and HEAD agree (or the path exists nowhere)
+    Clean,
+    /// Staged or unstaged changes to a tracked path
+    Modified,
+    /// A working tree file that is neither tracked nor in HEAD
+    Untracked,
+}
+
+/// Classify a path for the merge pre-checks
+fn path_state(path: &str, head: Option<&FileEntry>, index: Option<&IndexEntry>) -> Result<PathState> {
+    let working = match fs::symlink_metadata(path) {
+        Ok(metadata) => Some(FileEntry::new(
+            file_mode(&metadata),
+            blob_hash(&read_worktree_file(path)?),
+        )),
+        Err(_) => None,
+    };
+
+    let Some(index) = index else {
+        return Ok(match (head, working) {
+            (None, None) => PathState::Clean,
+            (None, Some(_)) => PathState::Untracked,
+            // Staged deletion (and maybe an untracked file put back)
+            (Some(_), _) => PathState::Modified,
+        });
+    };
+
+    let staged = FileEntry::new(index.mode, index.hash.clone());
+    if head != Some(&staged) || working.as_ref() != Some(&staged) {
+        Ok(PathState::Modified)
+    } else {
+        Ok(PathState::Clean)
+    }
+}
+
+/// Stage 0 index entries by path
+fn stage0_index() -> Result<HashMap<String, IndexEntry>> {
+    Ok(read_index_binary()?
+        .into_iter()
+        .filter(|e| !e.is_unmerged())
+        .map(|e| (e.path.clone(), e))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(entries: &[(&str, &str)]) -> HashMap<String, FileEntry> {
+        entries
+            .iter()
+            .map(|(path, hash)| (path.to_string(), FileEntry::new(0o100644, hash.to_string())))
+            .collect()
+    }
+
+    #[test]
+    fn test_changed_paths() {
+        let old = files(&[("a", "1"), ("b", "2"), ("c", "3")]);
+        let mut new = files(&[("a", "1"), ("b", "9"), ("d", "4")]);
+        new.get_mut("a").unwrap().mode = 0o100755;
+
+        let changed: Vec<String> = changed_paths(&old, &new).into_iter().collect();
+        assert_eq!(changed, vec!["a", "b", "c", "d"]);
+        assert!(changed_paths(&old, &old).is_empty());
+    }
+}
@@ -74,10 +74,43 @@ impl IndexEntry {
         }
     }
 
+    /// Create a stage 0 entry for a blob without working tree metadata
+    pub fn from_blob(path: String, hash: String, mode: u32) -> Self {
+        Self::conflict_stage(path, hash, mode, 0)
+    }
+
+    /// Create an entry for one side of a merge conflict (no working tree metadata)
+    ///
+    /// Stage 1 is the common ancestor, stage 2 is ours and stage 3 is theirs.
+    pub fn conflict_stage(path: String, hash: String, mode: u32, stage: u16) -> Self {
+        let name_len = std::cmp::min(path.len(), 0xFFF) as u16;
+
+        Self {
+            ctime_sec: 0,
+            ctime_nsec: 0,
+            mtime_sec: 0,
+            mtime_nsec: 0,
+            dev: 0,
+            ino: 0,
+            mode,
+            uid: 0,
+            gid: 0,
+            size: 0,
+            hash,
+            flags: ((stage & 0x3) << 12) | name_len,
+            path,
+        }
+    }
+
     /// Get the stage of this entry (0 = normal, 1 = base, 2 = ours, 3 = theirs)
     pub fn stage(&self) -> u16 {
         (self.flags >> 12) & 0x3
     }
+
+    /// Whether this entry is one side of an unresolved conflict
+    pub fn is_unmerged(&self) -> bool {
+        self.stage() != 0
+    }
 }
 
 /// Tree entry (for future tree implementation)
@@ -93,6 +126,34 @@ pub struct TreeEntry {
     pub is_tree: bool,
 }
 
+/// Mode of a regular file
+pub const MODE_FILE: u32 = 0o100644;
+/// Mode of an executable file
+pub const MODE_EXECUTABLE: u32 = 0o100755;
+/// Mode of a symbolic link (the blob holds the link target)
+pub const MODE_SYMLINK: u32 = 0o120000;
+
+/// A file in a flattened tree (path -> entry maps)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileEntry {
+    /// File mode (`MODE_FILE`, `MODE_EXECUTABLE` or `MODE_SYMLINK`)
+    pub mode: u32,
+    /// SHA-1 hash of the blob (40 hex characters)
+    pub hash: String,
+}
+
+impl FileEntry {
+    /// Create a file entry
+    pub fn new(mode: u32, hash: String) -> Self {
+        Self { mode, hash }
+    }
+
+    /// Create a file entry from a tree entry's octal mode string
+    pub fn from_tree_mode(mode: &str, hash: String) -> Self {
+        Self::new(u32::from_str_radix(mode, 8).unwrap_or(MODE_FILE), hash)
+    }
+}
+
 /// Commit object (for future commit implementation)
 #[derive(Debug, Clone)]
 pub struct Commit {
@@ -104,10 +165,33 @@ pub struct Commit {
     pub author: String,
     /// Author timestamp
     pub author_time: i64,
+    /// Author timezone offset, e.g. "+0530"
+    pub author_timezone: String,
     /// Committer name and email
     pub committer: String,
     /// Committer timestamp
     pub committer_time: i64,
+    /// Committer timezone offset, e.g. "-0800"
+    pub committer_timezone: String,
     /// Commit message
     pub message: String,
 }
+
+/// Annotated tag object
+#[derive(Debug, Clone)]
+pub struct Tag {
+    /// SHA-1 hash of the tagged object
+    pub object: String,
+    /// Type of the tagged object (usually "commit")
+    pub object_type: String,
+    /// Tag name
+    pub tag: String,
+    /// Tagger name and email
+    pub tagger: String,
+    /// Tagger timestamp
+    pub tagger_time: i64,
+    /// Tagger timezone offset, e.g. "+0200"
+    pub tagger_timezone: String,
+    /// Tag message
+    pub message: String,
+}
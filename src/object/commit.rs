@@ -1,21 +1,113 @@
 use crate::config::Config;
+use crate::date::{parse_date_with_offset, parse_offset};
+use crate::error::{KitcatError, Result};
 use crate::models::Commit;
-use crate::utils::{compress_data, compute_hash, decompress_data};
-use chrono::{DateTime, Local, TimeZone};
+use crate::object::read_object::read_object_data;
+use crate::signing::{sign_payload, SignRequest};
+use crate::utils::{compress_data, compute_hash, decompress};
+use chrono::{DateTime, Local};
+use std::env;
 use std::fs;
-use std::io;
 use std::path::Path;
 
-/// Create a commit object
+/// Identity and time recorded on a commit's `author` or `committer` line
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signature {
+    /// Name and email as "Name <email>"
+    pub identity: String,
+    /// Seconds since the Unix epoch
+    pub time: i64,
+    /// Timezone offset, e.g. "+0530"
+    pub timezone: String,
+}
+
+impl Signature {
+    /// The author: `KITCAT_AUTHOR_NAME`, `KITCAT_AUTHOR_EMAIL` and
+    /// `KITCAT_AUTHOR_DATE`, falling back to the configured user and now
+    pub fn author() -> Result<Self> {
+        Self::from_env("AUTHOR", &Config::read()?, |name| env::var(name).ok())
+    }
+
+    /// The committer, from the `KITCAT_COMMITTER_*` variables like `author`
+    pub fn committer() -> Result<Self> {
+        Self::from_env("COMMITTER", &Config::read()?, |name| env::var(name).ok())
+    }
+
+    /// Build a signature for `role` from `KITCAT_<role>_*` variables looked up with `var`
+    fn from_env(role: &str, config: &Config, var: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let name = var(&format!("KITCAT_{}_NAME", role)).unwrap_or_else(|| config.user.name.clone());
+        let email = var(&format!("KITCAT_{}_EMAIL", role)).unwrap_or_else(|| config.user.email.clone());
+        let (time, timezone) = match var(&format!("KITCAT_{}_DATE", role)) {
+            Some(date) => parse_date_with_offset(&date)?,
+            None => (Local::now().timestamp(), get_timezone_offset()),
+        };
+
+        Ok(Signature {
+            identity: format!("{} <{}>", name, email),
+            time,
+            timezone,
+        })
+    }
+
+    /// Replace the identity with a `Name <email>` given on the command line
+    pub fn with_identity(mut self, identity: &str) -> Result<Self> {
+        let valid = identity
+            .split_once('<')
+            .is_some_and(|(name, rest)| !name.trim().is_empty() && rest.ends_with('>'));
+        if !valid {
+            return Err(KitcatError::InvalidInput(format!("--author '{}' is not 'Name <email>'", identity)));
+        }
+        self.identity = identity.trim().to_string();
+        Ok(self)
+    }
+
+    /// Replace the time with a date given on the command line
+    pub fn with_date(mut self, date: &str) -> Result<Self> {
+        (self.time, self.timezone) = parse_date_with_offset(date)?;
+        Ok(self)
+    }
+
+    /// `Name <email> <timestamp> <timezone>`, as written in the object
+    pub fn format(&self) -> String {
+        format!("{} {} {}", self.identity, self.time, self.timezone)
+    }
+}
+
+/// Create a commit object authored and committed by the current user
 pub fn create_commit(
     tree_hash: &str,
     parent_hashes: &[String],
     message: &str,
-) -> io::Result<String> {
+) -> Result<String> {
+    create_commit_with_author(tree_hash, parent_hashes, message, &Signature::author()?)
+}
+
+/// Create a commit object with an explicit author (e.g. when applying patches)
+///
+/// The committer is the current user (see `Signature::committer`). The
+/// commit is signed when `commit.gpgSign` is set.
+pub fn create_commit_with_author(
+    tree_hash: &str,
+    parent_hashes: &[String],
+    message: &str,
+    author: &Signature,
+) -> Result<String> {
+    create_signed_commit(tree_hash, parent_hashes, message, author, &SignRequest::Config)
+}
+
+/// Create a commit object, signing it as `sign` asks
+///
+/// The signature covers the commit without its `gpgsig` header, which is
+/// inserted after the committer line.
+pub fn create_signed_commit(
+    tree_hash: &str,
+    parent_hashes: &[String],
+    message: &str,
+    author: &Signature,
+    sign: &SignRequest,
+) -> Result<String> {
     let config = Config::read()?;
-    let author = config.get_user_string();
-    let timestamp = Local::now().timestamp();
-    let timezone = get_timezone_offset();
+    let committer = Signature::committer()?;
 
     // Build commit content
     let mut content = String::new();
@@ -25,14 +117,17 @@ pub fn create_commit(
         content.push_str(&format!("parent {}\n", parent));
     }
 
-    content.push_str(&format!("author {} {} {}\n", author, timestamp, timezone));
-    content.push_str(&format!(
-        "committer {} {} {}\n",
-        author, timestamp, timezone
-    ));
-    content.push_str("\n");
+    content.push_str(&format!("author {}\n", author.format()));
+    content.push_str(&format!("committer {}\n", committer.format()));
+    let headers_end = content.len();
+    content.push('\n');
     content.push_str(message);
-    content.push_str("\n");
+    content.push('\n');
+
+    if sign.wants(config.commit.gpg_sign) {
+        let signature = sign_payload(&config, content.as_bytes(), sign.key(), &committer.identity)?;
+        content.insert_str(headers_end, &signature_header(&signature));
+    }
 
     // Create commit object with header
     let header = format!("commit {}\0", content.len());
@@ -49,26 +144,23 @@ pub fn create_commit(
 }
 
 /// Read a commit object
-pub fn read_commit(hash: &str) -> io::Result<Commit> {
+pub fn read_commit(hash: &str) -> Result<Commit> {
     let content = read_object_content(hash)?;
     parse_commit(&content)
 }
 
 /// Parse commit content
-fn parse_commit(data: &[u8]) -> io::Result<Commit> {
+fn parse_commit(data: &[u8]) -> Result<Commit> {
     // Find the null byte after the header
     let null_pos = data
         .iter()
         .position(|&b| b == 0)
-        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid commit object"))?;
+        .ok_or_else(|| KitcatError::CorruptObject("Invalid commit object".to_string()))?;
 
     // Verify this is a commit object
     let header = String::from_utf8_lossy(&data[0..null_pos]);
     if !header.starts_with("commit ") {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidData,
-            "Not a commit object",
-        ));
+        return Err(KitcatError::CorruptObject("Not a commit object".to_string()));
     }
 
     let content = String::from_utf8_lossy(&data[null_pos + 1..]);
@@ -78,8 +170,10 @@ fn parse_commit(data: &[u8]) -> io::Result<Commit> {
     let mut parents = Vec::new();
     let mut author = String::new();
     let mut author_time = 0i64;
+    let mut author_timezone = String::new();
     let mut committer = String::new();
     let mut committer_time = 0i64;
+    let mut committer_timezone = String::new();
     let mut message_start = 0;
 
     for (i, line) in lines.iter().enumerate() {
@@ -94,13 +188,15 @@ fn parse_commit(data: &[u8]) -> io::Result<Commit> {
             parents.push(parent_hash.to_string());
         } else if let Some(author_line) = line.strip_prefix("author ") {
             let parts: Vec<&str> = author_line.rsplitn(3, ' ').collect();
-            if parts.len() >= 2 {
+            if parts.len() == 3 {
+                author_timezone = parts[0].to_string();
                 author_time = parts[1].parse().unwrap_or(0);
                 author = parts[2].to_string();
             }
         } else if let Some(committer_line) = line.strip_prefix("committer ") {
             let parts: Vec<&str> = committer_line.rsplitn(3, ' ').collect();
-            if parts.len() >= 2 {
+            if parts.len() == 3 {
+                committer_timezone = parts[0].to_string();
                 committer_time = parts[1].parse().unwrap_or(0);
                 committer = parts[2].to_string();
             }
@@ -114,14 +210,56 @@ fn parse_commit(data: &[u8]) -> io::Result<Commit> {
         parents,
         author,
         author_time,
+        author_timezone,
         committer,
         committer_time,
+        committer_timezone,
         message,
     })
 }
 
+/// A signature as a `gpgsig` header, continuation lines indented by a space
+fn signature_header(signature: &str) -> String {
+    format!("gpgsig {}\n", signature.trim_end().replace('\n', "\n "))
+}
+
+/// The signed payload and signature of a commit, if it is signed
+pub fn read_commit_signature(hash: &str) -> Result<Option<(Vec<u8>, String)>> {
+    let (object_type, content) = read_object_data(hash)?;
+    if object_type != "commit" {
+        return Err(KitcatError::CorruptObject(format!("Object {} is a {}, not a commit", hash, object_type)));
+    }
+    Ok(split_signature(&String::from_utf8_lossy(&content)))
+}
+
+/// Separate the `gpgsig` header from the rest of a commit
+fn split_signature(content: &str) -> Option<(Vec<u8>, String)> {
+    let headers_end = content.find("\n\n").map_or(content.len(), |i| i + 1);
+    let (headers, body) = content.split_at(headers_end);
+
+    let mut payload = String::new();
+    let mut signature: Option<String> = None;
+    let mut in_signature = false;
+    for line in headers.split_inclusive('\n') {
+        if let Some(first) = line.strip_prefix("gpgsig ") {
+            signature = Some(first.to_string());
+            in_signature = true;
+        } else if let (true, Some(next), Some(signature)) =
+            (in_signature, line.strip_prefix(' '), signature.as_mut())
+        {
+            signature.push_str(next);
+        } else {
+            in_signature = false;
+            payload.push_str(line);
+        }
+    }
+    payload.push_str(body);
+
+    signature.map(|signature| (payload.into_bytes(), signature))
+}
+
 /// Store an object in the .kitcat/objects directory
-fn store_object(hash: &str, content: &[u8]) -> io::Result<()> {
+fn store_object(hash: &str, content: &[u8]) -> Result<()> {
     let dir_name = &hash[0..2];
     let file_name = &hash[2..];
 
@@ -143,7 +281,7 @@ fn store_object(hash: &str, content: &[u8]) -> io::Result<()> {
 }
 
 /// Read an object's content
-fn read_object_content(hash: &str) -> io::Result<Vec<u8>> {
+fn read_object_content(hash: &str) -> Result<Vec<u8>> {
     let dir_name = &hash[0..2];
     let file_name = &hash[2..];
 
@@ -152,14 +290,11 @@ fn read_object_content(hash: &str) -> io::Result<Vec<u8>> {
         .join(file_name);
 
     if !file_path.exists() {
-        return Err(io::Error::new(
-            io::ErrorKind::NotFound,
-            format!("Object {} not found", hash),
-        ));
+        return Err(KitcatError::ObjectNotFound(hash.to_string()));
     }
 
     let compressed = fs::read(file_path)?;
-    let decompressed = decompress_data(&compressed);
+    let decompressed = decompress(&compressed)?;
 
     Ok(decompressed)
 }
@@ -174,7 +309,7 @@ fn get_timezone_offset() -> String {
 }
 
 /// Display a commit in a human-readable format
-pub fn show_commit(hash: &str) -> io::Result<()> {
+pub fn show_commit(hash: &str) -> Result<()> {
     let commit = read_commit(hash)?;
 
     println!("commit {}", hash);
@@ -192,8 +327,11 @@ pub fn show_commit(hash: &str) -> io::Result<()> {
 
     println!("Author: {}", commit.author);
 
-    // Format timestamp
-    let dt = Local.timestamp_opt(commit.author_time, 0).unwrap();
+    // Format timestamp in the author's timezone
+    let offset = parse_offset(&commit.author_timezone).unwrap_or_else(|| *Local::now().offset());
+    let dt = DateTime::from_timestamp(commit.author_time, 0)
+        .unwrap_or_default()
+        .with_timezone(&offset);
     println!("Date:   {}", dt.format("%a %b %d %H:%M:%S %Y %z"));
 
     println!();
@@ -205,13 +343,23 @@ pub fn show_commit(hash: &str) -> io::Result<()> {
 }
 
 /// Get commit parents (for traversal)
-pub fn get_commit_parents(hash: &str) -> io::Result<Vec<String>> {
+///
+/// Answered from the commit-graph when the commit is in it.
+pub fn get_commit_parents(hash: &str) -> Result<Vec<String>> {
+    if let Some(commit) = super::commit_graph::lookup(hash) {
+        return Ok(commit.parents);
+    }
     let commit = read_commit(hash)?;
     Ok(commit.parents)
 }
 
 /// Get commit tree hash
-pub fn get_commit_tree(hash: &str) -> io::Result<String> {
+///
+/// Answered from the commit-graph when the commit is in it.
+pub fn get_commit_tree(hash: &str) -> Result<String> {
+    if let Some(commit) = super::commit_graph::lookup(hash) {
+        return Ok(commit.tree);
+    }
     let commit = read_commit(hash)?;
     Ok(commit.tree)
 }
@@ -237,4 +385,56 @@ mod tests {
         assert_eq!(commit.parents[0], "def456");
         assert!(commit.message.contains("Initial commit"));
     }
+
+    #[test]
+    fn test_parse_commit_keeps_timezones() {
+        let content = b"commit 200\0tree abc123\nauthor Jane Doe <jane@example.com> 1234567890 +0530\ncommitter John Roe <john@example.com> 1234567999 -0800\n\nFix\n";
+        let commit = parse_commit(content).unwrap();
+        assert_eq!(commit.author, "Jane Doe <jane@example.com>");
+        assert_eq!(commit.author_timezone, "+0530");
+        assert_eq!(commit.committer, "John Roe <john@example.com>");
+        assert_eq!(commit.committer_time, 1234567999);
+        assert_eq!(commit.committer_timezone, "-0800");
+    }
+
+    #[test]
+    fn test_split_signature() {
+        let payload = "tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904\nauthor A <a@example.com> 1 +0000\ncommitter A <a@example.com> 1 +0000\n\nSigned\n";
+        let signature = "-----BEGIN SSH SIGNATURE-----\nU1NIU0lH\n\n-----END SSH SIGNATURE-----\n";
+
+        let headers_end = payload.find("\n\n").unwrap() + 1;
+        let mut content = payload.to_string();
+        content.insert_str(headers_end, &signature_header(signature));
+        assert!(content.contains("\ngpgsig -----BEGIN SSH SIGNATURE-----\n U1NIU0lH\n \n -----END"));
+
+        let (unsigned, extracted) = split_signature(&content).unwrap();
+        assert_eq!(unsigned, payload.as_bytes());
+        assert_eq!(extracted, signature);
+        assert!(split_signature(payload).is_none());
+
+        // The signature header does not leak into the parsed commit
+        let object = format!("commit {}\0{}", content.len(), content);
+        let commit = parse_commit(object.as_bytes()).unwrap();
+        assert_eq!(commit.message, "Signed");
+    }
+
+    #[test]
+    fn test_signature_from_env() {
+        let config = Config::default();
+        let vars = |name: &str| match name {
+            "KITCAT_AUTHOR_NAME" => Some("Jane Doe".to_string()),
+            "KITCAT_AUTHOR_DATE" => Some("2024-01-15T10:30:00+05:30".to_string()),
+            _ => None,
+        };
+
+        let author = Signature::from_env("AUTHOR", &config, vars).unwrap();
+        assert_eq!(author.format(), "Jane Doe <unknown@example.com> 1705294800 +0530");
+
+        let committer = Signature::from_env("COMMITTER", &config, vars).unwrap();
+        assert_eq!(committer.identity, "Unknown <unknown@example.com>");
+
+        let author = author.with_identity("A U Thor <author@example.com>").unwrap();
+        assert_eq!(author.identity, "A U Thor <author@example.com>");
+        assert!(author.with_identity("no email").is_err());
+    }
 }
@@ -0,0 +1,536 @@
+/// Commit-graph file: parents, trees, dates and generation numbers of commits
+///
+/// `.kitcat/objects/info/commit-graph` uses Git's commit-graph layout so
+/// history walks can skip decompressing and parsing commit objects:
+///
+/// - header: `CGPH`, version 1, hash version 1 (SHA-1), chunk count, 0
+/// - chunk table: (id, offset) per chunk, then a terminating entry
+/// - `OIDF`: 256 cumulative counts of commits by first hash byte
+/// - `OIDL`: the sorted commit hashes
+/// - `CDAT`: per commit the root tree, two parent positions and
+///   generation number (30 bits) with commit time (34 bits)
+/// - `EDGE`: extra parents of octopus merges
+/// - trailer: SHA-1 of everything before it
+///
+/// The graph is closed under parents, so commits missing from it (made
+/// after it was written) are never ancestors of commits in it.
+use crate::error::{KitcatError, Result};
+use super::read_commit;
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Location of the commit-graph file
+pub const COMMIT_GRAPH_FILE: &str = ".kitcat/objects/info/commit-graph";
+
+const SIGNATURE: &[u8; 4] = b"CGPH";
+const HASH_LEN: usize = 20;
+/// Size of one `CDAT` record
+const DATA_LEN: usize = HASH_LEN + 16;
+/// Parent position meaning "no parent"
+const NO_PARENT: u32 = 0x7000_0000;
+/// Set on the second parent position when it indexes `EDGE`, and on the last edge
+const EXTRA_EDGES: u32 = 0x8000_0000;
+/// Largest generation number the file can hold
+const MAX_GENERATION: u32 = (1 << 30) - 1;
+
+/// A commit as recorded in the commit-graph
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphCommit {
+    /// Root tree hash
+    pub tree: String,
+    /// Parent hashes
+    pub parents: Vec<String>,
+    /// Committer timestamp
+    pub commit_time: i64,
+    /// 1 for root commits, otherwise one more than the largest parent generation
+    pub generation: u32,
+}
+
+/// A parsed commit-graph file
+#[derive(Debug, Clone)]
+pub struct CommitGraph {
+    data: Vec<u8>,
+    fanout: usize,
+    oids: usize,
+    commits: usize,
+    edges: Option<usize>,
+    count: usize,
+}
+
+impl CommitGraph {
+    /// Parse a commit-graph file, checking its header and chunk table
+    pub fn parse(data: Vec<u8>) -> Result<Self> {
+        let invalid = |message: &str| {
+            KitcatError::CorruptObject(format!("commit-graph: {}", message))
+        };
+
+        if data.len() < 8 + HASH_LEN || &data[..4] != SIGNATURE {
+            return Err(invalid("bad signature"));
+        }
+        if data[4] != 1 || data[5] != 1 {
+            return Err(invalid("unsupported version"));
+        }
+
+        let chunk_count = data[6] as usize;
+        let mut chunks = HashMap::new();
+        for i in 0..chunk_count {
+            let at = 8 + i * 12;
+            let entry = data
+                .get(at..at + 12)
+                .ok_or_else(|| invalid("truncated chunk table"))?;
+            let offset = u64::from_be_bytes(entry[4..12].try_into().unwrap()) as usize;
+            if offset > data.len() - HASH_LEN {
+                return Err(invalid("chunk offset out of range"));
+            }
+            chunks.insert(entry[..4].to_vec(), offset);
+        }
+
+        let chunk = |id: &[u8]| {
+            chunks
+                .get(id)
+                .copied()
+                .ok_or_else(|| invalid("missing chunk"))
+        };
+        let fanout = chunk(b"OIDF")?;
+        let oids = chunk(b"OIDL")?;
+        let commits = chunk(b"CDAT")?;
+        let edges = chunks.get(&b"EDGE"[..]).copied();
+
+        if fanout + 256 * 4 > data.len() {
+            return Err(invalid("truncated fanout"));
+        }
+        let count = read_u32(&data, fanout + 255 * 4) as usize;
+        if oids + count * HASH_LEN > data.len() || commits + count * DATA_LEN > data.len() {
+            return Err(invalid("truncated commit table"));
+        }
+
+        Ok(CommitGraph {
+            data,
+            fanout,
+            oids,
+            commits,
+            edges,
+            count,
+        })
+    }
+
+    /// Read the repository's commit-graph file, if there is one
+    pub fn read() -> Result<Option<Self>> {
+        match fs::read(COMMIT_GRAPH_FILE) {
+            Ok(data) => Ok(Some(Self::parse(data)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Number of commits in the graph
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    /// Look up a commit by hash
+    pub fn get(&self, hash: &str) -> Option<GraphCommit> {
+        let oid = hex_to_bytes(hash)?;
+        let first = oid[0] as usize;
+        let start = if first == 0 {
+            0
+        } else {
+            read_u32(&self.data, self.fanout + (first - 1) * 4) as usize
+        };
+        let end = read_u32(&self.data, self.fanout + first * 4) as usize;
+
+        let (mut low, mut high) = (start, end.min(self.count));
+        while low < high {
+            let mid = (low + high) / 2;
+            match self.oid(mid).cmp(&oid[..]) {
+                std::cmp::Ordering::Equal => return self.commit_at(mid),
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+            }
+        }
+        None
+    }
+
+    /// Hash of the commit at a position
+    fn oid(&self, position: usize) -> &[u8] {
+        let at = self.oids + position * HASH_LEN;
+        &self.data[at..at + HASH_LEN]
+    }
+
+    /// Hash of the commit at a position, as hex
+    fn hash_at(&self, position: usize) -> Option<String> {
+        (position < self.count).then(|| bytes_to_hex(self.oid(position)))
+    }
+
+    /// Decode the commit data at a position
+    fn commit_at(&self, position: usize) -> Option<GraphCommit> {
+        let at = self.commits + position * DATA_LEN;
+        let record = &self.data[at..at + DATA_LEN];
+
+        let mut parents = Vec::new();
+        let first = read_u32(record, HASH_LEN);
+        let second = read_u32(record, HASH_LEN + 4);
+        if first != NO_PARENT {
+            parents.push(self.hash_at(first as usize)?);
+        }
+        if second & EXTRA_EDGES != 0 {
+            let mut edge = self.edges? + (second & !EXTRA_EDGES) as usize * 4;
+            loop {
+                if edge + 4 > self.data.len() - HASH_LEN {
+                    return None;
+                }
+                let value = read_u32(&self.data, edge);
+                parents.push(self.hash_at((value & !EXTRA_EDGES) as usize)?);
+                if value & EXTRA_EDGES != 0 {
+                    break;
+                }
+                edge += 4;
+            }
+        } else if second != NO_PARENT {
+            parents.push(self.hash_at(second as usize)?);
+        }
+
+        let high = read_u32(record, HASH_LEN + 8);
+        let low = read_u32(record, HASH_LEN + 12);
+        Some(GraphCommit {
+            tree: bytes_to_hex(&record[..HASH_LEN]),
+            parents,
+            commit_time: (((high & 0x3) as i64) << 32) | low as i64,
+            generation: high >> 2,
+        })
+    }
+}
+
+/// The repository's commit-graph, read once per process
+fn cached_graph() -> Option<&'static CommitGraph> {
+    static GRAPH: OnceLock<Option<CommitGraph>> = OnceLock::new();
+    GRAPH
+        .get_or_init(|| CommitGraph::read().ok().flatten())
+        .as_ref()
+}
+
+/// Look up a commit in the repository's commit-graph
+pub fn lookup(hash: &str) -> Option<GraphCommit> {
+    cached_graph()?.get(hash)
+}
+
+/// Write a commit-graph with every commit reachable from `tips`
+///
+/// Tips that are not commits are skipped. Returns the number of commits written.
+pub fn write_commit_graph(tips: &[String]) -> Result<usize> {
+    let commits = collect_commits(tips)?;
+    let data = serialize(&commits);
+
+    let path = Path::new(COMMIT_GRAPH_FILE);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, data)?;
+
+    Ok(commits.len())
+}
+
+/// Check the commit-graph against its checksum and the commit objects
+///
+/// Returns the number of commits checked, or an error listing every problem.
+pub fn verify_commit_graph() -> Result<usize> {
+    let data = fs::read(COMMIT_GRAPH_FILE)?;
+    let mut problems = Vec::new();
+
+    let (body, trailer) = data.split_at(data.len().saturating_sub(HASH_LEN));
+    if Sha1::digest(body).as_slice() != trailer {
+        problems.push("checksum does not match".to_string());
+    }
+
+    let graph = CommitGraph::parse(data.clone())?;
+    for position in 0..graph.len() {
+        if position > 0 && graph.oid(position - 1) >= graph.oid(position) {
+            problems.push(format!(
+                "commit hashes out of order at position {}",
+                position
+            ));
+        }
+
+        let hash = bytes_to_hex(graph.oid(position));
+        let Some(recorded) = graph.commit_at(position) else {
+            problems.push(format!("commit {} has invalid parent positions", hash));
+            continue;
+        };
+        let commit = match read_commit(&hash) {
+            Ok(commit) => commit,
+            Err(e) => {
+                problems.push(format!("commit {} cannot be read: {}", hash, e));
+                continue;
+            }
+        };
+
+        if recorded.tree != commit.tree {
+            problems.push(format!(
+                "root tree for commit {} is {}, not {}",
+                hash, recorded.tree, commit.tree
+            ));
+        }
+        if recorded.parents != commit.parents {
+            problems.push(format!("parents for commit {} do not match", hash));
+        }
+        if recorded.commit_time != commit.committer_time.max(0) {
+            problems.push(format!("commit date for commit {} does not match", hash));
+        }
+
+        let expected = recorded
+            .parents
+            .iter()
+            .filter_map(|parent| graph.get(parent))
+            .map(|parent| parent.generation)
+            .max()
+            .map_or(1, |generation| (generation + 1).min(MAX_GENERATION));
+        if recorded.generation != expected {
+            problems.push(format!(
+                "generation for commit {} is {}, expected {}",
+                hash, recorded.generation, expected
+            ));
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(graph.len())
+    } else {
+        Err(KitcatError::CorruptObject(problems.join("\n")))
+    }
+}
+
+/// A commit to be written: hash, tree, parents and commit time
+#[derive(Debug, Clone)]
+struct GraphEntry {
+    hash: String,
+    tree: String,
+    parents: Vec<String>,
+    commit_time: i64,
+}
+
+/// All commits reachable from `tips`, sorted by hash
+fn collect_commits(tips: &[String]) -> Result<Vec<GraphEntry>> {
+    let mut commits: HashMap<String, GraphEntry> = HashMap::new();
+    let mut stack: Vec<String> = Vec::new();
+
+    for tip in tips {
+        // Only commits start a walk (refs may point at tags or trees)
+        if read_commit(tip).is_ok() {
+            stack.push(tip.clone());
+        }
+    }
+
+    while let Some(hash) = stack.pop() {
+        if commits.contains_key(&hash) {
+            continue;
+        }
+        let commit = read_commit(&hash)?;
+        stack.extend(commit.parents.iter().cloned());
+        commits.insert(
+            hash.clone(),
+            GraphEntry {
+                hash,
+                tree: commit.tree,
+                parents: commit.parents,
+                commit_time: commit.committer_time.max(0),
+            },
+        );
+    }
+
+    let mut commits: Vec<GraphEntry> = commits.into_values().collect();
+    commits.sort_by(|a, b| a.hash.cmp(&b.hash));
+    Ok(commits)
+}
+
+/// Generation numbers of sorted commits (parents must be among them)
+fn generations(commits: &[GraphEntry], positions: &HashMap<&str, usize>) -> Vec<u32> {
+    let mut generations = vec![0u32; commits.len()];
+
+    for start in 0..commits.len() {
+        let mut stack = vec![start];
+        while let Some(&current) = stack.last() {
+            if generations[current] != 0 {
+                stack.pop();
+                continue;
+            }
+
+            let pending: Vec<usize> = commits[current]
+                .parents
+                .iter()
+                .map(|parent| positions[parent.as_str()])
+                .filter(|&parent| generations[parent] == 0)
+                .collect();
+            if pending.is_empty() {
+                let parent_max = commits[current]
+                    .parents
+                    .iter()
+                    .map(|parent| generations[positions[parent.as_str()]])
+                    .max()
+                    .unwrap_or(0);
+                generations[current] = (parent_max + 1).min(MAX_GENERATION);
+                stack.pop();
+            } else {
+                stack.extend(pending);
+            }
+        }
+    }
+
+    generations
+}
+
+/// Build the commit-graph file contents
+fn serialize(commits: &[GraphEntry]) -> Vec<u8> {
+    let positions: HashMap<&str, usize> = commits
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (c.hash.as_str(), i))
+        .collect();
+    let generations = generations(commits, &positions);
+
+    let mut fanout = Vec::with_capacity(256 * 4);
+    let mut counts = [0u32; 256];
+    for commit in commits {
+        if let Some(oid) = hex_to_bytes(&commit.hash) {
+            counts[oid[0] as usize] += 1;
+        }
+    }
+    let mut total = 0;
+    for count in counts {
+        total += count;
+        fanout.extend_from_slice(&total.to_be_bytes());
+    }
+
+    let mut oids = Vec::with_capacity(commits.len() * HASH_LEN);
+    let mut data = Vec::with_capacity(commits.len() * DATA_LEN);
+    let mut edges: Vec<u8> = Vec::new();
+    for (commit, generation) in commits.iter().zip(&generations) {
+        oids.extend(hex_to_bytes(&commit.hash).unwrap_or_default());
+        data.extend(hex_to_bytes(&commit.tree).unwrap_or_default());
+
+        let parent_positions: Vec<u32> = commit
+            .parents
+            .iter()
+            .map(|p| positions[p.as_str()] as u32)
+            .collect();
+        let first = parent_positions.first().copied().unwrap_or(NO_PARENT);
+        let second = match parent_positions.as_slice() {
+            [] | [_] => NO_PARENT,
+            [_, second] => *second,
+            [_, rest @ ..] => {
+                let index = (edges.len() / 4) as u32 | EXTRA_EDGES;
+                for (i, parent) in rest.iter().enumerate() {
+                    let last = if i == rest.len() - 1 { EXTRA_EDGES } else { 0 };
+                    edges.extend_from_slice(&(parent | last).to_be_bytes());
+                }
+                index
+            }
+        };
+        data.extend_from_slice(&first.to_be_bytes());
+        data.extend_from_slice(&second.to_be_bytes());
+
+        let time = commit.commit_time as u64;
+        let high = (generation << 2) | ((time >> 32) as u32 & 0x3);
+        data.extend_from_slice(&high.to_be_bytes());
+        data.extend_from_slice(&(time as u32).to_be_bytes());
+    }
+
+    let mut chunks: Vec<(&[u8; 4], Vec<u8>)> =
+        vec![(b"OIDF", fanout), (b"OIDL", oids), (b"CDAT", data)];
+    if !edges.is_empty() {
+        chunks.push((b"EDGE", edges));
+    }
+
+    let mut file = Vec::new();
+    file.extend_from_slice(SIGNATURE);
+    file.extend_from_slice(&[1, 1, chunks.len() as u8, 0]);
+
+    let mut offset = (8 + (chunks.len() + 1) * 12) as u64;
+    for (id, content) in &chunks {
+        file.extend_from_slice(*id);
+        file.extend_from_slice(&offset.to_be_bytes());
+        offset += content.len() as u64;
+    }
+    file.extend_from_slice(&[0; 4]);
+    file.extend_from_slice(&offset.to_be_bytes());
+
+    for (_, content) in chunks {
+        file.extend(content);
+    }
+    let checksum = Sha1::digest(&file);
+    file.extend_from_slice(&checksum);
+    file
+}
+
+/// Read a big-endian u32
+fn read_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes(data[at..at + 4].try_into().unwrap())
+}
+
+/// Decode a 40 character hex hash
+fn hex_to_bytes(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() != HASH_LEN * 2 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Encode bytes as hex
+fn bytes_to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(hash_byte: &str, parents: &[&str], time: i64) -> GraphEntry {
+        GraphEntry {
+            hash: hash_byte.repeat(20),
+            tree: "ee".repeat(20),
+            parents: parents.iter().map(|p| p.repeat(20)).collect(),
+            commit_time: time,
+        }
+    }
+
+    #[test]
+    fn test_commit_graph_round_trip() {
+        // root <- a, root <- b, (a, b, root) octopus <- m
+        let mut commits = vec![
+            entry("01", &[], 1_000),
+            entry("a0", &["01"], 2_000),
+            entry("b0", &["01"], 3_000),
+            entry("ff", &["a0", "b0", "01"], 1 << 33),
+        ];
+        commits.sort_by(|a, b| a.hash.cmp(&b.hash));
+
+        let graph = CommitGraph::parse(serialize(&commits)).unwrap();
+        assert_eq!(graph.len(), 4);
+
+        let root = graph.get(&"01".repeat(20)).unwrap();
+        assert_eq!(root.generation, 1);
+        assert!(root.parents.is_empty());
+        assert_eq!(root.tree, "ee".repeat(20));
+
+        let merge = graph.get(&"ff".repeat(20)).unwrap();
+        assert_eq!(merge.generation, 3);
+        assert_eq!(merge.commit_time, 1 << 33);
+        assert_eq!(
+            merge.parents,
+            vec!["a0".repeat(20), "b0".repeat(20), "01".repeat(20)]
+        );
+
+        assert_eq!(
+            graph.get(&"b0".repeat(20)).unwrap().parents,
+            vec!["01".repeat(20)]
+        );
+        assert!(graph.get(&"cc".repeat(20)).is_none());
+        assert!(CommitGraph::parse(b"not a graph".to_vec()).is_err());
+    }
+}
@@ -1,19 +1,27 @@
+use crate::error::Result;
 use crate::utils::{compress_data, compute_hash};
 use std::fs;
+use std::path::Path;
 
 /// Create a blob object from a file and return its hash
-pub fn hash_object(file: String) -> String {
-    let data = std::fs::read(&file).expect("Failed to read file");
+pub fn hash_object(file: String) -> Result<String> {
+    write_blob(&fs::read(&file)?)
+}
+
+/// Create a blob object from in-memory content and return its hash
+pub fn write_blob(data: &[u8]) -> Result<String> {
     let header = format!("blob {}\0", data.len());
     let mut store = Vec::new();
     store.extend_from_slice(header.as_bytes());
-    store.extend_from_slice(&data);
+    store.extend_from_slice(data);
 
     let hash = compute_hash(&store);
     let dir = format!(".kitcat/objects/{}", &hash[..2]);
     let file_path = format!("{}/{}", dir, &hash[2..]);
-    fs::create_dir_all(&dir).unwrap();
-    fs::write(&file_path, compress_data(&store)).unwrap();
+    if !Path::new(&file_path).exists() {
+        fs::create_dir_all(&dir)?;
+        fs::write(&file_path, compress_data(&store))?;
+    }
 
-    hash
+    Ok(hash)
 }
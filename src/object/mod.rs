@@ -1,12 +1,22 @@
 pub mod commit;
+pub mod commit_graph;
 pub mod hash_object;
 pub mod pack;
 pub mod read_object;
+pub mod tag;
 pub mod tree;
 
 // Re-export functions
-pub use commit::{create_commit, get_commit_parents, get_commit_tree, read_commit, show_commit};
-pub use hash_object::hash_object;
+pub use commit::{
+    create_commit, create_commit_with_author, create_signed_commit, get_commit_parents,
+    get_commit_tree, read_commit, read_commit_signature, show_commit, Signature,
+};
+pub use commit_graph::{verify_commit_graph, write_commit_graph};
+pub use hash_object::{hash_object, write_blob};
 pub use pack::{pack_objects, PackFile};
-pub use read_object::read_object;
-pub use tree::{checkout_tree, list_tree, read_tree, write_tree_from_index};
+pub use read_object::{read_object, read_object_data, resolve_object_hash};
+pub use tag::{create_tag, read_tag, read_tag_signature};
+pub use tree::{
+    checkout_tree, find_tree_path, list_tree, read_blob_content, read_tree, write_tree_from_files,
+    write_tree_from_index,
+};
@@ -5,9 +5,10 @@
 /// - Compressing objects more efficiently
 /// - Using delta compression for similar objects
 
+use crate::error::{KitcatError, Result};
 use std::collections::HashMap;
 use std::fs::{self, File};
-use std::io::{self, Read, Write};
+use std::io::{Read, Write};
 use std::path::Path;
 
 /// Pack file header magic
@@ -64,7 +65,7 @@ impl PackFile {
     }
 
     /// Write pack file to disk
-    pub fn write_to_file(&self, path: &Path) -> io::Result<String> {
+    pub fn write_to_file(&self, path: &Path) -> Result<String> {
         let mut file = File::create(path)?;
         let mut data_to_hash = Vec::new();
 
@@ -97,7 +98,7 @@ impl PackFile {
     }
 
     /// Encode a pack entry
-    fn encode_entry(&self, entry: &PackEntry) -> io::Result<Vec<u8>> {
+    fn encode_entry(&self, entry: &PackEntry) -> Result<Vec<u8>> {
         let mut data = Vec::new();
 
         // Encode type and size in variable-length format
@@ -126,7 +127,7 @@ impl PackFile {
     }
 
     /// Read pack file from disk
-    pub fn read_from_file(path: &Path) -> io::Result<Self> {
+    pub fn read_from_file(path: &Path) -> Result<Self> {
         let mut file = File::open(path)?;
         let mut buffer = Vec::new();
         file.read_to_end(&mut buffer)?;
@@ -135,29 +136,20 @@ impl PackFile {
     }
 
     /// Parse pack file data
-    fn parse_pack(data: &[u8]) -> io::Result<Self> {
+    fn parse_pack(data: &[u8]) -> Result<Self> {
         if data.len() < 12 {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "Pack file too small",
-            ));
+            return Err(KitcatError::CorruptObject("Pack file too small".to_string()));
         }
 
         // Check signature
         if &data[0..4] != PACK_SIGNATURE {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "Invalid pack signature",
-            ));
+            return Err(KitcatError::CorruptObject("Invalid pack signature".to_string()));
         }
 
         // Read version
         let version = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
         if version != PACK_VERSION {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!("Unsupported pack version: {}", version),
-            ));
+            return Err(KitcatError::CorruptObject(format!("Unsupported pack version: {}", version)));
         }
 
         // Read object count
@@ -177,12 +169,9 @@ impl PackFile {
     }
 
     /// Decode a pack entry
-    fn decode_entry(data: &[u8]) -> io::Result<(PackEntry, usize)> {
+    fn decode_entry(data: &[u8]) -> Result<(PackEntry, usize)> {
         if data.is_empty() {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "Empty entry data",
-            ));
+            return Err(KitcatError::CorruptObject("Empty entry data".to_string()));
         }
 
         let mut offset = 0;
@@ -199,10 +188,7 @@ impl PackFile {
             6 => PackObjectType::OfsDelta,
             7 => PackObjectType::RefDelta,
             _ => {
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    "Invalid object type",
-                ))
+                return Err(KitcatError::CorruptObject("Invalid object type".to_string()))
             }
         };
 
@@ -236,7 +222,7 @@ impl PackFile {
 }
 
 /// Pack loose objects into a packfile
-pub fn pack_objects() -> io::Result<usize> {
+pub fn pack_objects() -> Result<usize> {
     let objects_dir = Path::new(".kitcat/objects");
     if !objects_dir.exists() {
         return Ok(0);
@@ -328,7 +314,7 @@ pub fn pack_objects() -> io::Result<usize> {
 }
 
 /// Create pack index for faster lookups
-pub fn create_pack_index(pack_path: &Path) -> io::Result<()> {
+pub fn create_pack_index(pack_path: &Path) -> Result<()> {
     let pack = PackFile::read_from_file(pack_path)?;
     let mut index = HashMap::new();
 
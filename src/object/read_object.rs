@@ -24,7 +24,8 @@
 //     }
 // }
 
-use crate::utils::decompress_data;
+use crate::error::{KitcatError, Result};
+use crate::utils::decompress;
 use std::fs;
 use std::io::{self, Write};
 use std::path::PathBuf;
@@ -36,13 +37,13 @@ struct ObjectInfo {
     modified: SystemTime,
 }
 
-pub fn read_object(hash: String, pretty: bool) {
+/// Print an object found by hash prefix, asking which one when several match
+pub fn read_object(hash: String, pretty: bool) -> Result<()> {
     // Search for matching objects
     let matches = find_matching_objects(&hash);
 
     if matches.is_empty() {
-        eprintln!("No objects found matching hash prefix: {}", hash);
-        std::process::exit(1);
+        return Err(KitcatError::ObjectNotFound(hash));
     }
 
     let selected_object = if matches.len() == 1 {
@@ -51,17 +52,51 @@ pub fn read_object(hash: String, pretty: bool) {
     } else {
         // Multiple matches, let user choose
         println!("Found {} object(s) matching '{}':\n", matches.len(), hash);
-        match select_object(&matches) {
+        match select_object(&matches)? {
             Some(obj) => obj,
-            None => {
-                println!("Invalid selection. Exiting.");
-                std::process::exit(1);
-            }
+            None => return Err(KitcatError::AmbiguousRevision(hash)),
         }
     };
 
     // Read and display the selected object
-    display_object(&selected_object.path, pretty);
+    display_object(&selected_object.path, pretty)
+}
+
+/// Expand an abbreviated object hash to the full hash
+pub fn resolve_object_hash(hash_prefix: &str) -> Result<String> {
+    if hash_prefix.len() < 4 || !hash_prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(KitcatError::InvalidInput(format!("Invalid object name '{}'", hash_prefix)));
+    }
+
+    let matches = find_matching_objects(hash_prefix);
+    match matches.len() {
+        0 => Err(KitcatError::ObjectNotFound(hash_prefix.to_string())),
+        1 => Ok(matches[0].hash.clone()),
+        _ => Err(KitcatError::AmbiguousRevision(hash_prefix.to_string())),
+    }
+}
+
+/// Read an object, returning its type (`blob`, `tree`, `commit`, `tag`) and payload
+pub fn read_object_data(hash: &str) -> Result<(String, Vec<u8>)> {
+    if hash.len() < 3 {
+        return Err(KitcatError::InvalidInput(format!("Invalid object name '{}'", hash)));
+    }
+
+    let path = PathBuf::from(format!(".kitcat/objects/{}/{}", &hash[..2], &hash[2..]));
+    if !path.exists() {
+        return Err(KitcatError::ObjectNotFound(hash.to_string()));
+    }
+
+    let data = decompress(&fs::read(path)?)?;
+    let null_pos = data
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or_else(|| KitcatError::CorruptObject("Invalid object format".to_string()))?;
+
+    let header = String::from_utf8_lossy(&data[..null_pos]);
+    let object_type = header.split(' ').next().unwrap_or("").to_string();
+
+    Ok((object_type, data[null_pos + 1..].to_vec()))
 }
 
 fn find_matching_objects(hash_prefix: &str) -> Vec<ObjectInfo> {
@@ -99,7 +134,7 @@ fn find_matching_objects(hash_prefix: &str) -> Vec<ObjectInfo> {
     matches
 }
 
-fn select_object(matches: &[ObjectInfo]) -> Option<&ObjectInfo> {
+fn select_object(matches: &[ObjectInfo]) -> Result<Option<&ObjectInfo>> {
     let mut start_idx = 0;
     let page_size = 10;
 
@@ -122,11 +157,11 @@ fn select_object(matches: &[ObjectInfo]) -> Option<&ObjectInfo> {
             println!("Enter a number to select (1-{}):", matches.len());
         }
         print!(":-) ");
-        io::stdout().flush().unwrap();
+        io::stdout().flush()?;
 
         // Get user input
         let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
+        io::stdin().read_line(&mut input)?;
         let input = input.trim();
 
         // Handle input
@@ -143,18 +178,18 @@ fn select_object(matches: &[ObjectInfo]) -> Option<&ObjectInfo> {
         } else if let Ok(choice) = input.parse::<usize>() {
             // Number entered - validate and return
             if choice > 0 && choice <= matches.len() {
-                return Some(&matches[choice - 1]);
+                return Ok(Some(&matches[choice - 1]));
             } else {
                 println!(
                     "Invalid selection. Please enter a number between 1 and {}.",
                     matches.len()
                 );
-                return None;
+                return Ok(None);
             }
         } else {
             // Invalid input
             println!("Invalid input.");
-            return None;
+            return Ok(None);
         }
     }
 }
@@ -173,9 +208,9 @@ fn format_time(time: &SystemTime) -> String {
     }
 }
 
-fn display_object(path: &PathBuf, pretty: bool) {
-    let compressed = fs::read(path).expect("Failed to read object");
-    let decompressed = decompress_data(&compressed);
+fn display_object(path: &PathBuf, pretty: bool) -> Result<()> {
+    let compressed = fs::read(path)?;
+    let decompressed = decompress(&compressed)?;
 
     if let Some(pos) = decompressed.iter().position(|&b| b == 0) {
         let header = &decompressed[..pos];
@@ -190,7 +225,8 @@ fn display_object(path: &PathBuf, pretty: bool) {
                 String::from_utf8_lossy(data)
             );
         }
+        Ok(())
     } else {
-        panic!("Invalid object format");
+        Err(KitcatError::CorruptObject("Invalid object format".to_string()))
     }
 }
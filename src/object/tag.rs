@@ -0,0 +1,152 @@
+use crate::config::Config;
+use crate::error::{KitcatError, Result};
+use crate::models::Tag;
+use crate::object::commit::Signature;
+use crate::object::read_object::read_object_data;
+use crate::signing::{sign_payload, SignRequest, PGP_ARMOR, SSH_ARMOR};
+use crate::utils::{compress_data, compute_hash};
+use std::fs;
+use std::path::Path;
+
+/// Create an annotated tag object, signing it as `sign` asks
+///
+/// A signature is appended to the tag's message, as Git does; it covers
+/// everything before it.
+pub fn create_tag(
+    object: &str,
+    object_type: &str,
+    name: &str,
+    tagger: &Signature,
+    message: &str,
+    sign: &SignRequest,
+) -> Result<String> {
+    let config = Config::read()?;
+
+    let mut content = format!(
+        "object {}\ntype {}\ntag {}\ntagger {}\n\n{}\n",
+        object,
+        object_type,
+        name,
+        tagger.format(),
+        message.trim_end()
+    );
+    if sign.wants(config.tag.gpg_sign) {
+        content.push_str(&sign_payload(&config, content.as_bytes(), sign.key(), &tagger.identity)?);
+    }
+
+    let mut store = format!("tag {}\0", content.len()).into_bytes();
+    store.extend_from_slice(content.as_bytes());
+
+    let hash = compute_hash(&store);
+    let dir = format!(".kitcat/objects/{}", &hash[..2]);
+    let file_path = format!("{}/{}", dir, &hash[2..]);
+    if !Path::new(&file_path).exists() {
+        fs::create_dir_all(&dir)?;
+        fs::write(&file_path, compress_data(&store))?;
+    }
+
+    Ok(hash)
+}
+
+/// The signed payload and signature of a tag, if it is signed
+pub fn read_tag_signature(hash: &str) -> Result<Option<(Vec<u8>, String)>> {
+    let (object_type, content) = read_object_data(hash)?;
+    if object_type != "tag" {
+        return Err(KitcatError::CorruptObject(format!("Object {} is a {}, not a tag", hash, object_type)));
+    }
+    Ok(split_signature(&String::from_utf8_lossy(&content)))
+}
+
+/// Separate the trailing signature from a tag
+fn split_signature(content: &str) -> Option<(Vec<u8>, String)> {
+    let start = signature_start(content)?;
+    Some((content.as_bytes()[..start].to_vec(), content[start..].to_string()))
+}
+
+/// Where a tag's trailing signature begins
+fn signature_start(content: &str) -> Option<usize> {
+    [PGP_ARMOR, SSH_ARMOR]
+        .iter()
+        .filter_map(|armor| content.rfind(&format!("\n{}", armor)))
+        .max()
+        .map(|newline| newline + 1)
+}
+
+/// Read an annotated tag object
+pub fn read_tag(hash: &str) -> Result<Tag> {
+    let (object_type, content) = read_object_data(hash)?;
+    if object_type != "tag" {
+        return Err(KitcatError::CorruptObject(format!("Object {} is a {}, not a tag", hash, object_type)));
+    }
+    parse_tag(&String::from_utf8_lossy(&content))
+}
+
+/// Parse tag content (without the object header)
+fn parse_tag(content: &str) -> Result<Tag> {
+    let mut tag = Tag {
+        object: String::new(),
+        object_type: String::new(),
+        tag: String::new(),
+        tagger: String::new(),
+        tagger_time: 0,
+        tagger_timezone: String::new(),
+        message: String::new(),
+    };
+
+    // A signature is not part of the message
+    let unsigned = &content[..signature_start(content).unwrap_or(content.len())];
+    let (headers, message) = unsigned.split_once("\n\n").unwrap_or((unsigned, ""));
+
+    for line in headers.lines() {
+        if let Some(object) = line.strip_prefix("object ") {
+            tag.object = object.to_string();
+        } else if let Some(object_type) = line.strip_prefix("type ") {
+            tag.object_type = object_type.to_string();
+        } else if let Some(name) = line.strip_prefix("tag ") {
+            tag.tag = name.to_string();
+        } else if let Some(tagger_line) = line.strip_prefix("tagger ") {
+            let parts: Vec<&str> = tagger_line.rsplitn(3, ' ').collect();
+            if parts.len() == 3 {
+                tag.tagger_timezone = parts[0].to_string();
+                tag.tagger_time = parts[1].parse().unwrap_or(0);
+                tag.tagger = parts[2].to_string();
+            }
+        }
+    }
+
+    if tag.object.is_empty() {
+        return Err(KitcatError::CorruptObject("Tag object has no target".to_string()));
+    }
+
+    tag.message = message.trim_end().to_string();
+    Ok(tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tag() {
+        let content = "object 0123456789012345678901234567890123456789\ntype commit\ntag v1.0\ntagger Jane Doe <jane@example.com> 1234567890 +0000\n\nRelease 1.0\n";
+        let tag = parse_tag(content).unwrap();
+        assert_eq!(tag.object_type, "commit");
+        assert_eq!(tag.tag, "v1.0");
+        assert_eq!(tag.tagger, "Jane Doe <jane@example.com>");
+        assert_eq!(tag.tagger_time, 1234567890);
+        assert_eq!(tag.message, "Release 1.0");
+    }
+
+    #[test]
+    fn test_split_tag_signature() {
+        let payload = "object 0123456789012345678901234567890123456789\ntype commit\ntag v1.0\ntagger Jane Doe <jane@example.com> 1234567890 +0000\n\nRelease 1.0\n";
+        let signature = "-----BEGIN PGP SIGNATURE-----\n\niQEz\n-----END PGP SIGNATURE-----\n";
+        let content = format!("{}{}", payload, signature);
+
+        let (unsigned, extracted) = split_signature(&content).unwrap();
+        assert_eq!(unsigned, payload.as_bytes());
+        assert_eq!(extracted, signature);
+        assert_eq!(parse_tag(&content).unwrap().message, "Release 1.0");
+        assert!(split_signature(payload).is_none());
+    }
+}
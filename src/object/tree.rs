@@ -1,12 +1,28 @@
-use crate::models::{IndexEntry, TreeEntry};
-use crate::utils::{compress_data, compute_hash, decompress_data};
-use std::collections::BTreeMap;
+use crate::error::{KitcatError, Result};
+use crate::models::{FileEntry, IndexEntry, TreeEntry};
+use crate::utils::{compress_data, compute_hash, decompress};
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::io;
 use std::path::Path;
 
 /// Build a tree from the current index
-pub fn write_tree_from_index(entries: &[IndexEntry]) -> io::Result<String> {
+///
+/// Fails while the index holds unmerged (stage 1/2/3) entries.
+pub fn write_tree_from_index(entries: &[IndexEntry]) -> Result<String> {
+    let mut unmerged: Vec<&str> = entries
+        .iter()
+        .filter(|e| e.is_unmerged())
+        .map(|e| e.path.as_str())
+        .collect();
+    unmerged.sort();
+    unmerged.dedup();
+    if !unmerged.is_empty() {
+        return Err(KitcatError::Conflict(format!(
+                "you need to resolve your current index first\nunmerged paths: {}",
+                unmerged.join(", ")
+            )));
+    }
+
     // Build tree structure from flat index
     let root = build_tree_structure(entries);
 
@@ -14,6 +30,15 @@ pub fn write_tree_from_index(entries: &[IndexEntry]) -> io::Result<String> {
     write_tree_recursive(&root)
 }
 
+/// Build a tree from a flattened file map (path -> mode and blob hash)
+pub fn write_tree_from_files(files: &HashMap<String, FileEntry>) -> Result<String> {
+    let entries: Vec<IndexEntry> = files
+        .iter()
+        .map(|(path, file)| IndexEntry::from_blob(path.clone(), file.hash.clone(), file.mode))
+        .collect();
+    write_tree_from_index(&entries)
+}
+
 /// Represents a node in the tree structure
 #[derive(Debug)]
 struct TreeNode {
@@ -64,7 +89,7 @@ fn insert_entry(node: &mut TreeNode, path_parts: &[&str], entry: &IndexEntry) {
 }
 
 /// Write a tree node recursively and return its hash
-fn write_tree_recursive(node: &TreeNode) -> io::Result<String> {
+fn write_tree_recursive(node: &TreeNode) -> Result<String> {
     let mut entries = Vec::new();
 
     // First, write all subdirectories and get their hashes
@@ -107,7 +132,7 @@ fn write_tree_recursive(node: &TreeNode) -> io::Result<String> {
 }
 
 /// Store an object in the .kitcat/objects directory
-fn store_object(hash: &str, content: &[u8]) -> io::Result<()> {
+fn store_object(hash: &str, content: &[u8]) -> Result<()> {
     let dir_name = &hash[0..2];
     let file_name = &hash[2..];
 
@@ -129,13 +154,30 @@ fn store_object(hash: &str, content: &[u8]) -> io::Result<()> {
 }
 
 /// Read a tree object and return its entries
-pub fn read_tree(hash: &str) -> io::Result<Vec<TreeEntry>> {
+pub fn read_tree(hash: &str) -> Result<Vec<TreeEntry>> {
     let content = read_object_content(hash)?;
     parse_tree_content(&content)
 }
 
+/// Look up a path inside a tree, returning the hash of the blob or subtree
+pub fn find_tree_path(tree_hash: &str, path: &str) -> Result<String> {
+    let mut current = tree_hash.to_string();
+
+    for component in path.split('/').filter(|c| !c.is_empty()) {
+        let entry = read_tree(&current)?
+            .into_iter()
+            .find(|e| e.name == component)
+            .ok_or_else(|| {
+                KitcatError::NotFound(format!("Path '{}' does not exist", path))
+            })?;
+        current = bytes_to_hex(&entry.hash);
+    }
+
+    Ok(current)
+}
+
 /// Read an object's content
-fn read_object_content(hash: &str) -> io::Result<Vec<u8>> {
+fn read_object_content(hash: &str) -> Result<Vec<u8>> {
     let dir_name = &hash[0..2];
     let file_name = &hash[2..];
 
@@ -144,33 +186,27 @@ fn read_object_content(hash: &str) -> io::Result<Vec<u8>> {
         .join(file_name);
 
     if !file_path.exists() {
-        return Err(io::Error::new(
-            io::ErrorKind::NotFound,
-            format!("Object {} not found", hash),
-        ));
+        return Err(KitcatError::ObjectNotFound(hash.to_string()));
     }
 
     let compressed = fs::read(file_path)?;
-    let decompressed = decompress_data(&compressed);
+    let decompressed = decompress(&compressed)?;
 
     Ok(decompressed)
 }
 
 /// Parse tree content into entries
-fn parse_tree_content(data: &[u8]) -> io::Result<Vec<TreeEntry>> {
+fn parse_tree_content(data: &[u8]) -> Result<Vec<TreeEntry>> {
     // Find the null byte after the header
     let null_pos = data
         .iter()
         .position(|&b| b == 0)
-        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid tree object"))?;
+        .ok_or_else(|| KitcatError::CorruptObject("Invalid tree object".to_string()))?;
 
     // Verify this is a tree object
     let header = String::from_utf8_lossy(&data[0..null_pos]);
     if !header.starts_with("tree ") {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidData,
-            "Not a tree object",
-        ));
+        return Err(KitcatError::CorruptObject("Not a tree object".to_string()));
     }
 
     let mut entries = Vec::new();
@@ -181,7 +217,7 @@ fn parse_tree_content(data: &[u8]) -> io::Result<Vec<TreeEntry>> {
         let space_pos = data[offset..]
             .iter()
             .position(|&b| b == b' ')
-            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid tree entry"))?;
+            .ok_or_else(|| KitcatError::CorruptObject("Invalid tree entry".to_string()))?;
 
         let mode = String::from_utf8_lossy(&data[offset..offset + space_pos]).to_string();
         offset += space_pos + 1;
@@ -189,17 +225,14 @@ fn parse_tree_content(data: &[u8]) -> io::Result<Vec<TreeEntry>> {
         let null_pos = data[offset..]
             .iter()
             .position(|&b| b == 0)
-            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid tree entry"))?;
+            .ok_or_else(|| KitcatError::CorruptObject("Invalid tree entry".to_string()))?;
 
         let name = String::from_utf8_lossy(&data[offset..offset + null_pos]).to_string();
         offset += null_pos + 1;
 
         // Read 20-byte hash
         if offset + 20 > data.len() {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "Incomplete hash in tree entry",
-            ));
+            return Err(KitcatError::CorruptObject("Incomplete hash in tree entry".to_string()));
         }
 
         let mut hash = [0u8; 20];
@@ -220,7 +253,7 @@ fn parse_tree_content(data: &[u8]) -> io::Result<Vec<TreeEntry>> {
 }
 
 /// List the contents of a tree (for debugging/display)
-pub fn list_tree(hash: &str, prefix: &str) -> io::Result<()> {
+pub fn list_tree(hash: &str, prefix: &str) -> Result<()> {
     let entries = read_tree(hash)?;
 
     for entry in entries {
@@ -256,7 +289,7 @@ fn bytes_to_hex(bytes: &[u8]) -> String {
 }
 
 /// Restore a tree to the working directory
-pub fn checkout_tree(hash: &str, target_dir: &Path) -> io::Result<()> {
+pub fn checkout_tree(hash: &str, target_dir: &Path) -> Result<()> {
     let entries = read_tree(hash)?;
 
     for entry in entries {
@@ -268,19 +301,10 @@ pub fn checkout_tree(hash: &str, target_dir: &Path) -> io::Result<()> {
             fs::create_dir_all(&entry_path)?;
             checkout_tree(&hash_hex, &entry_path)?;
         } else {
-            // Restore file
+            // Restore file, symlink or executable with its tree mode
             let blob_content = read_blob_content(&hash_hex)?;
-            fs::write(&entry_path, blob_content)?;
-
-            // Set file permissions
-            #[cfg(unix)]
-            {
-                use std::os::unix::fs::PermissionsExt;
-                if let Ok(mode) = u32::from_str_radix(&entry.mode, 8) {
-                    let perms = fs::Permissions::from_mode(mode);
-                    fs::set_permissions(&entry_path, perms)?;
-                }
-            }
+            let mode = u32::from_str_radix(&entry.mode, 8).unwrap_or(crate::models::MODE_FILE);
+            crate::utils::write_worktree_file(&entry_path, &blob_content, mode)?;
         }
     }
 
@@ -288,14 +312,14 @@ pub fn checkout_tree(hash: &str, target_dir: &Path) -> io::Result<()> {
 }
 
 /// Read blob content (file data)
-fn read_blob_content(hash: &str) -> io::Result<Vec<u8>> {
+pub fn read_blob_content(hash: &str) -> Result<Vec<u8>> {
     let content = read_object_content(hash)?;
 
     // Find the null byte after the header
     let null_pos = content
         .iter()
         .position(|&b| b == 0)
-        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid blob object"))?;
+        .ok_or_else(|| KitcatError::CorruptObject("Invalid blob object".to_string()))?;
 
     // Return content after the header
     Ok(content[null_pos + 1..].to_vec())
@@ -321,4 +345,17 @@ mod tests {
         let hex2 = bytes_to_hex(&bytes);
         assert_eq!(hex, hex2);
     }
+
+    #[test]
+    fn test_write_tree_refuses_unmerged_entries() {
+        let hash = "a94a8fe5ccb19ba61c4c0873d391e987982fbbd3".to_string();
+        let entries = vec![
+            IndexEntry::conflict_stage("file.txt".to_string(), hash.clone(), 0o100644, 2),
+            IndexEntry::conflict_stage("file.txt".to_string(), hash, 0o100644, 3),
+        ];
+
+        let err = write_tree_from_index(&entries).unwrap_err();
+        assert!(matches!(err, KitcatError::Conflict(_)));
+        assert!(err.to_string().contains("unmerged paths: file.txt"));
+    }
 }
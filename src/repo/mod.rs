@@ -1,22 +1,260 @@
+pub mod revision;
+
+use crate::error::{KitcatError, Result};
 use std::fs;
+use std::path::Path;
 
 /// Initialize the repository structure
-pub fn init_repo() -> std::io::Result<()> {
+pub fn init_repo() -> Result<()> {
     fs::create_dir_all(".kitcat/objects")?;
     fs::create_dir_all(".kitcat/refs/heads")?;
     fs::write(".kitcat/HEAD", "ref: refs/heads/master\n")?;
     Ok(())
 }
 
+/// Check that the current directory holds a repository
+pub fn ensure_repository() -> Result<()> {
+    if crate::utils::is_repo_init() {
+        Ok(())
+    } else {
+        Err(KitcatError::NotARepository)
+    }
+}
+
 /// Read the current HEAD reference or commit hash
-pub fn read_head() -> String {
-    let head = fs::read_to_string(".kitcat/HEAD").expect("Failed to read HEAD");
-    head.trim().to_string()
+pub fn read_head() -> Result<String> {
+    ensure_repository()?;
+    let head = fs::read_to_string(".kitcat/HEAD")?;
+    Ok(head.trim().to_string())
 }
 
 /// Update the HEAD reference or commit hash
-pub fn write_head(value: &str) {
-    fs::write(".kitcat/HEAD", value).expect("Failed to write HEAD");
+pub fn write_head(value: &str) -> Result<()> {
+    ensure_repository()?;
+    fs::write(".kitcat/HEAD", value)?;
+    Ok(())
+}
+
+/// Resolve HEAD to a commit hash (None if the current branch has no commits yet)
+pub fn resolve_head() -> Result<Option<String>> {
+    let head_content = read_head()?;
+
+    if let Some(branch_ref) = head_content.strip_prefix("ref: ") {
+        let branch_path = format!(".kitcat/{}", branch_ref.trim());
+        if !Path::new(&branch_path).exists() {
+            return Ok(None);
+        }
+        return Ok(Some(fs::read_to_string(&branch_path)?.trim().to_string()));
+    }
+
+    if head_content.len() == 40 {
+        Ok(Some(head_content))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Point HEAD (or the branch it refers to) at a new commit
+pub fn update_head(commit_hash: &str) -> Result<()> {
+    let head_content = read_head()?;
+
+    if let Some(branch_ref) = head_content.strip_prefix("ref: ") {
+        let branch_path = format!(".kitcat/{}", branch_ref.trim());
+        if let Some(parent) = Path::new(&branch_path).parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&branch_path, commit_hash)?;
+    } else {
+        fs::write(".kitcat/HEAD", commit_hash)?;
+    }
+
+    Ok(())
+}
+
+/// Resolve a revision to a commit hash
+///
+/// Accepts `HEAD`, branch and tag names, full or abbreviated hashes, and
+/// any number of `~N` (Nth first-parent ancestor), `^N` (Nth parent) and
+/// `^{}` / `^{commit}` (peel to the commit) suffixes, e.g. `master~2` or
+/// `HEAD^2`. Anything else after the name is an unknown revision.
+pub fn resolve_revision(rev: &str) -> Result<String> {
+    let split = rev.find(['~', '^']).unwrap_or(rev.len());
+    let (name, mut suffix) = rev.split_at(split);
+
+    let mut hash = peel_to_commit(&resolve_name(name)?)?;
+
+    while let Some(op) = suffix.chars().next() {
+        if let Some(rest) = suffix.strip_prefix("^{") {
+            // Already a commit, so only peeling to a commit is possible
+            let (kind, rest) = rest.split_once('}').ok_or_else(|| invalid_revision(rev))?;
+            if !kind.is_empty() && kind != "commit" {
+                return Err(invalid_revision(rev));
+            }
+            suffix = rest;
+            continue;
+        }
+        if op != '~' && op != '^' {
+            return Err(invalid_revision(rev));
+        }
+
+        let digits: String = suffix[1..].chars().take_while(|c| c.is_ascii_digit()).collect();
+        suffix = &suffix[1 + digits.len()..];
+        let n: usize = if digits.is_empty() {
+            1
+        } else {
+            digits.parse().map_err(|_| invalid_revision(rev))?
+        };
+
+        if op == '~' {
+            for _ in 0..n {
+                let parents = crate::object::get_commit_parents(&hash)?;
+                hash = parents.into_iter().next().ok_or_else(|| invalid_revision(rev))?;
+            }
+        } else if n > 0 {
+            let parents = crate::object::get_commit_parents(&hash)?;
+            hash = parents.into_iter().nth(n - 1).ok_or_else(|| invalid_revision(rev))?;
+        }
+    }
+
+    Ok(hash)
+}
+
+/// Resolve an object name to any object (tags are not peeled)
+///
+/// Besides revisions this accepts `rev:path` for a blob or tree inside a
+/// commit, `:path` for a file in the index, and a final `^{}`, `^{commit}`
+/// or `^{tree}` to peel the object.
+pub fn resolve_object_name(spec: &str) -> Result<String> {
+    if let Some(base) = spec.strip_suffix('}')
+        && let Some(open) = base.rfind("^{")
+    {
+        let hash = resolve_object_name(&base[..open])?;
+        return peel_object(&hash, &base[open + 2..], spec);
+    }
+
+    if let Some((rev, path)) = spec.split_once(':') {
+        if rev.is_empty() {
+            let entries = crate::index::read_index_binary()?;
+            return entries
+                .into_iter()
+                .find(|e| e.path == path && e.stage() == 0)
+                .map(|e| e.hash)
+                .ok_or_else(|| invalid_revision(spec));
+        }
+
+        let tree = crate::object::get_commit_tree(&resolve_revision(rev)?)?;
+        return crate::object::find_tree_path(&tree, path);
+    }
+
+    if spec.contains(['~', '^']) {
+        resolve_revision(spec)
+    } else {
+        resolve_name(spec)
+    }
+}
+
+/// Peel an object for a `^{kind}` suffix
+///
+/// An empty kind follows annotated tags; `commit` and `tree` find the
+/// commit, or the tree of a commit.
+fn peel_object(hash: &str, kind: &str, spec: &str) -> Result<String> {
+    let mut hash = hash.to_string();
+    while let Ok(tag) = crate::object::read_tag(&hash) {
+        hash = tag.object;
+    }
+
+    match kind {
+        "" => Ok(hash),
+        "commit" => peel_to_commit(&hash),
+        "tree" if crate::object::read_object_data(&hash)?.0 == "tree" => Ok(hash),
+        "tree" => crate::object::get_commit_tree(&peel_to_commit(&hash)?),
+        _ => Err(invalid_revision(spec)),
+    }
+}
+
+/// Follow annotated tags until reaching a commit
+fn peel_to_commit(hash: &str) -> Result<String> {
+    let mut hash = hash.to_string();
+
+    loop {
+        let (object_type, _) = crate::object::read_object_data(&hash)?;
+        match object_type.as_str() {
+            "commit" => return Ok(hash),
+            "tag" => hash = crate::object::read_tag(&hash)?.object,
+            other => {
+                return Err(KitcatError::InvalidInput(format!("Object {} is a {}, not a commit", hash, other)));
+            }
+        }
+    }
+}
+
+/// Resolve a revision name without suffixes
+fn resolve_name(name: &str) -> Result<String> {
+    if name == "HEAD" || name == "@" || name.is_empty() {
+        return resolve_head()?.ok_or_else(|| {
+            KitcatError::NotFound("HEAD does not point to a commit yet".to_string())
+        });
+    }
+
+    for candidate in [
+        format!(".kitcat/{}", name),
+        format!(".kitcat/refs/heads/{}", name),
+        format!(".kitcat/refs/tags/{}", name),
+    ] {
+        let path = Path::new(&candidate);
+        if path.is_file() && candidate.contains("/refs/") {
+            return Ok(fs::read_to_string(path)?.trim().to_string());
+        }
+    }
+
+    if name.len() >= 4 && name.chars().all(|c| c.is_ascii_hexdigit()) {
+        return crate::object::resolve_object_hash(name);
+    }
+
+    Err(invalid_revision(name))
 }
 
-// Add more repo functions like checkout, branch handling here
+/// Error for a revision that cannot be resolved
+fn invalid_revision(rev: &str) -> KitcatError {
+    KitcatError::UnknownRevision(rev.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{commit_files, in_temp_repo};
+
+    #[test]
+    fn test_resolve_revision_suffixes() {
+        in_temp_repo("revision-suffixes", || {
+            let first = commit_files(&[("a.txt", "1\n")], "first");
+            let second = commit_files(&[("a.txt", "2\n")], "second");
+            let third = commit_files(&[("a.txt", "3\n")], "third");
+
+            assert_eq!(resolve_revision("HEAD~2").unwrap(), first);
+            assert_eq!(resolve_revision("HEAD^^").unwrap(), first);
+            assert_eq!(resolve_revision("HEAD~1^{}").unwrap(), second);
+            assert_eq!(resolve_revision("HEAD^{commit}").unwrap(), third);
+
+            assert_eq!(
+                resolve_object_name("HEAD^{tree}").unwrap(),
+                crate::object::get_commit_tree(&third).unwrap()
+            );
+            assert_eq!(resolve_object_name("HEAD~2^{commit}").unwrap(), first);
+        });
+    }
+
+    #[test]
+    fn test_resolve_revision_rejects_malformed_suffixes() {
+        in_temp_repo("revision-malformed", || {
+            commit_files(&[("a.txt", "1\n")], "first");
+            commit_files(&[("a.txt", "2\n")], "second");
+
+            for rev in ["HEAD~x", "HEAD~1x", "HEAD^{tree}", "HEAD^{commit", "HEAD^{blob}x"] {
+                let err = resolve_revision(rev).unwrap_err();
+                assert!(matches!(err, KitcatError::UnknownRevision(_)), "{}", rev);
+            }
+            assert!(matches!(resolve_object_name("HEAD^{blob}"), Err(KitcatError::UnknownRevision(_))));
+        });
+    }
+}
@@ -0,0 +1,548 @@
+/// Revision walking for `log`
+///
+/// Turns revision arguments into the list of commits to show:
+/// - `B`: commits reachable from B
+/// - `^A`: exclude commits reachable from A
+/// - `A..B`: `^A B` (an empty side means HEAD)
+/// - `A...B`: commits reachable from either side but not from both
+/// - `--all` / `--branches`: every ref / every branch as a starting point
+///
+/// When commits are excluded, the walk first "limits" the history: commits
+/// are visited by generation and committer time, exclusion is passed down to
+/// parents, and the walk stops once only excluded commits remain queued and
+/// none of them can reach a listed commit. Without generation numbers from
+/// the commit-graph that means walking until the exclusion has reached every
+/// listed commit or the excluded history is exhausted.
+///
+/// Commits are then returned newest committer time first (Git's default),
+/// or sorted so that no parent comes before its children: `--date-order`
+/// keeps that order by commit time, `--topo-order` also avoids interleaving
+/// lines of history.
+
+use crate::error::Result;
+use super::resolve_revision;
+use crate::merge::{CommitWalker, find_merge_bases};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+/// Order of the walked commits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    /// Newest committer time first
+    #[default]
+    Default,
+    /// No parent before its children, otherwise by committer time
+    Date,
+    /// No parent before its children, lines of history kept together
+    Topo,
+}
+
+/// Which commits to walk and in what order
+#[derive(Debug, Clone, Default)]
+pub struct RevWalkOptions {
+    /// Revision arguments (`B`, `^A`, `A..B`, `A...B`); HEAD when empty
+    pub revisions: Vec<String>,
+    /// Start from every ref and HEAD
+    pub all: bool,
+    /// Start from every branch
+    pub branches: bool,
+    /// Output order
+    pub order: SortOrder,
+    /// Follow only the first parent of merges
+    pub first_parent: bool,
+    /// Only merge commits
+    pub merges: bool,
+    /// Only commits with at most one parent
+    pub no_merges: bool,
+}
+
+/// Starting points of a walk
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RevisionSet {
+    /// Commits whose history is shown
+    pub include: Vec<String>,
+    /// Commits whose history is excluded
+    pub exclude: Vec<String>,
+}
+
+impl RevisionSet {
+    /// Resolve revision arguments and ref options to commits
+    pub fn parse(options: &RevWalkOptions) -> Result<Self> {
+        let mut set = RevisionSet::default();
+
+        for revision in &options.revisions {
+            if let Some(excluded) = revision.strip_prefix('^') {
+                set.exclude.push(resolve_revision(excluded)?);
+            } else if let Some((left, right)) = revision.split_once("...") {
+                let left = resolve_revision(or_head(left))?;
+                let right = resolve_revision(or_head(right))?;
+                set.exclude.extend(find_merge_bases(&left, &right)?);
+                set.include.push(left);
+                set.include.push(right);
+            } else if let Some((left, right)) = revision.split_once("..") {
+                set.exclude.push(resolve_revision(or_head(left))?);
+                set.include.push(resolve_revision(or_head(right))?);
+            } else {
+                set.include.push(resolve_revision(revision)?);
+            }
+        }
+
+        if options.all {
+            if let Some(head) = super::resolve_head()? {
+                set.include.push(head);
+            }
+            set.include.extend(ref_tips(Path::new(".kitcat/refs"))?);
+        } else if options.branches {
+            set.include
+                .extend(ref_tips(Path::new(".kitcat/refs/heads"))?);
+        } else if options.revisions.is_empty() {
+            set.include.extend(super::resolve_head()?);
+        }
+
+        Ok(set)
+    }
+}
+
+/// HEAD for the empty side of a range
+fn or_head(revision: &str) -> &str {
+    if revision.is_empty() {
+        "HEAD"
+    } else {
+        revision
+    }
+}
+
+/// Commits that the refs below `dir` point to (tags are peeled, other objects skipped)
+fn ref_tips(dir: &Path) -> Result<Vec<String>> {
+    let mut tips = Vec::new();
+    if !dir.is_dir() {
+        return Ok(tips);
+    }
+
+    let mut entries: Vec<_> = fs::read_dir(dir)?.collect::<Result<_, _>>()?;
+    entries.sort_by_key(|entry| entry.path());
+    for entry in entries {
+        let path = entry.path();
+        if path.is_dir() {
+            tips.extend(ref_tips(&path)?);
+        } else if let Ok(name) = path.strip_prefix(".kitcat")
+            && let Ok(hash) = resolve_revision(&name.to_string_lossy())
+        {
+            tips.push(hash);
+        }
+    }
+
+    Ok(tips)
+}
+
+/// Walk history according to `options`, returning commit hashes in output order
+pub fn rev_walk(options: &RevWalkOptions) -> Result<RevWalk> {
+    let set = RevisionSet::parse(options)?;
+    RevWalk::new(&set, options)
+}
+
+/// Iterator over walked commits
+pub struct RevWalk {
+    walker: CommitWalker,
+    first_parent: bool,
+    merges: Option<bool>,
+    /// Commits not excluded, when the history was limited
+    allowed: Option<HashSet<String>>,
+    /// Streaming walk by committer time
+    queue: BinaryHeap<(i64, Reverse<usize>, String)>,
+    seen: HashSet<String>,
+    pushed: usize,
+    /// Pre-sorted commits for `--date-order` and `--topo-order`
+    sorted: Option<std::vec::IntoIter<String>>,
+}
+
+impl RevWalk {
+    /// Start a walk from resolved revisions
+    pub fn new(set: &RevisionSet, options: &RevWalkOptions) -> Result<Self> {
+        Self::with_walker(CommitWalker::new(), set, options)
+    }
+
+    /// Start a walk that reads commits through `walker`
+    fn with_walker(
+        walker: CommitWalker,
+        set: &RevisionSet,
+        options: &RevWalkOptions,
+    ) -> Result<Self> {
+        let mut walk = RevWalk {
+            walker,
+            first_parent: options.first_parent,
+            merges: match (options.merges, options.no_merges) {
+                (true, false) => Some(true),
+                (false, true) => Some(false),
+                _ => None,
+            },
+            allowed: None,
+            queue: BinaryHeap::new(),
+            seen: HashSet::new(),
+            pushed: 0,
+            sorted: None,
+        };
+
+        if !set.exclude.is_empty() {
+            walk.allowed = Some(walk.limit(&set.include, &set.exclude)?);
+        }
+        for tip in &set.include {
+            walk.push(tip)?;
+        }
+
+        if options.order != SortOrder::Default {
+            let mut commits = Vec::new();
+            while let Some(hash) = walk.next_by_date()? {
+                commits.push(hash);
+            }
+            let sorted = walk.sort_topologically(commits, options.order)?;
+            walk.sorted = Some(sorted.into_iter());
+        }
+
+        Ok(walk)
+    }
+
+    /// Parents followed by the walk
+    fn followed_parents(&mut self, hash: &str) -> Result<Vec<String>> {
+        let mut parents = self.walker.parents(hash)?;
+        if self.first_parent {
+            parents.truncate(1);
+        }
+        Ok(parents)
+    }
+
+    /// Queue a commit for the streaming walk unless already seen or excluded
+    fn push(&mut self, hash: &str) -> Result<()> {
+        if self.seen.contains(hash)
+            || self
+                .allowed
+                .as_ref()
+                .is_some_and(|allowed| !allowed.contains(hash))
+        {
+            return Ok(());
+        }
+        self.seen.insert(hash.to_string());
+        let time = self.walker.commit_time(hash)?;
+        self.queue
+            .push((time, Reverse(self.pushed), hash.to_string()));
+        self.pushed += 1;
+        Ok(())
+    }
+
+    /// Next commit by committer time, ties in the order they were reached
+    fn next_by_date(&mut self) -> Result<Option<String>> {
+        let Some((_, _, hash)) = self.queue.pop() else {
+            return Ok(None);
+        };
+        for parent in self.followed_parents(&hash)? {
+            self.push(&parent)?;
+        }
+        Ok(Some(hash))
+    }
+
+    /// Commits reachable from `include` but not from `exclude`
+    fn limit(&mut self, include: &[String], exclude: &[String]) -> Result<HashSet<String>> {
+        let mut uninteresting: HashSet<String> = HashSet::new();
+        let mut seen: HashSet<String> = HashSet::new();
+        let mut interesting = Vec::new();
+        let mut queue: BinaryHeap<(u32, i64, String)> = BinaryHeap::new();
+
+        for hash in exclude {
+            uninteresting.insert(hash.clone());
+        }
+        for hash in exclude.iter().chain(include) {
+            if seen.insert(hash.clone()) {
+                queue.push(self.queue_entry(hash)?);
+            }
+        }
+
+        while let Some((generation, _, _)) = queue.peek() {
+            // With only excluded commits queued, go on while one may still reach a listed commit
+            if queue.iter().all(|(_, _, hash)| uninteresting.contains(hash))
+                && !self.may_reach(*generation, &interesting, &uninteresting)?
+            {
+                break;
+            }
+            let Some((_, _, hash)) = queue.pop() else {
+                break;
+            };
+
+            if uninteresting.contains(&hash) {
+                // Exclusion follows every parent, even with --first-parent
+                for parent in self.walker.parents(&hash)? {
+                    self.mark_uninteresting(&parent, &seen, &mut uninteresting)?;
+                    if seen.insert(parent.clone()) {
+                        queue.push(self.queue_entry(&parent)?);
+                    }
+                }
+            } else {
+                interesting.push(hash.clone());
+                for parent in self.followed_parents(&hash)? {
+                    if seen.insert(parent.clone()) {
+                        queue.push(self.queue_entry(&parent)?);
+                    }
+                }
+            }
+        }
+
+        Ok(interesting
+            .into_iter()
+            .filter(|hash| !uninteresting.contains(hash))
+            .collect())
+    }
+
+    /// Whether a commit of `generation` may be an ancestor of a listed commit not yet excluded
+    ///
+    /// Commit times can be equal or skewed, so only generation numbers from
+    /// the commit-graph rule that out.
+    fn may_reach(
+        &mut self,
+        generation: u32,
+        interesting: &[String],
+        uninteresting: &HashSet<String>,
+    ) -> Result<bool> {
+        for hash in interesting {
+            if !uninteresting.contains(hash)
+                && (generation == u32::MAX || generation > self.walker.generation(hash)?)
+            {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Limit-walk queue entry: generation first, then committer time
+    fn queue_entry(&mut self, hash: &str) -> Result<(u32, i64, String)> {
+        Ok((
+            self.walker.generation(hash)?,
+            self.walker.commit_time(hash)?,
+            hash.to_string(),
+        ))
+    }
+
+    /// Exclude a commit and the already visited commits below it
+    fn mark_uninteresting(
+        &mut self,
+        hash: &str,
+        seen: &HashSet<String>,
+        uninteresting: &mut HashSet<String>,
+    ) -> Result<()> {
+        let mut stack = vec![hash.to_string()];
+        while let Some(hash) = stack.pop() {
+            if !uninteresting.insert(hash.clone()) || !seen.contains(&hash) {
+                continue;
+            }
+            stack.extend(self.walker.parents(&hash)?);
+        }
+        Ok(())
+    }
+
+    /// Sort commits (newest first) so that parents come after all their children
+    fn sort_topologically(
+        &mut self,
+        commits: Vec<String>,
+        order: SortOrder,
+    ) -> Result<Vec<String>> {
+        let members: HashSet<&str> = commits.iter().map(String::as_str).collect();
+        let mut children: HashMap<String, usize> = HashMap::new();
+        for hash in &commits {
+            for parent in self.followed_parents(hash)? {
+                if members.contains(parent.as_str()) {
+                    *children.entry(parent).or_default() += 1;
+                }
+            }
+        }
+
+        let mut ready_by_date: BinaryHeap<(i64, Reverse<usize>, String)> = BinaryHeap::new();
+        let mut ready_stack: Vec<String> = Vec::new();
+        let mut sequence = 0;
+        let tips = commits.iter().filter(|hash| !children.contains_key(*hash));
+        match order {
+            SortOrder::Topo => ready_stack.extend(tips.rev().cloned()),
+            _ => {
+                for hash in tips {
+                    ready_by_date.push((
+                        self.walker.commit_time(hash)?,
+                        Reverse(sequence),
+                        hash.clone(),
+                    ));
+                    sequence += 1;
+                }
+            }
+        }
+
+        let mut sorted = Vec::with_capacity(commits.len());
+        loop {
+            let hash = match order {
+                SortOrder::Topo => ready_stack.pop(),
+                _ => ready_by_date.pop().map(|(_, _, hash)| hash),
+            };
+            let Some(hash) = hash else {
+                break;
+            };
+
+            for parent in self.followed_parents(&hash)? {
+                let Some(count) = children.get_mut(&parent) else {
+                    continue;
+                };
+                *count -= 1;
+                if *count == 0 {
+                    if order == SortOrder::Topo {
+                        ready_stack.push(parent);
+                    } else {
+                        let time = self.walker.commit_time(&parent)?;
+                        ready_by_date.push((time, Reverse(sequence), parent));
+                        sequence += 1;
+                    }
+                }
+            }
+            sorted.push(hash);
+        }
+
+        Ok(sorted)
+    }
+
+    /// Next commit in output order, before the merge filters
+    fn next_unfiltered(&mut self) -> Result<Option<String>> {
+        match &mut self.sorted {
+            Some(sorted) => Ok(sorted.next()),
+            None => self.next_by_date(),
+        }
+    }
+
+    /// Whether a commit passes `--merges` / `--no-merges`
+    fn wanted(&mut self, hash: &str) -> Result<bool> {
+        Ok(match self.merges {
+            Some(merges) => (self.walker.parents(hash)?.len() > 1) == merges,
+            None => true,
+        })
+    }
+}
+
+impl Iterator for RevWalk {
+    type Item = Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.next_unfiltered() {
+                Ok(Some(hash)) => match self.wanted(&hash) {
+                    Ok(true) => return Some(Ok(hash)),
+                    Ok(false) => continue,
+                    Err(e) => return Some(Err(e)),
+                },
+                Ok(None) => return None,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //   a1 - a2 ------ m - a3
+    //  /              /
+    // r - b1 - b2 - b3
+    const HISTORY: &[(&str, i64, &[&str])] = &[
+        ("r", 1, &[]),
+        ("a1", 2, &["r"]),
+        ("b1", 3, &["r"]),
+        ("a2", 4, &["a1"]),
+        ("b2", 5, &["b1"]),
+        ("b3", 6, &["b2"]),
+        ("m", 7, &["a2", "b3"]),
+        ("a3", 8, &["m"]),
+    ];
+
+    fn walk(include: &[&str], exclude: &[&str], options: RevWalkOptions) -> Vec<String> {
+        walk_history(HISTORY, include, exclude, options)
+    }
+
+    fn walk_history(
+        history: &[(&str, i64, &[&str])],
+        include: &[&str],
+        exclude: &[&str],
+        options: RevWalkOptions,
+    ) -> Vec<String> {
+        let mut walker = CommitWalker::new();
+        for (hash, time, parents) in history {
+            walker.insert(hash, *time, parents);
+        }
+        let set = RevisionSet {
+            include: include.iter().map(|s| s.to_string()).collect(),
+            exclude: exclude.iter().map(|s| s.to_string()).collect(),
+        };
+        RevWalk::with_walker(walker, &set, &options)
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_walk_orders() {
+        assert_eq!(
+            walk(&["a3"], &[], RevWalkOptions::default()),
+            ["a3", "m", "b3", "b2", "a2", "b1", "a1", "r"]
+        );
+        let topo = RevWalkOptions {
+            order: SortOrder::Topo,
+            ..Default::default()
+        };
+        assert_eq!(
+            walk(&["a3"], &[], topo),
+            ["a3", "m", "b3", "b2", "b1", "a2", "a1", "r"]
+        );
+        let first_parent = RevWalkOptions {
+            first_parent: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            walk(&["a3"], &[], first_parent),
+            ["a3", "m", "a2", "a1", "r"]
+        );
+    }
+
+    #[test]
+    fn test_walk_limits_and_filters() {
+        assert_eq!(
+            walk(&["a3"], &["a2"], RevWalkOptions::default()),
+            ["a3", "m", "b3", "b2", "b1"]
+        );
+        assert_eq!(
+            walk(&["a2"], &["b3"], RevWalkOptions::default()),
+            ["a2", "a1"]
+        );
+        let merges = RevWalkOptions {
+            merges: true,
+            ..Default::default()
+        };
+        assert_eq!(walk(&["a3"], &[], merges), ["m"]);
+        let no_merges = RevWalkOptions {
+            no_merges: true,
+            ..Default::default()
+        };
+        assert_eq!(walk(&["b3"], &["r"], no_merges), ["b3", "b2", "b1"]);
+    }
+
+    #[test]
+    fn test_walk_limits_equal_times_without_generations() {
+        // s ----- p - r
+        //        /
+        // m - q -
+        // All at the same time, so the walk cannot stop once only `m` is queued
+        let history: &[(&str, i64, &[&str])] = &[
+            ("r", 1, &[]),
+            ("p", 1, &["r"]),
+            ("q", 1, &["p"]),
+            ("m", 1, &["q"]),
+            ("s", 1, &["p"]),
+        ];
+        assert_eq!(
+            walk_history(history, &["s"], &["m"], RevWalkOptions::default()),
+            ["s"]
+        );
+    }
+}
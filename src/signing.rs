@@ -0,0 +1,386 @@
+/// Signing and verifying commits and tags
+///
+/// Signatures are made and checked by external programs, like Git does:
+/// - `openpgp` (default): `gpg -bsau <key>` signs and `gpg --verify`
+///   checks against the local keyring
+/// - `ssh`: `ssh-keygen -Y sign -n git -f <key>` signs, and signatures are
+///   checked against the `<principal> <key>` lines of
+///   `gpg.ssh.allowedSignersFile`
+///
+/// The key is the one given on the command line, then `user.signingKey`;
+/// OpenPGP falls back to the signer's identity. An existing signature is
+/// checked according to its armor, whatever `gpg.format` says.
+
+use crate::config::Config;
+use crate::diff::driver::TempFile;
+use crate::error::{KitcatError, Result};
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+/// First line of an OpenPGP signature
+pub const PGP_ARMOR: &str = "-----BEGIN PGP SIGNATURE-----";
+
+/// First line of an SSH signature
+pub const SSH_ARMOR: &str = "-----BEGIN SSH SIGNATURE-----";
+
+/// Namespace SSH signatures are made in, as with Git
+const SSH_NAMESPACE: &str = "git";
+
+/// Kind of signature (`gpg.format`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureFormat {
+    OpenPgp,
+    Ssh,
+}
+
+impl SignatureFormat {
+    /// Parse a `gpg.format` value
+    pub fn parse(spec: &str) -> Result<Self> {
+        match spec {
+            "openpgp" => Ok(SignatureFormat::OpenPgp),
+            "ssh" => Ok(SignatureFormat::Ssh),
+            _ => Err(KitcatError::InvalidInput(format!("Unsupported signature format '{}' (expected openpgp or ssh)", spec))),
+        }
+    }
+
+    /// The format of an armored signature
+    pub fn detect(signature: &str) -> Option<Self> {
+        if signature.starts_with(PGP_ARMOR) {
+            Some(SignatureFormat::OpenPgp)
+        } else if signature.starts_with(SSH_ARMOR) {
+            Some(SignatureFormat::Ssh)
+        } else {
+            None
+        }
+    }
+}
+
+/// Whether to sign: `-S[<key>]`, `--no-gpg-sign` or the `*.gpgSign` setting
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum SignRequest {
+    /// Sign when the configuration says so
+    #[default]
+    Config,
+    /// Sign, with this key or the default one
+    Key(Option<String>),
+    /// Do not sign
+    Never,
+}
+
+impl SignRequest {
+    /// From `-S[=<key>]` (an empty key means the default) and `--no-gpg-sign`
+    pub fn from_flags(sign: Option<Option<String>>, no_sign: bool) -> Self {
+        match sign {
+            _ if no_sign => SignRequest::Never,
+            Some(key) => SignRequest::Key(key.filter(|key| !key.is_empty())),
+            None => SignRequest::Config,
+        }
+    }
+
+    /// Whether to sign, given the `commit.gpgSign` or `tag.gpgSign` setting
+    pub fn wants(&self, configured: Option<bool>) -> bool {
+        match self {
+            SignRequest::Config => configured.unwrap_or(false),
+            SignRequest::Key(_) => true,
+            SignRequest::Never => false,
+        }
+    }
+
+    /// The key given on the command line
+    pub fn key(&self) -> Option<&str> {
+        match self {
+            SignRequest::Key(key) => key.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+/// Outcome of checking a signature
+#[derive(Debug, Clone, PartialEq)]
+pub struct Verification {
+    /// Whether the signature is valid and made by a trusted key
+    pub good: bool,
+    /// Who signed: the key's user ID or the allowed signer's principal
+    pub signer: Option<String>,
+    /// Key ID or fingerprint
+    pub key: Option<String>,
+    /// What the program reported, for the user
+    pub output: String,
+}
+
+/// Sign `payload` and return the armored signature
+///
+/// `identity` ("Name <email>") is the OpenPGP key when none is set.
+pub fn sign_payload(config: &Config, payload: &[u8], key: Option<&str>, identity: &str) -> Result<String> {
+    let format = match &config.gpg.format {
+        Some(spec) => SignatureFormat::parse(spec)?,
+        None => SignatureFormat::OpenPgp,
+    };
+    let key = key.or(config.user.signing_key.as_deref());
+
+    match format {
+        SignatureFormat::OpenPgp => {
+            let mut command = Command::new(config.gpg.program.as_deref().unwrap_or("gpg"));
+            command.args(["--status-fd=2", "-bsau", key.unwrap_or(identity)]);
+            let output = run(command, payload)?;
+
+            let status = String::from_utf8_lossy(&output.stderr);
+            if !output.status.success() || !status.contains("[GNUPG:] SIG_CREATED ") {
+                return Err(KitcatError::CommandFailed(format!(
+                    "gpg failed to sign the data:\n{}",
+                    status.trim_end()
+                )));
+            }
+            Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+        }
+        SignatureFormat::Ssh => {
+            let key = key.ok_or_else(|| {
+                KitcatError::InvalidInput("user.signingKey needs to be set for ssh signing".to_string())
+            })?;
+
+            // A literal public key (`key::ssh-ed25519 ...`) signs through the agent
+            let literal = key
+                .strip_prefix("key::")
+                .map(|public| TempFile::new("signing_key.pub", public.as_bytes()))
+                .transpose()?;
+            let key_path = match &literal {
+                Some(file) => file.path().display().to_string(),
+                None => key.to_string(),
+            };
+
+            let payload_file = TempFile::new("payload", payload)?;
+            let mut command = Command::new(ssh_program(config));
+            command
+                .args(["-Y", "sign", "-n", SSH_NAMESPACE, "-f", &key_path])
+                .arg(payload_file.path());
+            let output = run(command, &[])?;
+
+            let signature_path = format!("{}.sig", payload_file.path().display());
+            let signature = fs::read_to_string(&signature_path);
+            let _ = fs::remove_file(&signature_path);
+            match signature {
+                Ok(signature) if output.status.success() => Ok(signature),
+                _ => Err(KitcatError::CommandFailed(format!(
+                    "ssh-keygen failed to sign the data:\n{}",
+                    String::from_utf8_lossy(&output.stderr).trim_end()
+                ))),
+            }
+        }
+    }
+}
+
+/// Check an armored signature over `payload`
+pub fn verify_payload(config: &Config, payload: &[u8], signature: &str) -> Result<Verification> {
+    let format = SignatureFormat::detect(signature).ok_or_else(|| {
+        KitcatError::CorruptObject("Unrecognized signature format".to_string())
+    })?;
+    let signature_file = TempFile::new("signature", signature.as_bytes())?;
+
+    match format {
+        SignatureFormat::OpenPgp => {
+            let mut command = Command::new(config.gpg.program.as_deref().unwrap_or("gpg"));
+            command
+                .args(["--keyid-format=long", "--status-fd=1", "--verify"])
+                .arg(signature_file.path())
+                .arg("-");
+            let output = run(command, payload)?;
+
+            let mut verification = parse_gpg_status(&String::from_utf8_lossy(&output.stdout));
+            verification.good &= output.status.success();
+            verification.output = String::from_utf8_lossy(&output.stderr).trim_end().to_string();
+            Ok(verification)
+        }
+        SignatureFormat::Ssh => {
+            let allowed = config.gpg.ssh.allowed_signers_file.as_deref().ok_or_else(|| {
+                KitcatError::NotFound("gpg.ssh.allowedSignersFile needs to be configured for ssh signature verification".to_string())
+            })?;
+            let program = ssh_program(config);
+
+            let mut command = Command::new(&program);
+            command
+                .args(["-Y", "find-principals", "-f", allowed, "-s"])
+                .arg(signature_file.path());
+            let found = run(command, &[])?;
+            let principals = String::from_utf8_lossy(&found.stdout).into_owned();
+
+            for principal in principals.lines().filter(|line| !line.is_empty()) {
+                let mut command = Command::new(&program);
+                command
+                    .args(["-Y", "verify", "-n", SSH_NAMESPACE, "-f", allowed, "-I", principal, "-s"])
+                    .arg(signature_file.path());
+                let output = run(command, payload)?;
+                if output.status.success() {
+                    let report = String::from_utf8_lossy(&output.stdout).trim_end().to_string();
+                    return Ok(Verification {
+                        good: true,
+                        signer: Some(principal.to_string()),
+                        key: ssh_key_fingerprint(&report),
+                        output: report,
+                    });
+                }
+            }
+
+            // Not from an allowed signer: still report the key if the signature holds
+            let mut command = Command::new(&program);
+            command
+                .args(["-Y", "check-novalidate", "-n", SSH_NAMESPACE, "-s"])
+                .arg(signature_file.path());
+            let output = run(command, payload)?;
+            let report = if output.status.success() {
+                String::from_utf8_lossy(&output.stdout).trim_end().to_string()
+            } else {
+                String::from_utf8_lossy(&output.stderr).trim_end().to_string()
+            };
+
+            Ok(Verification {
+                good: false,
+                signer: None,
+                key: ssh_key_fingerprint(&report),
+                output: format!("{}\nNo principal matched.", report).trim_start().to_string(),
+            })
+        }
+    }
+}
+
+/// The SSH signing program
+fn ssh_program(config: &Config) -> String {
+    config
+        .gpg
+        .ssh
+        .program
+        .clone()
+        .unwrap_or_else(|| "ssh-keygen".to_string())
+}
+
+/// Run a program with `input` on stdin, collecting its output
+fn run(mut command: Command, input: &[u8]) -> Result<Output> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| KitcatError::CommandFailed(format!("Cannot run {}: {}", program, e)))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input)?;
+    }
+    Ok(child.wait_with_output()?)
+}
+
+/// Read gpg's `--status-fd` lines
+///
+/// The signature is good with a `GOODSIG` line and no `BADSIG`, `ERRSIG`,
+/// expired or revoked key line. `VALIDSIG` gives the full fingerprint.
+fn parse_gpg_status(status: &str) -> Verification {
+    let mut verification = Verification {
+        good: false,
+        signer: None,
+        key: None,
+        output: String::new(),
+    };
+    let mut bad = false;
+
+    for line in status.lines() {
+        let Some(line) = line.strip_prefix("[GNUPG:] ") else {
+            continue;
+        };
+        let mut fields = line.splitn(3, ' ');
+        match fields.next() {
+            Some("GOODSIG") => {
+                verification.good = true;
+                verification.key = fields.next().map(str::to_string);
+                verification.signer = fields.next().map(str::to_string);
+            }
+            Some("VALIDSIG") => verification.key = fields.next().map(str::to_string),
+            Some("BADSIG" | "ERRSIG" | "EXPSIG" | "EXPKEYSIG" | "REVKEYSIG") => bad = true,
+            _ => {}
+        }
+    }
+
+    verification.good &= !bad;
+    verification
+}
+
+/// The key fingerprint in ssh-keygen's "... with ED25519 key SHA256:..." report
+fn ssh_key_fingerprint(report: &str) -> Option<String> {
+    report
+        .lines()
+        .find_map(|line| line.split_once(" key "))
+        .map(|(_, key)| key.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_parse_gpg_status() {
+        let status = "[GNUPG:] NEWSIG\n\
+                      [GNUPG:] GOODSIG 0123456789ABCDEF Jane Doe <jane@example.com>\n\
+                      [GNUPG:] VALIDSIG 89ABCDEF0123456789ABCDEF0123456789ABCDEF 2024-01-15 1705311000\n";
+        let verification = parse_gpg_status(status);
+        assert!(verification.good);
+        assert_eq!(verification.signer.as_deref(), Some("Jane Doe <jane@example.com>"));
+        assert_eq!(verification.key.as_deref(), Some("89ABCDEF0123456789ABCDEF0123456789ABCDEF"));
+
+        assert!(!parse_gpg_status("[GNUPG:] BADSIG 0123456789ABCDEF Jane Doe\n").good);
+        assert!(!parse_gpg_status("[GNUPG:] ERRSIG 0123456789ABCDEF 1 8 00 1705311000 9\n").good);
+    }
+
+    #[test]
+    fn test_sign_request() {
+        assert!(!SignRequest::Config.wants(None));
+        assert!(SignRequest::Config.wants(Some(true)));
+        assert!(SignRequest::Key(None).wants(Some(false)));
+        assert!(!SignRequest::Never.wants(Some(true)));
+        assert_eq!(SignRequest::Key(Some("ABCD".to_string())).key(), Some("ABCD"));
+        assert_eq!(SignatureFormat::detect("-----BEGIN SSH SIGNATURE-----\n"), Some(SignatureFormat::Ssh));
+        assert!(SignatureFormat::parse("x509").is_err());
+    }
+
+    /// Sign and verify with a throwaway SSH key, when ssh-keygen is available
+    #[test]
+    fn test_ssh_sign_and_verify() {
+        let dir = std::env::temp_dir().join(format!("kitcat-signing-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let key = dir.join("id_ed25519");
+
+        let generated = Command::new("ssh-keygen")
+            .args(["-q", "-t", "ed25519", "-N", "", "-C", "jane@example.com", "-f"])
+            .arg(&key)
+            .status();
+        if !generated.is_ok_and(|status| status.success()) {
+            eprintln!("ssh-keygen not available, skipping");
+            return;
+        }
+
+        let public = fs::read_to_string(dir.join("id_ed25519.pub")).unwrap();
+        let allowed = dir.join("allowed_signers");
+        fs::write(&allowed, format!("jane@example.com {}", public)).unwrap();
+
+        let mut config = Config::default();
+        config.gpg.format = Some("ssh".to_string());
+        config.user.signing_key = Some(key.display().to_string());
+        config.gpg.ssh.allowed_signers_file = Some(allowed.display().to_string());
+
+        let payload = b"tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904\n\nSigned\n";
+        let signature = sign_payload(&config, payload, None, "Jane <jane@example.com>").unwrap();
+        assert!(signature.starts_with(SSH_ARMOR));
+
+        let verification = verify_payload(&config, payload, &signature).unwrap();
+        assert!(verification.good);
+        assert_eq!(verification.signer.as_deref(), Some("jane@example.com"));
+        assert!(verification.key.is_some_and(|key| key.starts_with("SHA256:")));
+
+        // A tampered payload or an unknown signer is rejected
+        assert!(!verify_payload(&config, b"tampered\n", &signature).unwrap().good);
+        fs::write(&allowed, "").unwrap();
+        assert!(!verify_payload(&config, payload, &signature).unwrap().good);
+
+        fs::remove_dir_all(Path::new(&dir)).unwrap();
+    }
+}
@@ -1,3 +1,4 @@
+use crate::error::{KitcatError, Result};
 use flate2::Compression;
 use flate2::read::ZlibDecoder;
 use flate2::write::ZlibEncoder;
@@ -27,17 +28,118 @@ pub fn compress_data(data: &[u8]) -> Vec<u8> {
 }
 
 /// Decompress data using Zlib
-pub fn decompress_data(data: &[u8]) -> Vec<u8> {
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>> {
     let mut decoder = ZlibDecoder::new(data);
     let mut out = Vec::new();
-    decoder.read_to_end(&mut out).unwrap();
-    out
+    decoder
+        .read_to_end(&mut out)
+        .map_err(|e| KitcatError::CorruptObject(format!("Corrupt zlib data: {}", e)))?;
+    Ok(out)
 }
 
-/// Decompress data using Zlib (with Result for error handling)
-pub fn decompress(data: &[u8]) -> std::io::Result<Vec<u8>> {
-    let mut decoder = ZlibDecoder::new(data);
-    let mut out = Vec::new();
-    decoder.read_to_end(&mut out)?;
-    Ok(out)
+/// Git mode of a working tree file, from its `symlink_metadata`
+pub fn file_mode(metadata: &std::fs::Metadata) -> u32 {
+    use crate::models::{MODE_EXECUTABLE, MODE_FILE, MODE_SYMLINK};
+    use std::os::unix::fs::PermissionsExt;
+
+    if metadata.file_type().is_symlink() {
+        MODE_SYMLINK
+    } else if metadata.permissions().mode() & 0o111 != 0 {
+        MODE_EXECUTABLE
+    } else {
+        MODE_FILE
+    }
+}
+
+/// Read a working tree file as blob content
+///
+/// Symbolic links are not followed: their content is the link target.
+pub fn read_worktree_file(path: impl AsRef<Path>) -> Result<Vec<u8>> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = path.as_ref();
+    if std::fs::symlink_metadata(path)?.file_type().is_symlink() {
+        Ok(std::fs::read_link(path)?.as_os_str().as_bytes().to_vec())
+    } else {
+        Ok(std::fs::read(path)?)
+    }
+}
+
+/// Check whether a working tree path exists, counting dangling symlinks
+pub fn worktree_path_exists(path: impl AsRef<Path>) -> bool {
+    std::fs::symlink_metadata(path).is_ok()
+}
+
+/// Write blob content to the working tree with a Git mode
+///
+/// `MODE_SYMLINK` creates a symbolic link to the content, `MODE_EXECUTABLE`
+/// an executable file and anything else a regular file. Whatever was at the
+/// path before is replaced and missing parent directories are created.
+pub fn write_worktree_file(path: impl AsRef<Path>, content: &[u8], mode: u32) -> Result<()> {
+    use crate::models::{MODE_EXECUTABLE, MODE_SYMLINK};
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = path.as_ref();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    if std::fs::symlink_metadata(path).is_ok_and(|m| !m.is_dir()) {
+        std::fs::remove_file(path)?;
+    }
+
+    if mode == MODE_SYMLINK {
+        std::os::unix::fs::symlink(std::ffi::OsStr::from_bytes(content), path)?;
+        return Ok(());
+    }
+
+    std::fs::write(path, content)?;
+
+    // Executable files get `x` wherever they have `r`, like chmod +x under the umask
+    let current = std::fs::metadata(path)?.permissions().mode() & 0o777;
+    let permissions = if mode == MODE_EXECUTABLE {
+        current | ((current & 0o444) >> 2)
+    } else {
+        current & !0o111
+    };
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(permissions))?;
+    Ok(())
+}
+
+/// Run `f` inside a fresh repository in a temporary directory
+///
+/// Kitcat works on the current directory, so tests that need a repository
+/// take turns changing into one.
+#[cfg(test)]
+pub fn in_temp_repo<T>(name: &str, f: impl FnOnce() -> T) -> T {
+    static CWD: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    let _guard = CWD.lock().unwrap_or_else(|e| e.into_inner());
+
+    let dir = std::env::temp_dir().join(format!("kitcat-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let previous = std::env::current_dir().unwrap();
+    std::env::set_current_dir(&dir).unwrap();
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        crate::repo::init_repo().unwrap();
+        f()
+    }));
+
+    std::env::set_current_dir(previous).unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+    result.unwrap_or_else(|e| std::panic::resume_unwind(e))
+}
+
+/// Write and stage files, then commit them, returning the new HEAD commit
+#[cfg(test)]
+pub fn commit_files(files: &[(&str, &str)], message: &str) -> String {
+    use crate::commands::commit::{commit, CommitOptions};
+
+    for (path, content) in files {
+        std::fs::write(path, content).unwrap();
+        crate::index::add_to_index(path.to_string()).unwrap();
+    }
+    commit(CommitOptions { messages: vec![message.to_string()], ..Default::default() }).unwrap();
+    crate::repo::resolve_head().unwrap().unwrap()
 }